@@ -0,0 +1,23 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The exercise, replace the `todo!()` with your solution.
+
+use bitcoin::key::KeyPair;
+use bitcoin::secp256k1::{Secp256k1, Signing, Verification};
+use bitcoin::Address;
+
+/// Signs `message` with `keypair`, the internal key of the key path only p2tr `address`.
+///
+/// Returns the BIP-322 "simple" signature i.e., the base64 encoded witness stack.
+///
+/// Build the virtual transactions with `to_spend` and `to_sign` in `main.rs`, then sign the input
+/// of `to_sign` as an ordinary key path spend of the output of `to_spend`.
+#[allow(unused_variables)]
+pub fn sign<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    keypair: &KeyPair,
+    address: &Address,
+    message: &[u8],
+) -> String {
+    todo!("sign the message")
+}
@@ -0,0 +1,14 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Hints for the exercise, from least to most specific.
+
+pub const HINTS: &[&str] = &[
+    "Signing a message is signing a transaction. Build `to_spend` from the message and the script \
+     pubkey of the address, then `to_sign` which spends it, both helpers are in `main.rs`.",
+    "Sign input 0 of `to_sign` as a key path spend of `to_spend.output[0]`, exactly as in \
+     `sign-taproot`: `taproot_key_spend_signature_hash` with `TapSighashType::Default`, signed \
+     with `keypair.tap_tweak(secp, None)`.",
+    "The signature is the witness. Push `taproot::Signature { sig, hash_ty }.to_vec()` onto \
+     `Witness::new()`, serialize it with `consensus::encode::serialize` and encode the bytes with \
+     `base64::encode`.",
+];
@@ -0,0 +1,256 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Sign and verify a message for a p2tr address using BIP-322 "simple" signatures.
+//!
+//! BIP-322 proves control of an address by signing a virtual transaction that spends a virtual
+//! output locked to the address' script pubkey. Neither transaction is valid on chain, they exist
+//! only so that any script a wallet can spend can also sign a message.
+//!
+//! - `to_spend` has a single output locked to the address, its single input commits to the
+//!   message.
+//! - `to_sign` spends `to_spend`, the "simple" signature is the witness of its input.
+
+use std::str::FromStr;
+
+use bitcoin::base64;
+use bitcoin::consensus::encode::deserialize;
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::key::XOnlyPublicKey;
+use bitcoin::locktime::absolute;
+use bitcoin::opcodes::all::OP_RETURN;
+use bitcoin::opcodes::OP_0;
+use bitcoin::script::Builder;
+use bitcoin::secp256k1::{Message, Secp256k1, Verification};
+use bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
+use bitcoin::{
+    taproot, Address, Network, OutPoint, Script, ScriptBuf, Sequence, Transaction, TxIn, TxOut,
+    Txid, Witness,
+};
+use workshop_common::taproot::senders_keys;
+
+// The exercise is `sign` in `src/exercise.rs`, building with `--features solution` swaps in the
+// reference solution from `src/solution.rs`.
+#[cfg(not(feature = "solution"))]
+mod exercise;
+#[cfg(feature = "solution")]
+#[path = "solution.rs"]
+mod exercise;
+
+use exercise::sign;
+
+mod hints;
+
+/// The message we sign.
+const MESSAGE: &str = "Hello World";
+
+/// The tag used to hash the message (see BIP-340 for tagged hashes).
+const MESSAGE_TAG: &[u8] = b"BIP0322-signed-message";
+
+fn main() {
+    // Stuck? Run `cargo run -- hint 1`.
+    workshop_common::hint_command(hints::HINTS);
+
+    // We need a signing secp256k1 context, if you have not seen this before just pass it in when
+    // needed and otherwise ignore it.
+    let secp = Secp256k1::new();
+
+    // Check our virtual transactions against the test vectors in BIP-322. The vectors use a p2wpkh
+    // address but the transactions only depend on the message and the script pubkey.
+    let vector_address = "bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l";
+    let vector_script_pubkey = mainnet_address(vector_address).script_pubkey();
+    for (message, message_hash_hex, to_spend_txid, to_sign_txid) in [
+        (
+            "",
+            "c90c269c4f8fcbe6880f72a721ddfbf1914268a794cbb21cfafee13770ae19f1",
+            "c5680aa69bb8d860bf82d4e9cd3504b55dde018de765a91bb566283c545a99a7",
+            "1e9654e951a5ba44c8604c4de6c67fd78a27e81dcadcfe1edf638ba3aaebaed6",
+        ),
+        (
+            "Hello World",
+            "f0eb03b1a75ac6d9847f55c624a99169b5dccba2a31f5b23bea77ba270de0a7a",
+            "b79d196740ad5217771c1098fc4a4b51e0535c32236c71f1ea4d61a2d603352b",
+            "88737ae86f2077145f93cc4b153ae9a1cb8d56afa511988c149c5c8c9d93bddf",
+        ),
+    ] {
+        assert_eq!(
+            message_hash(message.as_bytes()).to_string(),
+            message_hash_hex
+        );
+        let to_spend = to_spend(message.as_bytes(), &vector_script_pubkey);
+        assert_eq!(to_spend.txid().to_string(), to_spend_txid);
+        assert_eq!(to_sign(&to_spend).txid().to_string(), to_sign_txid);
+    }
+
+    // And check that we verify the p2tr signature from the BIP-322 test vectors (this one uses
+    // SIGHASH_ALL so is 65 bytes long).
+    let vector_p2tr_address =
+        mainnet_address("bc1ppv609nr0vr25u07u95waq5lucwfm6tde4nydujnu8npg4q75mr5sxq8lt3");
+    let vector_signature = "AUHd69PrJQEv+oKTfZ8l+WROBHuy9HKrbFCJu7U1iK2iiEy1vMU5EfMtjc+VSHM7aU0SDbak5IUZRVno2P5mjSafAQ==";
+    verify(
+        &secp,
+        &vector_p2tr_address,
+        b"Hello World",
+        vector_signature,
+    )
+    .expect("BIP-322 test vector verifies");
+
+    // Get a keypair we control and the p2tr address for it.
+    // In a real application these would come from a stored secret.
+    let keypair = senders_keys(&secp);
+    let (internal_key, _parity) = keypair.x_only_public_key();
+    let address = Address::p2tr(&secp, internal_key, None, Network::Bitcoin);
+
+    // Sign the message, this is the exercise. The signature is the base64 encoded witness of
+    // `to_sign`.
+    let signature = sign(&secp, &keypair, &address, MESSAGE.as_bytes());
+    println!("Address: {}", address);
+    println!("Message: {}", MESSAGE);
+    println!("Signature: {}", signature);
+
+    // Anyone with the address, the message and the signature can verify it.
+    verify(&secp, &address, MESSAGE.as_bytes(), &signature).expect("valid signature");
+
+    // The signature does not verify for any other message or any other address.
+    let result = verify(&secp, &address, b"Hello World!", &signature);
+    assert!(matches!(result, Err(VerifyError::InvalidSignature)));
+    let other_address = mainnet_address(vector_address);
+    let result = verify(&secp, &other_address, MESSAGE.as_bytes(), &signature);
+    assert!(matches!(result, Err(VerifyError::NotP2tr)));
+}
+
+/// Returns the BIP-340 tagged hash of `message` using the BIP-322 tag.
+///
+/// `SHA256(SHA256(tag) || SHA256(tag) || message)`
+fn message_hash(message: &[u8]) -> sha256::Hash {
+    let tag = sha256::Hash::hash(MESSAGE_TAG);
+
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_byte_array());
+    engine.input(tag.as_byte_array());
+    engine.input(message);
+    sha256::Hash::from_engine(engine)
+}
+
+/// Returns the virtual `to_spend` transaction, its single output is locked to `message_challenge`
+/// (the script pubkey of the address signing the message).
+fn to_spend(message: &[u8], message_challenge: &Script) -> Transaction {
+    // Like a coinbase the input spends a null out point. The script sig is not executed, it is
+    // there to commit to the message.
+    let script_sig = Builder::new()
+        .push_opcode(OP_0)
+        .push_slice(message_hash(message).to_byte_array())
+        .into_script();
+
+    let input = TxIn {
+        previous_output: OutPoint {
+            txid: Txid::all_zeros(),
+            vout: 0xFFFFFFFF,
+        },
+        script_sig,
+        sequence: Sequence::ZERO,
+        witness: Witness::default(),
+    };
+
+    let output = TxOut {
+        value: 0,
+        script_pubkey: message_challenge.to_owned(),
+    };
+
+    Transaction {
+        version: 0,                          // Fixed by BIP-322.
+        lock_time: absolute::LockTime::ZERO, // Fixed by BIP-322.
+        input: vec![input],
+        output: vec![output],
+    }
+}
+
+/// Returns the virtual `to_sign` transaction, with an empty witness, that spends `to_spend`.
+fn to_sign(to_spend: &Transaction) -> Transaction {
+    let input = TxIn {
+        previous_output: OutPoint {
+            txid: to_spend.txid(),
+            vout: 0,
+        },
+        script_sig: ScriptBuf::default(), // Empty for segwit.
+        sequence: Sequence::ZERO,
+        witness: Witness::default(), // Filled in by the signer.
+    };
+
+    // A single unspendable output, `OP_RETURN` with no data.
+    let output = TxOut {
+        value: 0,
+        script_pubkey: Builder::new().push_opcode(OP_RETURN).into_script(),
+    };
+
+    Transaction {
+        version: 0,                          // Fixed by BIP-322.
+        lock_time: absolute::LockTime::ZERO, // Fixed by BIP-322.
+        input: vec![input],
+        output: vec![output],
+    }
+}
+
+/// Verifies a BIP-322 "simple" `signature` of `message` by the key path only p2tr `address`.
+fn verify<C: Verification>(
+    secp: &Secp256k1<C>,
+    address: &Address,
+    message: &[u8],
+    signature: &str,
+) -> Result<(), VerifyError> {
+    let script_pubkey = address.script_pubkey();
+    if !script_pubkey.is_v1_p2tr() {
+        return Err(VerifyError::NotP2tr);
+    }
+    // The output key is the 32 bytes after `OP_1 OP_PUSHBYTES_32`.
+    let output_key = XOnlyPublicKey::from_slice(&script_pubkey.as_bytes()[2..])
+        .map_err(|_| VerifyError::NotP2tr)?;
+
+    // Rebuild the virtual transactions from the message and the address, then put the witness
+    // from the signature in place.
+    let witness_bytes = base64::decode(signature).map_err(|_| VerifyError::Base64)?;
+    let witness: Witness = deserialize(&witness_bytes).map_err(|_| VerifyError::Witness)?;
+    let to_spend = to_spend(message, &script_pubkey);
+    let mut to_sign = to_sign(&to_spend);
+    to_sign.input[0].witness = witness;
+
+    // A key path spend has a single witness element, the signature.
+    let signature = match to_sign.input[0].witness.to_vec().as_slice() {
+        [signature] => {
+            taproot::Signature::from_slice(signature).map_err(|_| VerifyError::Witness)?
+        }
+        _ => return Err(VerifyError::Witness),
+    };
+    // BIP-322 only allows signatures that commit to the whole transaction.
+    if !matches!(
+        signature.hash_ty,
+        TapSighashType::Default | TapSighashType::All
+    ) {
+        return Err(VerifyError::SighashType);
+    }
+
+    let prevouts = [to_spend.output[0].clone()];
+    let sighash = SighashCache::new(&to_sign)
+        .taproot_key_spend_signature_hash(0, &Prevouts::All(&prevouts), signature.hash_ty)
+        .expect("failed to construct sighash");
+    let msg = Message::from_slice(sighash.as_byte_array()).expect("32 byte sighash");
+    secp.verify_schnorr(&signature.sig, &msg, &output_key)
+        .map_err(|_| VerifyError::InvalidSignature)
+}
+
+/// Why a signature failed to verify.
+#[derive(Debug)]
+enum VerifyError {
+    NotP2tr,
+    Base64,
+    Witness,
+    SighashType,
+    InvalidSignature,
+}
+
+/// Parses `s` as a mainnet address.
+fn mainnet_address(s: &str) -> Address {
+    Address::from_str(s)
+        .expect("a valid address")
+        .require_network(Network::Bitcoin)
+        .expect("valid address for mainnet")
+}
@@ -0,0 +1,45 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The reference solution, built with `--features solution`.
+
+use bitcoin::base64;
+use bitcoin::consensus::encode::serialize;
+use bitcoin::hashes::Hash;
+use bitcoin::key::{KeyPair, TapTweak, TweakedKeyPair};
+use bitcoin::secp256k1::{Message, Secp256k1, Signing, Verification};
+use bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
+use bitcoin::{taproot, Address, Witness};
+
+use super::{to_sign, to_spend};
+
+/// Signs `message` with `keypair`, the internal key of the key path only p2tr `address`.
+///
+/// Returns the BIP-322 "simple" signature i.e., the base64 encoded witness stack.
+pub fn sign<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    keypair: &KeyPair,
+    address: &Address,
+    message: &[u8],
+) -> String {
+    let to_spend = to_spend(message, &address.script_pubkey());
+    let to_sign = to_sign(&to_spend);
+
+    // This is an ordinary key path spend of the `to_spend` output, see `sign-taproot`.
+    let sighash_type = TapSighashType::Default;
+    let prevouts = [to_spend.output[0].clone()];
+    let sighash = SighashCache::new(&to_sign)
+        .taproot_key_spend_signature_hash(0, &Prevouts::All(&prevouts), sighash_type)
+        .expect("failed to construct sighash");
+
+    let tweaked: TweakedKeyPair = keypair.tap_tweak(secp, None);
+    let msg = Message::from_slice(sighash.as_byte_array()).expect("32 byte sighash");
+    let signature = taproot::Signature {
+        sig: secp.sign_schnorr(&msg, &tweaked.to_inner()),
+        hash_ty: sighash_type,
+    };
+
+    let mut witness = Witness::new();
+    witness.push(signature.to_vec());
+
+    base64::encode(serialize(&witness))
+}
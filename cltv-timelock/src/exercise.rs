@@ -0,0 +1,14 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The exercise, replace the `todo!()` with your solution.
+
+use bitcoin::absolute;
+use bitcoin::{PublicKey, ScriptBuf};
+
+/// Returns the script `<lock_time> OP_CHECKLOCKTIMEVERIFY OP_DROP <pk> OP_CHECKSIG`.
+///
+/// Build it with `bitcoin::script::Builder`, the opcodes are in `bitcoin::opcodes::all`.
+#[allow(unused_variables)]
+pub fn cltv_script(lock_time: absolute::LockTime, pk: &PublicKey) -> ScriptBuf {
+    todo!("build the lock time script")
+}
@@ -0,0 +1,13 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Hints for the exercise, from least to most specific.
+
+pub const HINTS: &[&str] = &[
+    "The script checks the lock time and then a signature. Build it with \
+     `bitcoin::script::Builder`, one push or opcode at a time.",
+    "`Builder::push_lock_time(lock_time)` pushes the lock time as a script number, the opcode is \
+     `OP_CLTV` from `bitcoin::opcodes::all`. It leaves its argument on the stack.",
+    "The script is `<lock_time> OP_CHECKLOCKTIMEVERIFY OP_DROP <pk> OP_CHECKSIG`: \
+     `push_lock_time(lock_time)`, `push_opcode(OP_CLTV)`, `push_opcode(OP_DROP)`, `push_key(pk)`, \
+     `push_opcode(OP_CHECKSIG)`, then `into_script()`.",
+];
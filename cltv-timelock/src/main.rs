@@ -0,0 +1,151 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Demonstrate locking coins until a block height with OP_CHECKLOCKTIMEVERIFY (BIP-65).
+//!
+//! The coins are locked to the p2wsh script `<height> OP_CHECKLOCKTIMEVERIFY OP_DROP <pubkey>
+//! OP_CHECKSIG`, the spending transaction has to set nLockTime to at least `<height>` and so can
+//! not be mined before then.
+
+use bitcoin::absolute::{self, Height, Time};
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::{Message, Secp256k1};
+use bitcoin::sighash::{EcdsaSighashType, SighashCache};
+use bitcoin::{ecdsa, PublicKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness};
+use workshop_common::interpreter::{execute, ScriptError};
+use workshop_common::segwit_v0::receivers_address;
+use workshop_common::{CHANGE_AMOUNT, SPEND_AMOUNT};
+
+// The exercise is `cltv_script` in `src/exercise.rs`, building with `--features solution` swaps in the
+// reference solution from `src/solution.rs`.
+#[cfg(not(feature = "solution"))]
+mod exercise;
+#[cfg(feature = "solution")]
+#[path = "solution.rs"]
+mod exercise;
+
+use exercise::cltv_script;
+
+mod hints;
+
+/// The coins can be spent in blocks from this height on.
+const LOCK_HEIGHT: u32 = 850_000;
+
+fn main() {
+    // Stuck? Run `cargo run -- hint 1`.
+    workshop_common::hint_command(hints::HINTS);
+
+    // We need a signing secp256k1 context, if you have not seen this before just pass it in when
+    // needed and otherwise ignore it.
+    let secp = Secp256k1::new();
+
+    // Get a secret key we control and the associated pubkey.
+    // In a real application these would come from a stored secret.
+    let sk = workshop_common::secret_key(0);
+    let pk = PublicKey::new(sk.public_key(&secp));
+
+    // The script the coins are locked to, this is the exercise.
+    let lock_time = absolute::LockTime::from_height(LOCK_HEIGHT).expect("valid height");
+    let witness_script = cltv_script(lock_time, &pk);
+
+    // Get an unspent output that is locked to the script above.
+    // In a real application these would come from the chain.
+    let (dummy_out_point, dummy_utxo) =
+        workshop_common::dummy_utxo(0, ScriptBuf::new_v0_p2wsh(&witness_script.wscript_hash()));
+
+    // Get an address to send to.
+    let address = receivers_address();
+
+    // The input for the transaction we are constructing. nLockTime is ignored if every input has
+    // the final sequence number (0xffffffff), OP_CHECKLOCKTIMEVERIFY fails if ours does.
+    let input = TxIn {
+        previous_output: dummy_out_point, // The dummy output we are spending.
+        script_sig: ScriptBuf::default(), // For a p2wsh script_sig is empty.
+        sequence: Sequence::ENABLE_LOCKTIME_NO_RBF,
+        witness: Witness::default(), // Filled in after signing.
+    };
+
+    // The spend output is locked to a key controlled by the receiver.
+    let spend = TxOut {
+        value: SPEND_AMOUNT,
+        script_pubkey: address.script_pubkey(),
+    };
+
+    // The change output is locked to a key controlled by us.
+    let change = TxOut {
+        value: CHANGE_AMOUNT,
+        script_pubkey: ScriptBuf::new_v0_p2wpkh(&pk.wpubkey_hash().expect("key is compressed")),
+    };
+
+    // The transaction we want to sign and broadcast. The lock time must be at least the one in the
+    // script (and in the same unit, blocks not seconds).
+    let unsigned_tx = Transaction {
+        version: 2,                  // Post BIP-68.
+        lock_time,                   // Can't be mined before LOCK_HEIGHT.
+        input: vec![input],          // Input goes into index 0.
+        output: vec![spend, change], // Outputs, order does not matter.
+    };
+    let input_index = 0;
+
+    // Get the sighash to sign. For p2wsh the script code is the witness script.
+    let sighash_type = EcdsaSighashType::All;
+    let mut sighasher = SighashCache::new(unsigned_tx);
+    let sighash = sighasher
+        .segwit_signature_hash(input_index, &witness_script, dummy_utxo.value, sighash_type)
+        .expect("failed to create sighash");
+
+    // Sign the sighash using the secp256k1 library (exported by rust-bitcoin).
+    let msg = Message::from_slice(sighash.as_byte_array()).expect("32 byte sighash");
+    let signature = ecdsa::Signature {
+        sig: secp.sign_ecdsa(&msg, &sk),
+        hash_ty: sighash_type,
+    };
+
+    // Update the witness stack: the input to the script (the signature) then the script itself.
+    let witness = sighasher
+        .witness_mut(input_index)
+        .expect("getting mutable witness reference should work");
+    witness.push(signature.to_vec());
+    witness.push(witness_script.as_bytes());
+
+    // Get the signed transaction.
+    let tx = sighasher.into_transaction();
+
+    // Check the script executes successfully for our transaction.
+    let stack = vec![signature.to_vec()];
+    execute(
+        &secp,
+        &witness_script,
+        stack,
+        &tx,
+        input_index,
+        dummy_utxo.value,
+    )
+    .expect("script executes");
+
+    // With an earlier lock time the script fails, whatever the signature.
+    let mut early_tx = tx.clone();
+    early_tx.lock_time = absolute::LockTime::from_height(LOCK_HEIGHT - 1).expect("valid height");
+    let stack = vec![signature.to_vec()];
+    let result = execute(
+        &secp,
+        &witness_script,
+        stack,
+        &early_tx,
+        input_index,
+        dummy_utxo.value,
+    );
+    assert!(matches!(result, Err(ScriptError::UnsatisfiedLockTime)));
+
+    // The script only checks nLockTime, it is nLockTime that stops the transaction being mined
+    // early.
+    let time = Time::MIN; // Ignored for a lock time in blocks.
+    let early = Height::from_consensus(LOCK_HEIGHT - 1).expect("valid height");
+    let ready = Height::from_consensus(LOCK_HEIGHT).expect("valid height");
+    assert!(!tx.is_absolute_timelock_satisfied(early, time));
+    assert!(tx.is_absolute_timelock_satisfied(ready, time));
+
+    // BOOM! Transaction signed and ready to broadcast, once the chain gets to LOCK_HEIGHT.
+    workshop_common::print_prevouts(&[dummy_utxo]);
+    println!("{}", serialize_hex(&tx));
+}
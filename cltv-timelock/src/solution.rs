@@ -0,0 +1,22 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The reference solution, built with `--features solution`.
+
+use bitcoin::absolute;
+use bitcoin::opcodes::all::{OP_CHECKSIG, OP_CLTV, OP_DROP};
+use bitcoin::script::Builder;
+use bitcoin::{PublicKey, ScriptBuf};
+
+/// Returns the script `<lock_time> OP_CHECKLOCKTIMEVERIFY OP_DROP <pk> OP_CHECKSIG`.
+///
+/// OP_CHECKLOCKTIMEVERIFY leaves its argument on the stack (it was OP_NOP2 before BIP-65 and old
+/// nodes must see the same stack) so we drop it ourselves.
+pub fn cltv_script(lock_time: absolute::LockTime, pk: &PublicKey) -> ScriptBuf {
+    Builder::new()
+        .push_lock_time(lock_time)
+        .push_opcode(OP_CLTV)
+        .push_opcode(OP_DROP)
+        .push_key(pk)
+        .push_opcode(OP_CHECKSIG)
+        .into_script()
+}
@@ -0,0 +1,13 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The exercise, replace the `todo!()` with your solution.
+
+use bitcoin::{Transaction, TxIn, TxOut};
+
+/// Builds the coinjoin transaction from the inputs and outputs the participants registered.
+///
+/// Sort the inputs and outputs so their position does not give away who registered them.
+#[allow(unused_variables)]
+pub fn coordinate(inputs: Vec<TxIn>, outputs: Vec<TxOut>) -> Transaction {
+    todo!("build the transaction from the registered inputs and outputs")
+}
@@ -0,0 +1,13 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Hints for the exercise, from least to most specific.
+
+pub const HINTS: &[&str] = &[
+    "The coordinator only puts the registered inputs and outputs into one transaction, nobody \
+     signs anything yet. The hard part is not giving away which input paid for which output.",
+    "If inputs and outputs kept the order they were registered in, their positions would link \
+     them to a participant. Sort both in an order that does not depend on who registered them.",
+    "Sort the inputs by `previous_output` and the outputs by `(value, script_pubkey.as_bytes())`, \
+     then return a `Transaction` with `version: 2`, `lock_time: absolute::LockTime::ZERO`, and \
+     the sorted inputs and outputs.",
+];
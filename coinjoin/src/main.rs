@@ -0,0 +1,235 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Demonstrate building a coinjoin style transaction with two participants.
+//!
+//! Each participant brings an input and gets back an output of the same value as everyone else's
+//! (plus change). An observer can not tell which equal value output belongs to which input. Nobody
+//! has to trust anybody: each participant checks their outputs are in the transaction before they
+//! sign, and only ever signs their own input.
+
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::{Message, Secp256k1, SecretKey, Signing, Verification};
+use bitcoin::sighash::{EcdsaSighashType, SighashCache};
+use bitcoin::{
+    ecdsa, Address, Network, OutPoint, PublicKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut,
+    Txid, Witness,
+};
+use workshop_common::{DUMMY_UTXO_AMOUNT, FEE};
+
+// The exercise is `coordinate` in `src/exercise.rs`, building with `--features solution` swaps in
+// the reference solution from `src/solution.rs`.
+#[cfg(not(feature = "solution"))]
+mod exercise;
+#[cfg(feature = "solution")]
+#[path = "solution.rs"]
+mod exercise;
+
+use exercise::coordinate;
+
+mod hints;
+
+/// The value of every mixed output.
+const DENOMINATION: u64 = 10_000_000;
+/// Each participant pays half of the 1000 sat fee.
+const FEE_SHARE: u64 = FEE / 2;
+
+/// Every signature commits to all the outputs but only to its own input.
+const SIGHASH_TYPE: EcdsaSighashType = EcdsaSighashType::AllPlusAnyoneCanPay;
+
+fn main() {
+    // Stuck? Run `cargo run -- hint 1`.
+    workshop_common::hint_command(hints::HINTS);
+
+    // We need a signing secp256k1 context, if you have not seen this before just pass it in when
+    // needed and otherwise ignore it.
+    let secp = Secp256k1::new();
+
+    // Two participants, each with a coin.
+    // In a real application these would come from the chain.
+    let alice = Participant::new(&secp, 0);
+    let bob = Participant::new(&secp, 1);
+
+    // Each participant registers their input and outputs with the coordinator. In a real coinjoin
+    // the mixed output is registered over a different identity (e.g. a new Tor circuit) so the
+    // coordinator can't link it to the input either.
+    let participants = [&alice, &bob];
+    let inputs = participants
+        .iter()
+        .map(|participant| participant.input())
+        .collect();
+    let outputs = participants
+        .iter()
+        .flat_map(|participant| participant.outputs())
+        .collect();
+
+    // The coordinator builds the transaction, this is the exercise.
+    let unsigned_tx = coordinate(inputs, outputs);
+
+    // Each participant checks the transaction before signing, then signs their own input.
+    let alice_sig = alice.sign(&secp, &unsigned_tx);
+    let bob_sig = bob.sign(&secp, &unsigned_tx);
+
+    // Because of ANYONECANPAY Alice's signature does not commit to Bob's input. If Bob had walked
+    // away the coordinator could swap in someone else's input, with the same outputs, and Alice
+    // would not need to sign again.
+    let carols_out_point = OutPoint {
+        txid: Txid::all_zeros(), // Carol's coin, obviously invalid.
+        vout: 2,
+    };
+    let mut replaced_tx = unsigned_tx.clone();
+    let bob_index = bob.input_index(&replaced_tx);
+    replaced_tx.input[bob_index].previous_output = carols_out_point;
+    assert_eq!(alice.sighash(&unsigned_tx), alice.sighash(&replaced_tx));
+
+    // The coordinator puts the signatures in place.
+    let mut tx = unsigned_tx;
+    for (participant, sig) in [(&alice, alice_sig), (&bob, bob_sig)] {
+        let index = participant.input_index(&tx);
+        tx.input[index].witness.push(sig.to_vec());
+        tx.input[index].witness.push(participant.pk.to_bytes());
+    }
+
+    // Anyone can check every signature, and the fee.
+    for participant in participants {
+        participant.verify(&secp, &tx);
+    }
+    let input_value = DUMMY_UTXO_AMOUNT * participants.len() as u64;
+    let output_value = tx.output.iter().map(|output| output.value).sum::<u64>();
+    assert_eq!(
+        input_value - output_value,
+        FEE_SHARE * participants.len() as u64
+    );
+
+    // BOOM! Transaction signed and ready to broadcast.
+    let prevouts = tx
+        .input
+        .iter()
+        .map(|input| {
+            let participant = participants
+                .iter()
+                .find(|participant| participant.out_point == input.previous_output)
+                .expect("every input belongs to a participant");
+            participant.utxo.clone()
+        })
+        .collect::<Vec<_>>();
+    workshop_common::print_prevouts(&prevouts);
+    println!("{}", serialize_hex(&tx));
+}
+
+/// One of the participants in the coinjoin.
+struct Participant {
+    sk: SecretKey,
+    pk: PublicKey,
+    /// The coin the participant brings.
+    out_point: OutPoint,
+    utxo: TxOut,
+    /// Where the mixed output goes, a fresh address not linked to the input.
+    mixed: ScriptBuf,
+    /// Where the change goes.
+    change: ScriptBuf,
+}
+
+impl Participant {
+    /// Returns participant `n`, spending the `n`th dummy utxo.
+    fn new<C: Signing>(secp: &Secp256k1<C>, n: u32) -> Self {
+        // In a real application these would be actual secrets, and the participants would not
+        // share a seed.
+        let keys = [0, 1, 2].map(|i| {
+            let sk = workshop_common::secret_key(3 * n + i);
+            (sk, PublicKey::new(sk.public_key(secp)))
+        });
+        let (sk, pk) = keys[0];
+        let (out_point, utxo) = workshop_common::dummy_utxo(n, p2wpkh(&pk));
+
+        // In a real application these would be addresses from the participant's wallet.
+        let mixed = p2wpkh(&keys[1].1);
+        let change = p2wpkh(&keys[2].1);
+
+        Participant {
+            sk,
+            pk,
+            out_point,
+            utxo,
+            mixed,
+            change,
+        }
+    }
+
+    /// The input the participant registers.
+    fn input(&self) -> TxIn {
+        TxIn {
+            previous_output: self.out_point,  // The dummy output we are spending.
+            script_sig: ScriptBuf::default(), // For a p2wpkh script_sig is empty.
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::default(), // Filled in after signing.
+        }
+    }
+
+    /// The outputs the participant registers, the mixed output and change.
+    fn outputs(&self) -> [TxOut; 2] {
+        let mixed = TxOut {
+            value: DENOMINATION,
+            script_pubkey: self.mixed.clone(),
+        };
+        let change = TxOut {
+            value: self.utxo.value - DENOMINATION - FEE_SHARE,
+            script_pubkey: self.change.clone(),
+        };
+        [mixed, change]
+    }
+
+    /// Returns the index of this participant's input in `tx`.
+    fn input_index(&self, tx: &Transaction) -> usize {
+        tx.input
+            .iter()
+            .position(|input| input.previous_output == self.out_point)
+            .expect("our input is in the transaction")
+    }
+
+    /// Returns the sighash for this participant's input in `tx`.
+    fn sighash(&self, tx: &Transaction) -> Message {
+        let script_code = self
+            .utxo
+            .script_pubkey
+            .p2wpkh_script_code()
+            .expect("valid p2wpkh script pubkey");
+        let sighash = SighashCache::new(tx)
+            .segwit_signature_hash(
+                self.input_index(tx),
+                &script_code,
+                self.utxo.value,
+                SIGHASH_TYPE,
+            )
+            .expect("failed to create sighash");
+        Message::from_slice(sighash.as_byte_array()).expect("32 byte sighash")
+    }
+
+    /// Checks our outputs are in `tx` then signs our input.
+    fn sign<C: Signing>(&self, secp: &Secp256k1<C>, tx: &Transaction) -> ecdsa::Signature {
+        // Never sign a transaction that does not pay us back.
+        for output in self.outputs() {
+            assert!(tx.output.contains(&output), "our output is missing");
+        }
+
+        ecdsa::Signature {
+            sig: secp.sign_ecdsa(&self.sighash(tx), &self.sk),
+            hash_ty: SIGHASH_TYPE,
+        }
+    }
+
+    /// Checks the signature on this participant's input of the signed `tx`.
+    fn verify<C: Verification>(&self, secp: &Secp256k1<C>, tx: &Transaction) {
+        let witness = &tx.input[self.input_index(tx)].witness;
+        let sig = ecdsa::Signature::from_slice(&witness.to_vec()[0]).expect("valid signature");
+        secp.verify_ecdsa(&self.sighash(tx), &sig.sig, &self.pk.inner)
+            .expect("signature verifies against the participant's key");
+    }
+}
+
+/// Returns the p2wpkh script pubkey for `pk`.
+fn p2wpkh(pk: &PublicKey) -> ScriptBuf {
+    Address::p2wpkh(pk, Network::Bitcoin)
+        .expect("key is compressed")
+        .script_pubkey()
+}
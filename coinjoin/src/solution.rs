@@ -0,0 +1,23 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The reference solution, built with `--features solution`.
+
+use bitcoin::locktime::absolute;
+use bitcoin::{Transaction, TxIn, TxOut};
+
+/// Builds the coinjoin transaction from the inputs and outputs the participants registered.
+///
+/// Inputs and outputs are sorted so their position does not give away who registered them.
+pub fn coordinate(mut inputs: Vec<TxIn>, mut outputs: Vec<TxOut>) -> Transaction {
+    inputs.sort_by_key(|input| input.previous_output);
+    outputs.sort_by(|a, b| {
+        (a.value, a.script_pubkey.as_bytes()).cmp(&(b.value, b.script_pubkey.as_bytes()))
+    });
+
+    Transaction {
+        version: 2,                          // Post BIP-68.
+        lock_time: absolute::LockTime::ZERO, // Ignore the locktime.
+        input: inputs,
+        output: outputs,
+    }
+}
@@ -0,0 +1,13 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The exercise, replace the `todo!()` with your solution.
+
+use bitcoin::{PublicKey, ScriptBuf, Sequence};
+
+/// Returns the script `<sequence> OP_CHECKSEQUENCEVERIFY OP_DROP <pk> OP_CHECKSIG`.
+///
+/// Build it with `bitcoin::script::Builder`, the opcodes are in `bitcoin::opcodes::all`.
+#[allow(unused_variables)]
+pub fn csv_script(sequence: Sequence, pk: &PublicKey) -> ScriptBuf {
+    todo!("build the relative lock time script")
+}
@@ -0,0 +1,13 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Hints for the exercise, from least to most specific.
+
+pub const HINTS: &[&str] = &[
+    "The script checks the input's relative lock time and then a signature. Build it with \
+     `bitcoin::script::Builder`, one push or opcode at a time.",
+    "`Builder::push_sequence(sequence)` pushes the sequence as a script number, the opcode is \
+     `OP_CSV` from `bitcoin::opcodes::all`. It leaves its argument on the stack.",
+    "The script is `<sequence> OP_CHECKSEQUENCEVERIFY OP_DROP <pk> OP_CHECKSIG`: \
+     `push_sequence(sequence)`, `push_opcode(OP_CSV)`, `push_opcode(OP_DROP)`, `push_key(pk)`, \
+     `push_opcode(OP_CHECKSIG)`, then `into_script()`.",
+];
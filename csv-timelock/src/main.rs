@@ -0,0 +1,167 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Demonstrate locking coins for a number of blocks after they confirm with
+//! OP_CHECKSEQUENCEVERIFY (BIP-112).
+//!
+//! The coins are locked to the p2wsh script `<blocks> OP_CHECKSEQUENCEVERIFY OP_DROP <pubkey>
+//! OP_CHECKSIG`, the spending input has to set its sequence number to a relative lock time (BIP-68)
+//! of at least `<blocks>` and so can not be mined until the output being spent is that deep.
+
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::{Message, Secp256k1};
+use bitcoin::sighash::{EcdsaSighashType, SighashCache};
+use bitcoin::{absolute, ecdsa, PublicKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness};
+use workshop_common::interpreter::{execute, ScriptError};
+use workshop_common::segwit_v0::receivers_address;
+use workshop_common::{CHANGE_AMOUNT, SPEND_AMOUNT};
+
+// The exercise is `csv_script` in `src/exercise.rs`, building with `--features solution` swaps in the
+// reference solution from `src/solution.rs`.
+#[cfg(not(feature = "solution"))]
+mod exercise;
+#[cfg(feature = "solution")]
+#[path = "solution.rs"]
+mod exercise;
+
+use exercise::csv_script;
+
+mod hints;
+
+/// The coins can be spent once the output being spent has this many confirmations.
+const LOCK_BLOCKS: u16 = 144; // About a day.
+
+fn main() {
+    // Stuck? Run `cargo run -- hint 1`.
+    workshop_common::hint_command(hints::HINTS);
+
+    // We need a signing secp256k1 context, if you have not seen this before just pass it in when
+    // needed and otherwise ignore it.
+    let secp = Secp256k1::new();
+
+    // Get a secret key we control and the associated pubkey.
+    // In a real application these would come from a stored secret.
+    let sk = workshop_common::secret_key(0);
+    let pk = PublicKey::new(sk.public_key(&secp));
+
+    // The script the coins are locked to, this is the exercise.
+    let sequence = Sequence::from_height(LOCK_BLOCKS);
+    let witness_script = csv_script(sequence, &pk);
+
+    // A relative lock time lives in the sequence number of the input. Bit 31 set disables it, bit
+    // 22 selects the unit (set for multiples of 512 seconds, clear for blocks) and the low 16 bits
+    // are the value.
+    println!(
+        "relative lock time of {} blocks, sequence: {:#010x}",
+        LOCK_BLOCKS,
+        sequence.to_consensus_u32()
+    );
+
+    // Get an unspent output that is locked to the script above.
+    // In a real application these would come from the chain.
+    let (dummy_out_point, dummy_utxo) =
+        workshop_common::dummy_utxo(0, ScriptBuf::new_v0_p2wsh(&witness_script.wscript_hash()));
+
+    // Get an address to send to.
+    let address = receivers_address();
+
+    // The input for the transaction we are constructing, its sequence number must be a relative
+    // lock time at least as long as the one in the script (and in the same unit).
+    let input = TxIn {
+        previous_output: dummy_out_point, // The dummy output we are spending.
+        script_sig: ScriptBuf::default(), // For a p2wsh script_sig is empty.
+        sequence,
+        witness: Witness::default(), // Filled in after signing.
+    };
+
+    // The spend output is locked to a key controlled by the receiver.
+    let spend = TxOut {
+        value: SPEND_AMOUNT,
+        script_pubkey: address.script_pubkey(),
+    };
+
+    // The change output is locked to a key controlled by us.
+    let change = TxOut {
+        value: CHANGE_AMOUNT,
+        script_pubkey: ScriptBuf::new_v0_p2wpkh(&pk.wpubkey_hash().expect("key is compressed")),
+    };
+
+    // The transaction we want to sign and broadcast. Relative lock times only apply from version 2
+    // on.
+    let unsigned_tx = Transaction {
+        version: 2,                          // Post BIP-68.
+        lock_time: absolute::LockTime::ZERO, // Ignore the (absolute) locktime.
+        input: vec![input],                  // Input goes into index 0.
+        output: vec![spend, change],         // Outputs, order does not matter.
+    };
+    let input_index = 0;
+
+    // Get the sighash to sign. For p2wsh the script code is the witness script.
+    let sighash_type = EcdsaSighashType::All;
+    let mut sighasher = SighashCache::new(unsigned_tx);
+    let sighash = sighasher
+        .segwit_signature_hash(input_index, &witness_script, dummy_utxo.value, sighash_type)
+        .expect("failed to create sighash");
+
+    // Sign the sighash using the secp256k1 library (exported by rust-bitcoin).
+    let msg = Message::from_slice(sighash.as_byte_array()).expect("32 byte sighash");
+    let signature = ecdsa::Signature {
+        sig: secp.sign_ecdsa(&msg, &sk),
+        hash_ty: sighash_type,
+    };
+
+    // Update the witness stack: the input to the script (the signature) then the script itself.
+    let witness = sighasher
+        .witness_mut(input_index)
+        .expect("getting mutable witness reference should work");
+    witness.push(signature.to_vec());
+    witness.push(witness_script.as_bytes());
+
+    // Get the signed transaction.
+    let tx = sighasher.into_transaction();
+
+    // Check the script executes successfully for our transaction.
+    let stack = vec![signature.to_vec()];
+    execute(
+        &secp,
+        &witness_script,
+        stack,
+        &tx,
+        input_index,
+        dummy_utxo.value,
+    )
+    .expect("script executes");
+
+    // With a sequence number one block short the script fails. The sequence number is covered by
+    // the signature so we could not just change it anyway.
+    let mut early_tx = tx.clone();
+    early_tx.input[input_index].sequence = Sequence::from_height(LOCK_BLOCKS - 1);
+    let stack = vec![signature.to_vec()];
+    let result = execute(
+        &secp,
+        &witness_script,
+        stack,
+        &early_tx,
+        input_index,
+        dummy_utxo.value,
+    );
+    assert!(matches!(result, Err(ScriptError::UnsatisfiedLockTime)));
+
+    // As does a sequence number that disables the relative lock time.
+    let mut disabled_tx = tx.clone();
+    disabled_tx.input[input_index].sequence = Sequence::ENABLE_RBF_NO_LOCKTIME;
+    let stack = vec![signature.to_vec()];
+    let result = execute(
+        &secp,
+        &witness_script,
+        stack,
+        &disabled_tx,
+        input_index,
+        dummy_utxo.value,
+    );
+    assert!(matches!(result, Err(ScriptError::UnsatisfiedLockTime)));
+
+    // BOOM! Transaction signed and ready to broadcast, once the output is LOCK_BLOCKS deep.
+    workshop_common::print_prevouts(&[dummy_utxo]);
+    println!("{}", serialize_hex(&tx));
+}
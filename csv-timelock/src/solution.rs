@@ -0,0 +1,21 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The reference solution, built with `--features solution`.
+
+use bitcoin::opcodes::all::{OP_CHECKSIG, OP_CSV, OP_DROP};
+use bitcoin::script::Builder;
+use bitcoin::{PublicKey, ScriptBuf, Sequence};
+
+/// Returns the script `<sequence> OP_CHECKSEQUENCEVERIFY OP_DROP <pk> OP_CHECKSIG`.
+///
+/// OP_CHECKSEQUENCEVERIFY leaves its argument on the stack (it was OP_NOP3 before BIP-112 and old
+/// nodes must see the same stack) so we drop it ourselves.
+pub fn csv_script(sequence: Sequence, pk: &PublicKey) -> ScriptBuf {
+    Builder::new()
+        .push_sequence(sequence)
+        .push_opcode(OP_CSV)
+        .push_opcode(OP_DROP)
+        .push_key(pk)
+        .push_opcode(OP_CHECKSIG)
+        .into_script()
+}
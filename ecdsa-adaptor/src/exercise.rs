@@ -0,0 +1,29 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The exercise, replace the `todo!()`s with your solution.
+
+use bitcoin::secp256k1::{ecdsa, PublicKey, Secp256k1, SecretKey, Signing};
+
+use super::AdaptorSignature;
+
+/// Decrypts `adaptor` with the secret `y`, `s = s' * y^-1`.
+///
+/// The signature's `r` is the x-coordinate of `adaptor.r`, use the scalar helpers in `main.rs`.
+/// Bitcoin only relays signatures with a low `s`.
+#[allow(unused_variables)]
+pub fn decrypt(adaptor: &AdaptorSignature, y: &SecretKey) -> ecdsa::Signature {
+    todo!("decrypt the pre-signature")
+}
+
+/// Recovers the secret for `encryption_point` from `adaptor` and the decrypted `sig`.
+///
+/// Undo the decryption, `y = s' * s^-1`, and remember that `s` may have been negated.
+#[allow(unused_variables)]
+pub fn recover<C: Signing>(
+    secp: &Secp256k1<C>,
+    adaptor: &AdaptorSignature,
+    sig: &ecdsa::Signature,
+    encryption_point: &PublicKey,
+) -> SecretKey {
+    todo!("recover the secret from the signature")
+}
@@ -0,0 +1,14 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Hints for the exercise, from least to most specific.
+
+pub const HINTS: &[&str] = &[
+    "Decrypting is one scalar operation, `s = s' * y^-1`, use the `mul` and `invert` helpers in \
+     `main.rs`. The `r` of the signature is the x-coordinate of `adaptor.r`, see `x_coordinate`.",
+    "Build the 64 byte compact signature, `r` then `s` as big endian bytes, with \
+     `ecdsa::Signature::from_compact`. Then call `normalize_s()`, Bitcoin only relays signatures \
+     with a low `s`.",
+    "To recover, read `s` back from `sig.serialize_compact()[32..]` and compute `y = s' * s^-1`, \
+     the `secret_key` helper turns it into a `SecretKey`. Normalizing may have negated `s`, so if \
+     `y.public_key(secp)` is not `encryption_point` return `y.negate()`.",
+];
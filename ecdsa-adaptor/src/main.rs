@@ -0,0 +1,359 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Demonstrate ECDSA adaptor signatures, the primitive underlying atomic swaps and DLCs.
+//!
+//! An adaptor signature (or "pre-signature") is a signature encrypted to a point `Y = y * G`. Anyone
+//! can check that it will be a valid signature once decrypted, only someone who knows `y` can
+//! decrypt it, and once the decrypted signature is published the signer learns `y`.
+//!
+//! Here Alice pays Bob in exchange for a secret, e.g. Bob reveals the secret to claim coins from
+//! Alice on another chain. rust-bitcoin does not implement adaptor signatures so the scheme is
+//! written out here with the scalar and point operations from the secp256k1 library. This is for
+//! learning only, use a reviewed implementation (e.g. `secp256k1-zkp`) for real coins.
+
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::locktime::absolute;
+use bitcoin::secp256k1::{
+    rand, Message, PublicKey, Scalar, Secp256k1, SecretKey, Signing, Verification,
+};
+use bitcoin::sighash::{EcdsaSighashType, SighashCache};
+use bitcoin::{ScriptBuf, Sequence, Transaction, TxIn, TxOut, WPubkeyHash, Witness};
+use workshop_common::segwit_v0::{dummy_unspent_transaction_output, receivers_address};
+use workshop_common::{CHANGE_AMOUNT, SPEND_AMOUNT};
+
+// The exercise is `decrypt` and `recover` in `src/exercise.rs`, building with `--features solution`
+// swaps in the reference solution from `src/solution.rs`.
+#[cfg(not(feature = "solution"))]
+mod exercise;
+#[cfg(feature = "solution")]
+#[path = "solution.rs"]
+mod exercise;
+
+use exercise::{decrypt, recover};
+
+mod hints;
+
+fn main() {
+    // Stuck? Run `cargo run -- hint 1`.
+    workshop_common::hint_command(hints::HINTS);
+
+    // We need a signing secp256k1 context, if you have not seen this before just pass it in when
+    // needed and otherwise ignore it.
+    let secp = Secp256k1::new();
+
+    // Bob's secret, he gives Alice the encryption point `Y` only.
+    let (y, encryption_point) = generate_keys(&secp);
+
+    // Get a secret key Alice controls and the pubkeyhash of the associated pubkey.
+    // In a real application these would come from a stored secret.
+    let sk = workshop_common::secret_key(0);
+    let pk = sk.public_key(&secp);
+    let wpkh = wpubkey_hash(&pk);
+
+    // Get an address to send to, Bob's address.
+    let address = receivers_address();
+
+    // Get an unspent output that is locked to Alice's key.
+    // In a real application these would come from the chain.
+    let (dummy_out_point, dummy_utxo) = dummy_unspent_transaction_output(&wpkh);
+
+    // The input for the transaction we are constructing.
+    let input = TxIn {
+        previous_output: dummy_out_point, // The dummy output we are spending.
+        script_sig: ScriptBuf::default(), // For a p2wpkh script_sig is empty.
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        witness: Witness::default(), // Filled in after signing.
+    };
+
+    // The spend output is locked to a key controlled by Bob.
+    let spend = TxOut {
+        value: SPEND_AMOUNT,
+        script_pubkey: address.script_pubkey(),
+    };
+
+    // The change output is locked to a key controlled by Alice.
+    let change = TxOut {
+        value: CHANGE_AMOUNT,
+        script_pubkey: ScriptBuf::new_v0_p2wpkh(&wpkh), // Change comes back to us.
+    };
+
+    // The transaction Alice pays Bob with.
+    let unsigned_tx = Transaction {
+        version: 2,                          // Post BIP-68.
+        lock_time: absolute::LockTime::ZERO, // Ignore the locktime.
+        input: vec![input],                  // Input goes into index 0.
+        output: vec![spend, change],         // Outputs, order does not matter.
+    };
+    let input_index = 0;
+
+    // Get the sighash to sign, exactly as in `sign-segwit-v0`.
+    let script_code = dummy_utxo
+        .script_pubkey
+        .p2wpkh_script_code()
+        .expect("valid p2wpkh script pubkey");
+    let sighash_type = EcdsaSighashType::All;
+    let mut sighasher = SighashCache::new(unsigned_tx);
+    let sighash = sighasher
+        .segwit_signature_hash(input_index, &script_code, dummy_utxo.value, sighash_type)
+        .expect("failed to create sighash");
+    let msg = Message::from_slice(sighash.as_byte_array()).expect("32 byte sighash");
+
+    // Alice signs the transaction, but encrypts the signature to Bob's point. On its own the
+    // pre-signature does not let Bob spend Alice's coins.
+    let pre_signature = encrypted_sign(&secp, &sk, &msg, &encryption_point);
+
+    // Bob checks the pre-signature, if it verifies he knows he can decrypt it to a valid signature
+    // so it is safe to go ahead with his side of the swap.
+    assert!(encrypted_verify(
+        &secp,
+        &pk,
+        &msg,
+        &encryption_point,
+        &pre_signature
+    ));
+
+    // Without the secret the pre-signature is useless, decrypting with any other key gives an
+    // invalid signature.
+    let (wrong_y, _) = generate_keys(&secp);
+    let wrong_sig = decrypt(&pre_signature, &wrong_y);
+    assert!(secp.verify_ecdsa(&msg, &wrong_sig, &pk).is_err());
+
+    // Bob decrypts the signature with his secret and gets a normal ECDSA signature, this is the
+    // exercise (and so is recovering the secret below).
+    let sig = decrypt(&pre_signature, &y);
+    secp.verify_ecdsa(&msg, &sig, &pk)
+        .expect("decrypted signature verifies against Alice's key");
+
+    // Bob completes the transaction and broadcasts it.
+    let mut sig_with_flag = sig.serialize_der().to_vec();
+    sig_with_flag.push(sighash_type.to_u32() as u8);
+    let witness = sighasher
+        .witness_mut(input_index)
+        .expect("getting mutable witness reference should work");
+    witness.push(sig_with_flag);
+    witness.push(pk.serialize());
+    let tx = sighasher.into_transaction();
+
+    // Alice sees the signature on chain and recovers Bob's secret from it, e.g. to claim her coins
+    // on the other chain.
+    let recovered = recover(&secp, &pre_signature, &sig, &encryption_point);
+    assert_eq!(recovered, y);
+
+    // BOOM! Transaction signed and ready to broadcast.
+    workshop_common::print_prevouts(&[dummy_utxo]);
+    println!("{}", serialize_hex(&tx));
+}
+
+/// An ECDSA signature encrypted to the point `Y`.
+struct AdaptorSignature {
+    /// The final nonce point `R = k * Y`, the signature's `r` is its x-coordinate.
+    r: PublicKey,
+    /// The nonce point before encryption `R' = k * G`.
+    r_prime: PublicKey,
+    /// The encrypted `s`, `s' = k^-1 * (m + r * x)`.
+    s_prime: Scalar,
+    /// Proof that `R` and `R'` use the same `k`.
+    proof: DleqProof,
+}
+
+/// Returns an adaptor signature for `msg` by `sk`, encrypted to `encryption_point`.
+fn encrypted_sign<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    sk: &SecretKey,
+    msg: &Message,
+    encryption_point: &PublicKey,
+) -> AdaptorSignature {
+    let k = SecretKey::new(&mut rand::thread_rng());
+    let r = encryption_point
+        .mul_tweak(secp, &Scalar::from(k))
+        .expect("not zero");
+    let r_prime = k.public_key(secp);
+
+    // Sign as for ECDSA, except that `r` comes from `R = k * Y` not `k * G`.
+    let m = message_scalar(msg);
+    let s_prime = mul(
+        invert(Scalar::from(k)),
+        add(m, mul(x_coordinate(&r), Scalar::from(*sk))),
+    );
+    let proof = DleqProof::new(secp, &k, encryption_point, &r_prime, &r);
+
+    AdaptorSignature {
+        r,
+        r_prime,
+        s_prime,
+        proof,
+    }
+}
+
+/// Returns true if `adaptor` decrypts, with the secret for `encryption_point`, to a valid
+/// signature for `msg` by `pk`.
+fn encrypted_verify<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    pk: &PublicKey,
+    msg: &Message,
+    encryption_point: &PublicKey,
+    adaptor: &AdaptorSignature,
+) -> bool {
+    // `R` must be `R'` encrypted to `Y`, without this Alice could choose an `R` that Bob can not
+    // decrypt a signature for.
+    if !adaptor
+        .proof
+        .verify(secp, encryption_point, &adaptor.r_prime, &adaptor.r)
+    {
+        return false;
+    }
+
+    // The ECDSA verification equation, with `R'` in place of `R`: `R' = s'^-1 * (m * G + r * X)`.
+    let s_inv = invert(adaptor.s_prime);
+    let u1 = mul(message_scalar(msg), s_inv);
+    let u2 = mul(x_coordinate(&adaptor.r), s_inv);
+    let u1_g = secret_key(u1).public_key(secp);
+    let u2_x = pk.mul_tweak(secp, &u2).expect("not zero");
+    u1_g.combine(&u2_x).ok() == Some(adaptor.r_prime)
+}
+
+/// A proof of discrete log equality, that `R' = k * G` and `R = k * Y` for the same unknown `k`
+/// (a Chaum-Pedersen proof).
+struct DleqProof {
+    /// `t * G` for a random `t`.
+    a_g: PublicKey,
+    /// `t * Y` for the same `t`.
+    a_y: PublicKey,
+    /// `z = t + c * k`.
+    z: Scalar,
+}
+
+impl DleqProof {
+    fn new<C: Signing + Verification>(
+        secp: &Secp256k1<C>,
+        k: &SecretKey,
+        y: &PublicKey,
+        r_prime: &PublicKey,
+        r: &PublicKey,
+    ) -> Self {
+        let t = SecretKey::new(&mut rand::thread_rng());
+        let a_g = t.public_key(secp);
+        let a_y = y.mul_tweak(secp, &Scalar::from(t)).expect("not zero");
+        let c = Self::challenge(y, r_prime, r, &a_g, &a_y);
+        let z = add(Scalar::from(t), mul(c, Scalar::from(*k)));
+        DleqProof { a_g, a_y, z }
+    }
+
+    /// Checks `z * G = A_G + c * R'` and `z * Y = A_Y + c * R`.
+    fn verify<C: Signing + Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        y: &PublicKey,
+        r_prime: &PublicKey,
+        r: &PublicKey,
+    ) -> bool {
+        let c = Self::challenge(y, r_prime, r, &self.a_g, &self.a_y);
+
+        let z_g = secret_key(self.z).public_key(secp);
+        let z_y = y.mul_tweak(secp, &self.z).expect("not zero");
+        let c_r_prime = r_prime.mul_tweak(secp, &c).expect("not zero");
+        let c_r = r.mul_tweak(secp, &c).expect("not zero");
+
+        self.a_g.combine(&c_r_prime).ok() == Some(z_g) && self.a_y.combine(&c_r).ok() == Some(z_y)
+    }
+
+    /// The challenge commits to everything, which makes the proof non-interactive (Fiat-Shamir).
+    fn challenge(
+        y: &PublicKey,
+        r_prime: &PublicKey,
+        r: &PublicKey,
+        a_g: &PublicKey,
+        a_y: &PublicKey,
+    ) -> Scalar {
+        scalar(tagged_hash(
+            "workshop/adaptor-dleq",
+            &[
+                &y.serialize(),
+                &r_prime.serialize(),
+                &r.serialize(),
+                &a_g.serialize(),
+                &a_y.serialize(),
+            ],
+        ))
+    }
+}
+
+/// Returns the x-coordinate of `point` as a scalar, this is the `r` of an ECDSA signature.
+fn x_coordinate(point: &PublicKey) -> Scalar {
+    let x = point.serialize()[1..].try_into().expect("32 bytes");
+    // The x-coordinate is less than the field size, which is only just bigger than the curve
+    // order. Hitting the gap is as likely as guessing a secret key.
+    scalar(x)
+}
+
+/// Returns the message as a scalar, the `m` of an ECDSA signature.
+fn message_scalar(msg: &Message) -> Scalar {
+    scalar(*msg.as_ref())
+}
+
+// The secp256k1 library does scalar arithmetic on secret keys, which can not be zero. Hitting zero
+// is as likely as guessing a secret key so we just panic.
+
+fn add(a: Scalar, b: Scalar) -> Scalar {
+    Scalar::from(secret_key(a).add_tweak(&b).expect("not zero"))
+}
+
+fn mul(a: Scalar, b: Scalar) -> Scalar {
+    Scalar::from(secret_key(a).mul_tweak(&b).expect("not zero"))
+}
+
+/// Returns `a^-1`, computed as `a^(n - 2)` (Fermat's little theorem) where `n` is the curve order.
+fn invert(a: Scalar) -> Scalar {
+    const N_MINUS_2: [u8; 32] = [
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xfe, 0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b, 0xbf, 0xd2, 0x5e, 0x8c, 0xd0, 0x36,
+        0x41, 0x3f,
+    ];
+    let mut result = Scalar::ONE;
+    for byte in N_MINUS_2 {
+        for bit in (0..8).rev() {
+            result = mul(result, result);
+            if byte >> bit & 1 == 1 {
+                result = mul(result, a);
+            }
+        }
+    }
+    result
+}
+
+fn secret_key(a: Scalar) -> SecretKey {
+    SecretKey::from_slice(&a.to_be_bytes()).expect("not zero")
+}
+
+/// Interprets a hash as a scalar, the chance of it being out of range is negligible.
+fn scalar(hash: [u8; 32]) -> Scalar {
+    Scalar::from_be_bytes(hash).expect("hash is less than the curve order")
+}
+
+/// The BIP-340 tagged hash `sha256(sha256(tag) || sha256(tag) || data)`.
+fn tagged_hash(tag: &str, data: &[&[u8]]) -> [u8; 32] {
+    let tag = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    for data in data {
+        engine.input(data);
+    }
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+/// Generates a random key pair, for Bob's secret.
+fn generate_keys<C: Signing>(secp: &Secp256k1<C>) -> (SecretKey, PublicKey) {
+    let sk = SecretKey::new(&mut rand::thread_rng());
+    let pk = sk.public_key(secp);
+
+    (sk, pk)
+}
+
+/// Returns the p2wpkh key hash for `pk`.
+fn wpubkey_hash(pk: &PublicKey) -> WPubkeyHash {
+    bitcoin::PublicKey::new(*pk)
+        .wpubkey_hash()
+        .expect("key is compressed")
+}
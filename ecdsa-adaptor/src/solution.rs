@@ -0,0 +1,39 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The reference solution, built with `--features solution`.
+
+use bitcoin::secp256k1::{ecdsa, PublicKey, Scalar, Secp256k1, SecretKey, Signing};
+
+use super::{invert, mul, secret_key, x_coordinate, AdaptorSignature};
+
+/// Decrypts `adaptor` with the secret `y`, `s = s' * y^-1`.
+pub fn decrypt(adaptor: &AdaptorSignature, y: &SecretKey) -> ecdsa::Signature {
+    let s = mul(adaptor.s_prime, invert(Scalar::from(*y)));
+
+    let mut compact = [0; 64];
+    compact[..32].copy_from_slice(&x_coordinate(&adaptor.r).to_be_bytes());
+    compact[32..].copy_from_slice(&s.to_be_bytes());
+    let mut sig = ecdsa::Signature::from_compact(&compact).expect("64 bytes");
+    // Bitcoin only relays signatures with a low `s`, this may negate `s`.
+    sig.normalize_s();
+    sig
+}
+
+/// Recovers the secret for `encryption_point` from `adaptor` and the decrypted `sig`.
+pub fn recover<C: Signing>(
+    secp: &Secp256k1<C>,
+    adaptor: &AdaptorSignature,
+    sig: &ecdsa::Signature,
+    encryption_point: &PublicKey,
+) -> SecretKey {
+    let s = Scalar::from_be_bytes(sig.serialize_compact()[32..].try_into().expect("32 bytes"))
+        .expect("valid s");
+    let y = secret_key(mul(adaptor.s_prime, invert(s)));
+
+    // If `s` was negated when normalizing we get `-y`.
+    if y.public_key(secp) == *encryption_point {
+        y
+    } else {
+        y.negate()
+    }
+}
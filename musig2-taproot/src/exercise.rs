@@ -0,0 +1,19 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The exercise, replace the `todo!()` with your solution.
+
+use bitcoin::secp256k1::{schnorr, Scalar};
+
+use super::{KeyAggContext, Session};
+
+impl Session {
+    /// Adds up the partial signatures and the tweak, returns the final signature.
+    ///
+    /// The partial signatures only sum to a signature for the untweaked key, the signers never
+    /// saw the taproot tweak so add `e * tacc` (negated if the tweaked key has an odd y). The
+    /// signature is the x-only `R` followed by `s`.
+    #[allow(unused_variables)]
+    pub fn aggregate(&self, key_agg: &KeyAggContext, partials: &[Scalar]) -> schnorr::Signature {
+        todo!("aggregate the partial signatures")
+    }
+}
@@ -0,0 +1,13 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Hints for the exercise, from least to most specific.
+
+pub const HINTS: &[&str] = &[
+    "The partial signatures are scalars, adding them up with the `add` helper gives the `s` of a \
+     signature for the untweaked aggregate key.",
+    "The signers never saw the taproot tweak. If `key_agg.tacc` is set add `mul(self.e, tacc)` to \
+     `s`, negated with `negate` if the tweaked key `key_agg.q` has an odd y (see `has_even_y`).",
+    "A BIP-340 signature is the x-only nonce point then `s`. Copy \
+     `self.r.x_only_public_key().0.serialize()` and `s.to_be_bytes()` into a 64 byte array and \
+     make the signature with `schnorr::Signature::from_slice`.",
+];
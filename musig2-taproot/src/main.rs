@@ -0,0 +1,350 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Demonstrate two parties key path spending a p2tr output with a MuSig2 signature (BIP-327).
+//!
+//! The output key is an aggregate of both parties' keys, on chain it looks like any other taproot
+//! key path spend. rust-bitcoin does not implement MuSig2 so the protocol is written out here with
+//! the scalar and point operations from the secp256k1 library. This is for learning only, use a
+//! reviewed implementation for real coins (nonce handling in particular is easy to get wrong).
+
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::key::{TapTweak, TweakedPublicKey, UntweakedPublicKey};
+use bitcoin::locktime::absolute;
+use bitcoin::secp256k1::{
+    rand, All, Message, PublicKey, Scalar, Secp256k1, SecretKey, Signing, Verification,
+};
+use bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
+use bitcoin::taproot::TapTweakHash;
+use bitcoin::{ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness};
+use workshop_common::taproot::receivers_address;
+use workshop_common::{CHANGE_AMOUNT, SPEND_AMOUNT};
+
+// The exercise is `Session::aggregate` in `src/exercise.rs`, building with `--features solution`
+// swaps in the reference solution from `src/solution.rs`.
+#[cfg(not(feature = "solution"))]
+mod exercise;
+#[cfg(feature = "solution")]
+#[path = "solution.rs"]
+mod exercise;
+
+mod hints;
+
+fn main() {
+    // Stuck? Run `cargo run -- hint 1`.
+    workshop_common::hint_command(hints::HINTS);
+
+    // We need a signing secp256k1 context, if you have not seen this before just pass it in when
+    // needed and otherwise ignore it.
+    let secp = Secp256k1::new();
+
+    // Each party has their own key and only ever shares the public key.
+    // In a real application these would be actual secrets, and the parties would not share a seed.
+    let [alice, bob] = [0, 1].map(|n| {
+        let sk = workshop_common::secret_key(n);
+        (sk, sk.public_key(&secp))
+    });
+
+    // Key aggregation, both parties do this themselves from the public keys.
+    let key_agg = KeyAggContext::new(&secp, &[alice.1, bob.1]);
+    let internal_key = key_agg.x_only_public_key();
+
+    // Taproot tweak the aggregate key (no script tree), the result is the output key.
+    let key_agg = key_agg.tap_tweak(&secp);
+    let output_key = key_agg.output_key();
+    // Same as tweaking the internal key the usual way.
+    assert_eq!(output_key, internal_key.tap_tweak(&secp, None).0);
+
+    // Get an unspent output that is locked to the aggregate key.
+    // In a real application these would come from the chain.
+    let (dummy_out_point, dummy_utxo) =
+        workshop_common::dummy_utxo(0, ScriptBuf::new_v1_p2tr_tweaked(output_key));
+
+    // Get an address to send to.
+    let address = receivers_address();
+
+    // The input for the transaction we are constructing.
+    let input = TxIn {
+        previous_output: dummy_out_point, // The dummy output we are spending.
+        script_sig: ScriptBuf::default(), // For a p2tr script_sig is empty.
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        witness: Witness::default(), // Filled in after signing.
+    };
+
+    // The spend output is locked to a key controlled by the receiver.
+    let spend = TxOut {
+        value: SPEND_AMOUNT,
+        script_pubkey: address.script_pubkey(),
+    };
+
+    // The change output goes back to the aggregate key.
+    let change = TxOut {
+        value: CHANGE_AMOUNT,
+        script_pubkey: ScriptBuf::new_v1_p2tr_tweaked(output_key),
+    };
+
+    // The transaction we want to sign and broadcast.
+    let unsigned_tx = Transaction {
+        version: 2,                          // Post BIP-68.
+        lock_time: absolute::LockTime::ZERO, // Ignore the locktime.
+        input: vec![input],                  // Input goes into index 0.
+        output: vec![spend, change],         // Outputs, order does not matter.
+    };
+    let input_index = 0;
+
+    // Get the sighash to sign, exactly as for a single signer key path spend.
+    let sighash_type = TapSighashType::Default;
+    let utxos = vec![dummy_utxo];
+    let prevouts = Prevouts::All(&utxos);
+
+    let mut sighasher = SighashCache::new(unsigned_tx);
+    let sighash = sighasher
+        .taproot_key_spend_signature_hash(input_index, &prevouts, sighash_type)
+        .expect("failed to construct sighash");
+    let msg = Message::from_slice(sighash.as_byte_array()).expect("32 byte sighash");
+
+    // Round 1: each party generates a secret nonce pair and sends the public half to the other.
+    let (alice_secnonce, alice_pubnonce) = nonce_gen(&secp);
+    let (bob_secnonce, bob_pubnonce) = nonce_gen(&secp);
+    let agg_nonce = nonce_agg(&[alice_pubnonce, bob_pubnonce]);
+
+    // Round 2: each party signs and sends their partial signature. The secret nonces are moved
+    // into `sign` so they can not be used twice, reusing a nonce leaks the secret key.
+    let session = Session::new(&secp, &key_agg, agg_nonce, &msg);
+    let alice_partial = session.sign(&secp, &key_agg, alice_secnonce, &alice.0);
+    let bob_partial = session.sign(&secp, &key_agg, bob_secnonce, &bob.0);
+
+    // Either party (or anyone else) adds up the partial signatures, this is the exercise.
+    let sig = session.aggregate(&key_agg, &[alice_partial, bob_partial]);
+
+    // The result is an ordinary BIP-340 signature for the output key.
+    secp.verify_schnorr(&sig, &msg, &output_key.to_inner())
+        .expect("signature verifies against the output key");
+
+    // Update the witness stack.
+    let signature = bitcoin::taproot::Signature {
+        sig,
+        hash_ty: sighash_type,
+    };
+    sighasher
+        .witness_mut(input_index)
+        .expect("getting mutable witness reference should work")
+        .push(signature.to_vec());
+
+    // Get the signed transaction.
+    let tx = sighasher.into_transaction();
+
+    // BOOM! Transaction signed and ready to broadcast.
+    workshop_common::print_prevouts(&utxos);
+    println!("{}", serialize_hex(&tx));
+}
+
+/// The aggregate public key along with what signers need to know about how it was made.
+struct KeyAggContext {
+    /// The individual public keys, sorted.
+    pubkeys: Vec<PublicKey>,
+    /// The aggregate key, the sum of each key multiplied by its coefficient.
+    q: PublicKey,
+    /// The tweak accumulator, so signers can account for tweaks applied after aggregation.
+    tacc: Option<Scalar>,
+    /// True if the key has been negated an odd number of times by tweaking (`gacc` in BIP-327).
+    negated: bool,
+}
+
+impl KeyAggContext {
+    /// Aggregates `pubkeys`.
+    fn new<C: Verification>(secp: &Secp256k1<C>, pubkeys: &[PublicKey]) -> Self {
+        // Sorting makes the aggregate key independent of the order the keys were given in.
+        let mut pubkeys = pubkeys.to_vec();
+        pubkeys.sort_by_key(|pk| pk.serialize());
+
+        let points = pubkeys
+            .iter()
+            .map(|pk| {
+                pk.mul_tweak(secp, &key_agg_coefficient(&pubkeys, pk))
+                    .expect("not zero")
+            })
+            .collect::<Vec<_>>();
+        let q = PublicKey::combine_keys(&points.iter().collect::<Vec<_>>()).expect("not infinity");
+
+        KeyAggContext {
+            pubkeys,
+            q,
+            tacc: None,
+            negated: false,
+        }
+    }
+
+    /// Applies the BIP-341 tweak for a key path only output.
+    fn tap_tweak<C: Verification>(mut self, secp: &Secp256k1<C>) -> Self {
+        let t = TapTweakHash::from_key_and_tweak(self.x_only_public_key(), None).to_scalar();
+
+        // An x-only tweak works on the even y version of the key.
+        if !has_even_y(&self.q) {
+            self.q = self.q.negate(secp);
+            self.negated = !self.negated;
+            self.tacc = self.tacc.map(negate);
+        }
+        self.q = self.q.add_exp_tweak(secp, &t).expect("not infinity");
+        self.tacc = Some(match self.tacc {
+            Some(tacc) => add(tacc, t),
+            None => t,
+        });
+        self
+    }
+
+    fn x_only_public_key(&self) -> UntweakedPublicKey {
+        self.q.x_only_public_key().0
+    }
+
+    /// Returns the output key, only valid once the taproot tweak has been applied.
+    fn output_key(&self) -> TweakedPublicKey {
+        TweakedPublicKey::dangerous_assume_tweaked(self.x_only_public_key())
+    }
+}
+
+/// Returns the coefficient `pk` is multiplied by in the aggregate key.
+///
+/// The coefficients stop a party choosing their key based on the others' keys so as to control
+/// the aggregate (a rogue key attack). The second distinct key gets 1, a small optimization.
+fn key_agg_coefficient(pubkeys: &[PublicKey], pk: &PublicKey) -> Scalar {
+    if pubkeys.iter().find(|other| *other != &pubkeys[0]) == Some(pk) {
+        return Scalar::ONE;
+    }
+    let list = pubkeys.iter().map(|pk| pk.serialize()).collect::<Vec<_>>();
+    let l = tagged_hash("KeyAgg list", &[&list.concat()]);
+    scalar(tagged_hash("KeyAgg coefficient", &[&l, &pk.serialize()]))
+}
+
+/// A secret nonce pair, deliberately not `Clone` or `Copy`.
+struct SecNonce(SecretKey, SecretKey);
+
+/// The public half of a nonce pair, sent to the other signers.
+#[derive(Clone, Copy)]
+struct PubNonce(PublicKey, PublicKey);
+
+/// Returns a fresh random nonce pair.
+///
+/// BIP-327 mixes more data into the nonces (the secret key, the message, ...) as protection
+/// against a bad random number generator, this is the bare minimum.
+fn nonce_gen(secp: &Secp256k1<All>) -> (SecNonce, PubNonce) {
+    let k1 = SecretKey::new(&mut rand::thread_rng());
+    let k2 = SecretKey::new(&mut rand::thread_rng());
+    let pubnonce = PubNonce(k1.public_key(secp), k2.public_key(secp));
+    (SecNonce(k1, k2), pubnonce)
+}
+
+/// Adds up the public nonces of all signers.
+fn nonce_agg(pubnonces: &[PubNonce]) -> PubNonce {
+    let r1 = pubnonces.iter().map(|nonce| &nonce.0).collect::<Vec<_>>();
+    let r2 = pubnonces.iter().map(|nonce| &nonce.1).collect::<Vec<_>>();
+    PubNonce(
+        PublicKey::combine_keys(&r1).expect("not infinity"),
+        PublicKey::combine_keys(&r2).expect("not infinity"),
+    )
+}
+
+/// Everything signers derive from the aggregate nonce and the message.
+struct Session {
+    /// The nonce coefficient, binds the second nonce to this session.
+    b: Scalar,
+    /// The final nonce point `R = R1 + b * R2`.
+    r: PublicKey,
+    /// The BIP-340 challenge.
+    e: Scalar,
+}
+
+impl Session {
+    fn new<C: Verification>(
+        secp: &Secp256k1<C>,
+        key_agg: &KeyAggContext,
+        agg_nonce: PubNonce,
+        msg: &Message,
+    ) -> Self {
+        let q = key_agg.x_only_public_key().serialize();
+        let b = scalar(tagged_hash(
+            "MuSig/noncecoef",
+            &[
+                &agg_nonce.0.serialize(),
+                &agg_nonce.1.serialize(),
+                &q,
+                msg.as_ref(),
+            ],
+        ));
+        let r2 = agg_nonce.1.mul_tweak(secp, &b).expect("not zero");
+        let r = agg_nonce.0.combine(&r2).expect("not infinity");
+        let e = scalar(tagged_hash(
+            "BIP0340/challenge",
+            &[&r.x_only_public_key().0.serialize(), &q, msg.as_ref()],
+        ));
+        Session { b, r, e }
+    }
+
+    /// Returns the partial signature `s = k1 + b * k2 + e * a * d`.
+    fn sign<C: Signing>(
+        &self,
+        secp: &Secp256k1<C>,
+        key_agg: &KeyAggContext,
+        secnonce: SecNonce,
+        sk: &SecretKey,
+    ) -> Scalar {
+        let pk = sk.public_key(secp);
+        assert!(key_agg.pubkeys.contains(&pk), "not one of the signers");
+
+        // BIP-340 only has even y nonces and keys, negate ours to match.
+        let (mut k1, mut k2) = (secnonce.0, secnonce.1);
+        if !has_even_y(&self.r) {
+            k1 = k1.negate();
+            k2 = k2.negate();
+        }
+        // The output key has an even y too, taking into account any negation while tweaking.
+        let mut d = *sk;
+        if has_even_y(&key_agg.q) == key_agg.negated {
+            d = d.negate();
+        }
+
+        let a = key_agg_coefficient(&key_agg.pubkeys, &pk);
+        let s = add(Scalar::from(k1), mul(Scalar::from(k2), self.b));
+        add(s, mul(mul(self.e, a), Scalar::from(d)))
+    }
+}
+
+// The secp256k1 library does scalar arithmetic on secret keys, which can not be zero. Hitting zero
+// is as likely as guessing a secret key so we just panic.
+
+fn add(a: Scalar, b: Scalar) -> Scalar {
+    Scalar::from(secret_key(a).add_tweak(&b).expect("not zero"))
+}
+
+fn mul(a: Scalar, b: Scalar) -> Scalar {
+    Scalar::from(secret_key(a).mul_tweak(&b).expect("not zero"))
+}
+
+fn negate(a: Scalar) -> Scalar {
+    Scalar::from(secret_key(a).negate())
+}
+
+fn secret_key(a: Scalar) -> SecretKey {
+    SecretKey::from_slice(&a.to_be_bytes()).expect("not zero")
+}
+
+/// Interprets a hash as a scalar, the chance of it being out of range is negligible.
+fn scalar(hash: [u8; 32]) -> Scalar {
+    Scalar::from_be_bytes(hash).expect("hash is less than the curve order")
+}
+
+fn has_even_y(pk: &PublicKey) -> bool {
+    pk.serialize()[0] == 0x02
+}
+
+/// The BIP-340 tagged hash `sha256(sha256(tag) || sha256(tag) || data)`.
+fn tagged_hash(tag: &str, data: &[&[u8]]) -> [u8; 32] {
+    let tag = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    for data in data {
+        engine.input(data);
+    }
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
@@ -0,0 +1,29 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The reference solution, built with `--features solution`.
+
+use bitcoin::secp256k1::{schnorr, Scalar};
+
+use super::{add, has_even_y, mul, negate, KeyAggContext, Session};
+
+impl Session {
+    /// Adds up the partial signatures and the tweak, returns the final signature.
+    pub fn aggregate(&self, key_agg: &KeyAggContext, partials: &[Scalar]) -> schnorr::Signature {
+        let mut s = partials.iter().copied().reduce(add).expect("at least one");
+        if let Some(tacc) = key_agg.tacc {
+            let tweak = mul(self.e, tacc);
+            s = add(
+                s,
+                if has_even_y(&key_agg.q) {
+                    tweak
+                } else {
+                    negate(tweak)
+                },
+            );
+        }
+        let mut sig = [0; 64];
+        sig[..32].copy_from_slice(&self.r.x_only_public_key().0.serialize());
+        sig[32..].copy_from_slice(&s.to_be_bytes());
+        schnorr::Signature::from_slice(&sig).expect("64 bytes")
+    }
+}
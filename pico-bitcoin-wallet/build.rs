@@ -0,0 +1,50 @@
+//! Captures build information printed by the `version` command.
+
+use std::process::Command;
+
+fn main() {
+    let commit = command_output("git", &["rev-parse", "--short", "HEAD"]);
+    println!("cargo:rustc-env=PICO_GIT_COMMIT={}", commit);
+
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_owned());
+    println!(
+        "cargo:rustc-env=PICO_RUSTC_VERSION={}",
+        command_output(&rustc, &["--version"])
+    );
+
+    let bitcoin = std::fs::read_to_string("Cargo.lock")
+        .ok()
+        .and_then(|lock| locked_version(&lock, "bitcoin"))
+        .unwrap_or_else(|| "unknown".to_owned());
+    println!("cargo:rustc-env=PICO_BITCOIN_VERSION={}", bitcoin);
+
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    let git_dir = command_output("git", &["rev-parse", "--git-dir"]);
+    if git_dir != "unknown" {
+        println!("cargo:rerun-if-changed={}/HEAD", git_dir);
+        println!("cargo:rerun-if-changed={}/refs", git_dir);
+    }
+}
+
+/// Runs `program` returning its trimmed stdout, or "unknown" if it fails.
+fn command_output(program: &str, args: &[&str]) -> String {
+    Command::new(program)
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+        .unwrap_or_else(|| "unknown".to_owned())
+}
+
+/// Finds the version of package `name` in the contents of a `Cargo.lock` file.
+fn locked_version(lock: &str, name: &str) -> Option<String> {
+    let name_line = format!("name = \"{}\"", name);
+    let mut lines = lock.lines();
+    lines.find(|line| *line == name_line)?;
+    let version = lines
+        .next()?
+        .strip_prefix("version = \"")?
+        .strip_suffix('"')?;
+    Some(version.to_owned())
+}
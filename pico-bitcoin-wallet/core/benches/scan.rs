@@ -0,0 +1,157 @@
+//! Scan throughput with synthetic blocks.
+//!
+//! `filter_block` is measured on its own in blocks/sec and outputs/sec, `filter_raw_block` adds
+//! decoding to that and `scan` is measured end to end against `MockChain` (download, decode, filter
+//! and store). Run with `cargo bench -p pico-wallet-core`.
+
+use std::collections::HashSet;
+
+use bitcoin::absolute::LockTime;
+use bitcoin::blockdata::constants::genesis_block;
+use bitcoin::consensus::encode::serialize;
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::SecretKey;
+use bitcoin::{
+    Block, Network, OutPoint, PrivateKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid,
+    WScriptHash, Witness,
+};
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use pico_wallet_core::config::Chain;
+use pico_wallet_core::db::Db;
+use pico_wallet_core::keys;
+use pico_wallet_core::mock::MockChain;
+use pico_wallet_core::scan;
+
+/// Inputs and outputs per transaction, roughly a typical payment with change.
+const INPUTS: usize = 2;
+const OUTPUTS: usize = 2;
+/// One in this many outputs pays to the wallet.
+const WALLET_EVERY: usize = 100;
+
+fn wallet_script() -> ScriptBuf {
+    let sk = SecretKey::from_slice(&[0x01; 32]).expect("valid secret key");
+    keys::address(&PrivateKey::new(sk, Network::Regtest), Chain::Regtest).script_pubkey()
+}
+
+/// Returns `n` transactions, each spending outputs of earlier ones so some of them are spends of
+/// wallet outputs.
+fn synthetic_txs(n: usize, wallet: &ScriptBuf, seed: u32) -> Vec<Transaction> {
+    let mut txs: Vec<Transaction> = Vec::with_capacity(n);
+    let mut output_count = 0;
+    for i in 0..n {
+        let input = (0..INPUTS)
+            .map(|j| {
+                let previous_output = match txs.len() {
+                    0 => OutPoint::new(Txid::all_zeros(), seed.wrapping_add(j as u32)),
+                    len => OutPoint::new(txs[(i * 7 + j) % len].txid(), j as u32),
+                };
+                TxIn {
+                    previous_output,
+                    script_sig: ScriptBuf::new(),
+                    sequence: Sequence::MAX,
+                    witness: Witness::from_slice(&[[0u8; 64]]),
+                }
+            })
+            .collect();
+        let output = (0..OUTPUTS)
+            .map(|_| {
+                output_count += 1;
+                let script_pubkey = if output_count % WALLET_EVERY == 0 {
+                    wallet.clone()
+                } else {
+                    let hash = WScriptHash::hash(&(seed as usize + output_count).to_le_bytes());
+                    ScriptBuf::new_v0_p2wsh(&hash)
+                };
+                TxOut {
+                    value: 10_000,
+                    script_pubkey,
+                }
+            })
+            .collect();
+        txs.push(Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input,
+            output,
+        });
+    }
+    txs
+}
+
+/// Returns a block of `txs` synthetic transactions, the header does not matter to the filter.
+fn synthetic_block(txs: usize, wallet: &ScriptBuf) -> Block {
+    Block {
+        header: genesis_block(Network::Regtest).header,
+        txdata: synthetic_txs(txs, wallet, 0),
+    }
+}
+
+fn filter_block(c: &mut Criterion) {
+    let wallet = wallet_script();
+
+    let mut group = c.benchmark_group("filter_block/blocks");
+    for txs in [100, 1_000, 4_000] {
+        let block = synthetic_block(txs, &wallet);
+        group.throughput(Throughput::Elements(1));
+        group.bench_with_input(BenchmarkId::from_parameter(txs), &block, |b, block| {
+            b.iter(|| scan::filter_block(block, &wallet, &mut HashSet::new()).unwrap())
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("filter_block/outputs");
+    for txs in [100, 1_000, 4_000] {
+        let block = synthetic_block(txs, &wallet);
+        let outputs = block.txdata.iter().map(|tx| tx.output.len()).sum::<usize>();
+        group.throughput(Throughput::Elements(outputs as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(txs), &block, |b, block| {
+            b.iter(|| scan::filter_block(block, &wallet, &mut HashSet::new()).unwrap())
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("filter_raw_block/outputs");
+    for txs in [100, 1_000, 4_000] {
+        let block = synthetic_block(txs, &wallet);
+        let outputs = block.txdata.iter().map(|tx| tx.output.len()).sum::<usize>();
+        let raw = serialize(&block);
+        group.throughput(Throughput::Elements(outputs as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(txs), &raw, |b, raw| {
+            b.iter(|| scan::filter_raw_block(raw, &wallet, &mut HashSet::new()).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn scan_pipeline(c: &mut Criterion) {
+    const BLOCKS: u32 = 50;
+    const TXS_PER_BLOCK: usize = 500;
+
+    let wallet = wallet_script();
+    let chain = MockChain::new();
+    for seed in 0..BLOCKS {
+        chain.mine(synthetic_txs(TXS_PER_BLOCK, &wallet, seed * 1_000));
+    }
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("failed to build runtime");
+
+    let mut group = c.benchmark_group("scan");
+    group.throughput(Throughput::Elements(u64::from(BLOCKS)));
+    group.sample_size(20);
+    group.bench_function("mock_chain", |b| {
+        b.iter_batched(
+            || Db::open_in_memory().unwrap(),
+            |mut db| {
+                runtime
+                    .block_on(scan::scan(&chain, &mut db, &wallet))
+                    .unwrap()
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, filter_block, scan_pipeline);
+criterion_main!(benches);
@@ -0,0 +1,14 @@
+//! Feeds arbitrary config files to the config parser, it must return an error rather than panic.
+
+#![no_main]
+
+use std::path::Path;
+
+use libfuzzer_sys::fuzz_target;
+use pico_wallet_core::config;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(toml_string) = std::str::from_utf8(data) {
+        let _ = config::parse(Path::new("config.toml"), toml_string);
+    }
+});
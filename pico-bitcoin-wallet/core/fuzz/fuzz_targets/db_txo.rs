@@ -0,0 +1,24 @@
+//! Feeds arbitrary rows of the `txos` table to the decoder, it must return an error rather than
+//! panic.
+
+#![no_main]
+
+use std::convert::TryInto;
+
+use libfuzzer_sys::fuzz_target;
+use pico_wallet_core::db;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 16 {
+        return;
+    }
+    let (numbers, txid) = data.split_at(16);
+    let idx = i64::from_le_bytes(numbers[..8].try_into().unwrap());
+    let amount_sat = i64::from_le_bytes(numbers[8..].try_into().unwrap());
+
+    if let Ok((txo, amount)) = db::decode_txo(txid, idx, amount_sat) {
+        assert_eq!(txid.len(), 32);
+        assert_eq!(i64::from(txo.vout), idx);
+        assert_eq!(amount.to_sat() as i64, amount_sat);
+    }
+});
@@ -0,0 +1,91 @@
+//! The wallet transactions as accounting entries, e.g. to export to a spreadsheet or tax tool.
+//!
+//! Only confirmed transactions are entries, unconfirmed ones may still change or never confirm.
+//! Conflicted transactions never confirm so are never entries.
+
+use bitcoin::{Amount, Txid};
+
+use crate::db::{Db, DbError};
+
+/// A confirmed wallet transaction and what it did to the balance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub txid: Txid,
+    /// The height of the block the transaction is in.
+    pub height: u64,
+    /// True if the transaction paid the wallet, false if the wallet paid somebody else.
+    pub incoming: bool,
+    /// What the wallet received, or paid to others not counting the fee.
+    ///
+    /// If the fee is not known what was paid includes it.
+    pub amount: Amount,
+    /// The fee the wallet paid, `None` for incoming payments or if not every input amount is known.
+    pub fee: Option<Amount>,
+    /// The confirmed balance after the transaction.
+    pub balance: Amount,
+    /// All outputs of the transaction, e.g. to tell who was paid.
+    pub outputs: Vec<bitcoin::TxOut>,
+}
+
+/// Returns an entry for every confirmed wallet transaction, oldest first.
+pub fn entries(db: &mut Db) -> Result<Vec<Entry>, DbError> {
+    let invalid = |txid: Txid| DbError::InvalidTx(format!("amounts of {} overflow", txid));
+
+    let mut balance = Amount::ZERO;
+    let mut entries = Vec::new();
+    for record in db.history()? {
+        let height = match record.height {
+            Some(height) => height,
+            None => continue,
+        };
+        let txid = record.txid;
+        balance = balance
+            .checked_add(record.received)
+            .and_then(|balance| balance.checked_sub(record.sent))
+            .ok_or_else(|| invalid(txid))?;
+        let outputs = db.tx_outputs(&txid)?;
+
+        let incoming = record.received > record.sent;
+        let (amount, fee) = if incoming {
+            (record.received - record.sent, None)
+        } else {
+            let net = record.sent - record.received;
+            let fee = fee(db, &txid, &outputs)?;
+            let amount = match fee {
+                Some(fee) => net.checked_sub(fee).ok_or_else(|| invalid(txid))?,
+                None => net,
+            };
+            (amount, fee)
+        };
+        entries.push(Entry {
+            txid,
+            height,
+            incoming,
+            amount,
+            fee,
+            balance,
+            outputs,
+        });
+    }
+    Ok(entries)
+}
+
+/// Returns the fee of the wallet transaction `txid`, `None` unless every input is a wallet output.
+fn fee(db: &mut Db, txid: &Txid, outputs: &[bitcoin::TxOut]) -> Result<Option<Amount>, DbError> {
+    let inputs = db.tx_inputs(txid)?;
+    if inputs.is_empty() {
+        return Ok(None);
+    }
+    let mut total = Amount::ZERO;
+    for input in &inputs {
+        match db.txo_amount(input)? {
+            Some(amount) => match total.checked_add(amount) {
+                Some(sum) => total = sum,
+                None => return Ok(None),
+            },
+            None => return Ok(None),
+        }
+    }
+    let paid = outputs.iter().map(|output| Amount::from_sat(output.value));
+    Ok(crate::fee::checked_sum(paid).and_then(|paid| total.checked_sub(paid)))
+}
@@ -0,0 +1,89 @@
+//! Checking the database against an independent view of the wallet's coins.
+//!
+//! The database only knows what scanning found, a missed block, a reorg it did not notice or a
+//! bug would leave it wrong without anything else noticing. The chain's UTXO set (e.g. from
+//! bitcoind's `scantxoutset`) is the authority: [`confirmed_unspent`] turns the database into the
+//! same view and [`compare`] reports where the two differ.
+
+use std::collections::{HashMap, HashSet};
+
+use bitcoin::{Amount, OutPoint};
+
+use crate::db::{Db, DbError};
+
+/// Where the database and the chain disagree about the wallet's unspent outputs.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Audit {
+    /// Unspent on chain but not in the database, e.g. paid in a block that was not scanned.
+    pub missing: Vec<(OutPoint, Amount)>,
+    /// Unspent in the database but not on chain, e.g. spent elsewhere or reorged out.
+    pub phantom: Vec<(OutPoint, Amount)>,
+    /// Unspent in both but for different amounts, the database's first.
+    pub mismatched: Vec<(OutPoint, Amount, Amount)>,
+}
+
+impl Audit {
+    /// Returns true if the database and the chain agree.
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty() && self.phantom.is_empty() && self.mismatched.is_empty()
+    }
+
+    /// Returns the number of outputs the two disagree about.
+    pub fn discrepancies(&self) -> usize {
+        self.missing.len() + self.phantom.len() + self.mismatched.len()
+    }
+}
+
+/// Returns the wallet outputs the database has as confirmed and unspent, as they would be in the
+/// chain's UTXO set.
+///
+/// Outputs of unconfirmed transactions are left out and outputs spent by unconfirmed transactions
+/// are included, the UTXO set knows nothing of the mempool.
+pub fn confirmed_unspent(db: &mut Db) -> Result<Vec<(OutPoint, Amount)>, DbError> {
+    let unconfirmed = db
+        .history()?
+        .into_iter()
+        .filter(|record| record.height.is_none())
+        .map(|record| record.txid)
+        .collect::<HashSet<_>>();
+
+    let mut utxos = db
+        .utxos()?
+        .into_iter()
+        .filter(|utxo| !unconfirmed.contains(&utxo.outpoint.txid))
+        .map(|utxo| (utxo.outpoint, utxo.amount))
+        .collect::<Vec<_>>();
+    for (_, outpoint) in db.pending_inputs()? {
+        if unconfirmed.contains(&outpoint.txid) || utxos.iter().any(|(known, _)| *known == outpoint)
+        {
+            continue;
+        }
+        if let Some(amount) = db.txo_amount(&outpoint)? {
+            utxos.push((outpoint, amount));
+        }
+    }
+    Ok(utxos)
+}
+
+/// Compares what the database has, see [`confirmed_unspent`], with what is on `chain`.
+pub fn compare(db: &[(OutPoint, Amount)], chain: &[(OutPoint, Amount)]) -> Audit {
+    let on_chain = chain.iter().copied().collect::<HashMap<_, _>>();
+    let in_db = db.iter().copied().collect::<HashMap<_, _>>();
+
+    let mut audit = Audit::default();
+    for (outpoint, amount) in db {
+        match on_chain.get(outpoint) {
+            None => audit.phantom.push((*outpoint, *amount)),
+            Some(chain_amount) if chain_amount != amount => {
+                audit.mismatched.push((*outpoint, *amount, *chain_amount))
+            }
+            Some(_) => {}
+        }
+    }
+    for (outpoint, amount) in chain {
+        if !in_db.contains_key(outpoint) {
+            audit.missing.push((*outpoint, *amount));
+        }
+    }
+    audit
+}
@@ -0,0 +1,273 @@
+//! The source of chain data, abstracted so the scanner does not care where blocks come from.
+//!
+//! Methods are async so that block downloads can overlap, the bitcoind RPC backend runs the
+//! blocking RPC calls on tokio's blocking thread pool.
+
+use std::future::Future;
+#[cfg(feature = "rpc")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "rpc")]
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "rpc")]
+use bitcoin::hashes::hex;
+use bitcoin::{BlockHash, FeeRate, Transaction, Txid};
+#[cfg(feature = "rpc")]
+use bitcoincore_rpc::RpcApi;
+
+#[cfg(feature = "rpc")]
+use crate::config::Config;
+#[cfg(feature = "rpc")]
+use crate::rpc::{self, RpcError};
+
+/// A source of blocks that transactions can also be broadcast to.
+pub trait Backend: Sync {
+    /// Returns the height of the chain tip.
+    fn block_count(&self) -> impl Future<Output = Result<u64, BackendError>> + Send;
+
+    /// Returns the hash of the block at `height` in the best chain.
+    fn block_hash(
+        &self,
+        height: u64,
+    ) -> impl Future<Output = Result<BlockHash, BackendError>> + Send;
+
+    /// Returns the consensus encoded block with `hash`.
+    ///
+    /// Raw bytes rather than a [`bitcoin::Block`] so the scanner can decode one transaction at a
+    /// time, a decoded block takes several times the memory of the encoded one.
+    fn raw_block(
+        &self,
+        hash: BlockHash,
+    ) -> impl Future<Output = Result<Vec<u8>, BackendError>> + Send;
+
+    /// Returns the fee rate needed to confirm within `target` blocks, if one can be estimated.
+    fn estimate_fee_rate(
+        &self,
+        target: u16,
+    ) -> impl Future<Output = Result<Option<FeeRate>, BackendError>> + Send;
+
+    /// Broadcasts `tx` to the network.
+    ///
+    /// Succeeds if the node already has `tx`, e.g. because an earlier broadcast timed out after
+    /// reaching it, so callers can record the transaction as theirs either way.
+    fn broadcast(&self, tx: Transaction)
+        -> impl Future<Output = Result<Txid, BackendError>> + Send;
+
+    /// Returns the txids of the transactions in the mempool.
+    fn mempool_txids(&self) -> impl Future<Output = Result<Vec<Txid>, BackendError>> + Send;
+
+    /// Returns the mempool transaction `txid`, `None` if it is no longer in the mempool.
+    fn mempool_tx(
+        &self,
+        txid: Txid,
+    ) -> impl Future<Output = Result<Option<Transaction>, BackendError>> + Send;
+}
+
+/// The error code bitcoind returns for a transaction it does not know about.
+#[cfg(feature = "rpc")]
+const RPC_INVALID_ADDRESS_OR_KEY: i32 = -5;
+
+/// The error code bitcoind returns when broadcasting a transaction that is already mined.
+#[cfg(feature = "rpc")]
+const RPC_VERIFY_ALREADY_IN_CHAIN: i32 = -27;
+
+/// The bitcoind RPC backend.
+///
+/// Holds several connections so that requests can be in flight at the same time, each
+/// `rpc::Client` only handles one request at a time.
+#[cfg(feature = "rpc")]
+pub struct RpcBackend {
+    clients: Vec<Arc<rpc::Client>>,
+    next: AtomicUsize,
+    limiter: Option<RateLimiter>,
+}
+
+#[cfg(feature = "rpc")]
+impl RpcBackend {
+    /// Opens `connections` connections to bitcoind, checking it is on the configured network.
+    pub fn connect(conf: &Config, connections: usize) -> Result<Self, RpcError> {
+        let mut clients = Vec::with_capacity(connections.max(1));
+        let client = rpc::Client::new(conf)?;
+        client.check_network(conf.chain, conf.signet_challenge.as_deref())?;
+        clients.push(Arc::new(client));
+        for _ in 1..connections {
+            clients.push(Arc::new(rpc::Client::new(conf)?));
+        }
+        Ok(RpcBackend {
+            clients,
+            next: AtomicUsize::new(0),
+            limiter: conf.rpc_rate_limit.map(RateLimiter::new),
+        })
+    }
+
+    /// Returns the first connection, for the blocking RPC calls that are not part of [`Backend`].
+    ///
+    /// Connections are kept alive for the life of the backend, a command should create one backend
+    /// and use it for everything rather than connecting again.
+    pub fn client(&self) -> &rpc::Client {
+        &self.clients[0]
+    }
+
+    /// Runs `call` with the next client on the blocking thread pool.
+    async fn run<T, F>(&self, call: F) -> Result<T, BackendError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&rpc::Client) -> bitcoincore_rpc::Result<T> + Send + 'static,
+    {
+        if let Some(limiter) = &self.limiter {
+            tokio::time::sleep(limiter.reserve()).await;
+        }
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.clients.len();
+        let client = Arc::clone(&self.clients[index]);
+        let result = tokio::task::spawn_blocking(move || call(&client))
+            .await
+            .map_err(BackendError::Task)?;
+        Ok(result?)
+    }
+}
+
+#[cfg(feature = "rpc")]
+impl Backend for RpcBackend {
+    async fn block_count(&self) -> Result<u64, BackendError> {
+        self.run(|client| client.get_block_count()).await
+    }
+
+    async fn block_hash(&self, height: u64) -> Result<BlockHash, BackendError> {
+        self.run(move |client| client.get_block_hash(height)).await
+    }
+
+    async fn raw_block(&self, hash: BlockHash) -> Result<Vec<u8>, BackendError> {
+        self.run(move |client| Ok(decode_hex(client.get_block_hex(&hash)?)?))
+            .await
+    }
+
+    async fn estimate_fee_rate(&self, target: u16) -> Result<Option<FeeRate>, BackendError> {
+        let estimate = self
+            .run(move |client| client.estimate_smart_fee(target, None))
+            .await?;
+        // bitcoind returns BTC/kvB, a kvB is 4000 weight units.
+        Ok(estimate
+            .fee_rate
+            .map(|rate| FeeRate::from_sat_per_kwu(rate.to_sat() / 4)))
+    }
+
+    async fn broadcast(&self, tx: Transaction) -> Result<Txid, BackendError> {
+        // bitcoind accepts a transaction already in its mempool again, not one already mined.
+        self.run(move |client| match client.send_raw_transaction(&tx) {
+            Err(bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Rpc(error)))
+                if error.code == RPC_VERIFY_ALREADY_IN_CHAIN =>
+            {
+                Ok(tx.txid())
+            }
+            result => result,
+        })
+        .await
+    }
+
+    async fn mempool_txids(&self) -> Result<Vec<Txid>, BackendError> {
+        self.run(|client| client.get_raw_mempool()).await
+    }
+
+    async fn mempool_tx(&self, txid: Txid) -> Result<Option<Transaction>, BackendError> {
+        self.run(
+            move |client| match client.get_raw_transaction(&txid, None) {
+                Ok(tx) => Ok(Some(tx)),
+                // Mined or evicted since we got the txids.
+                Err(bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Rpc(error)))
+                    if error.code == RPC_INVALID_ADDRESS_OR_KEY =>
+                {
+                    Ok(None)
+                }
+                Err(error) => Err(error),
+            },
+        )
+        .await
+    }
+}
+
+/// Decodes `hex` into its own buffer, so a block is not held as hex and as bytes at the same time.
+#[cfg(feature = "rpc")]
+fn decode_hex(hex: String) -> Result<Vec<u8>, hex::Error> {
+    if !hex.len().is_multiple_of(2) {
+        return Err(hex::Error::OddLengthString(hex.len()));
+    }
+    let digit = |c: u8| {
+        (c as char)
+            .to_digit(16)
+            .map(|d| d as u8)
+            .ok_or(hex::Error::InvalidChar(c))
+    };
+
+    // Byte `i` comes from characters `2i` and `2i + 1`, which have been read by the time it is
+    // written.
+    let mut buf = hex.into_bytes();
+    let len = buf.len() / 2;
+    for i in 0..len {
+        buf[i] = digit(buf[2 * i])? << 4 | digit(buf[2 * i + 1])?;
+    }
+    buf.truncate(len);
+    buf.shrink_to_fit();
+    Ok(buf)
+}
+
+/// A token bucket limiting how often a backend is called.
+///
+/// Holds up to one second's worth of calls, so short bursts go out at once but a long rescan
+/// settles at the configured rate instead of hammering a shared node.
+#[derive(Debug)]
+pub struct RateLimiter {
+    per_sec: f64,
+    state: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Calls that can be made right now, negative when calls are already waiting.
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// Allows `per_sec` calls per second, zero is treated as one.
+    pub fn new(per_sec: u32) -> Self {
+        let per_sec = f64::from(per_sec.max(1));
+        RateLimiter {
+            per_sec,
+            state: Mutex::new(Bucket {
+                tokens: per_sec,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes a token, returns how long to wait before making the call.
+    pub fn reserve(&self) -> Duration {
+        let mut bucket = self.state.lock().expect("rate limiter poisoned");
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.per_sec).min(self.per_sec) - 1.0;
+        bucket.updated = now;
+        if bucket.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / self.per_sec)
+        }
+    }
+}
+
+/// Errors returned by a [`Backend`].
+#[derive(Debug, thiserror::Error)]
+pub enum BackendError {
+    #[cfg(feature = "rpc")]
+    #[error(transparent)]
+    Rpc(#[from] bitcoincore_rpc::Error),
+    #[cfg(feature = "rpc")]
+    #[error("backend task failed")]
+    Task(#[source] tokio::task::JoinError),
+    #[error("{0} not found")]
+    NotFound(String),
+    #[error("transaction rejected: {0}")]
+    Rejected(String),
+}
@@ -0,0 +1,407 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use bitcoin::hashes::{hash160, hex, Hash};
+use bitcoin::secp256k1;
+use bitcoin::{Amount, FeeRate, Network, ScriptBuf};
+
+use crate::fee;
+use crate::recovery::Recovery;
+
+/// Gets the path to the mani configuration file, creating the project config directory in needed.
+///
+/// E.g., On Ubuntu: ~/.config/pico-bitcoin-wallet/config.toml
+#[cfg(feature = "fs")]
+pub fn config_file() -> Result<PathBuf, ConfigError> {
+    const CONFIG_FILE: &str = "config.toml";
+
+    let dir = dirs::config_dir().ok_or(ConfigError::NoConfigDir)?;
+    // If you change this directory then you'll likely want to change it in `db.rs` also.
+    let file = dir.join("pico-bitcoin-wallet").join(CONFIG_FILE);
+    Ok(file)
+}
+
+#[cfg(feature = "fs")]
+pub fn load() -> Result<Config, ConfigError> {
+    let conf_file = config_file()?;
+
+    match std::fs::read_to_string(&conf_file) {
+        Ok(toml_string) => parse(&conf_file, &toml_string),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Config::default(),
+        Err(source) => Err(ConfigError::Read {
+            path: conf_file,
+            source,
+        }),
+    }
+}
+
+/// Parses the contents of a configuration file, `path` is only used in messages.
+///
+/// Unknown keys are warned about rather than refused, so a config written for a newer version
+/// still loads.
+pub fn parse(path: &Path, toml_string: &str) -> Result<Config, ConfigError> {
+    let parse_error = |source| ConfigError::Parse {
+        path: path.to_owned(),
+        source,
+    };
+    let mut deserializer = toml::Deserializer::new(toml_string);
+    let config: ConfigFile = serde_ignored::deserialize(&mut deserializer, |key| {
+        eprintln!("warning: {}: unknown key `{}` ignored", path.display(), key)
+    })
+    .map_err(parse_error)?;
+    let auth = match (
+        config.bitcoind_cookie_path,
+        config.bitcoind_username,
+        config.bitcoind_password,
+    ) {
+        (None, None, None) => Auth::None,
+        (Some(cookie), None, None) => Auth::CookieFile(cookie),
+        (None, Some(username), Some(password)) => Auth::UserPass(username, password),
+        _ => return Err(ConfigError::InvalidAuth),
+    };
+    let chain = match config.network {
+        Some(network) => network.parse::<Chain>()?,
+        None => Chain::Regtest,
+    };
+    let bitcoind_uris = match config.bitcoind_uri {
+        Some(Uris::One(uri)) => vec![uri],
+        Some(Uris::Many(uris)) if uris.is_empty() => return Err(ConfigError::NoUris),
+        Some(Uris::Many(uris)) => uris,
+        None => vec![chain.default_uri()],
+    };
+    let signet_challenge = match config.signet_challenge {
+        Some(_) if chain != Chain::Signet => return Err(ConfigError::SignetChallengeWithoutSignet),
+        Some(hex) => Some(ScriptBuf::from_hex(&hex).map_err(ConfigError::InvalidSignetChallenge)?),
+        None => None,
+    };
+    let chain = match &signet_challenge {
+        Some(challenge) => Chain::CustomSignet(hash160::Hash::hash(challenge.as_bytes())),
+        None => chain,
+    };
+    let recovery = match (config.recovery_key, config.recovery_blocks) {
+        (None, None) => None,
+        (Some(key), Some(blocks)) => {
+            if blocks == 0 {
+                return Err(ConfigError::InvalidRecoveryBlocks);
+            }
+            let key = key.parse().map_err(ConfigError::InvalidRecoveryKey)?;
+            Some(Recovery { key, blocks })
+        }
+        _ => return Err(ConfigError::IncompleteRecovery),
+    };
+    Ok(Config {
+        chain,
+        signet_challenge,
+        bitcoind_uris,
+        bitcoind_auth: auth,
+        bitcoind_wallet: config.bitcoind_wallet,
+        proxy: config.proxy,
+        rpc_timeout: Duration::from_secs(config.rpc_timeout_secs),
+        rpc_retries: config.rpc_retries,
+        rpc_rate_limit: config.rpc_rate_limit,
+        dust_relay_fee: FeeRate::from_sat_per_kwu(config.dust_relay_fee.div_ceil(4)),
+        recovery,
+        reserve: Amount::from_sat(config.reserve_sat),
+        notify: config.notify,
+        price_url: config.price_url,
+        price_cache: Duration::from_secs(config.price_cache_secs),
+    })
+}
+
+/// Errors returned when loading the configuration.
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("the user config directory was not identified")]
+    NoConfigDir,
+    #[error("the user home directory was not identified")]
+    NoHomeDir,
+    #[error("failed to read file {}", path.display())]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to parse file {}", path.display())]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: toml::de::Error,
+    },
+    #[error(
+        "invalid configuration: either cookie path or both username and password must be specified"
+    )]
+    InvalidAuth,
+    #[error("invalid configuration: unknown network {0}")]
+    UnknownNetwork(String),
+    #[error("invalid configuration: bitcoind_uri is empty")]
+    NoUris,
+    #[error("invalid configuration: signet_challenge requires network = \"signet\"")]
+    SignetChallengeWithoutSignet,
+    #[error("invalid configuration: signet_challenge is not valid hex")]
+    InvalidSignetChallenge(#[source] hex::Error),
+    #[error("invalid configuration: recovery_key is not an x-only public key in hex")]
+    InvalidRecoveryKey(#[source] secp256k1::Error),
+    #[error("invalid configuration: recovery_key and recovery_blocks must be specified together")]
+    IncompleteRecovery,
+    #[error("invalid configuration: recovery_blocks must be at least 1")]
+    InvalidRecoveryBlocks,
+    #[error("failed to identify bitcoind configuration")]
+    NoBitcoind,
+    #[error("failed to check if bitcoind dir {} exists", path.display())]
+    BitcoindDir {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+}
+
+pub struct Config {
+    /// The chain we are operating on.
+    pub chain: Chain,
+    /// The block challenge of a custom signet, `None` means the default signet.
+    pub signet_challenge: Option<ScriptBuf>,
+    /// The nodes to connect to, in order of preference.
+    pub bitcoind_uris: Vec<String>,
+    pub bitcoind_auth: Auth,
+    /// Name of the bitcoind wallet to use, calls go to `/wallet/<name>` endpoints if set.
+    pub bitcoind_wallet: Option<String>,
+    /// SOCKS5 proxy to route RPC traffic through e.g., `socks5://127.0.0.1:9050` for Tor.
+    pub proxy: Option<String>,
+    /// Timeout applied when connecting to bitcoind and when waiting for each response.
+    pub rpc_timeout: Duration,
+    /// How many times to retry a call that failed because bitcoind could not be reached or was still
+    /// starting up.
+    pub rpc_retries: u32,
+    /// Maximum backend calls per second, `None` for no limit.
+    pub rpc_rate_limit: Option<u32>,
+    /// The fee rate dust limits are worked out at, outputs worth less than spending them at this
+    /// rate are not created.
+    pub dust_relay_fee: FeeRate,
+    /// Who can spend the wallet's outputs once they are old enough, `None` for key path only.
+    /// Changing it changes the wallet address.
+    pub recovery: Option<Recovery>,
+    /// Confirmed balance ordinary payments leave alone, e.g. to have something to bump fees with.
+    pub reserve: Amount,
+    /// Whether `daemon` shows a desktop notification for each incoming payment.
+    pub notify: bool,
+    /// Where to get the fiat price of bitcoin from, see `price::HttpProvider`. `None` disables fiat
+    /// values.
+    pub price_url: Option<String>,
+    /// How long a fetched price is used for.
+    pub price_cache: Duration,
+}
+
+#[cfg(feature = "fs")]
+impl Config {
+    fn default() -> Result<Self, ConfigError> {
+        let home_dir = dirs::home_dir().ok_or(ConfigError::NoHomeDir)?;
+        let bitcoind_dir = home_dir.join(".bitcoin");
+        let bitcoind_auth = match bitcoind_dir.metadata() {
+            Ok(_) => Auth::CookieFile(bitcoind_dir.join(".cookie")),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                if std::fs::metadata("/etc/bitcoin-rpc-proxy-regtest").is_ok() {
+                    Auth::UserPass("public".to_owned(), "public".to_owned())
+                } else {
+                    return Err(ConfigError::NoBitcoind);
+                }
+            }
+            Err(source) => {
+                return Err(ConfigError::BitcoindDir {
+                    path: bitcoind_dir,
+                    source,
+                })
+            }
+        };
+        Ok(Config {
+            chain: Chain::Regtest,
+            signet_challenge: None,
+            bitcoind_uris: vec![Chain::Regtest.default_uri()],
+            bitcoind_auth,
+            bitcoind_wallet: None,
+            proxy: None,
+            rpc_timeout: Duration::from_secs(DEFAULT_RPC_TIMEOUT_SECS),
+            rpc_retries: DEFAULT_RPC_RETRIES,
+            rpc_rate_limit: None,
+            dust_relay_fee: fee::DUST_RELAY_FEE,
+            recovery: None,
+            reserve: Amount::ZERO,
+            notify: false,
+            price_url: None,
+            price_cache: Duration::from_secs(DEFAULT_PRICE_CACHE_SECS),
+        })
+    }
+}
+
+/// How to authenticate to bitcoind.
+///
+/// Mirrors `bitcoincore_rpc::Auth` so that the configuration can be used without the RPC client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Auth {
+    None,
+    UserPass(String, String),
+    CookieFile(PathBuf),
+}
+
+/// The chains we support.
+///
+/// We cannot use `bitcoin::Network` directly because it does not know about testnet4.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chain {
+    Bitcoin,
+    Testnet,
+    Testnet4,
+    /// The default signet.
+    Signet,
+    /// A signet with its own block challenge, identified by the HASH160 of the challenge.
+    CustomSignet(hash160::Hash),
+    Regtest,
+}
+
+impl Chain {
+    /// All supported chains, custom signets are set up with `signet_challenge` instead.
+    pub const ALL: [Chain; 5] = [
+        Chain::Bitcoin,
+        Chain::Testnet,
+        Chain::Testnet4,
+        Chain::Signet,
+        Chain::Regtest,
+    ];
+
+    /// The network used for address and key encoding.
+    ///
+    /// Testnet4 uses the same encoding as testnet3.
+    pub fn network(self) -> Network {
+        match self {
+            Chain::Bitcoin => Network::Bitcoin,
+            Chain::Testnet | Chain::Testnet4 => Network::Testnet,
+            Chain::Signet | Chain::CustomSignet(_) => Network::Signet,
+            Chain::Regtest => Network::Regtest,
+        }
+    }
+
+    /// The chain name as reported by `bitcoind` in `getblockchaininfo`.
+    pub fn core_name(self) -> &'static str {
+        match self {
+            Chain::Bitcoin => "main",
+            Chain::Testnet => "test",
+            Chain::Testnet4 => "testnet4",
+            Chain::Signet | Chain::CustomSignet(_) => "signet",
+            Chain::Regtest => "regtest",
+        }
+    }
+
+    /// The default RPC port `bitcoind` listens on for this chain.
+    pub fn default_rpc_port(self) -> u16 {
+        match self {
+            Chain::Bitcoin => 8332,
+            Chain::Testnet => 18332,
+            Chain::Testnet4 => 48332,
+            Chain::Signet | Chain::CustomSignet(_) => 38332,
+            Chain::Regtest => 18443,
+        }
+    }
+
+    /// The RPC URI of a `bitcoind` running locally with default settings.
+    pub fn default_uri(self) -> String {
+        format!("http://127.0.0.1:{}", self.default_rpc_port())
+    }
+}
+
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Chain::CustomSignet(challenge) => return write!(f, "signet-{}", challenge),
+            Chain::Bitcoin => "bitcoin",
+            Chain::Testnet => "testnet",
+            Chain::Testnet4 => "testnet4",
+            Chain::Signet => "signet",
+            Chain::Regtest => "regtest",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for Chain {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, ConfigError> {
+        Chain::ALL
+            .iter()
+            .copied()
+            .find(|chain| chain.to_string() == s)
+            .ok_or_else(|| ConfigError::UnknownNetwork(s.to_owned()))
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    network: Option<String>,
+    #[serde(default)]
+    signet_challenge: Option<String>,
+    #[serde(default)]
+    bitcoind_uri: Option<Uris>,
+    #[serde(default)]
+    bitcoind_cookie_path: Option<std::path::PathBuf>,
+    #[serde(default)]
+    bitcoind_username: Option<String>,
+    #[serde(default)]
+    bitcoind_password: Option<String>,
+    #[serde(default)]
+    bitcoind_wallet: Option<String>,
+    #[serde(default)]
+    proxy: Option<String>,
+    #[serde(default = "default_rpc_timeout_secs")]
+    rpc_timeout_secs: u64,
+    #[serde(default = "default_rpc_retries")]
+    rpc_retries: u32,
+    #[serde(default)]
+    rpc_rate_limit: Option<u32>,
+    /// In sat/kvB like Core's `-dustrelayfee`, rounded up to a whole sat/kwu.
+    #[serde(default = "default_dust_relay_fee")]
+    dust_relay_fee: u64,
+    /// X-only public key in hex.
+    #[serde(default)]
+    recovery_key: Option<String>,
+    #[serde(default)]
+    recovery_blocks: Option<u16>,
+    #[serde(default)]
+    reserve_sat: u64,
+    #[serde(default)]
+    notify: bool,
+    #[serde(default)]
+    price_url: Option<String>,
+    #[serde(default = "default_price_cache_secs")]
+    price_cache_secs: u64,
+}
+
+const DEFAULT_RPC_TIMEOUT_SECS: u64 = 15;
+const DEFAULT_RPC_RETRIES: u32 = 2;
+const DEFAULT_PRICE_CACHE_SECS: u64 = 10 * 60;
+
+fn default_rpc_timeout_secs() -> u64 {
+    DEFAULT_RPC_TIMEOUT_SECS
+}
+
+fn default_rpc_retries() -> u32 {
+    DEFAULT_RPC_RETRIES
+}
+
+fn default_price_cache_secs() -> u64 {
+    DEFAULT_PRICE_CACHE_SECS
+}
+
+fn default_dust_relay_fee() -> u64 {
+    fee::DUST_RELAY_FEE.to_sat_per_kwu() * 4
+}
+
+/// `bitcoind_uri` may be a single URI or a list of URIs to fail over between.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum Uris {
+    One(String),
+    Many(Vec<String>),
+}
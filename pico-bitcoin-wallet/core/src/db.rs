@@ -0,0 +1,1109 @@
+#![allow(dead_code)]
+
+use std::io;
+use std::path::PathBuf;
+
+use core::convert::{TryFrom, TryInto};
+use rusqlite::{Connection, OptionalExtension, ToSql};
+
+use crate::config::Chain;
+use crate::scan::WalletTx;
+
+/// Gets the path to the database file, creating the project data directory if needed.
+///
+/// E.g., On Ubuntu: ~/.local/share/pico-bitcoin-wallet/data.db
+pub fn database_file(chain: Chain) -> Result<PathBuf, DbError> {
+    const DATABASE: &str = "data.db";
+
+    let data_dir = chain_data_dir(chain)?;
+    Ok(data_dir.join(DATABASE))
+}
+
+/// Gets the path to the private key file, creating the project data directory if needed.
+///
+/// E.g., On Ubuntu: ~/.local/share/pico-bitcoin-wallet/private.key
+pub fn private_key_file(chain: Chain) -> Result<PathBuf, DbError> {
+    const PRIVATE_KEY_FILE: &str = "private.key";
+
+    let data_dir = chain_data_dir(chain)?;
+    Ok(data_dir.join(PRIVATE_KEY_FILE))
+}
+
+/// Gets the path to the operation log, creating the project data directory if needed.
+///
+/// E.g., On Ubuntu: ~/.local/share/pico-bitcoin-wallet/ops.jsonl
+pub fn op_log_file(chain: Chain) -> Result<PathBuf, DbError> {
+    const OP_LOG_FILE: &str = "ops.jsonl";
+
+    let data_dir = chain_data_dir(chain)?;
+    Ok(data_dir.join(OP_LOG_FILE))
+}
+
+/// Gets the path to the control socket of a running `daemon`, creating the data directory if needed.
+///
+/// E.g., On Ubuntu: ~/.local/share/pico-bitcoin-wallet/daemon.sock
+pub fn control_socket_file(chain: Chain) -> Result<PathBuf, DbError> {
+    const CONTROL_SOCKET_FILE: &str = "daemon.sock";
+
+    let data_dir = chain_data_dir(chain)?;
+    Ok(data_dir.join(CONTROL_SOCKET_FILE))
+}
+
+/// Gets the path to the cache of fiat prices, creating the project data directory if needed.
+///
+/// Prices are the same whatever the chain.
+/// E.g., On Ubuntu: ~/.local/share/pico-bitcoin-wallet/prices.json
+pub fn price_cache_file() -> Result<PathBuf, DbError> {
+    const PRICE_CACHE_FILE: &str = "prices.json";
+
+    Ok(data_dir()?.join(PRICE_CACHE_FILE))
+}
+
+/// Gets the path to the data directory.
+///
+/// If the project data directory does not exist, attempts to create it.
+pub fn data_dir() -> Result<PathBuf, DbError> {
+    let dir = dirs::data_dir().ok_or(DbError::NoDataDir)?;
+    // If you change this directory then you'll likely want to change it in `config.rs` also.
+    let dir = dir.join("pico-bitcoin-wallet");
+    std::fs::create_dir_all(&dir).map_err(|source| DbError::CreateDir {
+        path: dir.clone(),
+        source,
+    })?;
+    Ok(dir)
+}
+
+/// Gets the path to the data directory for `chain`, creating it if needed.
+///
+/// Regtest uses the project data directory directly, other chains get a sub directory each so that
+/// keys and databases for different chains are never mixed up. Each custom signet is a chain of its
+/// own, e.g. `signet-<hash160 of the challenge>`.
+pub fn chain_data_dir(chain: Chain) -> Result<PathBuf, DbError> {
+    let dir = data_dir()?;
+    if chain == Chain::Regtest {
+        return Ok(dir);
+    }
+    let dir = dir.join(chain.to_string());
+    std::fs::create_dir_all(&dir).map_err(|source| DbError::CreateDir {
+        path: dir.clone(),
+        source,
+    })?;
+    Ok(dir)
+}
+
+const CREATE_TABLES: &str = r#"
+BEGIN;
+CREATE TABLE IF NOT EXISTS txos (txid BLOB, idx INTEGER, amount_sat INTEGER, spent_status INTEGER, PRIMARY KEY(txid, idx));
+CREATE TABLE IF NOT EXISTS last_block (block_height INTEGER);
+CREATE TABLE IF NOT EXISTS txs (txid BLOB PRIMARY KEY, block_height INTEGER, coinbase INTEGER, received_sat INTEGER, sent_sat INTEGER);
+CREATE TABLE IF NOT EXISTS tx_outputs (txid BLOB, idx INTEGER, script_pubkey BLOB, amount_sat INTEGER, PRIMARY KEY(txid, idx));
+CREATE TABLE IF NOT EXISTS tx_inputs (txid BLOB, idx INTEGER, prev_txid BLOB, prev_idx INTEGER, PRIMARY KEY(txid, idx));
+CREATE TABLE IF NOT EXISTS frozen (txid BLOB, idx INTEGER, PRIMARY KEY(txid, idx));
+CREATE TABLE IF NOT EXISTS conflicted (txid BLOB PRIMARY KEY);
+CREATE TABLE IF NOT EXISTS broadcasts (txid BLOB PRIMARY KEY, raw BLOB);
+CREATE TABLE IF NOT EXISTS watched (script_pubkey BLOB PRIMARY KEY);
+CREATE TABLE IF NOT EXISTS watched_txos (txid BLOB, idx INTEGER, script_pubkey BLOB, amount_sat INTEGER, spent_status INTEGER, PRIMARY KEY(txid, idx));
+CREATE TABLE IF NOT EXISTS payees (name TEXT PRIMARY KEY, address TEXT);
+CREATE TABLE IF NOT EXISTS scheduled (id INTEGER PRIMARY KEY, payee TEXT, amount_sat INTEGER, interval_secs INTEGER, next_due INTEGER);
+INSERT INTO last_block (block_height) SELECT 0 WHERE NOT EXISTS (SELECT * FROM last_block);
+COMMIT;
+"#;
+
+/// Inserts a new unspent txo, doing nothing if the outpoint is already known.
+///
+/// Makes scanning idempotent: scanning a range again (e.g. after a crash) neither fails on the
+/// primary key nor counts an output twice, and an output already marked as spent stays spent.
+const INSERT_TXO: &str = "INSERT INTO txos VALUES (?, ?, ?, 0) ON CONFLICT(txid, idx) DO NOTHING";
+
+/// Records a wallet transaction.
+///
+/// A transaction already recorded from the mempool gets the height of the block it confirmed in.
+/// Recording a confirmed transaction again changes nothing, for the same reason as above.
+const INSERT_TX: &str = "INSERT INTO txs VALUES (?, ?, ?, ?, ?) ON CONFLICT(txid) DO UPDATE SET \
+     block_height = excluded.block_height, received_sat = excluded.received_sat, \
+     sent_sat = excluded.sent_sat WHERE excluded.block_height IS NOT NULL";
+
+const INSERT_TX_OUTPUT: &str =
+    "INSERT INTO tx_outputs VALUES (?, ?, ?, ?) ON CONFLICT(txid, idx) DO NOTHING";
+
+const INSERT_TX_INPUT: &str =
+    "INSERT INTO tx_inputs VALUES (?, ?, ?, ?) ON CONFLICT(txid, idx) DO NOTHING";
+
+pub struct Db(Connection);
+
+impl Db {
+    pub fn open(chain: Chain) -> Result<Self, DbError> {
+        Db::open_at(database_file(chain)?)
+    }
+
+    /// Opens the database file at `path`, e.g. to prepare a fixture for tests.
+    pub fn open_at(path: PathBuf) -> Result<Self, DbError> {
+        let connection = match Connection::open(&path) {
+            Ok(connection) => connection,
+            Err(source) => return Err(DbError::Open { path, source }),
+        };
+        Db::init(connection)
+    }
+
+    /// Opens a fresh database that only lives in memory, handy for tests.
+    pub fn open_in_memory() -> Result<Self, DbError> {
+        let connection = Connection::open_in_memory().map_err(|source| DbError::Open {
+            path: PathBuf::from(":memory:"),
+            source,
+        })?;
+        Db::init(connection)
+    }
+
+    fn init(connection: Connection) -> Result<Self, DbError> {
+        connection
+            .execute_batch(CREATE_TABLES)
+            .sql_context("prepare the database tables")?;
+        Ok(Db(connection))
+    }
+
+    pub fn get_last_height(&mut self) -> Result<u64, DbError> {
+        let (height,) = self
+            .0
+            .query_row("SELECT block_height FROM  last_block", [], |row| {
+                row.try_into()
+            })
+            .sql_context("query last block height")?;
+        Ok(height)
+    }
+
+    pub fn store_txos(
+        &mut self,
+        txos: impl Iterator<
+            Item = Result<(impl std::borrow::Borrow<bitcoin::OutPoint>, bitcoin::Amount), DbError>,
+        >,
+        last_height: u64,
+    ) -> Result<(), DbError> {
+        use bitcoin::hashes::Hash;
+
+        let transaction = self
+            .0
+            .transaction()
+            .sql_context("begin database transaction")?;
+        for txo in txos {
+            let (prev_input, amount) = txo?;
+            let prev_input = prev_input.borrow();
+            let params = [
+                &(prev_input.txid.as_byte_array() as &[_]) as &dyn ToSql,
+                &prev_input.vout,
+                &amount.to_sat(),
+            ];
+            transaction
+                .execute(INSERT_TXO, params)
+                .with_sql_context(|| {
+                    format!(
+                        "insert txout {}:{} into the database",
+                        prev_input.txid, prev_input.vout
+                    )
+                })?;
+        }
+        let params = [&last_height as &dyn ToSql];
+        transaction
+            .execute("UPDATE last_block SET block_height = ?", params)
+            .sql_context("update last block in the database")?;
+        transaction
+            .commit()
+            .sql_context("commit database transaction")
+    }
+
+    /// Stores the wallet related parts of the block at `height` in a single database transaction.
+    ///
+    /// Inserts `received`, marks `spent` as spent, records `txs` for the history and records
+    /// `height` as the last scanned block. Storing the same block again changes nothing.
+    pub fn store_block(
+        &mut self,
+        height: u64,
+        received: &[(bitcoin::OutPoint, bitcoin::Amount)],
+        spent: &[bitcoin::OutPoint],
+        txs: &[WalletTx],
+    ) -> Result<(), DbError> {
+        use bitcoin::hashes::Hash;
+
+        let transaction = self
+            .0
+            .transaction()
+            .sql_context("begin database transaction")?;
+        for (txo, amount) in received {
+            let params = [
+                &(txo.txid.as_byte_array() as &[_]) as &dyn ToSql,
+                &txo.vout,
+                &amount.to_sat(),
+            ];
+            transaction
+                .execute(INSERT_TXO, params)
+                .with_sql_context(|| {
+                    format!("insert txout {}:{} into the database", txo.txid, txo.vout)
+                })?;
+        }
+        for txo in spent {
+            let params = [&(txo.txid.as_byte_array() as &[_]) as &dyn ToSql, &txo.vout];
+            transaction
+                .execute(
+                    "UPDATE txos SET spent_status = 1 WHERE txid = ? AND idx = ?",
+                    params,
+                )
+                .with_sql_context(|| format!("mark txo {}:{} as spent", txo.txid, txo.vout))?;
+        }
+        insert_txs(&transaction, Some(height), txs)?;
+        let params = [&height as &dyn ToSql];
+        transaction
+            .execute("UPDATE last_block SET block_height = ?", params)
+            .sql_context("update last block in the database")?;
+        transaction
+            .commit()
+            .sql_context("commit database transaction")
+    }
+
+    /// Records `txs` as the wallet transactions currently in the mempool.
+    ///
+    /// Unconfirmed transactions recorded before are forgotten, if they are still in the mempool
+    /// they are in `txs` too. Those in `conflicted` are kept but marked as conflicted instead, they
+    /// no longer count towards the balance. A conflicted transaction showing up in the mempool or a
+    /// block again is no longer conflicted.
+    pub fn store_unconfirmed(
+        &mut self,
+        txs: &[WalletTx],
+        conflicted: &[bitcoin::Txid],
+    ) -> Result<(), DbError> {
+        use bitcoin::hashes::Hash;
+
+        let transaction = self
+            .0
+            .transaction()
+            .sql_context("begin database transaction")?;
+        for txid in conflicted {
+            let params = [&(txid.as_byte_array() as &[_]) as &dyn ToSql];
+            transaction
+                .execute(
+                    "INSERT INTO conflicted VALUES (?) ON CONFLICT(txid) DO NOTHING",
+                    params,
+                )
+                .with_sql_context(|| format!("mark transaction {} as conflicted", txid))?;
+        }
+        transaction
+            .execute_batch(
+                "DELETE FROM tx_outputs WHERE txid IN (SELECT txid FROM txs WHERE block_height IS NULL) \
+                   AND txid NOT IN (SELECT txid FROM conflicted);
+                 DELETE FROM tx_inputs WHERE txid IN (SELECT txid FROM txs WHERE block_height IS NULL) \
+                   AND txid NOT IN (SELECT txid FROM conflicted);
+                 DELETE FROM txs WHERE block_height IS NULL \
+                   AND txid NOT IN (SELECT txid FROM conflicted);",
+            )
+            .sql_context("delete unconfirmed transactions")?;
+        insert_txs(&transaction, None, txs)?;
+        transaction
+            .commit()
+            .sql_context("commit database transaction")
+    }
+
+    /// Returns all unspent txos.
+    pub fn unspent(&mut self) -> Result<Vec<(bitcoin::OutPoint, bitcoin::Amount)>, DbError> {
+        let mut utxos = self.iter_unspent()?;
+        let result = utxos.iter()?.collect();
+        result
+    }
+
+    pub fn iter_unspent(&mut self) -> Result<Utxos<'_>, DbError> {
+        let prepared = self
+            .0
+            .prepare("SELECT txid, idx, amount_sat FROM txos WHERE spent_status = 0")
+            .sql_context("prepare query statement")?;
+        Ok(Utxos(prepared))
+    }
+
+    /// Returns the outpoints of all spent txos.
+    pub fn spent(&mut self) -> Result<Vec<bitcoin::OutPoint>, DbError> {
+        let mut statement = self
+            .0
+            .prepare("SELECT txid, idx, amount_sat FROM txos WHERE spent_status = 1")
+            .sql_context("prepare query statement")?;
+        let rows = statement
+            .query_map([], |row| row.try_into())
+            .sql_context("select spent txos")?;
+        rows.map(|result| {
+            let (txid, idx, amount_sat): (Vec<u8>, i64, i64) =
+                result.sql_context("convert SQL value to Rust type")?;
+            decode_txo(&txid, idx, amount_sat).map(|(outpoint, _amount)| outpoint)
+        })
+        .collect()
+    }
+
+    /// Returns the amount of the wallet output `txo`, spent or not, `None` if it is not ours.
+    pub fn txo_amount(
+        &mut self,
+        txo: &bitcoin::OutPoint,
+    ) -> Result<Option<bitcoin::Amount>, DbError> {
+        use bitcoin::hashes::Hash;
+
+        let params = [&(txo.txid.as_byte_array() as &[_]) as &dyn ToSql, &txo.vout];
+        let row: Option<(Vec<u8>, i64, i64)> = self
+            .0
+            .query_row(
+                "SELECT txid, idx, amount_sat FROM txos WHERE txid = ? AND idx = ?",
+                params,
+                |row| row.try_into(),
+            )
+            .optional()
+            .with_sql_context(|| format!("query txo {}:{}", txo.txid, txo.vout))?;
+        row.map(|(txid, idx, amount_sat)| {
+            decode_txo(&txid, idx, amount_sat).map(|(_outpoint, amount)| amount)
+        })
+        .transpose()
+    }
+
+    /// Returns the recorded wallet transactions, oldest first with the unconfirmed ones last.
+    pub fn history(&mut self) -> Result<Vec<TxRecord>, DbError> {
+        let mut statement = self
+            .0
+            .prepare(
+                "SELECT txid, block_height, received_sat, sent_sat, \
+                 txid IN (SELECT txid FROM conflicted) FROM txs \
+                 ORDER BY block_height IS NULL, block_height, rowid",
+            )
+            .sql_context("prepare query statement")?;
+        let rows = statement
+            .query_map([], |row| row.try_into())
+            .sql_context("select transactions")?;
+        rows.map(|result| {
+            let (txid, height, received_sat, sent_sat, conflicted): (
+                Vec<u8>,
+                Option<i64>,
+                i64,
+                i64,
+                bool,
+            ) = result.sql_context("convert SQL value to Rust type")?;
+            let height = height
+                .map(|height| {
+                    u64::try_from(height).map_err(|_| {
+                        DbError::InvalidTx(format!("block height {} is out of range", height))
+                    })
+                })
+                .transpose()?;
+            Ok(TxRecord {
+                txid: decode_txid(&txid).map_err(DbError::InvalidTx)?,
+                height,
+                received: decode_amount(received_sat).map_err(DbError::InvalidTx)?,
+                sent: decode_amount(sent_sat).map_err(DbError::InvalidTx)?,
+                conflicted,
+            })
+        })
+        .collect()
+    }
+
+    /// Returns all outputs of the recorded wallet transaction `txid`, in order.
+    pub fn tx_outputs(&mut self, txid: &bitcoin::Txid) -> Result<Vec<bitcoin::TxOut>, DbError> {
+        use bitcoin::hashes::Hash;
+
+        let mut statement = self
+            .0
+            .prepare("SELECT script_pubkey, amount_sat FROM tx_outputs WHERE txid = ? ORDER BY idx")
+            .sql_context("prepare query statement")?;
+        let params = [&(txid.as_byte_array() as &[_]) as &dyn ToSql];
+        let rows = statement
+            .query_map(params, |row| row.try_into())
+            .with_sql_context(|| format!("select outputs of {}", txid))?;
+        rows.map(|result| {
+            let (script_pubkey, amount_sat): (Vec<u8>, i64) =
+                result.sql_context("convert SQL value to Rust type")?;
+            let amount = decode_amount(amount_sat).map_err(DbError::InvalidTx)?;
+            Ok(bitcoin::TxOut {
+                value: amount.to_sat(),
+                script_pubkey: bitcoin::ScriptBuf::from_bytes(script_pubkey),
+            })
+        })
+        .collect()
+    }
+
+    /// Returns the outputs spent by the inputs of the recorded wallet transaction `txid`, in order.
+    ///
+    /// Empty for a transaction recorded before inputs were.
+    pub fn tx_inputs(&mut self, txid: &bitcoin::Txid) -> Result<Vec<bitcoin::OutPoint>, DbError> {
+        use bitcoin::hashes::Hash;
+
+        let mut statement = self
+            .0
+            .prepare("SELECT prev_txid, prev_idx FROM tx_inputs WHERE txid = ? ORDER BY idx")
+            .sql_context("prepare query statement")?;
+        let params = [&(txid.as_byte_array() as &[_]) as &dyn ToSql];
+        let rows = statement
+            .query_map(params, |row| row.try_into())
+            .with_sql_context(|| format!("select inputs of {}", txid))?;
+        rows.map(|result| {
+            let (prev_txid, prev_idx): (Vec<u8>, u32) =
+                result.sql_context("convert SQL value to Rust type")?;
+            Ok(bitcoin::OutPoint::new(
+                decode_txid(&prev_txid).map_err(DbError::InvalidTx)?,
+                prev_idx,
+            ))
+        })
+        .collect()
+    }
+
+    /// Returns the outputs spent by each input of the unconfirmed, not conflicted, transactions.
+    pub fn pending_inputs(&mut self) -> Result<Vec<(bitcoin::Txid, bitcoin::OutPoint)>, DbError> {
+        let mut statement = self
+            .0
+            .prepare(
+                "SELECT tx_inputs.txid, prev_txid, prev_idx FROM tx_inputs \
+                 JOIN txs ON txs.txid = tx_inputs.txid \
+                 WHERE txs.block_height IS NULL \
+                 AND tx_inputs.txid NOT IN (SELECT txid FROM conflicted)",
+            )
+            .sql_context("prepare query statement")?;
+        let rows = statement
+            .query_map([], |row| row.try_into())
+            .sql_context("select inputs of unconfirmed transactions")?;
+        rows.map(|result| {
+            let (txid, prev_txid, prev_idx): (Vec<u8>, Vec<u8>, u32) =
+                result.sql_context("convert SQL value to Rust type")?;
+            Ok((
+                decode_txid(&txid).map_err(DbError::InvalidTx)?,
+                bitcoin::OutPoint::new(
+                    decode_txid(&prev_txid).map_err(DbError::InvalidTx)?,
+                    prev_idx,
+                ),
+            ))
+        })
+        .collect()
+    }
+
+    /// Returns all unspent txos along with what else we know about them, oldest first.
+    pub fn utxos(&mut self) -> Result<Vec<Utxo>, DbError> {
+        // Outputs stored before transactions were recorded have no `txs` or `tx_outputs` row.
+        let mut statement = self
+            .0
+            .prepare(
+                "SELECT txos.txid, txos.idx, txos.amount_sat, txs.block_height, txs.coinbase, \
+                 tx_outputs.script_pubkey, frozen.txid IS NOT NULL \
+                 FROM txos \
+                 LEFT JOIN txs ON txs.txid = txos.txid \
+                 LEFT JOIN tx_outputs ON tx_outputs.txid = txos.txid AND tx_outputs.idx = txos.idx \
+                 LEFT JOIN frozen ON frozen.txid = txos.txid AND frozen.idx = txos.idx \
+                 WHERE txos.spent_status = 0 \
+                 ORDER BY txs.block_height, txos.rowid",
+            )
+            .sql_context("prepare query statement")?;
+        let rows = statement
+            .query_map([], |row| row.try_into())
+            .sql_context("select unspent txos")?;
+        rows.map(|result| {
+            #[allow(clippy::type_complexity)]
+            let (txid, idx, amount_sat, height, coinbase, script_pubkey, frozen): (
+                Vec<u8>,
+                i64,
+                i64,
+                Option<i64>,
+                Option<bool>,
+                Option<Vec<u8>>,
+                bool,
+            ) = result.sql_context("convert SQL value to Rust type")?;
+            let (outpoint, amount) = decode_txo(&txid, idx, amount_sat)?;
+            let height = height
+                .map(|height| {
+                    u64::try_from(height).map_err(|_| {
+                        DbError::InvalidTxo(format!("block height {} is out of range", height))
+                    })
+                })
+                .transpose()?;
+            Ok(Utxo {
+                outpoint,
+                amount,
+                height,
+                coinbase: coinbase.unwrap_or(false),
+                script_pubkey: script_pubkey.map(bitcoin::ScriptBuf::from_bytes),
+                frozen,
+            })
+        })
+        .collect()
+    }
+
+    /// Freezes (or unfreezes) the unspent txo `txo` so it is not used to pay for anything.
+    ///
+    /// Returns false if `txo` is not an unspent wallet output.
+    pub fn set_frozen(&mut self, txo: &bitcoin::OutPoint, frozen: bool) -> Result<bool, DbError> {
+        use bitcoin::hashes::Hash;
+
+        let params = [&(txo.txid.as_byte_array() as &[_]) as &dyn ToSql, &txo.vout];
+        let known = self
+            .0
+            .query_row(
+                "SELECT COUNT(*) FROM txos WHERE txid = ? AND idx = ? AND spent_status = 0",
+                params,
+                |row| row.get::<_, i64>(0),
+            )
+            .with_sql_context(|| format!("query txo {}:{}", txo.txid, txo.vout))?;
+        if known == 0 {
+            return Ok(false);
+        }
+        let sql = if frozen {
+            "INSERT INTO frozen VALUES (?, ?) ON CONFLICT(txid, idx) DO NOTHING"
+        } else {
+            "DELETE FROM frozen WHERE txid = ? AND idx = ?"
+        };
+        self.0
+            .execute(sql, params)
+            .with_sql_context(|| format!("freeze txo {}:{}", txo.txid, txo.vout))?;
+        Ok(true)
+    }
+
+    /// Saves `address` in the address book as `name`.
+    ///
+    /// Returns false, changing nothing, if there already is a payee called `name`.
+    pub fn add_payee(&mut self, name: &str, address: &str) -> Result<bool, DbError> {
+        let added = self
+            .0
+            .execute(
+                "INSERT INTO payees VALUES (?, ?) ON CONFLICT(name) DO NOTHING",
+                [name, address],
+            )
+            .with_sql_context(|| format!("add payee {}", name))?;
+        Ok(added == 1)
+    }
+
+    /// Returns the address saved as `name`, if any.
+    pub fn payee(&mut self, name: &str) -> Result<Option<String>, DbError> {
+        self.0
+            .query_row("SELECT address FROM payees WHERE name = ?", [name], |row| {
+                row.get(0)
+            })
+            .optional()
+            .with_sql_context(|| format!("query payee {}", name))
+    }
+
+    /// Returns the address book as (name, address) pairs, sorted by name.
+    pub fn payees(&mut self) -> Result<Vec<(String, String)>, DbError> {
+        let mut statement = self
+            .0
+            .prepare("SELECT name, address FROM payees ORDER BY name")
+            .sql_context("prepare query statement")?;
+        let rows = statement
+            .query_map([], |row| row.try_into())
+            .sql_context("select payees")?;
+        rows.map(|result| result.sql_context("convert SQL value to Rust type"))
+            .collect()
+    }
+
+    /// Schedules paying `amount` to `payee` every `interval_secs`, the first time at `next_due`.
+    ///
+    /// `payee` is the name of a payee or an address, as passed to `send`. Returns the id of the
+    /// new scheduled payment.
+    pub fn add_scheduled(
+        &mut self,
+        payee: &str,
+        amount: bitcoin::Amount,
+        interval_secs: u64,
+        next_due: u64,
+    ) -> Result<u64, DbError> {
+        let params = [
+            &payee as &dyn ToSql,
+            &amount.to_sat(),
+            &interval_secs,
+            &next_due,
+        ];
+        self.0
+            .execute(
+                "INSERT INTO scheduled (payee, amount_sat, interval_secs, next_due) \
+                 VALUES (?, ?, ?, ?)",
+                params,
+            )
+            .with_sql_context(|| format!("schedule payment to {}", payee))?;
+        Ok(self.0.last_insert_rowid() as u64)
+    }
+
+    /// Returns the scheduled payments, in the order they were added.
+    pub fn scheduled(&mut self) -> Result<Vec<ScheduledPayment>, DbError> {
+        let mut statement = self
+            .0
+            .prepare(
+                "SELECT id, payee, amount_sat, interval_secs, next_due FROM scheduled ORDER BY id",
+            )
+            .sql_context("prepare query statement")?;
+        let rows = statement
+            .query_map([], |row| row.try_into())
+            .sql_context("select scheduled payments")?;
+        rows.map(|result| {
+            let (id, payee, amount_sat, interval_secs, next_due): (u64, String, i64, u64, u64) =
+                result.sql_context("convert SQL value to Rust type")?;
+            Ok(ScheduledPayment {
+                id,
+                payee,
+                amount: decode_amount(amount_sat).map_err(DbError::InvalidTx)?,
+                interval_secs,
+                next_due,
+            })
+        })
+        .collect()
+    }
+
+    /// Sets when the scheduled payment `id` is due next.
+    pub fn set_next_due(&mut self, id: u64, next_due: u64) -> Result<(), DbError> {
+        self.0
+            .execute(
+                "UPDATE scheduled SET next_due = ? WHERE id = ?",
+                [next_due, id],
+            )
+            .with_sql_context(|| format!("update scheduled payment {}", id))?;
+        Ok(())
+    }
+
+    /// Cancels the scheduled payment `id`, returns false if there is no such payment.
+    pub fn cancel_scheduled(&mut self, id: u64) -> Result<bool, DbError> {
+        let deleted = self
+            .0
+            .execute("DELETE FROM scheduled WHERE id = ?", [id])
+            .with_sql_context(|| format!("cancel scheduled payment {}", id))?;
+        Ok(deleted == 1)
+    }
+
+    /// Keeps `tx`, which we broadcast, so it can be broadcast again until it confirms.
+    ///
+    /// The broadcasts `tx` replaces, those spending any of the same outputs, are forgotten so they
+    /// are not broadcast again.
+    pub fn store_broadcast(&mut self, tx: &bitcoin::Transaction) -> Result<(), DbError> {
+        use bitcoin::hashes::Hash;
+
+        let txid = tx.txid();
+        let conflicts = |other: &bitcoin::Transaction| {
+            other.input.iter().any(|input| {
+                tx.input
+                    .iter()
+                    .any(|ours| ours.previous_output == input.previous_output)
+            })
+        };
+        let replaced = self
+            .broadcasts("SELECT raw FROM broadcasts")?
+            .into_iter()
+            .filter(|other| other.txid() != txid && conflicts(other))
+            .map(|other| other.txid())
+            .collect::<Vec<_>>();
+
+        let transaction = self
+            .0
+            .transaction()
+            .sql_context("begin database transaction")?;
+        for replaced in replaced {
+            transaction
+                .execute(
+                    "DELETE FROM broadcasts WHERE txid = ?",
+                    [replaced.as_byte_array() as &[_]],
+                )
+                .with_sql_context(|| format!("forget replaced transaction {}", replaced))?;
+        }
+        let params = [
+            &(txid.as_byte_array() as &[_]) as &dyn ToSql,
+            &bitcoin::consensus::encode::serialize(tx),
+        ];
+        transaction
+            .execute(
+                "INSERT INTO broadcasts VALUES (?, ?) ON CONFLICT(txid) DO NOTHING",
+                params,
+            )
+            .with_sql_context(|| format!("store broadcast transaction {}", txid))?;
+        transaction
+            .commit()
+            .sql_context("commit database transaction")
+    }
+
+    /// Returns the transactions we broadcast that have neither confirmed nor been conflicted.
+    pub fn unconfirmed_broadcasts(&mut self) -> Result<Vec<bitcoin::Transaction>, DbError> {
+        // Confirmed ones are deleted when their block is stored.
+        self.broadcasts(
+            "SELECT raw FROM broadcasts WHERE txid NOT IN (SELECT txid FROM conflicted) \
+             ORDER BY rowid",
+        )
+    }
+
+    /// Returns the broadcast transactions `query` selects the raw transaction of.
+    fn broadcasts(&mut self, query: &str) -> Result<Vec<bitcoin::Transaction>, DbError> {
+        let mut statement = self
+            .0
+            .prepare(query)
+            .sql_context("prepare query statement")?;
+        let rows = statement
+            .query_map([], |row| row.get::<_, Vec<u8>>(0))
+            .sql_context("select broadcast transactions")?;
+        rows.map(|result| {
+            let raw = result.sql_context("convert SQL value to Rust type")?;
+            bitcoin::consensus::encode::deserialize(&raw)
+                .map_err(|error| DbError::InvalidTx(error.to_string()))
+        })
+        .collect()
+    }
+
+    /// Watches `script_pubkey`, outputs paying to it are stored as watch-only from the next scan.
+    ///
+    /// Returns false if it was already watched.
+    pub fn add_watched(&mut self, script_pubkey: &bitcoin::Script) -> Result<bool, DbError> {
+        let added = self
+            .0
+            .execute(
+                "INSERT INTO watched VALUES (?) ON CONFLICT(script_pubkey) DO NOTHING",
+                [script_pubkey.as_bytes()],
+            )
+            .sql_context("add watched script")?;
+        Ok(added == 1)
+    }
+
+    /// Returns the watched scripts, in the order they were added.
+    pub fn watched(&mut self) -> Result<Vec<bitcoin::ScriptBuf>, DbError> {
+        let mut statement = self
+            .0
+            .prepare("SELECT script_pubkey FROM watched ORDER BY rowid")
+            .sql_context("prepare query statement")?;
+        let rows = statement
+            .query_map([], |row| row.get::<_, Vec<u8>>(0))
+            .sql_context("select watched scripts")?;
+        rows.map(|result| {
+            let script_pubkey = result.sql_context("convert SQL value to Rust type")?;
+            Ok(bitcoin::ScriptBuf::from_bytes(script_pubkey))
+        })
+        .collect()
+    }
+
+    /// Returns the unspent outputs paying to watched scripts.
+    pub fn watched_unspent(&mut self) -> Result<Vec<bitcoin::OutPoint>, DbError> {
+        let mut statement = self
+            .0
+            .prepare("SELECT txid, idx, amount_sat FROM watched_txos WHERE spent_status = 0")
+            .sql_context("prepare query statement")?;
+        let rows = statement
+            .query_map([], |row| row.try_into())
+            .sql_context("select unspent watched txos")?;
+        rows.map(|result| {
+            let (txid, idx, amount_sat): (Vec<u8>, i64, i64) =
+                result.sql_context("convert SQL value to Rust type")?;
+            decode_txo(&txid, idx, amount_sat).map(|(outpoint, _amount)| outpoint)
+        })
+        .collect()
+    }
+
+    /// Stores `received` outputs paying to watched scripts and marks `spent` ones as spent.
+    ///
+    /// These are kept apart from the wallet's outputs, they are not ours to spend. Storing the
+    /// same outputs again changes nothing.
+    pub fn store_watched(
+        &mut self,
+        received: &[(bitcoin::OutPoint, bitcoin::TxOut)],
+        spent: &[bitcoin::OutPoint],
+    ) -> Result<(), DbError> {
+        use bitcoin::hashes::Hash;
+
+        if received.is_empty() && spent.is_empty() {
+            return Ok(());
+        }
+        let transaction = self
+            .0
+            .transaction()
+            .sql_context("begin database transaction")?;
+        for (txo, output) in received {
+            let params = [
+                &(txo.txid.as_byte_array() as &[_]) as &dyn ToSql,
+                &txo.vout,
+                &output.script_pubkey.as_bytes(),
+                &output.value,
+            ];
+            transaction
+                .execute(
+                    "INSERT INTO watched_txos VALUES (?, ?, ?, ?, 0) \
+                     ON CONFLICT(txid, idx) DO NOTHING",
+                    params,
+                )
+                .with_sql_context(|| format!("insert watched txo {}:{}", txo.txid, txo.vout))?;
+        }
+        for txo in spent {
+            let params = [&(txo.txid.as_byte_array() as &[_]) as &dyn ToSql, &txo.vout];
+            transaction
+                .execute(
+                    "UPDATE watched_txos SET spent_status = 1 WHERE txid = ? AND idx = ?",
+                    params,
+                )
+                .with_sql_context(|| {
+                    format!("mark watched txo {}:{} as spent", txo.txid, txo.vout)
+                })?;
+        }
+        transaction
+            .commit()
+            .sql_context("commit database transaction")
+    }
+
+    /// Returns each watched script with the number and total of its unspent outputs.
+    pub fn watched_balances(
+        &mut self,
+    ) -> Result<Vec<(bitcoin::ScriptBuf, usize, bitcoin::Amount)>, DbError> {
+        let mut statement = self
+            .0
+            .prepare(
+                "SELECT watched.script_pubkey, COUNT(watched_txos.txid), \
+                 COALESCE(SUM(watched_txos.amount_sat), 0) \
+                 FROM watched \
+                 LEFT JOIN watched_txos ON watched_txos.script_pubkey = watched.script_pubkey \
+                 AND watched_txos.spent_status = 0 \
+                 GROUP BY watched.script_pubkey ORDER BY watched.rowid",
+            )
+            .sql_context("prepare query statement")?;
+        let rows = statement
+            .query_map([], |row| row.try_into())
+            .sql_context("select watched balances")?;
+        rows.map(|result| {
+            let (script_pubkey, count, amount_sat): (Vec<u8>, usize, i64) =
+                result.sql_context("convert SQL value to Rust type")?;
+            Ok((
+                bitcoin::ScriptBuf::from_bytes(script_pubkey),
+                count,
+                decode_amount(amount_sat).map_err(DbError::InvalidTxo)?,
+            ))
+        })
+        .collect()
+    }
+
+    pub fn set_spent(&mut self, txo: &bitcoin::OutPoint) -> Result<usize, DbError> {
+        use bitcoin::hashes::Hash;
+
+        let params = [&(txo.txid.as_byte_array() as &[_]) as &dyn ToSql, &txo.vout];
+        self.0
+            .execute(
+                "UPDATE txos SET spent_status = 1 WHERE txid = ? AND idx = ?",
+                params,
+            )
+            .with_sql_context(|| format!("mark txo {}:{} as spent", txo.txid, txo.vout))
+    }
+}
+
+pub struct Utxos<'a>(rusqlite::Statement<'a>);
+
+impl<'a> Utxos<'a> {
+    pub fn iter(
+        &mut self,
+    ) -> Result<
+        impl Iterator<Item = Result<(bitcoin::OutPoint, bitcoin::Amount), DbError>> + '_,
+        DbError,
+    > {
+        let iter = self
+            .0
+            .query_map([], |row| row.try_into())
+            .sql_context("select unspent txos")?
+            .map(|result| {
+                let (txid, idx, amount_sat): (Vec<u8>, i64, i64) =
+                    result.sql_context("convert SQL value to Rust type")?;
+                decode_txo(&txid, idx, amount_sat)
+            });
+        Ok(iter)
+    }
+}
+
+/// A wallet transaction recorded while scanning.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxRecord {
+    pub txid: bitcoin::Txid,
+    /// The height of the block the transaction is in, `None` if it is still in the mempool.
+    pub height: Option<u64>,
+    /// The total paid to the wallet.
+    pub received: bitcoin::Amount,
+    /// The total of the wallet outputs spent.
+    pub sent: bitcoin::Amount,
+    /// True if the transaction was unconfirmed when another one double spent it (e.g. replaced
+    /// it), it will most likely never confirm.
+    pub conflicted: bool,
+}
+
+/// A payment made again and again, see [`Db::add_scheduled`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledPayment {
+    pub id: u64,
+    /// The name of a payee or an address.
+    pub payee: String,
+    pub amount: bitcoin::Amount,
+    /// How often the payment is made.
+    pub interval_secs: u64,
+    /// When the payment is due next, in seconds since the UNIX epoch.
+    pub next_due: u64,
+}
+
+/// Records `txs`, from the block at `height` or from the mempool if `None`, as part of
+/// `transaction`.
+fn insert_txs(
+    transaction: &rusqlite::Transaction,
+    height: Option<u64>,
+    txs: &[WalletTx],
+) -> Result<(), DbError> {
+    use bitcoin::hashes::Hash;
+
+    for tx in txs {
+        // Every output we spend is in `txos`, it was stored when the block paying it was.
+        let mut sent_sat = 0_u64;
+        for txo in &tx.spent {
+            let params = [&(txo.txid.as_byte_array() as &[_]) as &dyn ToSql, &txo.vout];
+            let amount_sat: u64 = transaction
+                .query_row(
+                    "SELECT COALESCE(SUM(amount_sat), 0) FROM txos WHERE txid = ? AND idx = ?",
+                    params,
+                    |row| row.get(0),
+                )
+                .with_sql_context(|| format!("query txo {}:{}", txo.txid, txo.vout))?;
+            sent_sat += amount_sat;
+        }
+
+        let txid = tx.txid.as_byte_array() as &[_];
+        let params = [
+            &txid as &dyn ToSql,
+            &height,
+            &tx.coinbase,
+            &tx.received.to_sat(),
+            &sent_sat,
+        ];
+        transaction
+            .execute(INSERT_TX, params)
+            .with_sql_context(|| format!("insert transaction {} into the database", tx.txid))?;
+        for (idx, output) in tx.outputs.iter().enumerate() {
+            let params = [
+                &txid as &dyn ToSql,
+                &idx,
+                &output.script_pubkey.as_bytes(),
+                &output.value,
+            ];
+            transaction
+                .execute(INSERT_TX_OUTPUT, params)
+                .with_sql_context(|| {
+                    format!("insert output {}:{} into the database", tx.txid, idx)
+                })?;
+        }
+        for (idx, input) in tx.inputs.iter().enumerate() {
+            let params = [
+                &txid as &dyn ToSql,
+                &idx,
+                &(input.txid.as_byte_array() as &[_]),
+                &input.vout,
+            ];
+            transaction
+                .execute(INSERT_TX_INPUT, params)
+                .with_sql_context(|| {
+                    format!("insert input {}:{} into the database", tx.txid, idx)
+                })?;
+        }
+        // Seen again, so whatever conflicted with it did not win after all.
+        transaction
+            .execute("DELETE FROM conflicted WHERE txid = ?", [&txid])
+            .with_sql_context(|| format!("unmark transaction {} as conflicted", tx.txid))?;
+        if height.is_some() {
+            transaction
+                .execute("DELETE FROM broadcasts WHERE txid = ?", [&txid])
+                .with_sql_context(|| format!("forget broadcast transaction {}", tx.txid))?;
+        }
+    }
+    Ok(())
+}
+
+/// An unspent wallet output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Utxo {
+    pub outpoint: bitcoin::OutPoint,
+    pub amount: bitcoin::Amount,
+    /// The height of the block the output was created in, if known.
+    pub height: Option<u64>,
+    /// True if the output was created by a coinbase transaction.
+    pub coinbase: bool,
+    /// The script the output pays to, if known.
+    pub script_pubkey: Option<bitcoin::ScriptBuf>,
+    /// True if the user froze the output, frozen outputs are not spent.
+    pub frozen: bool,
+}
+
+impl Utxo {
+    /// Returns the number of confirmations the output has with `tip` as the last block.
+    pub fn confirmations(&self, tip: u64) -> Option<u64> {
+        self.height.map(|height| (tip + 1).saturating_sub(height))
+    }
+
+    /// Returns true if this is a coinbase output that can not be spent yet with `tip` as the last
+    /// block.
+    pub fn is_immature(&self, tip: u64) -> bool {
+        let maturity = u64::from(bitcoin::blockdata::constants::COINBASE_MATURITY);
+        self.coinbase
+            && matches!(self.confirmations(tip), Some(confirmations) if confirmations < maturity)
+    }
+}
+
+/// Decodes a row of the `txos` table.
+///
+/// The database is just a file on disk so the values are checked rather than trusted.
+pub fn decode_txo(
+    txid: &[u8],
+    idx: i64,
+    amount_sat: i64,
+) -> Result<(bitcoin::OutPoint, bitcoin::Amount), DbError> {
+    let txid = decode_txid(txid).map_err(DbError::InvalidTxo)?;
+    let vout = idx
+        .try_into()
+        .map_err(|_| DbError::InvalidTxo(format!("output index {} is out of range", idx)))?;
+    let amount = decode_amount(amount_sat).map_err(DbError::InvalidTxo)?;
+    Ok((bitcoin::OutPoint { txid, vout }, amount))
+}
+
+fn decode_txid(txid: &[u8]) -> Result<bitcoin::Txid, String> {
+    use bitcoin::hashes::Hash;
+
+    txid.try_into()
+        .map(bitcoin::Txid::from_byte_array)
+        .map_err(|_| format!("txid is {} bytes long", txid.len()))
+}
+
+fn decode_amount(amount_sat: i64) -> Result<bitcoin::Amount, String> {
+    u64::try_from(amount_sat)
+        .ok()
+        .map(bitcoin::Amount::from_sat)
+        .filter(|amount| *amount <= bitcoin::Amount::MAX_MONEY)
+        .ok_or_else(|| format!("amount {} is out of range", amount_sat))
+}
+
+/// Errors returned by the database.
+#[derive(Debug, thiserror::Error)]
+pub enum DbError {
+    #[error("the user data directory was not identified")]
+    NoDataDir,
+    #[error("failed to create data dir at {}", path.display())]
+    CreateDir {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to open database at {}", path.display())]
+    Open {
+        path: PathBuf,
+        #[source]
+        source: rusqlite::Error,
+    },
+    /// An SQL statement failed, `what` describes what we were trying to do.
+    #[error("failed to {what}")]
+    Sql {
+        what: String,
+        #[source]
+        source: rusqlite::Error,
+    },
+    #[error("invalid txo in the database: {0}")]
+    InvalidTxo(String),
+    #[error("invalid transaction in the database: {0}")]
+    InvalidTx(String),
+}
+
+/// Like `anyhow::Context` but for turning SQLite errors into a [`DbError`].
+trait SqlContext<T> {
+    fn sql_context(self, what: &str) -> Result<T, DbError>;
+    fn with_sql_context(self, what: impl FnOnce() -> String) -> Result<T, DbError>;
+}
+
+impl<T> SqlContext<T> for rusqlite::Result<T> {
+    fn sql_context(self, what: &str) -> Result<T, DbError> {
+        self.with_sql_context(|| what.to_owned())
+    }
+
+    fn with_sql_context(self, what: impl FnOnce() -> String) -> Result<T, DbError> {
+        self.map_err(|source| DbError::Sql {
+            what: what(),
+            source,
+        })
+    }
+}
@@ -0,0 +1,276 @@
+//! Fee and change arithmetic shared by everything that builds a wallet spend.
+//!
+//! Sending, bumping and any future way of spending wallet outputs describe the spend as a
+//! [`Spend`] and let [`plan`] decide the fee and the change, so they all round and handle dust the
+//! same way. All amount arithmetic is checked, overflow is an error rather than a wrapped value.
+
+use bitcoin::consensus::Encodable;
+use bitcoin::transaction::{predict_weight, InputWeightPrediction};
+use bitcoin::{taproot, Amount, FeeRate, Script, Weight};
+
+/// Bitcoin Core's default `-dustrelayfee`, 3000 sat/kvB.
+pub const DUST_RELAY_FEE: FeeRate = FeeRate::from_sat_per_kwu(750);
+
+/// The shape of a transaction spending wallet outputs, what is needed to work out its fee.
+#[derive(Debug, Clone)]
+pub struct Spend<'a> {
+    /// The inputs spending wallet outputs, see [`wallet_input`].
+    pub inputs: Vec<InputWeightPrediction>,
+    /// The total value of the spent outputs.
+    pub input_value: Amount,
+    /// The script lengths of the outputs other than change.
+    pub output_script_lens: Vec<usize>,
+    /// The total value of the outputs other than change.
+    pub amount: Amount,
+    /// Where change goes.
+    pub change_script: &'a Script,
+    /// The fee rate the dust limit of the change output is worked out at, see [`dust_limit`].
+    pub dust_relay_fee: FeeRate,
+}
+
+impl Spend<'_> {
+    /// Predicts the weight of the signed transaction, with a change output.
+    ///
+    /// Always counting the change output means the fee rate is met whether or not there turns out
+    /// to be change.
+    pub fn weight(&self) -> Weight {
+        predict_weight(
+            self.inputs.iter().copied(),
+            self.output_script_lens
+                .iter()
+                .copied()
+                .chain(Some(self.change_script.len())),
+        )
+    }
+}
+
+/// How a [`Spend`] splits its input value between fee and change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Plan {
+    /// The absolute fee, including any change too small to be worth an output.
+    pub fee: Amount,
+    /// The value of the change output, `None` if there is none.
+    pub change: Option<Amount>,
+}
+
+/// Works out the fee and change of `spend` at `fee_rate`, paying at least `min_fee`.
+///
+/// Change below the dust limit of `change_script` at `dust_relay_fee` is added to the fee.
+pub fn plan(spend: &Spend, fee_rate: FeeRate, min_fee: Amount) -> Result<Plan, FeeError> {
+    let fee = fee_for(spend.weight(), fee_rate)
+        .ok_or(FeeError::Overflow)?
+        .max(min_fee);
+
+    let change = spend
+        .input_value
+        .checked_sub(spend.amount)
+        .and_then(|left| left.checked_sub(fee))
+        .ok_or(FeeError::InsufficientFunds {
+            available: spend.input_value,
+            amount: spend.amount,
+            fee,
+        })?;
+    if change >= dust_limit(spend.change_script, spend.dust_relay_fee) {
+        Ok(Plan {
+            fee,
+            change: Some(change),
+        })
+    } else {
+        Ok(Plan {
+            fee: fee + change,
+            change: None,
+        })
+    }
+}
+
+/// Predicts the weight of a signed transaction spending `inputs` taproot wallet outputs by key path.
+///
+/// `output_script_lens` are the lengths of the output scripts.
+pub fn predict_spend_weight(
+    inputs: usize,
+    output_script_lens: impl IntoIterator<Item = usize>,
+) -> Weight {
+    predict_weight(vec![p2tr_key_path(); inputs], output_script_lens)
+}
+
+// The predictions below are built with `InputWeightPrediction::new`, which counts the (empty)
+// script_sig length byte of the input. The constants in rust-bitcoin 0.30, e.g.
+// `P2TR_KEY_DEFAULT_SIGHASH`, do not and would underestimate: a transaction paying exactly the
+// minimum relay fee would be rejected. Pass these to `bitcoin::transaction::predict_weight`.
+
+/// Predicts a taproot key path input signed with `SIGHASH_DEFAULT`, as wallet outputs are.
+pub fn p2tr_key_path() -> InputWeightPrediction {
+    InputWeightPrediction::new(0, [64])
+}
+
+/// Predicts a P2WPKH input, with the signature counted at its largest (72 bytes of DER plus the
+/// sighash type) so the fee rate is met whatever the signer produces.
+pub fn p2wpkh() -> InputWeightPrediction {
+    InputWeightPrediction::new(0, [73, 33])
+}
+
+/// Predicts the input spending a wallet output paying to `script_pubkey`: P2WPKH outputs are spent
+/// as such, any other (the wallet's taproot outputs) by key path.
+pub fn wallet_input(script_pubkey: &Script) -> InputWeightPrediction {
+    if script_pubkey.is_v0_p2wpkh() {
+        p2wpkh()
+    } else {
+        p2tr_key_path()
+    }
+}
+
+/// Predicts a taproot script path input spending a leaf of `script_len` bytes, `depth` levels down
+/// the script tree, with `signatures` `SIGHASH_DEFAULT` signatures in the witness.
+///
+/// The witness is the signatures, the leaf script and the control block, which holds a 32 byte
+/// hash per level of the merkle path; `depth` is 0 if the leaf is the only one.
+pub fn p2tr_script_path(
+    script_len: usize,
+    depth: usize,
+    signatures: usize,
+) -> InputWeightPrediction {
+    let control_block =
+        taproot::TAPROOT_CONTROL_BASE_SIZE + depth * taproot::TAPROOT_CONTROL_NODE_SIZE;
+    let witness = std::iter::repeat_n(64, signatures).chain([script_len, control_block]);
+    InputWeightPrediction::new(0, witness)
+}
+
+/// Predicts a P2WSH input spending a `signers`-of-n `OP_CHECKMULTISIG` witness script of
+/// `script_len` bytes.
+///
+/// The witness is the empty dummy element `OP_CHECKMULTISIG` pops, the signatures and the witness
+/// script. Signatures are counted at their largest, 72 bytes of DER plus the sighash type, so the
+/// fee rate is met whatever the signers produce.
+pub fn p2wsh_multisig(script_len: usize, signers: usize) -> InputWeightPrediction {
+    let witness = std::iter::once(0)
+        .chain(std::iter::repeat_n(73, signers))
+        .chain([script_len]);
+    InputWeightPrediction::new(0, witness)
+}
+
+/// Returns the smallest output paying to `script_pubkey` that is not dust at `dust_relay_fee`.
+///
+/// An output is dust if spending it would cost more than it is worth, so the limit depends on the
+/// script type: a segwit output is cheaper to spend than a legacy one. Same as Bitcoin Core's
+/// `GetDustThreshold`, rust-bitcoin's `Script::dust_value` hard codes the default fee rate. The fee
+/// is worked out per kvB and rounded up like Core does, so rates that are not a whole number of
+/// sat/vB give the same limit.
+pub fn dust_limit(script_pubkey: &Script, dust_relay_fee: FeeRate) -> Amount {
+    if script_pubkey.is_op_return() {
+        return Amount::ZERO;
+    }
+    // The size of the output itself, value and script.
+    let output_size = 8 + script_pubkey
+        .consensus_encode(&mut std::io::sink())
+        .expect("sinks don't error") as u64;
+    // The size of the input spending it, as assumed by Core.
+    let input_size = if script_pubkey.is_witness_program() {
+        32 + 4 + 1 + 107 / 4 + 4
+    } else {
+        32 + 4 + 1 + 107 + 4
+    };
+    let sat_per_kvb = dust_relay_fee.to_sat_per_kwu().saturating_mul(4);
+    Amount::from_sat(
+        (output_size + input_size)
+            .saturating_mul(sat_per_kvb)
+            .div_ceil(1000),
+    )
+}
+
+/// Returns the fee for a transaction of `weight` at `fee_rate`, rounded up to the next satoshi.
+///
+/// Returns `None` if the fee does not fit in a `u64`.
+pub fn fee_for(weight: Weight, fee_rate: FeeRate) -> Option<Amount> {
+    let sat_kwu = weight.to_wu().checked_mul(fee_rate.to_sat_per_kwu())?;
+    Some(Amount::from_sat(sat_kwu.div_ceil(1000)))
+}
+
+/// Adds up `amounts`, returns `None` on overflow.
+///
+/// Use this rather than `Sum`, which wraps around in release builds.
+pub fn checked_sum(amounts: impl IntoIterator<Item = Amount>) -> Option<Amount> {
+    amounts
+        .into_iter()
+        .try_fold(Amount::ZERO, |total, amount| total.checked_add(amount))
+}
+
+/// Lower bounds of the buckets of [`mempool_histogram`], in sat/vB.
+pub const HISTOGRAM_BUCKETS: [u64; 10] = [0, 1, 2, 3, 5, 10, 20, 50, 100, 200];
+
+/// The vbytes of a block left for transactions, a little is kept for the coinbase as miners do.
+const BLOCK_VSIZE: u64 = 1_000_000 - 4_000;
+
+/// Mempool transactions paying at least `min` but less than the next bucket up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeBucket {
+    pub min: FeeRate,
+    /// The number of transactions.
+    pub count: usize,
+    /// Their total size in vbytes.
+    pub vsize: u64,
+}
+
+/// Sorts mempool transactions, each given as `(vsize, fee)`, into the [`HISTOGRAM_BUCKETS`].
+///
+/// Transactions are bucketed by their own fee rate, ignoring that a low fee parent may be mined
+/// along with a high fee child.
+pub fn mempool_histogram(txs: &[(u64, Amount)]) -> Vec<FeeBucket> {
+    let mut buckets = HISTOGRAM_BUCKETS
+        .iter()
+        .map(|min| FeeBucket {
+            min: FeeRate::from_sat_per_vb_unchecked(*min),
+            count: 0,
+            vsize: 0,
+        })
+        .collect::<Vec<_>>();
+    for (vsize, fee) in txs {
+        let rate = tx_fee_rate(*vsize, *fee);
+        let bucket = buckets
+            .iter_mut()
+            .rev()
+            .find(|bucket| bucket.min <= rate)
+            .expect("the lowest bucket starts at zero");
+        bucket.count += 1;
+        bucket.vsize += vsize;
+    }
+    buckets
+}
+
+/// Returns the fee rate that gets a transaction into the next block if miners fill it with the
+/// highest fee rate mempool transactions, each given as `(vsize, fee)`.
+///
+/// That is the rate of the first transaction left out, or `min_fee` if the whole mempool fits.
+pub fn next_block_fee_rate(txs: &[(u64, Amount)], min_fee: FeeRate) -> FeeRate {
+    let mut rates = txs
+        .iter()
+        .map(|(vsize, fee)| (tx_fee_rate(*vsize, *fee), *vsize))
+        .collect::<Vec<_>>();
+    rates.sort_unstable_by_key(|(rate, _)| std::cmp::Reverse(*rate));
+    let mut used = 0;
+    for (rate, vsize) in rates {
+        used += vsize;
+        if used > BLOCK_VSIZE {
+            return rate.max(min_fee);
+        }
+    }
+    min_fee
+}
+
+/// Returns the fee rate of a transaction of `vsize` vbytes paying `fee`, rounded down.
+fn tx_fee_rate(vsize: u64, fee: Amount) -> FeeRate {
+    // A vbyte is 4 weight units, so sat/kwu is sat/vB * 250.
+    FeeRate::from_sat_per_kwu(fee.to_sat().saturating_mul(250) / vsize.max(1))
+}
+
+/// Errors returned when planning a spend.
+#[derive(Debug, thiserror::Error)]
+pub enum FeeError {
+    #[error("insufficient funds: have {available}, need {amount} plus a fee of {fee}")]
+    InsufficientFunds {
+        available: Amount,
+        amount: Amount,
+        fee: Amount,
+    },
+    #[error("amount overflow")]
+    Overflow,
+}
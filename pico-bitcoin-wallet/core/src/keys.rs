@@ -0,0 +1,162 @@
+//! The wallet's private key and the address derived from it.
+//!
+//! Keys are erased from memory when no longer needed, see [`erase`]. Call [`load_address`] rather
+//! than loading the key if all you need is the address.
+
+#[cfg(feature = "fs")]
+use std::io;
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::{self, SECP256K1};
+use bitcoin::taproot::TaprootSpendInfo;
+use bitcoin::{Address, PrivateKey};
+#[cfg(feature = "fs")]
+use zeroize::Zeroizing;
+
+use crate::config::Chain;
+#[cfg(feature = "fs")]
+use crate::db::{self, DbError};
+use crate::recovery::Recovery;
+
+/// Loads a private key from file.
+///
+/// Creates a new private key for `chain` if file is not found.
+#[cfg(feature = "fs")]
+pub fn load_private_key(chain: Chain) -> Result<PrivateKey, KeyError> {
+    let sk_path = db::private_key_file(chain)?;
+
+    match std::fs::read_to_string(&sk_path).map(Zeroizing::new) {
+        Ok(wif) => wif.parse().map_err(KeyError::Parse),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            let key = generate(chain);
+            let wif = Zeroizing::new(key.to_wif());
+            std::fs::write(&sk_path, wif.as_bytes()).map_err(KeyError::Save)?;
+            Ok(key)
+        }
+        Err(error) => Err(KeyError::Read(error)),
+    }
+}
+
+/// Saves `key` as the wallet's private key for `chain`, unless there already is one.
+///
+/// Returns false, leaving the file alone, if the key file exists.
+#[cfg(feature = "fs")]
+pub fn save_private_key(chain: Chain, key: &PrivateKey) -> Result<bool, KeyError> {
+    let sk_path = db::private_key_file(chain)?;
+
+    let mut file = match std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&sk_path)
+    {
+        Ok(file) => file,
+        Err(error) if error.kind() == io::ErrorKind::AlreadyExists => return Ok(false),
+        Err(error) => return Err(KeyError::Save(error)),
+    };
+    let wif = Zeroizing::new(key.to_wif());
+    io::Write::write_all(&mut file, wif.as_bytes()).map_err(KeyError::Save)?;
+    Ok(true)
+}
+
+/// Loads the private key just long enough to derive the wallet address.
+///
+/// With a `recovery` path the address commits to it, see [`spend_info`].
+#[cfg(feature = "fs")]
+pub fn load_address(chain: Chain, recovery: Option<&Recovery>) -> Result<Address, KeyError> {
+    let spend_info = load_spend_info(chain, recovery)?;
+    Ok(Address::p2tr_tweaked(
+        spend_info.output_key(),
+        chain.network(),
+    ))
+}
+
+/// Loads the private key just long enough to derive the wallet's P2WPKH address, see
+/// [`p2wpkh_address`].
+#[cfg(feature = "fs")]
+pub fn load_p2wpkh_address(chain: Chain) -> Result<Option<Address>, KeyError> {
+    let mut key = load_private_key(chain)?;
+    let address = p2wpkh_address(&key, chain);
+    erase(&mut key);
+    Ok(address)
+}
+
+/// Loads the private key just long enough to work out how the wallet's outputs are spent.
+#[cfg(feature = "fs")]
+pub fn load_spend_info(
+    chain: Chain,
+    recovery: Option<&Recovery>,
+) -> Result<TaprootSpendInfo, KeyError> {
+    let mut key = load_private_key(chain)?;
+    let spend_info = spend_info(&key, recovery);
+    erase(&mut key);
+    Ok(spend_info)
+}
+
+/// Overwrites `key` so the secret does not linger in memory.
+///
+/// Best effort only: `PrivateKey` is `Copy` and the compiler is free to leave copies behind, so
+/// also avoid copying keys around and keep them alive for as short a time as possible.
+pub fn erase(key: &mut PrivateKey) {
+    key.inner.non_secure_erase();
+}
+
+/// Generates a new random private key for `chain`.
+pub fn generate(chain: Chain) -> PrivateKey {
+    PrivateKey::new(
+        secp256k1::SecretKey::new(&mut rand::thread_rng()),
+        chain.network(),
+    )
+}
+
+/// Derives a private key for `chain` from `seed`, the same seed always gives the same key.
+///
+/// For reproducing transactions, e.g. when grading, anybody who knows the seed has the key.
+pub fn from_seed(seed: &str, chain: Chain) -> PrivateKey {
+    let hash = sha256::Hash::hash(format!("pico-bitcoin-wallet/key/{}", seed).as_bytes());
+    let secret_key = secp256k1::SecretKey::from_slice(hash.as_byte_array())
+        .expect("a hash is a valid secret key with overwhelming probability");
+    PrivateKey::new(secret_key, chain.network())
+}
+
+/// Returns the taproot address of `key`, spendable by key path only.
+///
+/// In a production wallet one would never reuse a single address like this but for demonstration
+/// purposes it will suffice.
+pub fn address(key: &PrivateKey, chain: Chain) -> Address {
+    let (internal_key, _parity) = key.inner.x_only_public_key(SECP256K1);
+    Address::p2tr(SECP256K1, internal_key, None, chain.network())
+}
+
+/// Returns the P2WPKH (segwit v0) address of `key`, for being paid by wallets that can not pay to
+/// taproot addresses yet. Outputs paying to it are spent with an ECDSA signature.
+///
+/// Returns `None` for an uncompressed key (e.g. imported from an old WIF), those have no P2WPKH
+/// address and the wallet only uses its taproot address.
+pub fn p2wpkh_address(key: &PrivateKey, chain: Chain) -> Option<Address> {
+    Address::p2wpkh(&key.public_key(SECP256K1), chain.network()).ok()
+}
+
+/// Returns the taproot spend data of the wallet's outputs: the internal key is that of `key` and
+/// the script tree is the `recovery` leaf, if any. Without one these are the outputs paying to
+/// [`address`].
+pub fn spend_info(key: &PrivateKey, recovery: Option<&Recovery>) -> TaprootSpendInfo {
+    let (internal_key, _parity) = key.inner.x_only_public_key(SECP256K1);
+    match recovery {
+        Some(recovery) => recovery.spend_info(internal_key),
+        None => TaprootSpendInfo::new_key_spend(SECP256K1, internal_key, None),
+    }
+}
+
+/// Errors returned when loading the private key.
+#[cfg(feature = "fs")]
+#[derive(Debug, thiserror::Error)]
+pub enum KeyError {
+    #[error(transparent)]
+    Db(#[from] DbError),
+    #[error("failed to read private key")]
+    Read(#[source] io::Error),
+    #[error("failed to parse private key")]
+    Parse(#[source] bitcoin::key::Error),
+    #[error("failed to save private key")]
+    Save(#[source] io::Error),
+}
@@ -0,0 +1,38 @@
+//! Core logic of the pico Bitcoin wallet: keys, database, scanning and transaction building.
+//!
+//! The `pico-bitcoin-wallet` binary is a thin command line interface on top of this library, tests
+//! and other workshop exercises can use it directly. Talking to the chain is async (tokio), callers
+//! that are not async themselves can block on a runtime.
+//!
+//! Two features are enabled by default:
+//!
+//! - `rpc`: the bitcoind RPC client and `RpcBackend`.
+//! - `fs`: everything that touches the filesystem, i.e. the database, the key file, the operation
+//!   log and loading the config file. Scanning into the database and broadcasting need it too.
+//!
+//! With `--no-default-features` only key handling, transaction building, signing and block
+//! filtering remain, these compile to `wasm32-unknown-unknown` for use in the browser.
+
+#[cfg(feature = "fs")]
+pub mod accounting;
+#[cfg(feature = "fs")]
+pub mod audit;
+pub mod backend;
+pub mod config;
+#[cfg(feature = "fs")]
+pub mod db;
+pub mod fee;
+pub mod keys;
+pub mod mock;
+#[cfg(feature = "fs")]
+pub mod oplog;
+#[cfg(feature = "rpc")]
+pub mod price;
+#[cfg(feature = "rpc")]
+mod proxy;
+pub mod recovery;
+#[cfg(feature = "rpc")]
+pub mod rpc;
+pub mod scan;
+pub mod sign;
+pub mod tx;
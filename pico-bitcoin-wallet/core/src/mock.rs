@@ -0,0 +1,395 @@
+//! An in-memory chain implementing [`Backend`], for testing scan and send logic without bitcoind.
+//!
+//! Blocks are either canned (e.g. loaded from fixtures) or mined on demand from the transactions
+//! handed in and those broadcast to the mock mempool. Broadcast transactions are checked enough to
+//! catch wallet bugs: inputs must exist and be unspent, values must add up and taproot and P2WPKH
+//! signatures must verify. Script path spends are only understood as far as a leaf ending in
+//! `<key> OP_CHECKSIG` goes, anything before that (e.g. a timelock) is not checked.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+
+use bitcoin::absolute::LockTime;
+use bitcoin::block::{Header, Version};
+use bitcoin::blockdata::constants::genesis_block;
+use bitcoin::consensus::encode::serialize;
+use bitcoin::hash_types::TxMerkleNode;
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::{Message, XOnlyPublicKey, SECP256K1};
+use bitcoin::sighash::{Prevouts, SighashCache};
+use bitcoin::taproot::{ControlBlock, TapLeafHash};
+use bitcoin::{
+    ecdsa, taproot, Amount, Block, BlockHash, FeeRate, Network, OutPoint, PublicKey, Script,
+    ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness,
+};
+
+use crate::backend::{Backend, BackendError};
+use crate::fee;
+
+/// A chain that only exists in memory.
+pub struct MockChain {
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    blocks: Vec<Block>,
+    mempool: Vec<Transaction>,
+    fee_rate: Option<FeeRate>,
+}
+
+impl MockChain {
+    /// Creates a chain containing only the regtest genesis block.
+    pub fn new() -> Self {
+        MockChain::from_blocks(vec![genesis_block(Network::Regtest)])
+    }
+
+    /// Creates a chain from canned blocks, `blocks[0]` is at height 0.
+    pub fn from_blocks(blocks: Vec<Block>) -> Self {
+        MockChain {
+            inner: Mutex::new(Inner {
+                blocks,
+                mempool: Vec::new(),
+                fee_rate: None,
+            }),
+        }
+    }
+
+    /// Mines a block containing the mempool followed by `txs`, returns its hash.
+    ///
+    /// `txs` are not checked, use them to fund the wallet from outputs that do not exist.
+    pub fn mine(&self, txs: impl IntoIterator<Item = Transaction>) -> BlockHash {
+        let mut inner = self.lock();
+        let height = inner.blocks.len() as u64;
+        let prev = inner.blocks.last().expect("chain has a genesis block");
+        let prev_blockhash = prev.block_hash();
+        let time = prev.header.time + 600;
+        let bits = prev.header.bits;
+
+        let mut txdata = vec![coinbase(height)];
+        txdata.append(&mut inner.mempool);
+        txdata.extend(txs);
+
+        let mut block = Block {
+            header: Header {
+                version: Version::TWO,
+                prev_blockhash,
+                merkle_root: TxMerkleNode::all_zeros(),
+                time,
+                bits,
+                nonce: 0,
+            },
+            txdata,
+        };
+        block.header.merkle_root = block.compute_merkle_root().expect("block has a coinbase");
+        let hash = block.block_hash();
+        inner.blocks.push(block);
+        hash
+    }
+
+    /// Mines `n` empty blocks.
+    pub fn mine_empty(&self, n: usize) {
+        for _ in 0..n {
+            self.mine(None);
+        }
+    }
+
+    /// Sets the fee rate returned by `estimate_fee_rate`, `None` (the default) means no estimate.
+    pub fn set_fee_rate(&self, fee_rate: Option<FeeRate>) {
+        self.lock().fee_rate = fee_rate;
+    }
+
+    /// Adds `tx` to the mempool without checking it, e.g. an unconfirmed [`funding_tx`].
+    pub fn add_to_mempool(&self, tx: Transaction) {
+        self.lock().mempool.push(tx);
+    }
+
+    /// Drops every transaction from the mempool, like a node restarting without its mempool.
+    pub fn clear_mempool(&self) {
+        self.lock().mempool.clear();
+    }
+
+    /// Returns the transactions broadcast since the last block was mined.
+    pub fn mempool(&self) -> Vec<Transaction> {
+        self.lock().mempool.clone()
+    }
+
+    /// Returns the height of the chain tip.
+    pub fn height(&self) -> u64 {
+        self.lock().blocks.len() as u64 - 1
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().expect("poisoned mutex")
+    }
+}
+
+impl Default for MockChain {
+    fn default() -> Self {
+        MockChain::new()
+    }
+}
+
+impl Inner {
+    /// Returns the outputs that are unspent in the chain plus mempool.
+    fn utxos(&self) -> HashMap<OutPoint, TxOut> {
+        let mut utxos = HashMap::new();
+        let txs = self
+            .blocks
+            .iter()
+            .flat_map(|block| &block.txdata)
+            .chain(&self.mempool);
+        for tx in txs {
+            for input in &tx.input {
+                utxos.remove(&input.previous_output);
+            }
+            let txid = tx.txid();
+            for (vout, output) in tx.output.iter().enumerate() {
+                utxos.insert(OutPoint::new(txid, vout as u32), output.clone());
+            }
+        }
+        utxos
+    }
+
+    /// Checks `tx` could be accepted to the mempool, returns the reason if not.
+    fn check(&self, tx: &Transaction) -> Result<(), String> {
+        let utxos = self.utxos();
+        let prevouts = tx
+            .input
+            .iter()
+            .map(|input| {
+                utxos
+                    .get(&input.previous_output)
+                    .cloned()
+                    .ok_or_else(|| format!("missing or spent input {}", input.previous_output))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let sum = |values: &mut dyn Iterator<Item = u64>| {
+            fee::checked_sum(values.map(Amount::from_sat)).ok_or("value overflow")
+        };
+        let input_value = sum(&mut prevouts.iter().map(|prevout| prevout.value))?;
+        let output_value = sum(&mut tx.output.iter().map(|output| output.value))?;
+        if output_value > input_value {
+            return Err(format!(
+                "outputs ({} sat) exceed inputs ({} sat)",
+                output_value.to_sat(),
+                input_value.to_sat()
+            ));
+        }
+
+        let mut cache = SighashCache::new(tx);
+        for (index, prevout) in prevouts.iter().enumerate() {
+            if prevout.script_pubkey.is_v0_p2wpkh() {
+                check_p2wpkh(&mut cache, index, prevout, &tx.input[index].witness)
+                    .map_err(|error| format!("input {}: {}", index, error))?;
+                continue;
+            }
+            if !prevout.script_pubkey.is_v1_p2tr() {
+                continue;
+            }
+            let output_key = XOnlyPublicKey::from_slice(&prevout.script_pubkey.as_bytes()[2..])
+                .map_err(|error| format!("input {}: {}", index, error))?;
+            let witness = &tx.input[index].witness;
+            let (signature, key, leaf_hash) = match witness.len() {
+                1 => (&witness[0], output_key, None),
+                3 => {
+                    let (key, leaf_hash) = check_script_path(&witness[1], &witness[2], output_key)
+                        .map_err(|error| format!("input {}: {}", index, error))?;
+                    (&witness[0], key, Some(leaf_hash))
+                }
+                _ => return Err(format!("input {}: unsupported taproot spend", index)),
+            };
+            let signature = taproot::Signature::from_slice(signature)
+                .map_err(|error| format!("input {}: {}", index, error))?;
+            let prevouts = Prevouts::All(&prevouts);
+            let sighash = match leaf_hash {
+                None => cache.taproot_key_spend_signature_hash(index, &prevouts, signature.hash_ty),
+                Some(leaf_hash) => cache.taproot_script_spend_signature_hash(
+                    index,
+                    &prevouts,
+                    leaf_hash,
+                    signature.hash_ty,
+                ),
+            }
+            .map_err(|error| format!("input {}: {}", index, error))?;
+            let message = Message::from_slice(sighash.as_ref()).expect("sighash is 32 bytes");
+            SECP256K1
+                .verify_schnorr(&signature.sig, &message, &key)
+                .map_err(|_| format!("input {}: invalid signature", index))?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks `witness` spends the P2WPKH output `prevout`, input `index` of the transaction in `cache`.
+fn check_p2wpkh(
+    cache: &mut SighashCache<&Transaction>,
+    index: usize,
+    prevout: &TxOut,
+    witness: &Witness,
+) -> Result<(), String> {
+    let (signature, public_key) = match (witness.nth(0), witness.nth(1), witness.len()) {
+        (Some(signature), Some(public_key), 2) => (signature, public_key),
+        _ => return Err("unsupported P2WPKH witness".to_owned()),
+    };
+    let public_key = PublicKey::from_slice(public_key).map_err(|error| error.to_string())?;
+    let hash = public_key
+        .wpubkey_hash()
+        .ok_or("uncompressed key in P2WPKH witness")?;
+    if prevout.script_pubkey != ScriptBuf::new_v0_p2wpkh(&hash) {
+        return Err("key does not match the output".to_owned());
+    }
+    let signature = ecdsa::Signature::from_slice(signature).map_err(|error| error.to_string())?;
+    let script_code = ScriptBuf::new_p2pkh(&public_key.pubkey_hash());
+    let sighash = cache
+        .segwit_signature_hash(index, &script_code, prevout.value, signature.hash_ty)
+        .map_err(|error| error.to_string())?;
+    let message = Message::from_slice(sighash.as_ref()).expect("sighash is 32 bytes");
+    SECP256K1
+        .verify_ecdsa(&message, &signature.sig, &public_key.inner)
+        .map_err(|_| "invalid signature".to_owned())
+}
+
+/// Checks `script` is a leaf of the tree `output_key` commits to, as proven by `control_block`.
+///
+/// Returns the key the leaf's final `OP_CHECKSIG` checks against and the leaf hash.
+fn check_script_path(
+    script: &[u8],
+    control_block: &[u8],
+    output_key: XOnlyPublicKey,
+) -> Result<(XOnlyPublicKey, TapLeafHash), String> {
+    let control_block = ControlBlock::decode(control_block).map_err(|error| error.to_string())?;
+    let script = Script::from_bytes(script);
+    if !control_block.verify_taproot_commitment(SECP256K1, output_key, script) {
+        return Err("script is not committed to by the output".to_owned());
+    }
+    // The last 34 bytes are a push of the 32 byte key followed by OP_CHECKSIG.
+    let bytes = script.as_bytes();
+    let key = match bytes.len().checked_sub(34).map(|start| &bytes[start..]) {
+        Some([0x20, key @ .., 0xac]) => {
+            XOnlyPublicKey::from_slice(key).map_err(|error| error.to_string())?
+        }
+        _ => return Err("unsupported script, must end in <key> OP_CHECKSIG".to_owned()),
+    };
+    Ok((
+        key,
+        TapLeafHash::from_script(script, control_block.leaf_version),
+    ))
+}
+
+impl Backend for MockChain {
+    async fn block_count(&self) -> Result<u64, BackendError> {
+        Ok(self.height())
+    }
+
+    async fn block_hash(&self, height: u64) -> Result<BlockHash, BackendError> {
+        self.lock()
+            .blocks
+            .get(height as usize)
+            .map(|block| block.block_hash())
+            .ok_or_else(|| BackendError::NotFound(format!("block at height {}", height)))
+    }
+
+    async fn raw_block(&self, hash: BlockHash) -> Result<Vec<u8>, BackendError> {
+        self.lock()
+            .blocks
+            .iter()
+            .find(|block| block.block_hash() == hash)
+            .map(serialize)
+            .ok_or_else(|| BackendError::NotFound(format!("block {}", hash)))
+    }
+
+    async fn estimate_fee_rate(&self, _target: u16) -> Result<Option<FeeRate>, BackendError> {
+        Ok(self.lock().fee_rate)
+    }
+
+    async fn broadcast(&self, tx: Transaction) -> Result<Txid, BackendError> {
+        let mut inner = self.lock();
+        let txid = tx.txid();
+        // Like bitcoind, which relays it again.
+        if inner.mempool.iter().any(|other| other.txid() == txid) {
+            return Ok(txid);
+        }
+        // Replaces mempool transactions spending the same outputs, without BIP-125's fee rules.
+        let spends = |other: &Transaction| {
+            other.input.iter().any(|input| {
+                tx.input
+                    .iter()
+                    .any(|ours| ours.previous_output == input.previous_output)
+            })
+        };
+        let (replaced, kept) = inner.mempool.drain(..).partition::<Vec<_>, _>(spends);
+        inner.mempool = kept;
+        if let Err(reason) = inner.check(&tx) {
+            inner.mempool.extend(replaced);
+            return Err(BackendError::Rejected(reason));
+        }
+        inner.mempool.push(tx);
+        Ok(txid)
+    }
+
+    async fn mempool_txids(&self) -> Result<Vec<Txid>, BackendError> {
+        Ok(self.lock().mempool.iter().map(Transaction::txid).collect())
+    }
+
+    async fn mempool_tx(&self, txid: Txid) -> Result<Option<Transaction>, BackendError> {
+        Ok(self
+            .lock()
+            .mempool
+            .iter()
+            .find(|tx| tx.txid() == txid)
+            .cloned())
+    }
+}
+
+/// Returns a transaction paying each of `amounts` to `script_pubkey`.
+///
+/// The input spends a made up outpoint so the transaction can only be mined with
+/// [`MockChain::mine`], never broadcast.
+pub fn funding_tx(script_pubkey: &Script, amounts: &[Amount]) -> Transaction {
+    // Makes every funding transaction unique.
+    static NONCE: AtomicU32 = AtomicU32::new(0);
+
+    Transaction {
+        version: 2,
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(
+                Txid::all_zeros(),
+                NONCE.fetch_add(1, Ordering::Relaxed),
+            ),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::MAX,
+            witness: Witness::new(),
+        }],
+        output: amounts
+            .iter()
+            .map(|amount| TxOut {
+                value: amount.to_sat(),
+                script_pubkey: script_pubkey.to_owned(),
+            })
+            .collect(),
+    }
+}
+
+/// Returns a coinbase transaction for the block at `height`, paying to an empty script.
+fn coinbase(height: u64) -> Transaction {
+    Transaction {
+        version: 2,
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            // BIP-34 height push, also makes each coinbase txid unique.
+            script_sig: bitcoin::script::Builder::new()
+                .push_int(height as i64)
+                .into_script(),
+            sequence: Sequence::MAX,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: 50 * Amount::ONE_BTC.to_sat(),
+            script_pubkey: ScriptBuf::new(),
+        }],
+    }
+}
@@ -0,0 +1,153 @@
+//! Append-only log of what the wallet did, one JSON object per line.
+//!
+//! Nothing in the wallet reads the log back to make decisions, it is an audit trail for the user:
+//! which blocks were scanned and which transactions were sent, when. Lines are only ever appended
+//! so the file can be tailed or processed with `jq`.
+
+use std::fs::OpenOptions;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bitcoin::Txid;
+
+use crate::config::Chain;
+use crate::db::{self, DbError};
+
+/// A significant operation, serialized with an `op` field naming the variant.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Op {
+    /// Blocks `from_height..=to_height` were scanned.
+    Scan {
+        from_height: u64,
+        to_height: u64,
+        received: usize,
+        spent: usize,
+    },
+    /// A payment was broadcast.
+    Send {
+        txid: Txid,
+        recipient: String,
+        amount_sat: u64,
+        fee_sat: u64,
+    },
+    /// A payment was replaced by one paying a higher fee.
+    Bump {
+        replaced: Txid,
+        txid: Txid,
+        fee_sat: u64,
+    },
+}
+
+impl Op {
+    /// The value of the `op` field.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Op::Scan { .. } => "scan",
+            Op::Send { .. } => "send",
+            Op::Bump { .. } => "bump",
+        }
+    }
+}
+
+/// A line of the log.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Entry {
+    /// When the operation happened, in seconds since the UNIX epoch.
+    pub time: u64,
+    #[serde(flatten)]
+    pub op: Op,
+}
+
+/// The operation log of one chain.
+pub struct OpLog {
+    path: PathBuf,
+}
+
+impl OpLog {
+    /// Opens the log for `chain` in the data directory, the file is created on first append.
+    pub fn open(chain: Chain) -> Result<Self, OpLogError> {
+        Ok(OpLog::at(db::op_log_file(chain)?))
+    }
+
+    /// Uses the log file at `path`.
+    pub fn at(path: PathBuf) -> Self {
+        OpLog { path }
+    }
+
+    /// Appends `op`, timestamped with the current time.
+    pub fn append(&self, op: Op) -> Result<(), OpLogError> {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+        let mut line = serde_json::to_string(&Entry { time, op }).expect("entries serialize");
+        line.push('\n');
+
+        // A single write so concurrent appends do not interleave within a line.
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|source| OpLogError::Write {
+                path: self.path.clone(),
+                source,
+            })
+    }
+
+    /// Returns every entry in the log, oldest first. A missing log has no entries.
+    pub fn entries(&self) -> Result<Vec<Entry>, OpLogError> {
+        let read_error = |source| OpLogError::Read {
+            path: self.path.clone(),
+            source,
+        };
+        let file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(source) => return Err(read_error(source)),
+        };
+
+        let mut entries = Vec::new();
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(read_error)?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry = serde_json::from_str(&line).map_err(|source| OpLogError::Parse {
+                path: self.path.clone(),
+                line: index + 1,
+                source,
+            })?;
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+}
+
+/// Errors returned when writing or reading the operation log.
+#[derive(Debug, thiserror::Error)]
+pub enum OpLogError {
+    #[error(transparent)]
+    Path(#[from] DbError),
+    #[error("failed to write to operation log {}", path.display())]
+    Write {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to read operation log {}", path.display())]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("invalid entry on line {line} of operation log {}", path.display())]
+    Parse {
+        path: PathBuf,
+        line: usize,
+        #[source]
+        source: serde_json::Error,
+    },
+}
@@ -0,0 +1,230 @@
+//! Fiat prices of bitcoin, to show approximate fiat values next to amounts.
+//!
+//! Nothing is fetched unless the user asks for a fiat value and has configured where to get prices
+//! from, the wallet works offline without them. A [`PriceProvider`] gets the price, usually an
+//! [`HttpProvider`] wrapped in [`Cached`] so that running a few commands in a row asks the source
+//! once. Prices are only ever the current ones and only good for display: amounts in the wallet are
+//! always bitcoin.
+
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bitcoin::Amount;
+
+use crate::proxy::{self, parse_proxy, ProxyError};
+
+/// Where the price of bitcoin comes from.
+pub trait PriceProvider {
+    /// Returns the price of one bitcoin in `currency`, e.g. `USD`.
+    fn price(&self, currency: &str) -> Result<f64, PriceError>;
+}
+
+/// Gets prices with an HTTP GET request, e.g. from a price service running next to bitcoind.
+///
+/// `{currency}` in the URL is replaced by the currency asked for. The response is JSON, the price
+/// is either the whole of it or, anywhere in it, the number (or string) under a key that is the
+/// currency in any case. E.g. `{"bitcoin":{"usd":65000.0}}` for `USD`. Only plain HTTP is
+/// supported, there is no TLS.
+pub struct HttpProvider {
+    url: String,
+    proxy: Option<String>,
+    timeout: Duration,
+}
+
+impl HttpProvider {
+    /// Creates a provider for `url`, connecting through the SOCKS5 `proxy` if given.
+    pub fn new(url: &str, proxy: Option<&str>, timeout: Duration) -> Result<Self, PriceError> {
+        // Checked now rather than on the first request.
+        split_url(url)?;
+        Ok(HttpProvider {
+            url: url.to_owned(),
+            proxy: proxy.map(parse_proxy).transpose()?,
+            timeout,
+        })
+    }
+
+    fn get(&self, currency: &str) -> Result<Vec<u8>, PriceError> {
+        let url = self.url.replace("{currency}", currency);
+        let (host, port, path) = split_url(&url)?;
+        let request = format!(
+            "GET {} HTTP/1.0\r\nHost: {}\r\nAccept: application/json\r\nConnection: close\r\n\r\n",
+            path, host
+        );
+
+        let mut stream = match &self.proxy {
+            Some(proxy) => proxy::connect(proxy, host, port, self.timeout)?,
+            None => {
+                let addr = (host, port)
+                    .to_socket_addrs()?
+                    .next()
+                    .ok_or_else(|| PriceError::InvalidUrl(url.clone()))?;
+                TcpStream::connect_timeout(&addr, self.timeout)?
+            }
+        };
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        stream.write_all(request.as_bytes())?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response)?;
+
+        let split = response
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .ok_or_else(|| PriceError::Http("malformed response".to_owned()))?;
+        let status = String::from_utf8_lossy(&response[..split]);
+        let status = status.lines().next().unwrap_or_default();
+        if status.split_whitespace().nth(1) != Some("200") {
+            return Err(PriceError::Http(status.to_owned()));
+        }
+        Ok(response[split + 4..].to_vec())
+    }
+}
+
+impl PriceProvider for HttpProvider {
+    fn price(&self, currency: &str) -> Result<f64, PriceError> {
+        let body = self.get(currency)?;
+        parse_price(&body, currency)
+    }
+}
+
+/// Remembers the prices `provider` returned in a file for `max_age`.
+pub struct Cached<P> {
+    provider: P,
+    path: PathBuf,
+    max_age: Duration,
+}
+
+/// A price as kept in the cache file.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CachedPrice {
+    price: f64,
+    /// Seconds since the UNIX epoch.
+    time: u64,
+}
+
+impl<P: PriceProvider> Cached<P> {
+    /// Caches the prices of `provider` in the file at `path`, see [`crate::db::price_cache_file`].
+    pub fn new(provider: P, path: PathBuf, max_age: Duration) -> Self {
+        Cached {
+            provider,
+            path,
+            max_age,
+        }
+    }
+
+    /// Reads the cache, a missing or unreadable file is an empty cache.
+    fn load(&self) -> HashMap<String, CachedPrice> {
+        std::fs::read(&self.path)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .unwrap_or_default()
+    }
+}
+
+impl<P: PriceProvider> PriceProvider for Cached<P> {
+    fn price(&self, currency: &str) -> Result<f64, PriceError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system clock is after 1970")
+            .as_secs();
+        let mut prices = self.load();
+        if let Some(cached) = prices.get(currency) {
+            if now.saturating_sub(cached.time) < self.max_age.as_secs() {
+                return Ok(cached.price);
+            }
+        }
+
+        let price = self.provider.price(currency)?;
+        prices.insert(currency.to_owned(), CachedPrice { price, time: now });
+        let json = serde_json::to_vec(&prices).expect("prices serialize");
+        std::fs::write(&self.path, json).map_err(|source| PriceError::Cache {
+            path: self.path.clone(),
+            source,
+        })?;
+        Ok(price)
+    }
+}
+
+/// Finds the price in `currency` in the JSON `body`, see [`HttpProvider`].
+pub fn parse_price(body: &[u8], currency: &str) -> Result<f64, PriceError> {
+    fn number(value: &serde_json::Value) -> Option<f64> {
+        match value {
+            serde_json::Value::Number(number) => number.as_f64(),
+            serde_json::Value::String(s) => s.parse().ok(),
+            _ => None,
+        }
+    }
+    fn find(value: &serde_json::Value, currency: &str) -> Option<f64> {
+        match value {
+            serde_json::Value::Object(object) => object.iter().find_map(|(key, value)| {
+                if key.eq_ignore_ascii_case(currency) {
+                    number(value)
+                } else {
+                    find(value, currency)
+                }
+            }),
+            serde_json::Value::Array(array) => array.iter().find_map(|value| find(value, currency)),
+            _ => None,
+        }
+    }
+
+    let json = serde_json::from_slice::<serde_json::Value>(body)
+        .map_err(|_| PriceError::NoPrice(currency.to_owned()))?;
+    number(&json)
+        .or_else(|| find(&json, currency))
+        .filter(|price| price.is_finite() && *price > 0.0)
+        .ok_or_else(|| PriceError::NoPrice(currency.to_owned()))
+}
+
+/// Returns what `amount` is worth at `price`, in the price's currency.
+pub fn fiat_value(amount: Amount, price: f64) -> f64 {
+    amount.to_btc() * price
+}
+
+/// Splits an `http://host[:port]/path` URL.
+fn split_url(url: &str) -> Result<(&str, u16, &str), PriceError> {
+    let invalid = || PriceError::InvalidUrl(url.to_owned());
+    let rest = match url.split_once("://") {
+        Some(("http", rest)) => rest,
+        Some(_) => return Err(PriceError::UnsupportedScheme(url.to_owned())),
+        None => return Err(invalid()),
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(slash) => rest.split_at(slash),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+        None => (authority, 80),
+    };
+    if host.is_empty() {
+        return Err(invalid());
+    }
+    Ok((host, port, path))
+}
+
+/// Errors returned when getting a price.
+#[derive(Debug, thiserror::Error)]
+pub enum PriceError {
+    #[error("invalid price URL {0}")]
+    InvalidUrl(String),
+    #[error("unsupported price URL {0}, only http:// is supported")]
+    UnsupportedScheme(String),
+    #[error("invalid proxy")]
+    Proxy(#[from] ProxyError),
+    #[error("failed to reach the price source")]
+    Io(#[from] io::Error),
+    #[error("the price source replied {0}")]
+    Http(String),
+    #[error("the price source did not return a {0} price")]
+    NoPrice(String),
+    #[error("failed to write price cache {}", path.display())]
+    Cache {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+}
@@ -0,0 +1,274 @@
+//! A minimal JSON-RPC transport that tunnels HTTP requests through a SOCKS5 proxy (e.g. Tor).
+//!
+//! `jsonrpc` has a `proxy` feature but enabling it routes *every* connection through the proxy and
+//! resolves the target host locally, which does not work for onion addresses. This transport hands
+//! the host name to the proxy instead so `.onion` backends can be reached.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::num::ParseIntError;
+use std::time::Duration;
+
+use jsonrpc::{Request, Response};
+
+/// The SOCKS protocol version we speak.
+const SOCKS_VERSION: u8 = 5;
+/// The authentication method for no authentication.
+const NO_AUTH: u8 = 0;
+/// The command to open a TCP connection.
+const CONNECT: u8 = 1;
+/// Address types in requests and replies.
+const IPV4: u8 = 1;
+const DOMAIN_NAME: u8 = 3;
+const IPV6: u8 = 4;
+
+/// JSON-RPC over HTTP over SOCKS5.
+///
+/// Opens a fresh connection for each request, this is slow but Tor circuits are slow anyway.
+pub struct Socks5Transport {
+    proxy: String,
+    host: String,
+    port: u16,
+    path: String,
+    basic_auth: Option<String>,
+    timeout: Duration,
+}
+
+impl Socks5Transport {
+    /// Creates a new transport for `uri` (e.g. `http://xyz.onion:8332`) going via `proxy`.
+    ///
+    /// `proxy` is the value of the `proxy` config option e.g., `socks5://127.0.0.1:9050`. The
+    /// `timeout` applies to connecting, through the proxy too, and to reading and writing.
+    pub fn new(
+        proxy: &str,
+        uri: &str,
+        auth: bitcoincore_rpc::Auth,
+        timeout: Duration,
+    ) -> Result<Self, ProxyError> {
+        let proxy = parse_proxy(proxy)?;
+
+        let after_scheme = match uri.split_once("://") {
+            Some(("http", rest)) => rest,
+            Some((scheme, _)) => {
+                return Err(ProxyError::UnsupportedScheme {
+                    scheme: scheme.to_owned(),
+                    uri: uri.to_owned(),
+                })
+            }
+            None => uri,
+        };
+        let (authority, path) = match after_scheme.find('/') {
+            Some(slash) => after_scheme.split_at(slash),
+            None => (after_scheme, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => {
+                let port = port.parse().map_err(|source| ProxyError::InvalidPort {
+                    uri: uri.to_owned(),
+                    source,
+                })?;
+                (host, port)
+            }
+            None => (authority, 80),
+        };
+
+        let (user, pass) = auth.get_user_pass().map_err(ProxyError::Credentials)?;
+        let basic_auth = user.map(|user| {
+            let credentials = format!("{}:{}", user, pass.unwrap_or_default());
+            format!("Basic {}", base64::encode(credentials))
+        });
+
+        Ok(Socks5Transport {
+            proxy,
+            host: host.to_owned(),
+            port,
+            path: path.to_owned(),
+            basic_auth,
+            timeout,
+        })
+    }
+
+    fn request<R>(&self, body: &impl serde::Serialize) -> Result<R, jsonrpc::Error>
+    where
+        R: for<'a> serde::de::Deserialize<'a>,
+    {
+        let body = serde_json::to_vec(body)?;
+
+        let mut request = Vec::new();
+        write!(request, "POST {} HTTP/1.1\r\n", self.path).map_err(transport_error)?;
+        write!(request, "Host: {}:{}\r\n", self.host, self.port).map_err(transport_error)?;
+        write!(request, "Content-Type: application/json\r\n").map_err(transport_error)?;
+        write!(request, "Content-Length: {}\r\n", body.len()).map_err(transport_error)?;
+        write!(request, "Connection: close\r\n").map_err(transport_error)?;
+        if let Some(ref auth) = self.basic_auth {
+            write!(request, "Authorization: {}\r\n", auth).map_err(transport_error)?;
+        }
+        request.extend_from_slice(b"\r\n");
+        request.extend_from_slice(&body);
+
+        let mut stream =
+            connect(&self.proxy, &self.host, self.port, self.timeout).map_err(transport_error)?;
+        stream.write_all(&request).map_err(transport_error)?;
+        stream.flush().map_err(transport_error)?;
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).map_err(transport_error)?;
+
+        let split = response
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .ok_or_else(|| transport_error(ProxyError::MalformedResponse))?;
+        let (header, body) = (&response[..split], &response[split + 4..]);
+        // bitcoind sends a Content-Length, read as the rest of the response.
+        let header_text = String::from_utf8_lossy(header).to_ascii_lowercase();
+        let chunked = header_text.lines().any(|line| {
+            matches!(line.split_once(':'), Some((name, value))
+                if name.trim() == "transfer-encoding" && value.contains("chunked"))
+        });
+        if chunked {
+            return Err(transport_error(ProxyError::Chunked));
+        }
+
+        // bitcoind replies with status 500 and a JSON body for RPC errors so we only bail if there
+        // is nothing to deserialize.
+        if body.is_empty() {
+            let status = String::from_utf8_lossy(header);
+            let status = status.lines().next().unwrap_or_default();
+            return Err(transport_error(ProxyError::Http(status.to_owned())));
+        }
+        Ok(serde_json::from_slice(body)?)
+    }
+}
+
+impl jsonrpc::Transport for Socks5Transport {
+    fn send_request(&self, req: Request) -> Result<Response, jsonrpc::Error> {
+        self.request(&req)
+    }
+
+    fn send_batch(&self, reqs: &[Request]) -> Result<Vec<Response>, jsonrpc::Error> {
+        self.request(&reqs)
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "http://{}:{}{} via socks5://{}",
+            self.host, self.port, self.path, self.proxy
+        )
+    }
+}
+
+/// Strips the scheme from a `socks5://host:port` proxy string.
+pub(crate) fn parse_proxy(proxy: &str) -> Result<String, ProxyError> {
+    match proxy.split_once("://") {
+        Some(("socks5", addr)) | Some(("socks5h", addr)) => Ok(addr.to_owned()),
+        Some((scheme, _)) => Err(ProxyError::UnsupportedProxyScheme(scheme.to_owned())),
+        None => Ok(proxy.to_owned()),
+    }
+}
+
+/// Connects to `host:port` through the SOCKS5 `proxy` (an address as returned by [`parse_proxy`]),
+/// leaving `host` for the proxy to resolve.
+///
+/// `timeout` applies to connecting to the proxy and to each read and write after that, including
+/// waiting for the proxy to connect on to `host`, so a stalled Tor circuit fails rather than
+/// hanging. The returned stream keeps the read and write timeouts.
+pub(crate) fn connect(
+    proxy: &str,
+    host: &str,
+    port: u16,
+    timeout: Duration,
+) -> io::Result<TcpStream> {
+    let mut result = Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("proxy address {} did not resolve", proxy),
+    ));
+    for addr in proxy.to_socket_addrs()? {
+        result = TcpStream::connect_timeout(&addr, timeout);
+        if result.is_ok() {
+            break;
+        }
+    }
+    let mut stream = result?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    stream.write_all(&[SOCKS_VERSION, 1, NO_AUTH])?;
+    let mut reply = [0; 2];
+    stream.read_exact(&mut reply)?;
+    if reply != [SOCKS_VERSION, NO_AUTH] {
+        return Err(socks_error("the proxy requires authentication"));
+    }
+
+    let host_len = u8::try_from(host.len()).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("host name {} is too long", host),
+        )
+    })?;
+    let mut request = vec![SOCKS_VERSION, CONNECT, 0, DOMAIN_NAME, host_len];
+    request.extend_from_slice(host.as_bytes());
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request)?;
+
+    let mut reply = [0; 4];
+    stream.read_exact(&mut reply)?;
+    if reply[0] != SOCKS_VERSION {
+        return Err(socks_error("unexpected reply from the proxy"));
+    }
+    if reply[1] != 0 {
+        return Err(socks_error(&format!(
+            "the proxy failed to connect to {}:{}, reply code {}",
+            host, port, reply[1]
+        )));
+    }
+    // Followed by the address the proxy connected from, which we have no use for.
+    let addr_len = match reply[3] {
+        IPV4 => 4,
+        IPV6 => 16,
+        DOMAIN_NAME => {
+            let mut len = [0; 1];
+            stream.read_exact(&mut len)?;
+            usize::from(len[0])
+        }
+        _ => return Err(socks_error("unexpected reply from the proxy")),
+    };
+    let mut bound = vec![0; addr_len + 2];
+    stream.read_exact(&mut bound)?;
+    Ok(stream)
+}
+
+fn socks_error(message: &str) -> io::Error {
+    io::Error::other(format!("SOCKS5: {}", message))
+}
+
+fn transport_error(error: impl Into<ProxyError>) -> jsonrpc::Error {
+    jsonrpc::Error::Transport(Box::new(error.into()))
+}
+
+/// Errors returned when setting up or using the proxy transport.
+#[derive(Debug, thiserror::Error)]
+pub enum ProxyError {
+    #[error("unsupported scheme {scheme} in bitcoind URI {uri}")]
+    UnsupportedScheme { scheme: String, uri: String },
+    #[error("invalid port in bitcoind URI {uri}")]
+    InvalidPort {
+        uri: String,
+        #[source]
+        source: ParseIntError,
+    },
+    #[error("unsupported proxy scheme {0}, use socks5")]
+    UnsupportedProxyScheme(String),
+    #[error("failed to read bitcoind credentials")]
+    Credentials(#[source] bitcoincore_rpc::Error),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("malformed HTTP response")]
+    MalformedResponse,
+    #[error("chunked HTTP responses are not supported")]
+    Chunked,
+    #[error("HTTP error: {0}")]
+    Http(String),
+}
@@ -0,0 +1,183 @@
+//! The optional recovery path of wallet outputs, e.g. for inheritance or a lost key.
+//!
+//! With a [`Recovery`] configured every wallet output commits to a single script leaf,
+//! `<blocks> OP_CHECKSEQUENCEVERIFY OP_DROP <key> OP_CHECKSIG`, next to the key path. The wallet
+//! keeps spending by key path as usual, the holder of the recovery key can sweep outputs that have
+//! been confirmed for `blocks` blocks with [`build_sweep`] and [`sign_sweep`]. Spending an output
+//! (e.g. to yourself) before then resets the clock.
+
+use std::collections::BTreeMap;
+
+use bitcoin::absolute::LockTime;
+use bitcoin::opcodes::all::{OP_CHECKSIG, OP_CSV, OP_DROP};
+use bitcoin::script::Builder;
+use bitcoin::secp256k1::{KeyPair, Message, XOnlyPublicKey, SECP256K1};
+use bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
+use bitcoin::taproot::{self, LeafVersion, TapLeafHash, TaprootBuilder, TaprootSpendInfo};
+use bitcoin::transaction::predict_weight;
+use bitcoin::{
+    Address, Amount, FeeRate, OutPoint, PrivateKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut,
+    Witness,
+};
+
+use crate::fee;
+use crate::sign::SignError;
+use crate::tx::{Payment, SendError};
+
+/// Who can spend wallet outputs by script path, and after how long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Recovery {
+    /// The key that can spend by script path.
+    pub key: XOnlyPublicKey,
+    /// How many blocks an output must have been confirmed for before it can be recovered, a BIP-68
+    /// relative timelock so at most 65535 (about 15 months).
+    pub blocks: u16,
+}
+
+impl Recovery {
+    /// Returns the script of the recovery leaf.
+    pub fn script(&self) -> ScriptBuf {
+        Builder::new()
+            .push_int(self.blocks.into())
+            .push_opcode(OP_CSV)
+            .push_opcode(OP_DROP)
+            .push_x_only_key(&self.key)
+            .push_opcode(OP_CHECKSIG)
+            .into_script()
+    }
+
+    /// Returns the taproot spend data of outputs with `internal_key` on the key path and the
+    /// recovery leaf as the whole script tree.
+    pub fn spend_info(&self, internal_key: XOnlyPublicKey) -> TaprootSpendInfo {
+        TaprootBuilder::new()
+            .add_leaf(0, self.script())
+            .expect("a single leaf is a valid tree")
+            .finalize(SECP256K1, internal_key)
+            .expect("the tree is complete")
+    }
+
+    /// Returns whether an output confirmed at `height` can be recovered in the block after `tip`.
+    pub fn is_expired(&self, height: u64, tip: u64) -> bool {
+        tip + 1 >= height + u64::from(self.blocks)
+    }
+}
+
+/// Builds a transaction sweeping `utxos`, all wallet outputs spent via `spend_info`, to `recipient`
+/// by the recovery path.
+///
+/// The fee comes out of the swept amount. Callers pick expired outputs, see
+/// [`Recovery::is_expired`], the transaction is not valid before every input is.
+pub fn build_sweep(
+    utxos: &[(OutPoint, Amount)],
+    spend_info: &TaprootSpendInfo,
+    recovery: &Recovery,
+    recipient: &Address,
+    fee_rate: FeeRate,
+    dust_relay_fee: FeeRate,
+) -> Result<Payment, SendError> {
+    let wallet_script = ScriptBuf::new_v1_p2tr_tweaked(spend_info.output_key());
+    let recipient_script = recipient.script_pubkey();
+    let available =
+        fee::checked_sum(utxos.iter().map(|(_, value)| *value)).ok_or(SendError::Overflow)?;
+
+    // The recovery leaf is the only one, so its control block has no merkle path.
+    let prediction = fee::p2tr_script_path(recovery.script().len(), 0, 1);
+    let weight = predict_weight(vec![prediction; utxos.len()], [recipient_script.len()]);
+    let fee = fee::fee_for(weight, fee_rate).ok_or(SendError::Overflow)?;
+    let amount = available
+        .checked_sub(fee)
+        .ok_or(SendError::InsufficientFunds {
+            available,
+            amount: Amount::ZERO,
+            fee,
+        })?;
+    let limit = fee::dust_limit(&recipient_script, dust_relay_fee);
+    if amount < limit {
+        return Err(SendError::Dust { amount, limit });
+    }
+
+    let input = utxos
+        .iter()
+        .map(|(outpoint, _)| TxIn {
+            previous_output: *outpoint,
+            script_sig: ScriptBuf::new(),
+            // The relative timelock OP_CHECKSEQUENCEVERIFY checks, also signals RBF.
+            sequence: Sequence::from_height(recovery.blocks),
+            witness: Witness::new(),
+        })
+        .collect();
+    let prevouts = utxos
+        .iter()
+        .map(|(_, value)| TxOut {
+            value: value.to_sat(),
+            script_pubkey: wallet_script.clone(),
+        })
+        .collect();
+    Ok(Payment {
+        tx: Transaction {
+            // BIP-68 relative timelocks need version 2.
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input,
+            output: vec![TxOut {
+                value: amount.to_sat(),
+                script_pubkey: recipient_script,
+            }],
+        },
+        prevouts,
+        fee,
+        sighash_types: BTreeMap::new(),
+    })
+}
+
+/// Signs every input of `sweep` by the recovery path with `key`, the recovery key.
+///
+/// `aux_rand` is used as the BIP-340 auxiliary randomness if given, see
+/// [`crate::sign::KeySigner::with_aux_rand`], otherwise each signature gets fresh random bytes.
+pub fn sign_sweep(
+    sweep: &Payment,
+    spend_info: &TaprootSpendInfo,
+    recovery: &Recovery,
+    key: &PrivateKey,
+    aux_rand: Option<[u8; 32]>,
+) -> Result<Transaction, SignError> {
+    let mut keypair = KeyPair::from_secret_key(SECP256K1, &key.inner);
+    if keypair.x_only_public_key().0 != recovery.key {
+        keypair.non_secure_erase();
+        return Err(SignError::NotRecoveryKey);
+    }
+    let script = recovery.script();
+    let control_block = spend_info
+        .control_block(&(script.clone(), LeafVersion::TapScript))
+        .expect("the recovery leaf is in the tree");
+    let leaf_hash = TapLeafHash::from_script(&script, LeafVersion::TapScript);
+
+    let mut tx = sweep.tx.clone();
+    let mut cache = SighashCache::new(&sweep.tx);
+    for (index, input) in tx.input.iter_mut().enumerate() {
+        let sighash = cache
+            .taproot_script_spend_signature_hash(
+                index,
+                &Prevouts::All(&sweep.prevouts),
+                leaf_hash,
+                TapSighashType::Default,
+            )
+            .map_err(|source| SignError::Sighash { index, source })?;
+        let message = Message::from_slice(sighash.as_ref()).expect("sighash is 32 bytes");
+        let signature = taproot::Signature {
+            sig: SECP256K1.sign_schnorr_with_aux_rand(
+                &message,
+                &keypair,
+                &aux_rand.unwrap_or_else(rand::random),
+            ),
+            hash_ty: TapSighashType::Default,
+        };
+        input.witness = Witness::from_slice(&[
+            signature.to_vec(),
+            script.to_bytes(),
+            control_block.serialize(),
+        ]);
+    }
+    keypair.non_secure_erase();
+    Ok(tx)
+}
@@ -0,0 +1,337 @@
+//! RPC connection to `bitcoind`.
+//!
+//! Wraps `bitcoincore_rpc::Client` so that we can retry failed calls and fail over between multiple
+//! configured nodes.
+
+use std::error::Error;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bitcoin::{Network, Script};
+use bitcoincore_rpc::RpcApi;
+
+use crate::config::{Auth, Chain, Config};
+use crate::proxy;
+
+pub use crate::proxy::ProxyError;
+
+/// The block challenge of the default signet, a 1-of-2 multisig.
+const DEFAULT_SIGNET_CHALLENGE: &str = "512103ad5e0edad18cb1f0fc0d28a3d4f1f3e445640337489abb10404f2d8e086be430210359ef5021964fe22d6f8e05b2463c9540ce96883fe3b278760f048f5189f2e6c452ae";
+
+/// How long to wait before the first retry, doubled for each subsequent one.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+/// The longest we wait between two retries, however many are configured.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// The error code bitcoind returns while it is still starting up (e.g. loading the block index).
+const RPC_IN_WARMUP: i32 = -28;
+
+/// An RPC client that fails over to the next configured `bitcoind` endpoint.
+pub struct Client {
+    endpoints: Endpoints,
+    /// The index of the endpoint we are currently using and its client.
+    current: Mutex<(usize, bitcoincore_rpc::Client)>,
+}
+
+impl Client {
+    /// Connects to the first healthy endpoint in the configuration.
+    pub fn new(conf: &Config) -> Result<Self, RpcError> {
+        let endpoints = Endpoints::new(conf, conf.bitcoind_uris.clone());
+        let current = endpoints.connect_healthy(0)?;
+        Ok(Client {
+            endpoints,
+            current: Mutex::new(current),
+        })
+    }
+
+    /// Connects to `uri` only, without retrying or failing over.
+    ///
+    /// Used to diagnose a single endpoint, the error is returned as is so the cause can be
+    /// inspected.
+    pub fn connect_to(conf: &Config, uri: &str) -> Result<Self, RpcError> {
+        let mut endpoints = Endpoints::new(conf, vec![uri.to_owned()]);
+        endpoints.retries = 0;
+        let client = endpoints.connect(uri)?;
+        health_check(&client)?;
+        Ok(Client {
+            endpoints,
+            current: Mutex::new((0, client)),
+        })
+    }
+
+    /// Checks that the node is running on the chain we are configured for.
+    ///
+    /// On signet also checks that the node uses the expected block challenge, `signet_challenge` for
+    /// a custom signet or the default signet's.
+    pub fn check_network(
+        &self,
+        chain: Chain,
+        signet_challenge: Option<&Script>,
+    ) -> Result<(), RpcError> {
+        let info = self
+            .get_blockchain_info()
+            .map_err(RpcError::call("get blockchain info"))?;
+        if info.chain != chain.core_name() {
+            return Err(RpcError::WrongChain {
+                node: info.chain,
+                wallet: chain,
+            });
+        }
+
+        if chain.network() == Network::Signet {
+            let challenge = match signet_challenge {
+                Some(challenge) => challenge.to_hex_string(),
+                None => DEFAULT_SIGNET_CHALLENGE.to_owned(),
+            };
+            let mining_info = self
+                .call::<serde_json::Value>("getmininginfo", &[])
+                .map_err(RpcError::call("get mining info"))?;
+            let node_challenge = mining_info["signet_challenge"].as_str().unwrap_or_default();
+            if node_challenge != challenge {
+                return Err(RpcError::WrongSignetChallenge {
+                    node: node_challenge.to_owned(),
+                    wallet: challenge,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the URI of the endpoint currently in use.
+    pub fn uri(&self) -> String {
+        let current = self.current.lock().expect("poisoned mutex");
+        self.endpoints.uris[current.0].clone()
+    }
+}
+
+impl From<Auth> for bitcoincore_rpc::Auth {
+    fn from(auth: Auth) -> Self {
+        match auth {
+            Auth::None => bitcoincore_rpc::Auth::None,
+            Auth::UserPass(user, pass) => bitcoincore_rpc::Auth::UserPass(user, pass),
+            Auth::CookieFile(path) => bitcoincore_rpc::Auth::CookieFile(path),
+        }
+    }
+}
+
+/// Everything needed to (re)connect to one of the configured nodes.
+struct Endpoints {
+    uris: Vec<String>,
+    auth: bitcoincore_rpc::Auth,
+    wallet: Option<String>,
+    proxy: Option<String>,
+    timeout: Duration,
+    retries: u32,
+}
+
+impl Endpoints {
+    fn new(conf: &Config, uris: Vec<String>) -> Self {
+        Endpoints {
+            uris,
+            auth: conf.bitcoind_auth.clone().into(),
+            wallet: conf.bitcoind_wallet.clone(),
+            proxy: conf.proxy.clone(),
+            timeout: conf.rpc_timeout,
+            retries: conf.rpc_retries,
+        }
+    }
+
+    /// Tries each endpoint in turn, starting at index `start`, returning the first healthy one.
+    fn connect_healthy(&self, start: usize) -> Result<(usize, bitcoincore_rpc::Client), RpcError> {
+        let mut errors = Vec::new();
+
+        for i in 0..self.uris.len() {
+            let index = (start + i) % self.uris.len();
+            let uri = &self.uris[index];
+            match self
+                .connect(uri)
+                .and_then(|client| health_check(&client).map(|_| client))
+            {
+                Ok(client) => return Ok((index, client)),
+                Err(error) => errors.push((uri.clone(), error)),
+            }
+        }
+        Err(RpcError::Unreachable(errors))
+    }
+
+    fn connect(&self, uri: &str) -> Result<bitcoincore_rpc::Client, RpcError> {
+        let uri = &match self.wallet {
+            Some(ref wallet) => format!(
+                "{}/wallet/{}",
+                uri.trim_end_matches('/'),
+                encode_path_segment(wallet)
+            ),
+            None => uri.to_owned(),
+        };
+
+        let client = match self.proxy {
+            Some(ref proxy) => {
+                let transport =
+                    proxy::Socks5Transport::new(proxy, uri, self.auth.clone(), self.timeout)
+                        .map_err(RpcError::Proxy)?;
+                bitcoincore_rpc::Client::from_jsonrpc(jsonrpc::Client::with_transport(transport))
+            }
+            None => {
+                let (user, pass) = self
+                    .auth
+                    .clone()
+                    .get_user_pass()
+                    .map_err(RpcError::Credentials)?;
+                let mut builder = jsonrpc::simple_http::Builder::new()
+                    .url(uri)
+                    .map_err(|source| RpcError::InvalidUri {
+                        uri: uri.to_owned(),
+                        source,
+                    })?
+                    .timeout(self.timeout);
+                if let Some(user) = user {
+                    builder = builder.auth(user, pass);
+                }
+                bitcoincore_rpc::Client::from_jsonrpc(jsonrpc::Client::with_transport(
+                    builder.build(),
+                ))
+            }
+        };
+        Ok(client)
+    }
+}
+
+impl RpcApi for Client {
+    fn call<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &[serde_json::Value],
+    ) -> bitcoincore_rpc::Result<T> {
+        let mut current = self.current.lock().expect("poisoned mutex");
+        let uris = &self.endpoints.uris;
+        let mut attempt = 0;
+
+        loop {
+            match current.1.call(cmd, args) {
+                Err(error) if is_retryable(&error) && attempt < self.endpoints.retries => {
+                    let delay = RETRY_DELAY
+                        .checked_mul(2u32.saturating_pow(attempt))
+                        .map_or(MAX_RETRY_DELAY, |delay| delay.min(MAX_RETRY_DELAY));
+                    attempt += 1;
+                    eprintln!(
+                        "warning: {} failed ({}), retrying in {}s",
+                        cmd,
+                        error,
+                        delay.as_secs()
+                    );
+                    std::thread::sleep(delay);
+                    // bitcoind may have restarted, connect again so a new cookie file is read.
+                    if let Ok(client) = self.endpoints.connect(&uris[current.0]) {
+                        current.1 = client;
+                    }
+                }
+                Err(error) if is_transport_error(&error) && uris.len() > 1 => {
+                    return match self.endpoints.connect_healthy(current.0 + 1) {
+                        Ok(healthy) => {
+                            eprintln!(
+                                "warning: {} unreachable, failing over to {}",
+                                uris[current.0], uris[healthy.0]
+                            );
+                            *current = healthy;
+                            current.1.call(cmd, args)
+                        }
+                        Err(_) => Err(error),
+                    };
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Checks that the node answers a cheap RPC call.
+/// Percent-encodes everything but the unreserved characters of `segment`, so a wallet name with
+/// e.g. `/`, `?` or spaces stays one segment of the path. bitcoind decodes it again.
+fn encode_path_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(char::from(byte))
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn health_check(client: &bitcoincore_rpc::Client) -> Result<(), RpcError> {
+    client.get_block_count().map_err(RpcError::HealthCheck)?;
+    Ok(())
+}
+
+/// True if `error` was caused by failing to talk to the node (as opposed to the node returning an
+/// error).
+fn is_transport_error(error: &bitcoincore_rpc::Error) -> bool {
+    matches!(
+        error,
+        bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Transport(_))
+            | bitcoincore_rpc::Error::Io(_)
+    )
+}
+
+/// True if the call may succeed when tried again, either the node could not be reached or it is
+/// still starting up.
+fn is_retryable(error: &bitcoincore_rpc::Error) -> bool {
+    is_transport_error(error)
+        || matches!(
+            error,
+            bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Rpc(rpc)) if rpc.code == RPC_IN_WARMUP
+        )
+}
+
+/// Errors returned when talking to `bitcoind`.
+#[derive(Debug, thiserror::Error)]
+pub enum RpcError {
+    /// None of the endpoints could be reached, holds the URI and error for each.
+    #[error("failed to connect to bitcoind, tried:{}", display_unreachable(.0))]
+    Unreachable(Vec<(String, RpcError)>),
+    #[error("invalid bitcoind URI {uri}")]
+    InvalidUri {
+        uri: String,
+        #[source]
+        source: jsonrpc::simple_http::Error,
+    },
+    #[error("failed to read bitcoind credentials")]
+    Credentials(#[source] bitcoincore_rpc::Error),
+    #[error("invalid proxy configuration")]
+    Proxy(#[source] ProxyError),
+    #[error("health check failed")]
+    HealthCheck(#[source] bitcoincore_rpc::Error),
+    #[error("bitcoind is running on {node} but the wallet is configured for {}", wallet.core_name())]
+    WrongChain { node: String, wallet: Chain },
+    #[error("bitcoind is running a signet with challenge {node} but the wallet is configured for {wallet}")]
+    WrongSignetChallenge { node: String, wallet: String },
+    /// An RPC call failed, `what` describes what we were trying to do.
+    #[error("failed to {what}")]
+    Call {
+        what: &'static str,
+        #[source]
+        source: bitcoincore_rpc::Error,
+    },
+}
+
+impl RpcError {
+    /// Returns a function that wraps a failed call to do `what`, for use with `map_err`.
+    pub fn call(what: &'static str) -> impl FnOnce(bitcoincore_rpc::Error) -> RpcError {
+        move |source| RpcError::Call { what, source }
+    }
+}
+
+fn display_unreachable(errors: &[(String, RpcError)]) -> String {
+    let mut s = String::new();
+    for (uri, error) in errors {
+        s.push_str(&format!("\n  {}: {}", uri, error));
+        let mut source = error.source();
+        while let Some(error) = source {
+            s.push_str(&format!(": {}", error));
+            source = error.source();
+        }
+    }
+    s
+}
@@ -0,0 +1,449 @@
+//! Scanning the chain for outputs paying to the wallet and inputs spending them.
+
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "fs")]
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use bitcoin::block::Header;
+use bitcoin::consensus::encode::{self, Decodable, VarInt};
+use bitcoin::{Amount, Block, BlockHash, OutPoint, Script, ScriptBuf, Transaction, TxOut, Txid};
+#[cfg(feature = "fs")]
+use futures_util::stream::{self, StreamExt, TryStreamExt};
+
+#[cfg(feature = "fs")]
+use crate::backend::Backend;
+use crate::backend::BackendError;
+#[cfg(feature = "fs")]
+use crate::db::{Db, DbError};
+use crate::fee;
+
+/// How many blocks to download ahead of the one being processed.
+///
+/// Each waits encoded, so the blocks in flight take at most this many times 4 MB.
+#[cfg(feature = "fs")]
+const DOWNLOAD_AHEAD: usize = 8;
+
+/// What a call to [`scan`] found.
+#[derive(Debug, Default)]
+pub struct Summary {
+    /// The number of blocks scanned.
+    pub blocks: u64,
+    /// The height of the last block scanned.
+    pub height: u64,
+    /// Outputs paying to the wallet.
+    pub received: Vec<(OutPoint, Amount)>,
+    /// Wallet outputs that were spent.
+    pub spent: Vec<OutPoint>,
+    /// Transactions paying to or spending from the wallet.
+    pub txs: Vec<WalletTx>,
+    /// Transactions paying to or spending from the wallet that are still in the mempool.
+    pub unconfirmed: Vec<WalletTx>,
+    /// Unconfirmed wallet transactions that were double spent (e.g. replaced) by another one.
+    pub conflicted: Vec<Txid>,
+    /// Outputs paying to watched addresses.
+    pub watched: Vec<(OutPoint, TxOut)>,
+    /// True if the scan was asked to stop before reaching the tip.
+    pub stopped: bool,
+}
+
+/// The wallet related parts of a single block.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct BlockMatches {
+    /// Outputs paying to the wallet.
+    pub received: Vec<(OutPoint, Amount)>,
+    /// Wallet outputs spent in this block.
+    pub spent: Vec<OutPoint>,
+    /// The transactions the above came from, in block order.
+    pub txs: Vec<WalletTx>,
+    /// Outputs paying to watched addresses, these are not the wallet's.
+    pub watched: Vec<(OutPoint, TxOut)>,
+    /// Outputs paying to watched addresses spent in this block.
+    pub watched_spent: Vec<OutPoint>,
+}
+
+/// A transaction paying to or spending from the wallet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalletTx {
+    pub txid: Txid,
+    /// True for a coinbase transaction, its outputs can not be spent until they mature.
+    pub coinbase: bool,
+    /// The total paid to the wallet.
+    pub received: Amount,
+    /// Wallet outputs spent by the transaction.
+    pub spent: Vec<OutPoint>,
+    /// The outputs spent by all inputs of the transaction, not only the wallet ones.
+    pub inputs: Vec<OutPoint>,
+    /// All outputs of the transaction, not only those paying to the wallet.
+    pub outputs: Vec<TxOut>,
+}
+
+/// Scans the blocks after the last scanned height up to the chain tip.
+///
+/// Outputs paying to `script_pubkey` are stored in the database and wallet outputs spent by any
+/// transaction are marked as spent, the transactions involved are kept for the history. Each block
+/// is stored atomically so an interrupted scan can simply be restarted. A few blocks are downloaded
+/// ahead but processed in order. They are held encoded and decoded a transaction at a time, a
+/// decoded block would take several times the memory.
+///
+/// Once at the tip the mempool is checked for wallet transactions too, these are recorded as
+/// unconfirmed (replacing those recorded by the previous scan) until they show up in a block.
+/// Unconfirmed transactions recorded before that have an input spent by another transaction, in a
+/// block or in the mempool, are marked as conflicted.
+///
+/// Outputs paying to the addresses watched with [`Db::add_watched`] are stored too, apart from the
+/// wallet's. Only confirmed ones, the mempool is not checked for them.
+#[cfg(feature = "fs")]
+pub async fn scan(
+    backend: &impl Backend,
+    db: &mut Db,
+    script_pubkey: &Script,
+) -> Result<Summary, ScanError> {
+    scan_until(backend, db, &[script_pubkey], &AtomicBool::new(false)).await
+}
+
+/// Like [`scan`] but stops early, after storing the block being processed, once `stop` is set.
+///
+/// For shutting down cleanly on a signal, the database is left at a block boundary and the next
+/// scan carries on from there. Outputs paying to any of `script_pubkeys` are the wallet's, for a
+/// wallet that receives at more than one address type.
+#[cfg(feature = "fs")]
+pub async fn scan_until(
+    backend: &impl Backend,
+    db: &mut Db,
+    script_pubkeys: &[&Script],
+    stop: &AtomicBool,
+) -> Result<Summary, ScanError> {
+    // Outputs already marked as spent when we broadcast are watched too, so that the spending
+    // transaction is recorded once it confirms. They are not reported as spent again.
+    let already_spent = db.spent()?.into_iter().collect::<HashSet<_>>();
+    let mut unspent = db
+        .unspent()?
+        .into_iter()
+        .map(|(outpoint, _amount)| outpoint)
+        .chain(already_spent.iter().copied())
+        .collect::<HashSet<_>>();
+    let mut conflicts = Conflicts {
+        watched: db
+            .pending_inputs()?
+            .into_iter()
+            .map(|(txid, input)| (input, txid))
+            .collect(),
+        ..Default::default()
+    };
+    let mut watched = Watched {
+        scripts: db.watched()?,
+        unspent: db.watched_unspent()?.into_iter().collect(),
+    };
+
+    // The last block has height equal to the block count.
+    let tip = backend.block_count().await.map_err(ScanError::BlockCount)?;
+    let mut summary = Summary {
+        height: db.get_last_height()?,
+        ..Default::default()
+    };
+
+    let mut blocks = stream::iter(summary.height + 1..=tip)
+        .map(|height| fetch_block(backend, height))
+        .buffered(DOWNLOAD_AHEAD);
+    while let Some((height, hash, raw)) = blocks.try_next().await? {
+        let matches = filter_raw(
+            &raw,
+            script_pubkeys,
+            &mut unspent,
+            &mut conflicts,
+            &mut watched,
+        )
+        .map_err(|source| ScanError::Filter { hash, source })?;
+        // Before the block, which records it as scanned. Storing these again changes nothing.
+        db.store_watched(&matches.watched, &matches.watched_spent)?;
+        db.store_block(height, &matches.received, &matches.spent, &matches.txs)?;
+
+        summary.blocks += 1;
+        summary.height = height;
+        summary.received.extend(matches.received);
+        summary.spent.extend(
+            matches
+                .spent
+                .into_iter()
+                .filter(|outpoint| !already_spent.contains(outpoint)),
+        );
+        summary.txs.extend(matches.txs);
+        summary.watched.extend(matches.watched);
+
+        if stop.load(Ordering::Relaxed) && height < tip {
+            summary.stopped = true;
+            return Ok(summary);
+        }
+    }
+
+    summary.unconfirmed =
+        scan_mempool(backend, script_pubkeys, &mut unspent, &mut conflicts).await?;
+    summary.conflicted = conflicts.found.into_iter().collect();
+    db.store_unconfirmed(&summary.unconfirmed, &summary.conflicted)?;
+    Ok(summary)
+}
+
+/// Scans the blocks from `from_height` up to the last scanned height again, for outputs paying to
+/// watched scripts only.
+///
+/// For an address watched after those blocks were scanned, the wallet's outputs and the scanned
+/// height are left alone. Returns the watched outputs found, those already stored included.
+#[cfg(feature = "fs")]
+pub async fn rescan_watched(
+    backend: &impl Backend,
+    db: &mut Db,
+    from_height: u64,
+) -> Result<Vec<(OutPoint, TxOut)>, ScanError> {
+    let mut watched = Watched {
+        scripts: db.watched()?,
+        unspent: db.watched_unspent()?.into_iter().collect(),
+    };
+    let mut found = Vec::new();
+
+    let mut blocks = stream::iter(from_height..=db.get_last_height()?)
+        .map(|height| fetch_block(backend, height))
+        .buffered(DOWNLOAD_AHEAD);
+    while let Some((_height, hash, raw)) = blocks.try_next().await? {
+        let matches = filter_raw(
+            &raw,
+            &[],
+            &mut HashSet::new(),
+            &mut Conflicts::default(),
+            &mut watched,
+        )
+        .map_err(|source| ScanError::Filter { hash, source })?;
+        db.store_watched(&matches.watched, &matches.watched_spent)?;
+        found.extend(matches.watched);
+    }
+    Ok(found)
+}
+
+/// Returns the wallet transactions in the mempool, `unspent` as for [`filter_block`].
+#[cfg(feature = "fs")]
+async fn scan_mempool(
+    backend: &impl Backend,
+    script_pubkeys: &[&Script],
+    unspent: &mut HashSet<OutPoint>,
+    conflicts: &mut Conflicts,
+) -> Result<Vec<WalletTx>, ScanError> {
+    let txids = backend.mempool_txids().await.map_err(ScanError::Mempool)?;
+    let mut txs = stream::iter(txids)
+        .map(|txid| backend.mempool_tx(txid))
+        .buffered(DOWNLOAD_AHEAD);
+    let mut matches = BlockMatches::default();
+    while let Some(tx) = txs.try_next().await.map_err(ScanError::Mempool)? {
+        // Gone from the mempool since we got the txids.
+        if let Some(tx) = tx {
+            conflicts.check(&tx);
+            filter_tx(&tx, script_pubkeys, unspent, &mut matches)
+                .map_err(ScanError::FilterMempool)?;
+        }
+    }
+    Ok(matches.txs)
+}
+
+#[cfg(feature = "fs")]
+async fn fetch_block(
+    backend: &impl Backend,
+    height: u64,
+) -> Result<(u64, BlockHash, Vec<u8>), ScanError> {
+    let hash = backend
+        .block_hash(height)
+        .await
+        .map_err(|source| ScanError::BlockHash { height, source })?;
+    let raw = backend
+        .raw_block(hash)
+        .await
+        .map_err(|source| ScanError::Block { hash, source })?;
+    Ok((height, hash, raw))
+}
+
+/// Finds outputs paying to `script_pubkey` and inputs spending any of `unspent` in `block`.
+///
+/// `unspent` is updated as we go so outputs created and spent within the same block are found.
+pub fn filter_block(
+    block: &Block,
+    script_pubkey: &Script,
+    unspent: &mut HashSet<OutPoint>,
+) -> Result<BlockMatches, FilterError> {
+    let mut matches = BlockMatches::default();
+    for tx in &block.txdata {
+        filter_tx(tx, &[script_pubkey], unspent, &mut matches)?;
+    }
+    Ok(matches)
+}
+
+/// Does the same as [`filter_block`] on a consensus encoded block.
+///
+/// Transactions are decoded and filtered one at a time, only one is ever held in memory.
+pub fn filter_raw_block(
+    raw: &[u8],
+    script_pubkey: &Script,
+    unspent: &mut HashSet<OutPoint>,
+) -> Result<BlockMatches, FilterError> {
+    filter_raw(
+        raw,
+        &[script_pubkey],
+        unspent,
+        &mut Conflicts::default(),
+        &mut Watched::default(),
+    )
+}
+
+/// [`filter_raw_block`] that also checks every transaction for `conflicts` and `watched` outputs.
+fn filter_raw(
+    mut raw: &[u8],
+    script_pubkeys: &[&Script],
+    unspent: &mut HashSet<OutPoint>,
+    conflicts: &mut Conflicts,
+    watched: &mut Watched,
+) -> Result<BlockMatches, FilterError> {
+    let mut matches = BlockMatches::default();
+
+    Header::consensus_decode(&mut raw)?;
+    let VarInt(count) = VarInt::consensus_decode(&mut raw)?;
+    for _ in 0..count {
+        let tx = Transaction::consensus_decode(&mut raw)?;
+        conflicts.check(&tx);
+        watched.check(&tx, &mut matches);
+        filter_tx(&tx, script_pubkeys, unspent, &mut matches)?;
+    }
+    if !raw.is_empty() {
+        return Err(encode::Error::ParseFailed("data not consumed entirely").into());
+    }
+    Ok(matches)
+}
+
+fn filter_tx(
+    tx: &Transaction,
+    script_pubkeys: &[&Script],
+    unspent: &mut HashSet<OutPoint>,
+    matches: &mut BlockMatches,
+) -> Result<(), FilterError> {
+    let mut spent = Vec::new();
+    for input in &tx.input {
+        if unspent.remove(&input.previous_output) {
+            spent.push(input.previous_output);
+        }
+    }
+
+    let txid = tx.txid();
+    let first_received = matches.received.len();
+    for (vout, output) in tx.output.iter().enumerate() {
+        if script_pubkeys.contains(&output.script_pubkey.as_script()) {
+            let outpoint = OutPoint::new(txid, vout as u32);
+            unspent.insert(outpoint);
+            matches
+                .received
+                .push((outpoint, Amount::from_sat(output.value)));
+        }
+    }
+
+    let received = &matches.received[first_received..];
+    if received.is_empty() && spent.is_empty() {
+        return Ok(());
+    }
+    // Only a block that breaks the consensus rules, or a lying backend, can overflow.
+    let received = fee::checked_sum(received.iter().map(|(_, amount)| *amount))
+        .ok_or(FilterError::Overflow(txid))?;
+    matches.spent.extend(&spent);
+    matches.txs.push(WalletTx {
+        txid,
+        coinbase: tx.is_coin_base(),
+        received,
+        spent,
+        inputs: tx.input.iter().map(|input| input.previous_output).collect(),
+        outputs: tx.output.clone(),
+    });
+    Ok(())
+}
+
+/// Looks out for transactions double spending the unconfirmed wallet transactions we track.
+#[derive(Debug, Default)]
+struct Conflicts {
+    /// The outputs spent by the tracked transactions, and which one spends each.
+    watched: HashMap<OutPoint, Txid>,
+    /// Tracked transactions an input of which was spent by some other transaction.
+    found: HashSet<Txid>,
+}
+
+impl Conflicts {
+    fn check(&mut self, tx: &Transaction) {
+        for input in &tx.input {
+            if let Some(tracked) = self.watched.get(&input.previous_output) {
+                // Only hash the transaction if it might be a conflict, most are not.
+                if *tracked != tx.txid() {
+                    self.found.insert(*tracked);
+                }
+            }
+        }
+    }
+}
+
+/// Addresses watched on top of the wallet's and their unspent outputs.
+#[derive(Debug, Default)]
+struct Watched {
+    scripts: Vec<ScriptBuf>,
+    unspent: HashSet<OutPoint>,
+}
+
+impl Watched {
+    fn check(&mut self, tx: &Transaction, matches: &mut BlockMatches) {
+        for input in &tx.input {
+            if self.unspent.remove(&input.previous_output) {
+                matches.watched_spent.push(input.previous_output);
+            }
+        }
+        if self.scripts.is_empty() {
+            return;
+        }
+        for (vout, output) in tx.output.iter().enumerate() {
+            if self.scripts.contains(&output.script_pubkey) {
+                let outpoint = OutPoint::new(tx.txid(), vout as u32);
+                self.unspent.insert(outpoint);
+                matches.watched.push((outpoint, output.clone()));
+            }
+        }
+    }
+}
+
+/// Errors returned while scanning.
+#[derive(Debug, thiserror::Error)]
+pub enum ScanError {
+    #[cfg(feature = "fs")]
+    #[error(transparent)]
+    Db(#[from] DbError),
+    #[error("failed to get block count")]
+    BlockCount(#[source] BackendError),
+    #[error("failed to get hash of block {height}")]
+    BlockHash {
+        height: u64,
+        #[source]
+        source: BackendError,
+    },
+    #[error("failed to get block {hash}")]
+    Block {
+        hash: BlockHash,
+        #[source]
+        source: BackendError,
+    },
+    #[error("failed to get the mempool")]
+    Mempool(#[source] BackendError),
+    #[error("failed to scan block {hash}")]
+    Filter {
+        hash: BlockHash,
+        #[source]
+        source: FilterError,
+    },
+    #[error("failed to scan the mempool")]
+    FilterMempool(#[source] FilterError),
+}
+
+/// Errors returned while filtering a block or transaction for wallet outputs.
+#[derive(Debug, thiserror::Error)]
+pub enum FilterError {
+    #[error("failed to decode")]
+    Decode(#[from] encode::Error),
+    #[error("the amounts paid to the wallet by {0} overflow")]
+    Overflow(Txid),
+}
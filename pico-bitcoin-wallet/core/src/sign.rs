@@ -0,0 +1,341 @@
+//! Signing transaction inputs.
+//!
+//! [`taproot_key_spend`] and [`p2wpkh`] do no I/O and take any randomness as an argument, so the
+//! same transaction and key always produce the same signature and they can be checked against test
+//! vectors. [`Signer`] is what the rest of the wallet uses, it works on PSBTs so that the key does
+//! not have to be in this process at all.
+
+use std::borrow::Borrow;
+
+use bitcoin::hashes::Hash;
+use bitcoin::key::TapTweak;
+use bitcoin::psbt::Psbt;
+use bitcoin::secp256k1::{KeyPair, Message, SECP256K1};
+use bitcoin::sighash::{
+    EcdsaSighashType, NonStandardSighashType, Prevouts, SighashCache, TapSighashType,
+};
+use bitcoin::taproot::TapNodeHash;
+use bitcoin::{ecdsa, taproot, Amount, PrivateKey, PubkeyHash, ScriptBuf, Transaction, TxOut};
+use rand::Rng;
+use rayon::prelude::*;
+
+use crate::keys;
+
+/// Something that can sign wallet inputs.
+///
+/// A signer adds signatures to the inputs it has keys for and leaves the others alone,
+/// [`crate::tx::finalize`] checks that every input ended up signed. Implemented by [`KeySigner`]
+/// for a key in memory and [`PsbtOnly`] for exporting unsigned PSBTs, a hardware wallet signer
+/// (e.g. via HWI) would pass the PSBT to the device.
+pub trait Signer {
+    /// Sets `tap_key_sig` on the taproot key path inputs this signer can sign.
+    fn sign_taproot_key_spend(&self, psbt: &mut Psbt) -> Result<(), SignError>;
+
+    /// Adds to `partial_sigs` of the P2WPKH inputs this signer can sign.
+    fn sign_ecdsa(&self, psbt: &mut Psbt) -> Result<(), SignError>;
+}
+
+/// Signs with a private key held in memory, the key is erased when the signer is dropped.
+///
+/// The wallet's outputs commit to a script tree when a recovery path is configured (see
+/// [`crate::recovery`]), create the signer [`KeySigner::with_merkle_root`] then. Inputs are
+/// independent so they are signed in parallel, which matters for consolidations with many inputs.
+/// Each worker has its own sighash cache, signatures are put back in input order.
+pub struct KeySigner {
+    key: PrivateKey,
+    merkle_root: Option<TapNodeHash>,
+    aux_rand: Option<[u8; 32]>,
+}
+
+impl KeySigner {
+    pub fn new(key: PrivateKey) -> Self {
+        KeySigner::with_merkle_root(key, None)
+    }
+
+    /// Creates a signer for key path spends of outputs committing to the script tree `merkle_root`.
+    pub fn with_merkle_root(key: PrivateKey, merkle_root: Option<TapNodeHash>) -> Self {
+        KeySigner {
+            key,
+            merkle_root,
+            aux_rand: None,
+        }
+    }
+
+    /// Uses `aux_rand` as the BIP-340 auxiliary randomness of every Schnorr signature instead of
+    /// fresh random bytes, so signing the same transaction again gives the same signatures.
+    ///
+    /// Nonces still depend on the key and the message, this only gives up the protection fresh
+    /// randomness adds against side channel attacks. ECDSA signatures are deterministic anyway.
+    pub fn with_aux_rand(mut self, aux_rand: [u8; 32]) -> Self {
+        self.aux_rand = Some(aux_rand);
+        self
+    }
+}
+
+impl Drop for KeySigner {
+    fn drop(&mut self) {
+        keys::erase(&mut self.key);
+    }
+}
+
+impl Signer for KeySigner {
+    fn sign_taproot_key_spend(&self, psbt: &mut Psbt) -> Result<(), SignError> {
+        let prevouts = witness_utxos(psbt)?;
+        let (internal_key, _parity) = self.key.inner.x_only_public_key(SECP256K1);
+        let script_pubkey = ScriptBuf::new_v1_p2tr(SECP256K1, internal_key, self.merkle_root);
+
+        let tx = &psbt.unsigned_tx;
+        let signatures = psbt
+            .inputs
+            .par_iter()
+            .enumerate()
+            .filter(|(index, _)| prevouts[*index].script_pubkey == script_pubkey)
+            .map_init(
+                || (SighashCache::new(tx), rand::thread_rng()),
+                |(cache, rng), (index, input)| {
+                    let hash_ty = input
+                        .taproot_hash_ty()
+                        .map_err(|source| SignError::Sighash { index, source })?;
+                    let signature = taproot_key_spend(
+                        cache,
+                        index,
+                        &prevouts,
+                        &self.key,
+                        self.merkle_root,
+                        hash_ty,
+                        &self.aux_rand.unwrap_or_else(|| rng.gen()),
+                    )?;
+                    Ok((index, signature))
+                },
+            )
+            .collect::<Result<Vec<_>, SignError>>()?;
+
+        for (index, signature) in signatures {
+            psbt.inputs[index].tap_key_sig = Some(signature);
+        }
+        Ok(())
+    }
+
+    fn sign_ecdsa(&self, psbt: &mut Psbt) -> Result<(), SignError> {
+        let prevouts = witness_utxos(psbt)?;
+        let public_key = self.key.public_key(SECP256K1);
+        let script_pubkey = match public_key.wpubkey_hash() {
+            Some(hash) => ScriptBuf::new_v0_p2wpkh(&hash),
+            // Uncompressed keys can not have P2WPKH outputs.
+            None => return Ok(()),
+        };
+
+        let tx = &psbt.unsigned_tx;
+        let signatures = psbt
+            .inputs
+            .par_iter()
+            .enumerate()
+            .filter(|(index, _)| prevouts[*index].script_pubkey == script_pubkey)
+            .map_init(
+                || SighashCache::new(tx),
+                |cache, (index, input)| {
+                    let hash_ty = input
+                        .ecdsa_hash_ty()
+                        .map_err(|source| SignError::NonStandardSighash { index, source })?;
+                    let value = Amount::from_sat(prevouts[index].value);
+                    let signature = p2wpkh(cache, index, value, &self.key, hash_ty)?;
+                    Ok((index, signature))
+                },
+            )
+            .collect::<Result<Vec<_>, SignError>>()?;
+
+        for (index, signature) in signatures {
+            psbt.inputs[index]
+                .partial_sigs
+                .insert(public_key, signature);
+        }
+        Ok(())
+    }
+}
+
+/// Does not sign anything, for when the PSBT is to be exported and signed elsewhere.
+pub struct PsbtOnly;
+
+impl Signer for PsbtOnly {
+    fn sign_taproot_key_spend(&self, _psbt: &mut Psbt) -> Result<(), SignError> {
+        Ok(())
+    }
+
+    fn sign_ecdsa(&self, _psbt: &mut Psbt) -> Result<(), SignError> {
+        Ok(())
+    }
+}
+
+/// What an input of a PSBT commits to, the message a signing device signs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputSighash {
+    /// A taproot key path spend, signed with Schnorr (BIP-341).
+    TaprootKeySpend {
+        message: Message,
+        hash_ty: TapSighashType,
+    },
+    /// A P2WPKH spend, signed with ECDSA (BIP-143).
+    P2wpkh {
+        message: Message,
+        hash_ty: EcdsaSighashType,
+    },
+    /// Spends an output of another type, or one missing from the PSBT, the wallet can't sign it.
+    Unsupported,
+}
+
+/// Works out what each input of `psbt` commits to, in input order, as the signers above do.
+///
+/// Lets the messages be checked against what an air-gapped signing device displays. Taproot
+/// sighashes commit to every output spent so they all have to be in the PSBT.
+pub fn sighashes(psbt: &Psbt) -> Result<Vec<InputSighash>, SignError> {
+    let mut cache = SighashCache::new(&psbt.unsigned_tx);
+    let prevouts = psbt
+        .inputs
+        .iter()
+        .map(|input| input.witness_utxo.clone())
+        .collect::<Option<Vec<_>>>();
+
+    let mut sighashes = Vec::with_capacity(psbt.inputs.len());
+    for (index, input) in psbt.inputs.iter().enumerate() {
+        let prevout = match &input.witness_utxo {
+            Some(prevout) => prevout,
+            None => {
+                sighashes.push(InputSighash::Unsupported);
+                continue;
+            }
+        };
+        let script_pubkey = &prevout.script_pubkey;
+        let sighash = if script_pubkey.is_v1_p2tr() {
+            let prevouts = match &prevouts {
+                Some(prevouts) => prevouts,
+                None => return Err(witness_utxos(psbt).unwrap_err()),
+            };
+            let hash_ty = input
+                .taproot_hash_ty()
+                .map_err(|source| SignError::Sighash { index, source })?;
+            let sighash = cache
+                .taproot_key_spend_signature_hash(index, &Prevouts::All(prevouts), hash_ty)
+                .map_err(|source| SignError::Sighash { index, source })?;
+            InputSighash::TaprootKeySpend {
+                message: Message::from_slice(sighash.as_ref()).expect("sighash is 32 bytes"),
+                hash_ty,
+            }
+        } else if script_pubkey.is_v0_p2wpkh() {
+            let hash_ty = input
+                .ecdsa_hash_ty()
+                .map_err(|source| SignError::NonStandardSighash { index, source })?;
+            // The script code of a P2WPKH output is the equivalent P2PKH script (BIP-143).
+            let pubkey_hash = PubkeyHash::from_slice(&script_pubkey.as_bytes()[2..])
+                .expect("P2WPKH programs are 20 bytes");
+            let sighash = cache
+                .segwit_signature_hash(
+                    index,
+                    &ScriptBuf::new_p2pkh(&pubkey_hash),
+                    prevout.value,
+                    hash_ty,
+                )
+                .map_err(|source| SignError::Sighash { index, source })?;
+            InputSighash::P2wpkh {
+                message: Message::from_slice(sighash.as_ref()).expect("sighash is 32 bytes"),
+                hash_ty,
+            }
+        } else {
+            InputSighash::Unsupported
+        };
+        sighashes.push(sighash);
+    }
+    Ok(sighashes)
+}
+
+/// Returns the outputs spent by `psbt`, in input order.
+fn witness_utxos(psbt: &Psbt) -> Result<Vec<TxOut>, SignError> {
+    psbt.inputs
+        .iter()
+        .enumerate()
+        .map(|(index, input)| {
+            input
+                .witness_utxo
+                .clone()
+                .ok_or(SignError::MissingUtxo(index))
+        })
+        .collect()
+}
+
+/// Signs input `index` as a taproot key path spend by `key`.
+///
+/// `key` is the untweaked internal key and `merkle_root` the script tree the output commits to, if
+/// any. `prevouts` are all the outputs spent by the transaction, in input order. `aux_rand` is the
+/// BIP-340 auxiliary randomness, it should be fresh random bytes when signing for real.
+pub fn taproot_key_spend<T: Borrow<Transaction>>(
+    cache: &mut SighashCache<T>,
+    index: usize,
+    prevouts: &[TxOut],
+    key: &PrivateKey,
+    merkle_root: Option<TapNodeHash>,
+    hash_ty: TapSighashType,
+    aux_rand: &[u8; 32],
+) -> Result<taproot::Signature, SignError> {
+    // rust-bitcoin only checks the index for ANYONECANPAY sighashes.
+    if index >= cache.transaction().input.len() {
+        return Err(SignError::MissingInput(index));
+    }
+    let mut keypair = KeyPair::from_secret_key(SECP256K1, &key.inner)
+        .tap_tweak(SECP256K1, merkle_root)
+        .to_inner();
+    let sighash = cache
+        .taproot_key_spend_signature_hash(index, &Prevouts::All(prevouts), hash_ty)
+        .map_err(|source| SignError::Sighash { index, source })?;
+    let message = Message::from_slice(sighash.as_ref()).expect("sighash is 32 bytes");
+    let sig = SECP256K1.sign_schnorr_with_aux_rand(&message, &keypair, aux_rand);
+    keypair.non_secure_erase();
+    Ok(taproot::Signature { sig, hash_ty })
+}
+
+/// Signs input `index`, a P2WPKH output worth `value`, by `key`.
+///
+/// ECDSA signatures are deterministic (RFC6979), no randomness needed.
+pub fn p2wpkh<T: Borrow<Transaction>>(
+    cache: &mut SighashCache<T>,
+    index: usize,
+    value: Amount,
+    key: &PrivateKey,
+    hash_ty: EcdsaSighashType,
+) -> Result<ecdsa::Signature, SignError> {
+    let public_key = key.public_key(SECP256K1);
+    if !public_key.compressed {
+        return Err(SignError::UncompressedKey);
+    }
+    // The script code of a P2WPKH output is the equivalent P2PKH script (BIP-143).
+    let script_code = ScriptBuf::new_p2pkh(&public_key.pubkey_hash());
+    let sighash = cache
+        .segwit_signature_hash(index, &script_code, value.to_sat(), hash_ty)
+        .map_err(|source| SignError::Sighash { index, source })?;
+    let message = Message::from_slice(sighash.as_ref()).expect("sighash is 32 bytes");
+    let sig = SECP256K1.sign_ecdsa(&message, &key.inner);
+    Ok(ecdsa::Signature { sig, hash_ty })
+}
+
+/// Errors returned when signing an input.
+#[derive(Debug, thiserror::Error)]
+pub enum SignError {
+    #[error("failed to compute sighash for input {index}")]
+    Sighash {
+        index: usize,
+        #[source]
+        source: bitcoin::sighash::Error,
+    },
+    #[error("input {index} has a non-standard sighash type")]
+    NonStandardSighash {
+        index: usize,
+        #[source]
+        source: NonStandardSighashType,
+    },
+    #[error("transaction has no input {0}")]
+    MissingInput(usize),
+    #[error("PSBT input {0} is missing the output it spends")]
+    MissingUtxo(usize),
+    #[error("P2WPKH requires a compressed public key")]
+    UncompressedKey,
+    #[error("the key is not the recovery key")]
+    NotRecoveryKey,
+}
@@ -0,0 +1,669 @@
+//! Building and signing transactions that spend wallet outputs.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+use bitcoin::absolute::LockTime;
+use bitcoin::psbt::{Psbt, PsbtSighashType};
+#[cfg(feature = "fs")]
+use bitcoin::Txid;
+use bitcoin::{
+    Address, Amount, FeeRate, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness,
+};
+
+#[cfg(feature = "fs")]
+use crate::backend::Backend;
+use crate::backend::BackendError;
+#[cfg(feature = "fs")]
+use crate::db::{Db, DbError};
+use crate::fee::{self, FeeError, Plan, Spend};
+use crate::sign::{SignError, Signer};
+
+/// An unsigned transaction paying someone and the outputs it spends.
+#[derive(Debug, Clone)]
+pub struct Payment {
+    pub tx: Transaction,
+    /// The outputs spent by `tx`, in input order, needed for signing.
+    pub prevouts: Vec<TxOut>,
+    /// The absolute fee paid by `tx`.
+    pub fee: Amount,
+    /// The sighash types of the inputs not signed with the default, by input index. The default is
+    /// `SIGHASH_DEFAULT` for taproot inputs and `SIGHASH_ALL` for P2WPKH ones.
+    pub sighash_types: BTreeMap<usize, PsbtSighashType>,
+}
+
+impl Payment {
+    /// Returns an unsigned PSBT for the payment, with the spent outputs and sighash types filled in.
+    pub fn psbt(&self) -> Psbt {
+        let mut psbt =
+            Psbt::from_unsigned_tx(self.tx.clone()).expect("payment transactions are unsigned");
+        for (index, (input, prevout)) in psbt.inputs.iter_mut().zip(&self.prevouts).enumerate() {
+            input.witness_utxo = Some(prevout.clone());
+            input.sighash_type = self.sighash_types.get(&index).copied();
+        }
+        psbt
+    }
+
+    /// Signs input `index` with `sighash_type` rather than the default, e.g.
+    /// `SIGHASH_SINGLE|SIGHASH_ANYONECANPAY` to let others add inputs and outputs.
+    ///
+    /// Fails if there is no such input or the type is not valid for the output it spends, ECDSA
+    /// types for P2WPKH and taproot ones (`SIGHASH_DEFAULT` included) otherwise. A non-default
+    /// taproot sighash type adds a byte to the input's witness, which the fee was not planned for.
+    pub fn set_sighash_type(
+        &mut self,
+        index: usize,
+        sighash_type: PsbtSighashType,
+    ) -> Result<(), SendError> {
+        let prevout = self
+            .prevouts
+            .get(index)
+            .ok_or(SignError::MissingInput(index))?;
+        if prevout.script_pubkey.is_v0_p2wpkh() {
+            sighash_type
+                .ecdsa_hash_ty()
+                .map_err(|source| SignError::NonStandardSighash { index, source })?;
+        } else {
+            sighash_type
+                .taproot_hash_ty()
+                .map_err(|source| SignError::Sighash { index, source })?;
+        }
+        self.sighash_types.insert(index, sighash_type);
+        Ok(())
+    }
+}
+
+/// How [`build_payment`] picks the wallet outputs to spend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CoinSelection {
+    /// Spends the largest outputs first, fewest inputs so the lowest fee for this payment.
+    #[default]
+    LargestFirst,
+    /// Spends outputs in the order given, callers pass them oldest first.
+    OldestFirst,
+    /// Searches for outputs adding up to the payment and its fee without change (branch and bound),
+    /// no change output is cheaper now and later. Falls back to largest first if there are none.
+    BranchAndBound,
+    /// Avoids what gives away which outputs are ours, see [`build_payment`].
+    Privacy,
+}
+
+impl FromStr for CoinSelection {
+    type Err = UnknownCoinSelection;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "largest-first" => Ok(CoinSelection::LargestFirst),
+            "oldest-first" => Ok(CoinSelection::OldestFirst),
+            "bnb" => Ok(CoinSelection::BranchAndBound),
+            "privacy" => Ok(CoinSelection::Privacy),
+            _ => Err(UnknownCoinSelection(s.to_owned())),
+        }
+    }
+}
+
+/// The error returned when parsing an unknown [`CoinSelection`].
+#[derive(Debug, thiserror::Error)]
+#[error("unknown coin selection {0}, expected bnb, largest-first, oldest-first or privacy")]
+pub struct UnknownCoinSelection(String);
+
+/// Builds a transaction paying `amount` to `recipient`, spending from `utxos`.
+///
+/// All `utxos` must pay to `wallet`, change goes back there too. Which of them are spent is up to
+/// `selection`. Change below the dust limit at `dust_relay_fee` is added to the fee instead, and
+/// paying less than the recipient's dust limit is an error.
+///
+/// With [`CoinSelection::Privacy`] a change output of a different type than the payment would
+/// stand out as ours, so if the recipient's address type is not the wallet's a changeless set of
+/// outputs is looked for first. Otherwise a single output is spent if one is enough, spending
+/// several together links them. If neither works out the largest are spent first. For a wallet
+/// with more than one address type see [`build_payment_from`].
+pub fn build_payment(
+    utxos: &[(OutPoint, Amount)],
+    wallet: &Address,
+    recipient: &Address,
+    amount: Amount,
+    fee_rate: FeeRate,
+    dust_relay_fee: FeeRate,
+    selection: CoinSelection,
+) -> Result<Payment, SendError> {
+    let wallet_script = wallet.script_pubkey();
+    let utxos = utxos
+        .iter()
+        .map(|(outpoint, value)| {
+            let output = TxOut {
+                value: value.to_sat(),
+                script_pubkey: wallet_script.clone(),
+            };
+            (*outpoint, output)
+        })
+        .collect::<Vec<_>>();
+    build_payment_from(
+        &utxos,
+        std::slice::from_ref(wallet),
+        recipient,
+        amount,
+        fee_rate,
+        dust_relay_fee,
+        selection,
+    )
+}
+
+/// Like [`build_payment`] but for `utxos` paying to any of the `wallet` addresses, each given with
+/// the output it is.
+///
+/// Each input is signed (and its weight predicted, see [`fee::wallet_input`]) according to the
+/// script of the output it spends. Change goes to the wallet address of the recipient's type if
+/// there is one, otherwise to the first. Outputs of different types may be spent together, which
+/// links the wallet's addresses, except with [`CoinSelection::Privacy`]: it spends outputs of a
+/// single type, trying the recipient's type first, and fails if no type has enough on its own.
+pub fn build_payment_from(
+    utxos: &[(OutPoint, TxOut)],
+    wallet: &[Address],
+    recipient: &Address,
+    amount: Amount,
+    fee_rate: FeeRate,
+    dust_relay_fee: FeeRate,
+    selection: CoinSelection,
+) -> Result<Payment, SendError> {
+    let recipient_script = recipient.script_pubkey();
+    let change = wallet
+        .iter()
+        .find(|address| address.address_type() == recipient.address_type())
+        .or_else(|| wallet.first())
+        .expect("the wallet has an address");
+    let change_script = change.script_pubkey();
+    let scripts = utxos
+        .iter()
+        .map(|(outpoint, output)| (*outpoint, output.script_pubkey.as_script()))
+        .collect::<HashMap<_, _>>();
+    let limit = fee::dust_limit(&recipient_script, dust_relay_fee);
+    if amount < limit {
+        return Err(SendError::Dust { amount, limit });
+    }
+
+    let plan = |selected: &[(OutPoint, Amount)]| {
+        let spend = Spend {
+            inputs: selected
+                .iter()
+                .map(|(outpoint, _)| fee::wallet_input(scripts[outpoint]))
+                .collect(),
+            input_value: fee::checked_sum(selected.iter().map(|(_, value)| *value))
+                .ok_or(FeeError::Overflow)?,
+            output_script_lens: vec![recipient_script.len()],
+            amount,
+            change_script: &change_script,
+            dust_relay_fee,
+        };
+        fee::plan(&spend, fee_rate, Amount::ZERO)
+    };
+    let largest_first = |utxos: &[(OutPoint, Amount)]| {
+        let mut utxos = utxos.to_vec();
+        utxos.sort_by_key(|(_, value)| Reverse(*value));
+        accumulate(&utxos, plan)
+    };
+    let utxos = utxos
+        .iter()
+        .map(|(outpoint, output)| (*outpoint, Amount::from_sat(output.value)))
+        .collect::<Vec<_>>();
+    let selected = match selection {
+        CoinSelection::LargestFirst => largest_first(&utxos)?,
+        CoinSelection::OldestFirst => accumulate(&utxos, plan)?,
+        CoinSelection::BranchAndBound => match branch_and_bound(&utxos, plan) {
+            Some(selected) => selected,
+            None => largest_first(&utxos)?,
+        },
+        CoinSelection::Privacy => {
+            // Outputs grouped by type, in the order given, the recipient's type first.
+            let mut groups = Vec::<Vec<(OutPoint, Amount)>>::new();
+            for utxo in &utxos {
+                let version = scripts[&utxo.0].witness_version();
+                match groups
+                    .iter_mut()
+                    .find(|group| scripts[&group[0].0].witness_version() == version)
+                {
+                    Some(group) => group.push(*utxo),
+                    None => groups.push(vec![*utxo]),
+                }
+            }
+            let recipient_version = recipient_script.witness_version();
+            groups.sort_by_key(|group| scripts[&group[0].0].witness_version() != recipient_version);
+
+            let mut result = largest_first(&[]);
+            for group in &groups {
+                let changeless = if recipient.address_type() != change.address_type() {
+                    branch_and_bound(group, plan)
+                } else {
+                    None
+                };
+                let single = || {
+                    group
+                        .iter()
+                        .filter(|utxo| plan(&[**utxo]).is_ok())
+                        .min_by_key(|(_, value)| *value)
+                        .map(|utxo| vec![*utxo])
+                };
+                result = match changeless.or_else(single) {
+                    Some(selected) => Ok(selected),
+                    None => largest_first(group),
+                };
+                if result.is_ok() {
+                    break;
+                }
+            }
+            result?
+        }
+    };
+    let plan = plan(&selected)?;
+
+    let mut output = vec![TxOut {
+        value: amount.to_sat(),
+        script_pubkey: recipient_script,
+    }];
+    if let Some(value) = plan.change {
+        output.push(TxOut {
+            value: value.to_sat(),
+            script_pubkey: change_script,
+        });
+    }
+
+    let input = selected
+        .iter()
+        .map(|(outpoint, _)| TxIn {
+            previous_output: *outpoint,
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::new(),
+        })
+        .collect();
+    let prevouts = selected
+        .iter()
+        .map(|(outpoint, value)| TxOut {
+            value: value.to_sat(),
+            script_pubkey: scripts[outpoint].to_owned(),
+        })
+        .collect();
+
+    Ok(Payment {
+        tx: Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input,
+            output,
+        },
+        prevouts,
+        fee: plan.fee,
+        sighash_types: BTreeMap::new(),
+    })
+}
+
+/// Returns the outputs of `utxos` an ordinary payment may spend, keeping back at least `reserve`.
+///
+/// The reserve is kept in as few outputs as possible: the smallest output worth enough on its own,
+/// otherwise the largest ones. The others are returned in the order given, none if `utxos` do not
+/// add up to the reserve.
+pub fn without_reserve(utxos: &[(OutPoint, Amount)], reserve: Amount) -> Vec<(OutPoint, Amount)> {
+    if reserve == Amount::ZERO {
+        return utxos.to_vec();
+    }
+    let reserved = match utxos
+        .iter()
+        .filter(|(_, value)| *value >= reserve)
+        .min_by_key(|(_, value)| *value)
+    {
+        Some((outpoint, _)) => vec![*outpoint],
+        None => {
+            let mut largest = utxos.to_vec();
+            largest.sort_by_key(|(_, value)| Reverse(*value));
+            let mut reserved = Vec::new();
+            let mut total = Amount::ZERO;
+            for (outpoint, value) in largest {
+                if total >= reserve {
+                    break;
+                }
+                reserved.push(outpoint);
+                total += value;
+            }
+            if total < reserve {
+                return Vec::new();
+            }
+            reserved
+        }
+    };
+    utxos
+        .iter()
+        .filter(|(outpoint, _)| !reserved.contains(outpoint))
+        .copied()
+        .collect()
+}
+
+/// Spends `utxos` in order until `plan` succeeds, returns the outputs spent.
+fn accumulate(
+    utxos: &[(OutPoint, Amount)],
+    plan: impl Fn(&[(OutPoint, Amount)]) -> Result<Plan, FeeError>,
+) -> Result<Vec<(OutPoint, Amount)>, FeeError> {
+    let mut selected = Vec::new();
+    let mut result = plan(&selected);
+    for utxo in utxos {
+        if result.is_ok() {
+            break;
+        }
+        selected.push(*utxo);
+        result = plan(&selected);
+    }
+    result.map(|_| selected)
+}
+
+/// Gives up on finding a changeless selection after this many tries, as Bitcoin Core does.
+const BNB_TRIES: usize = 100_000;
+
+/// Looks for outputs in `utxos` that `plan` spends without change, depth first, largest first.
+///
+/// Adding an output worth more than the fee to spend it only increases the change, so a branch is
+/// abandoned as soon as there is some change, or when what is left can not cover the payment.
+fn branch_and_bound(
+    utxos: &[(OutPoint, Amount)],
+    plan: impl Fn(&[(OutPoint, Amount)]) -> Result<Plan, FeeError>,
+) -> Option<Vec<(OutPoint, Amount)>> {
+    let mut utxos = utxos.to_vec();
+    utxos.sort_by_key(|(_, value)| Reverse(*value));
+    // What is left to add after each index, for pruning.
+    let mut remaining = vec![Amount::ZERO; utxos.len() + 1];
+    for i in (0..utxos.len()).rev() {
+        remaining[i] = remaining[i + 1].checked_add(utxos[i].1)?;
+    }
+
+    let mut tries = 0;
+    let mut selected = Vec::new();
+    search(&utxos, &remaining, 0, &mut selected, &plan, &mut tries).then_some(selected)
+}
+
+/// A step of [`branch_and_bound`], returns true once `selected` needs no change.
+fn search(
+    utxos: &[(OutPoint, Amount)],
+    remaining: &[Amount],
+    next: usize,
+    selected: &mut Vec<(OutPoint, Amount)>,
+    plan: &impl Fn(&[(OutPoint, Amount)]) -> Result<Plan, FeeError>,
+    tries: &mut usize,
+) -> bool {
+    *tries += 1;
+    if *tries > BNB_TRIES {
+        return false;
+    }
+    match plan(selected) {
+        Ok(Plan { change: None, .. }) => return !selected.is_empty(),
+        Ok(Plan {
+            change: Some(_), ..
+        }) => return false,
+        Err(FeeError::InsufficientFunds {
+            available,
+            amount,
+            fee,
+        }) => {
+            // Even adding everything left is not enough.
+            match (
+                available.checked_add(remaining[next]),
+                amount.checked_add(fee),
+            ) {
+                (Some(most), Some(needed)) if most >= needed => {}
+                _ => return false,
+            }
+        }
+        Err(FeeError::Overflow) => return false,
+    }
+    for i in next..utxos.len() {
+        selected.push(utxos[i]);
+        if search(utxos, remaining, i + 1, selected, plan, tries) {
+            return true;
+        }
+        selected.pop();
+    }
+    false
+}
+
+/// Builds a replacement for `payment` paying `fee_rate`, for when it is stuck in the mempool.
+///
+/// Spends the same inputs (all of which signal RBF) and takes the extra fee out of the change
+/// output, any output paying to one of the `wallet` addresses, dropping it if what is left would
+/// be dust at `dust_relay_fee`. The new fee also covers the relay cost of the replacement itself,
+/// as required by BIP-125. The returned payment needs signing again.
+pub fn bump_fee(
+    payment: &Payment,
+    wallet: &[Address],
+    fee_rate: FeeRate,
+    dust_relay_fee: FeeRate,
+) -> Result<Payment, SendError> {
+    let wallet_scripts = wallet
+        .iter()
+        .map(Address::script_pubkey)
+        .collect::<Vec<_>>();
+    let change_script = payment
+        .tx
+        .output
+        .iter()
+        .map(|output| &output.script_pubkey)
+        .find(|script| wallet_scripts.contains(script))
+        .or_else(|| wallet_scripts.first())
+        .expect("the wallet has an address")
+        .clone();
+    let total = fee::checked_sum(
+        payment
+            .prevouts
+            .iter()
+            .map(|prevout| Amount::from_sat(prevout.value)),
+    )
+    .ok_or(SendError::Overflow)?;
+    let mut output = payment
+        .tx
+        .output
+        .iter()
+        .filter(|output| !wallet_scripts.contains(&output.script_pubkey))
+        .cloned()
+        .collect::<Vec<_>>();
+    let amount = fee::checked_sum(output.iter().map(|output| Amount::from_sat(output.value)))
+        .ok_or(SendError::Overflow)?;
+
+    let spend = Spend {
+        inputs: payment
+            .prevouts
+            .iter()
+            .map(|prevout| fee::wallet_input(&prevout.script_pubkey))
+            .collect(),
+        input_value: total,
+        output_script_lens: output
+            .iter()
+            .map(|output| output.script_pubkey.len())
+            .collect(),
+        amount,
+        change_script: &change_script,
+        dust_relay_fee,
+    };
+    // BIP-125 requires the replacement to also pay for its own relay.
+    let min_fee = fee::fee_for(spend.weight(), FeeRate::BROADCAST_MIN)
+        .and_then(|relay_fee| payment.fee.checked_add(relay_fee))
+        .ok_or(SendError::Overflow)?;
+    let plan = fee::plan(&spend, fee_rate, min_fee)?;
+    if let Some(change) = plan.change {
+        output.push(TxOut {
+            value: change.to_sat(),
+            script_pubkey: change_script,
+        });
+    }
+
+    let mut tx = payment.tx.clone();
+    tx.output = output;
+    for input in &mut tx.input {
+        input.witness = Witness::new();
+    }
+    Ok(Payment {
+        tx,
+        prevouts: payment.prevouts.clone(),
+        fee: plan.fee,
+        sighash_types: payment.sighash_types.clone(),
+    })
+}
+
+/// Rebuilds the payment made by `tx`, one of our transactions, e.g. to bump its fee.
+///
+/// The amounts and scripts of the outputs spent are looked up in `db`. Returns `None` if `tx`
+/// spends an output that is not ours, or one we don't know about yet such as the change of another
+/// unconfirmed transaction.
+#[cfg(feature = "fs")]
+pub fn rebuild_payment(
+    tx: &Transaction,
+    db: &mut Db,
+    wallet: &Address,
+) -> Result<Option<Payment>, SendError> {
+    let mut prevouts = Vec::with_capacity(tx.input.len());
+    for input in &tx.input {
+        let amount = match db.txo_amount(&input.previous_output)? {
+            Some(amount) => amount,
+            None => return Ok(None),
+        };
+        // Outputs recorded before their transactions were pay to the taproot address.
+        let script_pubkey = db
+            .tx_outputs(&input.previous_output.txid)?
+            .into_iter()
+            .nth(input.previous_output.vout as usize)
+            .map(|output| output.script_pubkey)
+            .unwrap_or_else(|| wallet.script_pubkey());
+        prevouts.push(TxOut {
+            value: amount.to_sat(),
+            script_pubkey,
+        });
+    }
+    let input_value = fee::checked_sum(
+        prevouts
+            .iter()
+            .map(|prevout| Amount::from_sat(prevout.value)),
+    )
+    .ok_or(SendError::Overflow)?;
+    let output_value = fee::checked_sum(
+        tx.output
+            .iter()
+            .map(|output| Amount::from_sat(output.value)),
+    )
+    .ok_or(SendError::Overflow)?;
+    let fee = input_value
+        .checked_sub(output_value)
+        .ok_or(SendError::Overflow)?;
+
+    let mut tx = tx.clone();
+    for input in &mut tx.input {
+        input.witness = Witness::new();
+    }
+    Ok(Some(Payment {
+        tx,
+        prevouts,
+        fee,
+        sighash_types: BTreeMap::new(),
+    }))
+}
+
+/// Signs `payment` with `signer`, returns a PSBT holding whatever signatures the signer added.
+pub fn sign(payment: &Payment, signer: &(impl Signer + ?Sized)) -> Result<Psbt, SendError> {
+    let mut psbt = payment.psbt();
+    signer.sign_taproot_key_spend(&mut psbt)?;
+    signer.sign_ecdsa(&mut psbt)?;
+    Ok(psbt)
+}
+
+/// Puts the signatures in `psbt` into the witnesses of its transaction, ready to broadcast.
+///
+/// Fails with [`SendError::Unsigned`] if any input has not been signed, in which case the PSBT can
+/// be exported to be signed elsewhere.
+pub fn finalize(psbt: Psbt) -> Result<Transaction, SendError> {
+    let mut tx = psbt.unsigned_tx;
+    for (index, (input, psbt_input)) in tx.input.iter_mut().zip(psbt.inputs).enumerate() {
+        input.witness = if let Some(signature) = psbt_input.tap_key_sig {
+            Witness::from_slice(&[signature.to_vec()])
+        } else if let Some((public_key, signature)) = psbt_input.partial_sigs.iter().next() {
+            Witness::from_slice(&[signature.to_vec(), public_key.to_bytes()])
+        } else {
+            return Err(SendError::Unsigned(index));
+        };
+    }
+    Ok(tx)
+}
+
+/// Broadcasts `tx` and marks the wallet outputs it spends as spent.
+///
+/// `tx` is kept in the database so that [`rebroadcast`] can broadcast it again.
+#[cfg(feature = "fs")]
+pub async fn broadcast(
+    backend: &impl Backend,
+    db: &mut Db,
+    tx: &Transaction,
+) -> Result<Txid, SendError> {
+    let txid = backend
+        .broadcast(tx.clone())
+        .await
+        .map_err(SendError::Broadcast)?;
+    for input in &tx.input {
+        db.set_spent(&input.previous_output)?;
+    }
+    db.store_broadcast(tx)?;
+    Ok(txid)
+}
+
+/// Broadcasts our transactions that have not confirmed yet again.
+///
+/// Mempools drop transactions (e.g. when they restart or fill up) and nobody rebroadcasts them for
+/// us. Transactions that were conflicted are left alone. Returns the txid of each transaction
+/// broadcast along with the result, one that is no longer valid (e.g. one of its inputs was spent
+/// by another transaction) is rejected.
+#[cfg(feature = "fs")]
+pub async fn rebroadcast(
+    backend: &impl Backend,
+    db: &mut Db,
+) -> Result<Vec<(Txid, Result<Txid, BackendError>)>, DbError> {
+    let mut results = Vec::new();
+    for tx in db.unconfirmed_broadcasts()? {
+        let txid = tx.txid();
+        results.push((txid, backend.broadcast(tx).await));
+    }
+    Ok(results)
+}
+
+/// Errors returned when building, signing or broadcasting a payment.
+#[derive(Debug, thiserror::Error)]
+pub enum SendError {
+    #[error("amount {amount} is below the dust limit of {limit}")]
+    Dust { amount: Amount, limit: Amount },
+    #[error("insufficient funds: have {available}, need {amount} plus a fee of {fee}")]
+    InsufficientFunds {
+        available: Amount,
+        amount: Amount,
+        fee: Amount,
+    },
+    #[error("failed to sign transaction")]
+    Sign(#[from] SignError),
+    #[error("input {0} is not signed")]
+    Unsigned(usize),
+    #[error("amount overflow")]
+    Overflow,
+    #[error("failed to broadcast transaction")]
+    Broadcast(#[source] BackendError),
+    #[cfg(feature = "fs")]
+    #[error(transparent)]
+    Db(#[from] DbError),
+}
+
+impl From<FeeError> for SendError {
+    fn from(error: FeeError) -> Self {
+        match error {
+            FeeError::InsufficientFunds {
+                available,
+                amount,
+                fee,
+            } => SendError::InsufficientFunds {
+                available,
+                amount,
+                fee,
+            },
+            FeeError::Overflow => SendError::Overflow,
+        }
+    }
+}
@@ -0,0 +1,436 @@
+//! Property tests for coin selection and fee calculation in `tx::build_payment`.
+
+use std::collections::{HashMap, HashSet};
+
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::{SecretKey, SECP256K1};
+use bitcoin::{Address, Amount, FeeRate, Network, OutPoint, PrivateKey, PublicKey, TxOut, Txid};
+use pico_wallet_core::config::Chain;
+use pico_wallet_core::sign::KeySigner;
+use pico_wallet_core::tx::{self, CoinSelection, SendError};
+use pico_wallet_core::{fee, keys};
+use proptest::prelude::*;
+
+fn key(byte: u8) -> PrivateKey {
+    let sk = SecretKey::from_slice(&[byte; 32]).expect("valid secret key");
+    PrivateKey::new(sk, Network::Regtest)
+}
+
+fn wallet_key() -> PrivateKey {
+    key(0x01)
+}
+
+/// Recipients with different output script lengths.
+fn recipient() -> impl Strategy<Value = Address> {
+    prop_oneof![
+        Just(keys::address(&key(0x02), Chain::Regtest)),
+        Just(
+            Address::p2wpkh(
+                &PublicKey::from_private_key(SECP256K1, &key(0x03)),
+                Network::Regtest
+            )
+            .expect("compressed key")
+        ),
+    ]
+}
+
+fn selection() -> impl Strategy<Value = CoinSelection> {
+    prop_oneof![
+        Just(CoinSelection::LargestFirst),
+        Just(CoinSelection::OldestFirst),
+        Just(CoinSelection::BranchAndBound),
+        Just(CoinSelection::Privacy),
+    ]
+}
+
+fn utxos() -> impl Strategy<Value = Vec<(OutPoint, Amount)>> {
+    prop::collection::vec(1_000u64..=100_000_000, 1..12).prop_map(|values| {
+        values
+            .into_iter()
+            .enumerate()
+            .map(|(vout, value)| {
+                (
+                    OutPoint::new(Txid::all_zeros(), vout as u32),
+                    Amount::from_sat(value),
+                )
+            })
+            .collect()
+    })
+}
+
+fn sum(amounts: impl IntoIterator<Item = Amount>) -> Amount {
+    amounts.into_iter().sum()
+}
+
+proptest! {
+    #[test]
+    fn selection_covers_amount_and_fee(
+        utxos in utxos(),
+        recipient in recipient(),
+        amount in 0u64..200_000_000,
+        sat_per_vb in 1u64..200,
+        selection in selection(),
+    ) {
+        let key = wallet_key();
+        let wallet = keys::address(&key, Chain::Regtest);
+        let amount = Amount::from_sat(amount);
+        let fee_rate = FeeRate::from_sat_per_vb(sat_per_vb).unwrap();
+
+        let result = tx::build_payment(
+            &utxos,
+            &wallet,
+            &recipient,
+            amount,
+            fee_rate,
+            fee::DUST_RELAY_FEE,
+            selection,
+        );
+        let mut payment = match result {
+            Ok(payment) => payment,
+            Err(SendError::Dust { limit, .. }) => {
+                prop_assert!(amount < limit);
+                return Ok(());
+            }
+            Err(SendError::InsufficientFunds { available, fee, .. }) => {
+                // Only fails if spending everything would not be enough.
+                prop_assert_eq!(available, sum(utxos.iter().map(|(_, value)| *value)));
+                prop_assert!(available < amount + fee);
+                return Ok(());
+            }
+            Err(error) => return Err(TestCaseError::fail(error.to_string())),
+        };
+
+        // Inputs are distinct wallet outputs and prevouts match them.
+        let available = utxos.iter().copied().collect::<HashMap<_, _>>();
+        let spent = payment.tx.input.iter().map(|input| input.previous_output).collect::<HashSet<_>>();
+        prop_assert_eq!(spent.len(), payment.tx.input.len());
+        prop_assert_eq!(payment.prevouts.len(), payment.tx.input.len());
+        for (input, prevout) in payment.tx.input.iter().zip(&payment.prevouts) {
+            let value = available.get(&input.previous_output);
+            prop_assert_eq!(value.map(|value| value.to_sat()), Some(prevout.value));
+            prop_assert_eq!(&prevout.script_pubkey, &wallet.script_pubkey());
+        }
+
+        // Selected >= target + fee and nothing goes missing.
+        let selected = sum(payment.prevouts.iter().map(|prevout| Amount::from_sat(prevout.value)));
+        let outputs = sum(payment.tx.output.iter().map(|output| Amount::from_sat(output.value)));
+        prop_assert!(selected >= amount + payment.fee);
+        prop_assert_eq!(selected, outputs + payment.fee);
+
+        // The recipient gets exactly the amount and change, if any, is not dust.
+        prop_assert_eq!(payment.tx.output[0].value, amount.to_sat());
+        prop_assert_eq!(&payment.tx.output[0].script_pubkey, &recipient.script_pubkey());
+        prop_assert!(payment.tx.output.len() <= 2);
+        let change = payment.tx.output.get(1).cloned();
+        if let Some(change) = &change {
+            prop_assert_eq!(&change.script_pubkey, &wallet.script_pubkey());
+            prop_assert!(change.value >= change.script_pubkey.dust_value().to_sat());
+        }
+
+        // The fee rate of the signed transaction is at least the target. It is exact unless dust
+        // change was added to the fee.
+        let psbt = tx::sign(&payment, &KeySigner::new(key)).unwrap();
+        payment.tx = tx::finalize(psbt).unwrap();
+        let min_fee = fee::fee_for(payment.tx.weight(), fee_rate).unwrap();
+        prop_assert!(payment.fee >= min_fee);
+        if change.is_some() {
+            prop_assert_eq!(payment.fee, min_fee);
+        } else {
+            prop_assert!(payment.fee - min_fee < wallet.script_pubkey().dust_value());
+        }
+    }
+
+    #[test]
+    fn reserve_is_left_unspent(
+        utxos in utxos(),
+        reserve in 0u64..300_000_000,
+    ) {
+        let reserve = Amount::from_sat(reserve);
+        let spendable = tx::without_reserve(&utxos, reserve);
+
+        // What may be spent keeps its order and what is held back covers the reserve.
+        let kept = utxos.iter().filter(|utxo| !spendable.contains(utxo)).copied().collect::<Vec<_>>();
+        let order = utxos.iter().filter(|utxo| spendable.contains(utxo)).copied().collect::<Vec<_>>();
+        prop_assert_eq!(&spendable, &order);
+        if sum(utxos.iter().map(|(_, value)| *value)) < reserve {
+            prop_assert!(spendable.is_empty());
+        } else {
+            prop_assert!(sum(kept.iter().map(|(_, value)| *value)) >= reserve);
+        }
+    }
+
+    #[test]
+    fn selection_spends_largest_first(
+        utxos in utxos(),
+        amount in 1_000_000u64..50_000_000,
+    ) {
+        let wallet = keys::address(&wallet_key(), Chain::Regtest);
+        let recipient = keys::address(&key(0x02), Chain::Regtest);
+        let result = tx::build_payment(
+            &utxos,
+            &wallet,
+            &recipient,
+            Amount::from_sat(amount),
+            FeeRate::BROADCAST_MIN,
+            fee::DUST_RELAY_FEE,
+            CoinSelection::LargestFirst,
+        );
+        if let Ok(payment) = result {
+            let mut values = utxos.iter().map(|(_, value)| value.to_sat()).collect::<Vec<_>>();
+            values.sort_unstable_by(|a, b| b.cmp(a));
+            let selected = payment.prevouts.iter().map(|prevout| prevout.value).collect::<Vec<_>>();
+            prop_assert_eq!(&selected[..], &values[..selected.len()]);
+        }
+    }
+
+    #[test]
+    fn selection_spends_oldest_first(
+        utxos in utxos(),
+        amount in 1_000_000u64..50_000_000,
+    ) {
+        let wallet = keys::address(&wallet_key(), Chain::Regtest);
+        let recipient = keys::address(&key(0x02), Chain::Regtest);
+        let result = tx::build_payment(
+            &utxos,
+            &wallet,
+            &recipient,
+            Amount::from_sat(amount),
+            FeeRate::BROADCAST_MIN,
+            fee::DUST_RELAY_FEE,
+            CoinSelection::OldestFirst,
+        );
+        if let Ok(payment) = result {
+            let spent = payment.tx.input.iter().map(|input| input.previous_output).collect::<Vec<_>>();
+            let oldest = utxos.iter().map(|(outpoint, _)| *outpoint).take(spent.len()).collect::<Vec<_>>();
+            prop_assert_eq!(spent, oldest);
+        }
+    }
+
+    #[test]
+    fn privacy_spends_a_single_output_if_one_is_enough(
+        utxos in utxos(),
+        amount in 1_000_000u64..50_000_000,
+    ) {
+        let wallet = keys::address(&wallet_key(), Chain::Regtest);
+        // Same type as the wallet, change doesn't stand out.
+        let recipient = keys::address(&key(0x02), Chain::Regtest);
+        let build = |selection| tx::build_payment(
+            &utxos,
+            &wallet,
+            &recipient,
+            Amount::from_sat(amount),
+            FeeRate::BROADCAST_MIN,
+            fee::DUST_RELAY_FEE,
+            selection,
+        );
+        if let Ok(payment) = build(CoinSelection::Privacy) {
+            let largest_first = build(CoinSelection::LargestFirst).unwrap();
+            if largest_first.tx.input.len() == 1 {
+                // The smallest output that is enough.
+                prop_assert_eq!(payment.tx.input.len(), 1);
+                let value = payment.prevouts[0].value;
+                let weight = fee::predict_spend_weight(1, [34, 34]);
+                let needed = amount + fee::fee_for(weight, FeeRate::BROADCAST_MIN).unwrap().to_sat();
+                let larger_or_not_enough = |other: u64| other >= value || other < needed;
+                prop_assert!(utxos.iter().all(|(_, other)| larger_or_not_enough(other.to_sat())));
+            } else {
+                prop_assert_eq!(payment.tx.input.len(), largest_first.tx.input.len());
+            }
+        }
+    }
+}
+
+/// Some of these outputs add up to the payment and its fee exactly, spending the largest would leave
+/// change.
+#[test]
+fn bnb_avoids_change() {
+    let wallet = keys::address(&wallet_key(), Chain::Regtest);
+    let recipient = keys::address(&key(0x02), Chain::Regtest);
+    let fee_rate = FeeRate::BROADCAST_MIN;
+    let amount = Amount::from_sat(70_000);
+    let fee = fee::fee_for(fee::predict_spend_weight(2, [34, 34]), fee_rate).unwrap();
+    let utxo = |vout, sat| {
+        (
+            OutPoint::new(Txid::all_zeros(), vout),
+            Amount::from_sat(sat),
+        )
+    };
+    let utxos = [
+        utxo(0, 1_000_000),
+        utxo(1, 50_000),
+        utxo(2, 20_000 + fee.to_sat()),
+        utxo(3, 5_000),
+    ];
+
+    let build = |selection| {
+        tx::build_payment(
+            &utxos,
+            &wallet,
+            &recipient,
+            amount,
+            fee_rate,
+            fee::DUST_RELAY_FEE,
+            selection,
+        )
+        .unwrap()
+    };
+    let largest_first = build(CoinSelection::LargestFirst);
+    assert_eq!(largest_first.tx.output.len(), 2);
+
+    let bnb = build(CoinSelection::BranchAndBound);
+    assert_eq!(bnb.tx.output.len(), 1);
+    assert_eq!(bnb.fee, fee);
+    let spent = bnb
+        .tx
+        .input
+        .iter()
+        .map(|input| input.previous_output)
+        .collect::<Vec<_>>();
+    assert_eq!(spent, vec![utxos[1].0, utxos[2].0]);
+}
+
+/// Paying to another address type, change would stand out so avoiding it comes first.
+#[test]
+fn privacy_avoids_change_of_another_type() {
+    let wallet = keys::address(&wallet_key(), Chain::Regtest);
+    let fee_rate = FeeRate::BROADCAST_MIN;
+    let amount = Amount::from_sat(70_000);
+    let utxo = |vout, sat| {
+        (
+            OutPoint::new(Txid::all_zeros(), vout),
+            Amount::from_sat(sat),
+        )
+    };
+    let build = |recipient: &Address, utxos: &[(OutPoint, Amount)]| {
+        tx::build_payment(
+            utxos,
+            &wallet,
+            recipient,
+            amount,
+            fee_rate,
+            fee::DUST_RELAY_FEE,
+            CoinSelection::Privacy,
+        )
+        .unwrap()
+    };
+
+    let p2wpkh = Address::p2wpkh(
+        &PublicKey::from_private_key(SECP256K1, &key(0x03)),
+        Network::Regtest,
+    )
+    .expect("compressed key");
+    let fee = fee::fee_for(fee::predict_spend_weight(2, [22, 34]), fee_rate).unwrap();
+    let utxos = [
+        utxo(0, 1_000_000),
+        utxo(1, 50_000),
+        utxo(2, 20_000 + fee.to_sat()),
+    ];
+    let payment = build(&p2wpkh, &utxos);
+    assert_eq!(payment.tx.output.len(), 1);
+    assert_eq!(payment.tx.input.len(), 2);
+
+    // To our own type a single input with change is preferred.
+    let p2tr = keys::address(&key(0x02), Chain::Regtest);
+    let fee = fee::fee_for(fee::predict_spend_weight(2, [34, 34]), fee_rate).unwrap();
+    let utxos = [
+        utxo(0, 1_000_000),
+        utxo(1, 50_000),
+        utxo(2, 20_000 + fee.to_sat()),
+    ];
+    let payment = build(&p2tr, &utxos);
+    assert_eq!(payment.tx.input.len(), 1);
+    assert_eq!(payment.tx.output.len(), 2);
+}
+
+/// Spending a P2WPKH and a taproot output together would link the wallet's two addresses.
+#[test]
+fn privacy_spends_a_single_output_type() {
+    let wallet = keys::address(&wallet_key(), Chain::Regtest);
+    let p2wpkh = keys::p2wpkh_address(&wallet_key(), Chain::Regtest).unwrap();
+    let addresses = [wallet.clone(), p2wpkh.clone()];
+    let recipient = Address::p2wpkh(
+        &PublicKey::from_private_key(SECP256K1, &key(0x03)),
+        Network::Regtest,
+    )
+    .expect("compressed key");
+    let utxo = |vout, address: &Address, sat| {
+        let output = TxOut {
+            value: sat,
+            script_pubkey: address.script_pubkey(),
+        };
+        (OutPoint::new(Txid::all_zeros(), vout), output)
+    };
+    let build = |utxos: &[(OutPoint, TxOut)], selection| {
+        tx::build_payment_from(
+            utxos,
+            &addresses,
+            &recipient,
+            Amount::from_sat(80_000),
+            FeeRate::BROADCAST_MIN,
+            fee::DUST_RELAY_FEE,
+            selection,
+        )
+    };
+    let spent_scripts = |payment: &tx::Payment| {
+        payment
+            .prevouts
+            .iter()
+            .map(|prevout| prevout.script_pubkey.clone())
+            .collect::<HashSet<_>>()
+    };
+
+    let utxos = [
+        utxo(0, &p2wpkh, 60_000),
+        utxo(1, &wallet, 60_000),
+        utxo(2, &wallet, 30_000),
+    ];
+    let largest_first = build(&utxos, CoinSelection::LargestFirst).unwrap();
+    assert_eq!(spent_scripts(&largest_first).len(), 2);
+    // Change of the recipient's type doesn't stand out.
+    let change = &largest_first.tx.output[1];
+    assert_eq!(change.script_pubkey, p2wpkh.script_pubkey());
+
+    let privacy = build(&utxos, CoinSelection::Privacy).unwrap();
+    let spent = privacy
+        .tx
+        .input
+        .iter()
+        .map(|input| input.previous_output)
+        .collect::<HashSet<_>>();
+    assert_eq!(spent, HashSet::from([utxos[1].0, utxos[2].0]));
+
+    // Enough together but not of either type alone.
+    let utxos = [utxo(0, &p2wpkh, 50_000), utxo(1, &wallet, 50_000)];
+    assert!(build(&utxos, CoinSelection::LargestFirst).is_ok());
+    assert!(matches!(
+        build(&utxos, CoinSelection::Privacy),
+        Err(SendError::InsufficientFunds { .. })
+    ));
+}
+
+#[test]
+fn reserve_is_kept_in_few_outputs() {
+    let utxo = |vout, sat| {
+        (
+            OutPoint::new(Txid::all_zeros(), vout),
+            Amount::from_sat(sat),
+        )
+    };
+    let utxos = [
+        utxo(0, 30_000),
+        utxo(1, 500_000),
+        utxo(2, 200_000),
+        utxo(3, 80_000),
+    ];
+
+    // The smallest output covering the reserve on its own.
+    let spendable = tx::without_reserve(&utxos, Amount::from_sat(150_000));
+    assert_eq!(spendable, [utxos[0], utxos[1], utxos[3]]);
+
+    // Otherwise the largest.
+    let spendable = tx::without_reserve(&utxos, Amount::from_sat(600_000));
+    assert_eq!(spendable, [utxos[0], utxos[3]]);
+
+    assert!(tx::without_reserve(&utxos, Amount::from_sat(900_000)).is_empty());
+    assert_eq!(tx::without_reserve(&utxos, Amount::ZERO), utxos);
+}
@@ -0,0 +1,275 @@
+//! The fee and change arithmetic in `fee`.
+
+use bitcoin::absolute::LockTime;
+use bitcoin::transaction::{predict_weight, InputWeightPrediction};
+use bitcoin::{
+    Amount, FeeRate, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Weight, Witness,
+};
+use pico_wallet_core::fee::{self, FeeError, Plan, Spend};
+
+/// A taproot output script, as used for both recipient and change in these tests.
+fn p2tr_script() -> ScriptBuf {
+    ScriptBuf::from(vec![0x51; 34])
+}
+
+fn spend(input_value: u64, amount: u64, change_script: &ScriptBuf) -> Spend<'_> {
+    Spend {
+        inputs: vec![fee::p2tr_key_path()],
+        input_value: Amount::from_sat(input_value),
+        output_script_lens: vec![34],
+        amount: Amount::from_sat(amount),
+        change_script,
+        dust_relay_fee: fee::DUST_RELAY_FEE,
+    }
+}
+
+#[test]
+fn fee_for_rounds_up() {
+    let rate = FeeRate::from_sat_per_kwu(250);
+    let fee = |wu| fee::fee_for(Weight::from_wu(wu), rate).unwrap().to_sat();
+    assert_eq!(fee(4), 1);
+    assert_eq!(fee(400), 100);
+    assert_eq!(fee(401), 101);
+    assert_eq!(fee(0), 0);
+}
+
+#[test]
+fn overflow_is_an_error() {
+    let rate = FeeRate::from_sat_per_kwu(u64::MAX);
+    assert_eq!(fee::fee_for(Weight::from_wu(1_001), rate), None);
+    assert_eq!(
+        fee::checked_sum([Amount::MAX_MONEY, Amount::from_sat(u64::MAX)]),
+        None
+    );
+
+    let change = p2tr_script();
+    let result = fee::plan(&spend(100_000, 50_000, &change), rate, Amount::ZERO);
+    assert!(matches!(result, Err(FeeError::Overflow)));
+}
+
+#[test]
+fn one_in_two_out_weight() {
+    // 1 key path input, 2 taproot outputs: 154 vB with the script_sig length byte counted.
+    let change = p2tr_script();
+    assert_eq!(spend(0, 0, &change).weight().to_vbytes_ceil(), 154);
+}
+
+/// Returns the weight `prediction` predicts and the actual weight of a transaction with one input
+/// witnessed by elements of `witness_lens` bytes, paying one taproot output.
+fn predicted_and_actual(
+    prediction: InputWeightPrediction,
+    witness_lens: &[usize],
+) -> (Weight, Weight) {
+    let tx = Transaction {
+        version: 2,
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::MAX,
+            witness: Witness::from_slice(
+                &witness_lens
+                    .iter()
+                    .map(|len| vec![0; *len])
+                    .collect::<Vec<_>>(),
+            ),
+        }],
+        output: vec![TxOut {
+            value: 0,
+            script_pubkey: p2tr_script(),
+        }],
+    };
+    (predict_weight([prediction], [34]), tx.weight())
+}
+
+#[test]
+fn key_path_prediction_is_exact() {
+    let (predicted, actual) = predicted_and_actual(fee::p2tr_key_path(), &[64]);
+    assert_eq!(predicted, actual);
+}
+
+#[test]
+fn script_path_prediction_is_exact() {
+    // One signature under a 36 byte leaf, the only one.
+    let (predicted, actual) = predicted_and_actual(fee::p2tr_script_path(36, 0, 1), &[64, 36, 33]);
+    assert_eq!(predicted, actual);
+
+    // Two signatures under a leaf two levels down the tree.
+    let (predicted, actual) =
+        predicted_and_actual(fee::p2tr_script_path(70, 2, 2), &[64, 64, 70, 33 + 2 * 32]);
+    assert_eq!(predicted, actual);
+}
+
+#[test]
+fn multisig_prediction_counts_the_largest_signatures() {
+    // 2-of-3: OP_2, three 33 byte keys with their pushes, OP_3 and OP_CHECKMULTISIG.
+    let script_len = 1 + 3 * 34 + 1 + 1;
+    let (predicted, actual) =
+        predicted_and_actual(fee::p2wsh_multisig(script_len, 2), &[0, 73, 73, script_len]);
+    assert_eq!(predicted, actual);
+
+    // Signatures are usually smaller, the prediction errs on the high side.
+    let (predicted, actual) =
+        predicted_and_actual(fee::p2wsh_multisig(script_len, 2), &[0, 71, 72, script_len]);
+    assert!(predicted > actual);
+}
+
+#[test]
+fn plan_with_change() {
+    let change = p2tr_script();
+    let spend = spend(100_000, 50_000, &change);
+    let rate = FeeRate::from_sat_per_vb(2).unwrap();
+    let fee = fee::fee_for(spend.weight(), rate).unwrap();
+
+    assert_eq!(
+        fee::plan(&spend, rate, Amount::ZERO).unwrap(),
+        Plan {
+            fee,
+            change: Some(Amount::from_sat(50_000) - fee),
+        }
+    );
+}
+
+#[test]
+fn dust_change_goes_to_fee() {
+    let change = p2tr_script();
+    let rate = FeeRate::from_sat_per_vb(2).unwrap();
+    let fee = fee::fee_for(spend(0, 0, &change).weight(), rate).unwrap();
+    let dust = fee::dust_limit(&change, fee::DUST_RELAY_FEE) - Amount::from_sat(1);
+    let spend = spend(50_000 + fee.to_sat() + dust.to_sat(), 50_000, &change);
+
+    assert_eq!(
+        fee::plan(&spend, rate, Amount::ZERO).unwrap(),
+        Plan {
+            fee: fee + dust,
+            change: None,
+        }
+    );
+}
+
+#[test]
+fn dust_limit_depends_on_script_type() {
+    let secp = bitcoin::secp256k1::Secp256k1::new();
+    let key = bitcoin::PrivateKey::from_slice(&[0x01; 32], bitcoin::Network::Regtest)
+        .unwrap()
+        .public_key(&secp);
+    let p2pkh = ScriptBuf::new_p2pkh(&key.pubkey_hash());
+    let p2wpkh = ScriptBuf::new_v0_p2wpkh(&key.wpubkey_hash().unwrap());
+    let p2tr = ScriptBuf::new_v1_p2tr(&secp, key.inner.x_only_public_key().0, None);
+
+    // Bitcoin Core's well known limits at its default dust relay fee.
+    assert_eq!(fee::dust_limit(&p2pkh, fee::DUST_RELAY_FEE).to_sat(), 546);
+    assert_eq!(fee::dust_limit(&p2wpkh, fee::DUST_RELAY_FEE).to_sat(), 294);
+    assert_eq!(fee::dust_limit(&p2tr, fee::DUST_RELAY_FEE).to_sat(), 330);
+    for script in [&p2pkh, &p2wpkh, &p2tr] {
+        assert_eq!(
+            fee::dust_limit(script, fee::DUST_RELAY_FEE),
+            script.dust_value()
+        );
+    }
+
+    let rate = FeeRate::from_sat_per_vb(1).unwrap();
+    assert_eq!(fee::dust_limit(&p2tr, rate).to_sat(), 110);
+    // 1500 sat/kvB, half the default and not a whole number of sat/vB.
+    let rate = FeeRate::from_sat_per_kwu(375);
+    assert_eq!(fee::dust_limit(&p2pkh, rate).to_sat(), 273);
+    assert_eq!(fee::dust_limit(&p2wpkh, rate).to_sat(), 147);
+    assert_eq!(fee::dust_limit(&p2tr, rate).to_sat(), 165);
+    assert_eq!(fee::dust_limit(&p2pkh, FeeRate::ZERO), Amount::ZERO);
+}
+
+#[test]
+fn higher_dust_relay_fee_drops_more_change() {
+    let change = p2tr_script();
+    let rate = FeeRate::BROADCAST_MIN;
+    let fee = fee::fee_for(spend(0, 0, &change).weight(), rate).unwrap();
+    let mut spend = spend(50_000 + fee.to_sat() + 1_000, 50_000, &change);
+    assert_eq!(
+        fee::plan(&spend, rate, Amount::ZERO).unwrap().change,
+        Some(Amount::from_sat(1_000))
+    );
+
+    spend.dust_relay_fee = FeeRate::from_sat_per_vb(10).unwrap();
+    assert_eq!(
+        fee::plan(&spend, rate, Amount::ZERO).unwrap(),
+        Plan {
+            fee: fee + Amount::from_sat(1_000),
+            change: None,
+        }
+    );
+}
+
+#[test]
+fn min_fee_is_a_floor() {
+    let change = p2tr_script();
+    let spend = spend(100_000, 50_000, &change);
+    let plan = fee::plan(&spend, FeeRate::BROADCAST_MIN, Amount::from_sat(5_000)).unwrap();
+    assert_eq!(plan.fee, Amount::from_sat(5_000));
+    assert_eq!(plan.change, Some(Amount::from_sat(45_000)));
+}
+
+#[test]
+fn insufficient_funds() {
+    let change = p2tr_script();
+    let spend = spend(50_000, 50_000, &change);
+    let rate = FeeRate::BROADCAST_MIN;
+
+    match fee::plan(&spend, rate, Amount::ZERO) {
+        Err(FeeError::InsufficientFunds {
+            available,
+            amount,
+            fee,
+        }) => {
+            assert_eq!(available, Amount::from_sat(50_000));
+            assert_eq!(amount, Amount::from_sat(50_000));
+            assert_eq!(fee, fee::fee_for(spend.weight(), rate).unwrap());
+        }
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn mempool_histogram_buckets_by_fee_rate() {
+    let txs = [
+        (200, Amount::from_sat(100)),    // 0.5 sat/vB
+        (100, Amount::from_sat(100)),    // 1 sat/vB
+        (150, Amount::from_sat(3_000)),  // 20 sat/vB
+        (250, Amount::from_sat(4_999)),  // just under 20 sat/vB
+        (100, Amount::from_sat(50_000)), // 500 sat/vB
+    ];
+    let buckets = fee::mempool_histogram(&txs);
+    assert_eq!(buckets.len(), fee::HISTOGRAM_BUCKETS.len());
+
+    let bucket = |sat_per_vb| {
+        buckets
+            .iter()
+            .find(|bucket| bucket.min == FeeRate::from_sat_per_vb(sat_per_vb).unwrap())
+            .map(|bucket| (bucket.count, bucket.vsize))
+            .unwrap()
+    };
+    assert_eq!(bucket(0), (1, 200));
+    assert_eq!(bucket(1), (1, 100));
+    assert_eq!(bucket(10), (1, 250));
+    assert_eq!(bucket(20), (1, 150));
+    assert_eq!(bucket(200), (1, 100));
+    assert_eq!(bucket(2), (0, 0));
+}
+
+#[test]
+fn next_block_fee_rate_is_the_first_left_out() {
+    let min_fee = FeeRate::from_sat_per_vb(1).unwrap();
+    let rate = |sat_per_vb| FeeRate::from_sat_per_vb(sat_per_vb).unwrap();
+
+    // Everything fits.
+    let txs = [(100_000, Amount::from_sat(1_000_000))];
+    assert_eq!(fee::next_block_fee_rate(&txs, min_fee), min_fee);
+
+    // Half a block at 30 sat/vB, more than half at 20, then some at 5.
+    let txs = [
+        (400_000, Amount::from_sat(2_000_000)),
+        (500_000, Amount::from_sat(15_000_000)),
+        (600_000, Amount::from_sat(12_000_000)),
+    ];
+    assert_eq!(fee::next_block_fee_rate(&txs, min_fee), rate(20));
+    assert_eq!(fee::next_block_fee_rate(&txs, rate(25)), rate(25));
+}
@@ -0,0 +1,146 @@
+//! Scans the canned regtest blocks in `fixtures/regtest-blocks.hex`, no bitcoind required.
+//!
+//! The blocks pay to and spend from a known key, see the comments in the fixture file for what
+//! each one contains. Unlike the other mock chain tests these go through the same decoding as
+//! blocks downloaded from bitcoind.
+
+use std::collections::HashSet;
+
+use bitcoin::blockdata::constants::genesis_block;
+use bitcoin::consensus::encode::deserialize;
+use bitcoin::hashes::hex::FromHex;
+use bitcoin::secp256k1::SecretKey;
+use bitcoin::{Amount, Block, Network, OutPoint, PrivateKey, ScriptBuf};
+use pico_wallet_core::config::Chain;
+use pico_wallet_core::db::Db;
+use pico_wallet_core::mock::MockChain;
+use pico_wallet_core::{keys, scan};
+
+const FIXTURE: &str = include_str!("fixtures/regtest-blocks.hex");
+
+/// Returns the raw fixture blocks, `blocks[0]` is at height 1.
+fn raw_blocks() -> Vec<Vec<u8>> {
+    FIXTURE
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| Vec::from_hex(line).expect("valid hex"))
+        .collect()
+}
+
+fn blocks() -> Vec<Block> {
+    raw_blocks()
+        .iter()
+        .map(|raw| deserialize(raw).expect("valid block"))
+        .collect()
+}
+
+fn wallet_script() -> ScriptBuf {
+    let sk = SecretKey::from_slice(&[0x01; 32]).expect("valid secret key");
+    keys::address(&PrivateKey::new(sk, Network::Regtest), Chain::Regtest).script_pubkey()
+}
+
+#[test]
+fn fixture_blocks_form_a_chain() {
+    let blocks = blocks();
+    assert_eq!(blocks.len(), 5);
+    let mut prev = genesis_block(Network::Regtest).block_hash();
+    for block in &blocks {
+        assert_eq!(block.header.prev_blockhash, prev);
+        assert!(block.check_merkle_root());
+        prev = block.block_hash();
+    }
+}
+
+#[test]
+fn filter_raw_fixture_blocks() {
+    let wallet = wallet_script();
+    let blocks = blocks();
+    let mut unspent = HashSet::new();
+
+    let matches = raw_blocks()
+        .iter()
+        .map(|raw| scan::filter_raw_block(raw, &wallet, &mut unspent).unwrap())
+        .collect::<Vec<_>>();
+
+    let funding = blocks[0].txdata[1].txid();
+    let payment = blocks[2].txdata[1].txid();
+    let received = |txid, vout, sat| (OutPoint::new(txid, vout), Amount::from_sat(sat));
+    assert_eq!(
+        matches[0].received,
+        [
+            received(funding, 0, 100_000_000),
+            received(funding, 1, 20_000)
+        ]
+    );
+    assert!(matches[0].spent.is_empty());
+    assert_eq!(matches[1], Default::default());
+    assert_eq!(matches[2].received, [received(payment, 1, 69_999_692)]);
+    assert_eq!(matches[2].spent, [OutPoint::new(funding, 0)]);
+    assert_eq!(
+        matches[3].received,
+        [received(blocks[3].txdata[1].txid(), 0, 70_000)]
+    );
+    assert_eq!(matches[4], Default::default());
+
+    // Decoding a transaction at a time finds the same as decoding the whole block.
+    let mut unspent = HashSet::new();
+    for (block, expected) in blocks.iter().zip(&matches) {
+        assert_eq!(
+            &scan::filter_block(block, &wallet, &mut unspent).unwrap(),
+            expected
+        );
+    }
+}
+
+#[test]
+fn filter_block_rejects_overflowing_amounts() {
+    let wallet = wallet_script();
+    let mut block = blocks().remove(0);
+    let tx = &mut block.txdata[1];
+    assert!(tx.output.len() > 1);
+    for output in &mut tx.output {
+        output.script_pubkey = wallet.clone();
+        output.value = u64::MAX;
+    }
+    let txid = tx.txid();
+
+    let result = scan::filter_block(&block, &wallet, &mut HashSet::new());
+    assert!(matches!(result, Err(scan::FilterError::Overflow(overflowed)) if overflowed == txid));
+}
+
+#[tokio::test]
+async fn scan_fixture_blocks() {
+    let blocks = blocks();
+    let funding = blocks[0].txdata[1].txid();
+    let payment = blocks[2].txdata[1].txid();
+    let last = blocks[3].txdata[1].txid();
+    let chain = MockChain::from_blocks(
+        std::iter::once(genesis_block(Network::Regtest))
+            .chain(blocks)
+            .collect(),
+    );
+    let mut db = Db::open_in_memory().unwrap();
+
+    let summary = scan::scan(&chain, &mut db, &wallet_script()).await.unwrap();
+
+    assert_eq!(summary.blocks, 5);
+    assert_eq!(summary.height, 5);
+    assert_eq!(summary.received.len(), 4);
+    assert_eq!(summary.spent, [OutPoint::new(funding, 0)]);
+    assert_eq!(db.get_last_height().unwrap(), 5);
+    let unspent = db.unspent().unwrap().into_iter().collect::<HashSet<_>>();
+    let expected = [
+        (OutPoint::new(funding, 1), Amount::from_sat(20_000)),
+        (OutPoint::new(payment, 1), Amount::from_sat(69_999_692)),
+        (OutPoint::new(last, 0), Amount::from_sat(70_000)),
+    ]
+    .iter()
+    .copied()
+    .collect::<HashSet<_>>();
+    assert_eq!(unspent, expected);
+
+    // Nothing new the second time round.
+    let summary = scan::scan(&chain, &mut db, &wallet_script()).await.unwrap();
+    assert_eq!(summary.blocks, 0);
+    assert_eq!(db.unspent().unwrap().len(), 3);
+}
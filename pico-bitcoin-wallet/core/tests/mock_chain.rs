@@ -0,0 +1,1082 @@
+//! Scan and send against the in-memory `MockChain`, no bitcoind required.
+
+use std::sync::atomic::AtomicBool;
+
+use bitcoin::secp256k1::{SecretKey, SECP256K1};
+use bitcoin::{Address, Amount, FeeRate, Network, OutPoint, PrivateKey, Transaction, TxOut};
+use pico_wallet_core::backend::{Backend, BackendError};
+use pico_wallet_core::config::Chain;
+use pico_wallet_core::db::Db;
+use pico_wallet_core::mock::{funding_tx, MockChain};
+use pico_wallet_core::recovery::{self, Recovery};
+use pico_wallet_core::sign::{KeySigner, SignError};
+use pico_wallet_core::tx::{CoinSelection, SendError};
+use pico_wallet_core::{accounting, audit, fee, keys, scan, tx};
+
+fn wallet_key() -> PrivateKey {
+    let sk = SecretKey::from_slice(&[0x01; 32]).expect("valid secret key");
+    PrivateKey::new(sk, Network::Regtest)
+}
+
+/// Somebody else's address.
+fn other_address() -> Address {
+    let sk = SecretKey::from_slice(&[0x02; 32]).expect("valid secret key");
+    keys::address(&PrivateKey::new(sk, Network::Regtest), Chain::Regtest)
+}
+
+/// Signs `payment` with `key`, returns the transaction ready to broadcast.
+fn sign(payment: &tx::Payment, key: &PrivateKey) -> Transaction {
+    let psbt = tx::sign(payment, &KeySigner::new(*key)).unwrap();
+    tx::finalize(psbt).unwrap()
+}
+
+fn balance(db: &mut Db) -> Amount {
+    db.unspent()
+        .expect("failed to read utxos")
+        .into_iter()
+        .map(|(_, amount)| amount)
+        .sum()
+}
+
+#[tokio::test]
+async fn scan_finds_payments_to_the_wallet() {
+    let wallet = keys::address(&wallet_key(), Chain::Regtest);
+    let chain = MockChain::new();
+    let mut db = Db::open_in_memory().unwrap();
+
+    chain.mine(vec![
+        funding_tx(
+            &wallet.script_pubkey(),
+            &[Amount::from_sat(100_000), Amount::from_sat(20_000)],
+        ),
+        funding_tx(&other_address().script_pubkey(), &[Amount::ONE_BTC]),
+    ]);
+    chain.mine_empty(3);
+
+    let summary = scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+
+    assert_eq!(summary.blocks, 4);
+    assert_eq!(summary.height, 4);
+    assert_eq!(summary.received.len(), 2);
+    assert!(summary.spent.is_empty());
+    assert_eq!(db.get_last_height().unwrap(), 4);
+    assert_eq!(balance(&mut db), Amount::from_sat(120_000));
+}
+
+#[tokio::test]
+async fn scan_is_incremental() {
+    let wallet = keys::address(&wallet_key(), Chain::Regtest);
+    let chain = MockChain::new();
+    let mut db = Db::open_in_memory().unwrap();
+
+    chain.mine(vec![funding_tx(
+        &wallet.script_pubkey(),
+        &[Amount::from_sat(50_000)],
+    )]);
+    scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+
+    let summary = scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+    assert_eq!(summary.blocks, 0);
+
+    chain.mine(vec![funding_tx(
+        &wallet.script_pubkey(),
+        &[Amount::from_sat(70_000)],
+    )]);
+    let summary = scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+    assert_eq!(summary.blocks, 1);
+    assert_eq!(summary.received.len(), 1);
+    assert_eq!(balance(&mut db), Amount::from_sat(120_000));
+}
+
+/// Scanning a range again, e.g. after a crash, must not double count or unspend outputs.
+#[tokio::test]
+async fn rescanning_a_block_changes_nothing() {
+    let wallet = keys::address(&wallet_key(), Chain::Regtest);
+    let chain = MockChain::new();
+    let mut db = Db::open_in_memory().unwrap();
+
+    chain.mine(vec![funding_tx(
+        &wallet.script_pubkey(),
+        &[Amount::from_sat(50_000), Amount::from_sat(70_000)],
+    )]);
+    let summary = scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+    let (spent, _) = summary.received[0];
+    db.set_spent(&spent).unwrap();
+
+    db.store_block(1, &summary.received, &summary.spent, &summary.txs)
+        .unwrap();
+    assert_eq!(balance(&mut db), Amount::from_sat(70_000));
+}
+
+#[tokio::test]
+async fn send_then_scan_leaves_only_change() {
+    let key = wallet_key();
+    let wallet = keys::address(&key, Chain::Regtest);
+    let chain = MockChain::new();
+    let mut db = Db::open_in_memory().unwrap();
+
+    chain.mine(vec![funding_tx(
+        &wallet.script_pubkey(),
+        &[Amount::ONE_BTC],
+    )]);
+    scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+
+    let amount = Amount::from_sat(30_000_000);
+    let fee_rate = FeeRate::from_sat_per_vb(2).unwrap();
+    let utxos = db.unspent().unwrap();
+    let mut payment = tx::build_payment(
+        &utxos,
+        &wallet,
+        &other_address(),
+        amount,
+        fee_rate,
+        fee::DUST_RELAY_FEE,
+        CoinSelection::LargestFirst,
+    )
+    .unwrap();
+    payment.tx = sign(&payment, &key);
+    assert!(payment.fee >= fee::fee_for(payment.tx.weight(), fee_rate).unwrap());
+
+    // The mock verifies the signatures.
+    let txid = tx::broadcast(&chain, &mut db, &payment.tx).await.unwrap();
+    assert_eq!(txid, payment.tx.txid());
+    assert_eq!(chain.mempool().len(), 1);
+    assert_eq!(balance(&mut db), Amount::ZERO);
+
+    chain.mine(None);
+    let summary = scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+    assert_eq!(summary.received.len(), 1);
+    // Already marked as spent when we broadcast.
+    assert!(summary.spent.is_empty());
+    assert_eq!(balance(&mut db), Amount::ONE_BTC - amount - payment.fee);
+}
+
+#[tokio::test]
+async fn send_spends_p2wpkh_and_taproot_outputs_together() {
+    let key = wallet_key();
+    let wallet = keys::address(&key, Chain::Regtest);
+    let p2wpkh = keys::p2wpkh_address(&key, Chain::Regtest).unwrap();
+    let scripts = [wallet.script_pubkey(), p2wpkh.script_pubkey()];
+    let scripts = scripts
+        .iter()
+        .map(|script| script.as_script())
+        .collect::<Vec<_>>();
+    let chain = MockChain::new();
+    let mut db = Db::open_in_memory().unwrap();
+
+    chain.mine(vec![
+        funding_tx(&p2wpkh.script_pubkey(), &[Amount::ONE_BTC]),
+        funding_tx(&wallet.script_pubkey(), &[Amount::from_sat(50_000_000)]),
+    ]);
+    let summary = scan::scan_until(&chain, &mut db, &scripts, &AtomicBool::new(false))
+        .await
+        .unwrap();
+    assert_eq!(summary.received.len(), 2);
+
+    let utxos = db
+        .utxos()
+        .unwrap()
+        .into_iter()
+        .map(|utxo| {
+            let output = TxOut {
+                value: utxo.amount.to_sat(),
+                script_pubkey: utxo.script_pubkey.unwrap(),
+            };
+            (utxo.outpoint, output)
+        })
+        .collect::<Vec<_>>();
+    // More than either output, both have to be spent.
+    let amount = Amount::from_sat(120_000_000);
+    let fee_rate = FeeRate::from_sat_per_vb(2).unwrap();
+    let mut payment = tx::build_payment_from(
+        &utxos,
+        &[wallet.clone(), p2wpkh.clone()],
+        &other_address(),
+        amount,
+        fee_rate,
+        fee::DUST_RELAY_FEE,
+        CoinSelection::LargestFirst,
+    )
+    .unwrap();
+    assert_eq!(payment.tx.input.len(), 2);
+    payment.tx = sign(&payment, &key);
+    assert!(payment.fee >= fee::fee_for(payment.tx.weight(), fee_rate).unwrap());
+
+    // The mock verifies the ECDSA signature as well as the Schnorr one.
+    tx::broadcast(&chain, &mut db, &payment.tx).await.unwrap();
+    chain.mine(None);
+    scan::scan_until(&chain, &mut db, &scripts, &AtomicBool::new(false))
+        .await
+        .unwrap();
+    assert_eq!(
+        balance(&mut db),
+        Amount::from_sat(150_000_000) - amount - payment.fee
+    );
+}
+
+/// Spends we broadcast are already marked as spent but still show up in the history once mined.
+#[tokio::test]
+async fn history_records_receives_and_sends() {
+    let key = wallet_key();
+    let wallet = keys::address(&key, Chain::Regtest);
+    let chain = MockChain::new();
+    let mut db = Db::open_in_memory().unwrap();
+
+    let funding = funding_tx(&wallet.script_pubkey(), &[Amount::ONE_BTC]);
+    chain.mine(vec![funding.clone()]);
+    scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+
+    let utxos = db.unspent().unwrap();
+    let mut payment = tx::build_payment(
+        &utxos,
+        &wallet,
+        &other_address(),
+        Amount::from_sat(30_000_000),
+        FeeRate::BROADCAST_MIN,
+        fee::DUST_RELAY_FEE,
+        CoinSelection::LargestFirst,
+    )
+    .unwrap();
+    payment.tx = sign(&payment, &key);
+    tx::broadcast(&chain, &mut db, &payment.tx).await.unwrap();
+    chain.mine(None);
+    scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+
+    let history = db.history().unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].txid, funding.txid());
+    assert_eq!(history[0].height, Some(1));
+    assert_eq!(history[0].received, Amount::ONE_BTC);
+    assert_eq!(history[0].sent, Amount::ZERO);
+    assert_eq!(history[1].txid, payment.tx.txid());
+    assert_eq!(history[1].height, Some(2));
+    assert_eq!(history[1].sent, Amount::ONE_BTC);
+    assert_eq!(
+        history[1].received,
+        Amount::ONE_BTC - Amount::from_sat(30_000_000) - payment.fee
+    );
+    assert_eq!(
+        db.tx_outputs(&payment.tx.txid()).unwrap(),
+        payment.tx.output
+    );
+}
+
+#[tokio::test]
+async fn accounting_entries_have_fees_and_running_balance() {
+    let key = wallet_key();
+    let wallet = keys::address(&key, Chain::Regtest);
+    let chain = MockChain::new();
+    let mut db = Db::open_in_memory().unwrap();
+
+    let funding = funding_tx(&wallet.script_pubkey(), &[Amount::ONE_BTC]);
+    chain.mine(vec![funding.clone()]);
+    scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+
+    let mut payment = tx::build_payment(
+        &db.unspent().unwrap(),
+        &wallet,
+        &other_address(),
+        Amount::from_sat(30_000_000),
+        FeeRate::BROADCAST_MIN,
+        fee::DUST_RELAY_FEE,
+        CoinSelection::LargestFirst,
+    )
+    .unwrap();
+    payment.tx = sign(&payment, &key);
+    tx::broadcast(&chain, &mut db, &payment.tx).await.unwrap();
+    chain.mine(None);
+    // Still in the mempool, not an entry yet.
+    chain.add_to_mempool(funding_tx(
+        &wallet.script_pubkey(),
+        &[Amount::from_sat(5_000_000)],
+    ));
+    scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+
+    let entries = accounting::entries(&mut db).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].txid, funding.txid());
+    assert_eq!(entries[0].height, 1);
+    assert!(entries[0].incoming);
+    assert_eq!(entries[0].amount, Amount::ONE_BTC);
+    assert_eq!(entries[0].fee, None);
+    assert_eq!(entries[0].balance, Amount::ONE_BTC);
+    assert_eq!(entries[1].txid, payment.tx.txid());
+    assert_eq!(entries[1].height, 2);
+    assert!(!entries[1].incoming);
+    assert_eq!(entries[1].amount, Amount::from_sat(30_000_000));
+    assert_eq!(entries[1].fee, Some(payment.fee));
+    assert_eq!(
+        entries[1].balance,
+        Amount::ONE_BTC - Amount::from_sat(30_000_000) - payment.fee
+    );
+}
+
+#[tokio::test]
+async fn audit_compares_the_confirmed_view_of_the_database() {
+    let key = wallet_key();
+    let wallet = keys::address(&key, Chain::Regtest);
+    let chain = MockChain::new();
+    let mut db = Db::open_in_memory().unwrap();
+
+    let funding = funding_tx(
+        &wallet.script_pubkey(),
+        &[Amount::ONE_BTC, Amount::from_sat(50_000)],
+    );
+    chain.mine(vec![funding.clone()]);
+    scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+    let utxos = db.unspent().unwrap();
+
+    // Unconfirmed: a payment spending the larger output and one to the wallet.
+    let mut payment = tx::build_payment(
+        &utxos[..1],
+        &wallet,
+        &other_address(),
+        Amount::from_sat(30_000_000),
+        FeeRate::BROADCAST_MIN,
+        fee::DUST_RELAY_FEE,
+        CoinSelection::LargestFirst,
+    )
+    .unwrap();
+    payment.tx = sign(&payment, &key);
+    tx::broadcast(&chain, &mut db, &payment.tx).await.unwrap();
+    chain.add_to_mempool(funding_tx(
+        &wallet.script_pubkey(),
+        &[Amount::from_sat(70_000)],
+    ));
+    scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+
+    let mut confirmed = audit::confirmed_unspent(&mut db).unwrap();
+    confirmed.sort();
+    let mut expected = utxos.clone();
+    expected.sort();
+    assert_eq!(confirmed, expected);
+    assert!(audit::compare(&confirmed, &utxos).is_clean());
+
+    let missing = (OutPoint::new(payment.tx.txid(), 5), Amount::from_sat(1_000));
+    let mut chain_view = vec![missing];
+    chain_view.push((utxos[0].0, utxos[0].1 + Amount::from_sat(1)));
+    let found = audit::compare(&confirmed, &chain_view);
+    assert_eq!(found.missing, vec![missing]);
+    assert_eq!(found.phantom, vec![utxos[1]]);
+    assert_eq!(
+        found.mismatched,
+        vec![(utxos[0].0, utxos[0].1, utxos[0].1 + Amount::from_sat(1))]
+    );
+    assert_eq!(found.discrepancies(), 3);
+}
+
+#[tokio::test]
+async fn utxos_know_their_age_and_frozen_state() {
+    let wallet = keys::address(&wallet_key(), Chain::Regtest);
+    let chain = MockChain::new();
+    let mut db = Db::open_in_memory().unwrap();
+
+    let funding = funding_tx(
+        &wallet.script_pubkey(),
+        &[Amount::from_sat(50_000), Amount::from_sat(70_000)],
+    );
+    chain.mine(vec![funding.clone()]);
+    chain.mine_empty(2);
+    scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+
+    let (first, second) = (
+        OutPoint::new(funding.txid(), 0),
+        OutPoint::new(funding.txid(), 1),
+    );
+    assert!(db.set_frozen(&second, true).unwrap());
+    // Only unspent wallet outputs can be frozen.
+    assert!(!db
+        .set_frozen(&OutPoint::new(funding.txid(), 2), true)
+        .unwrap());
+
+    let utxos = db.utxos().unwrap();
+    assert_eq!(utxos.len(), 2);
+    for utxo in &utxos {
+        assert_eq!(utxo.height, Some(1));
+        assert_eq!(utxo.confirmations(3), Some(3));
+        assert!(!utxo.is_immature(3));
+        assert_eq!(utxo.script_pubkey, Some(wallet.script_pubkey()));
+        assert_eq!(utxo.frozen, utxo.outpoint == second);
+    }
+    assert_eq!(utxos[0].outpoint, first);
+
+    assert!(db.set_frozen(&second, false).unwrap());
+    assert!(db.utxos().unwrap().iter().all(|utxo| !utxo.frozen));
+}
+
+#[tokio::test]
+async fn scan_records_mempool_transactions_until_they_confirm() {
+    let key = wallet_key();
+    let wallet = keys::address(&key, Chain::Regtest);
+    let chain = MockChain::new();
+    let mut db = Db::open_in_memory().unwrap();
+
+    chain.mine(vec![funding_tx(
+        &wallet.script_pubkey(),
+        &[Amount::ONE_BTC],
+    )]);
+    scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+    let utxos = db.unspent().unwrap();
+    let mut payment = tx::build_payment(
+        &utxos,
+        &wallet,
+        &other_address(),
+        Amount::from_sat(30_000_000),
+        FeeRate::BROADCAST_MIN,
+        fee::DUST_RELAY_FEE,
+        CoinSelection::LargestFirst,
+    )
+    .unwrap();
+    payment.tx = sign(&payment, &key);
+    tx::broadcast(&chain, &mut db, &payment.tx).await.unwrap();
+    let incoming = funding_tx(&wallet.script_pubkey(), &[Amount::from_sat(20_000)]);
+    chain.add_to_mempool(incoming.clone());
+
+    let summary = scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+    assert_eq!(summary.blocks, 0);
+    assert_eq!(summary.unconfirmed.len(), 2);
+    let unconfirmed = db
+        .history()
+        .unwrap()
+        .into_iter()
+        .filter(|record| record.height.is_none())
+        .collect::<Vec<_>>();
+    assert_eq!(unconfirmed.len(), 2);
+    let change = Amount::ONE_BTC - Amount::from_sat(30_000_000) - payment.fee;
+    let ours = unconfirmed
+        .iter()
+        .find(|record| record.txid == payment.tx.txid())
+        .unwrap();
+    assert_eq!((ours.received, ours.sent), (change, Amount::ONE_BTC));
+    let theirs = unconfirmed
+        .iter()
+        .find(|record| record.txid == incoming.txid())
+        .unwrap();
+    assert_eq!(
+        (theirs.received, theirs.sent),
+        (Amount::from_sat(20_000), Amount::ZERO)
+    );
+
+    chain.mine(None);
+    let summary = scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+    assert!(summary.unconfirmed.is_empty());
+    let history = db.history().unwrap();
+    assert_eq!(history.len(), 3);
+    for record in &history[1..] {
+        assert_eq!(record.height, Some(2));
+        let pending = unconfirmed
+            .iter()
+            .find(|pending| pending.txid == record.txid)
+            .unwrap();
+        assert_eq!(
+            (record.received, record.sent),
+            (pending.received, pending.sent)
+        );
+    }
+
+    // Only unconfirmed transactions are replaced by the next mempool scan.
+    db.store_unconfirmed(&[], &[]).unwrap();
+    assert_eq!(db.history().unwrap(), history);
+}
+
+#[tokio::test]
+async fn scan_marks_outputs_spent_elsewhere() {
+    let key = wallet_key();
+    let wallet = keys::address(&key, Chain::Regtest);
+    let chain = MockChain::new();
+    let mut db = Db::open_in_memory().unwrap();
+
+    chain.mine(vec![funding_tx(
+        &wallet.script_pubkey(),
+        &[Amount::ONE_BTC],
+    )]);
+    // Spend from the same key without telling the database, e.g. a restored backup.
+    let mut other_db = Db::open_in_memory().unwrap();
+    scan::scan(&chain, &mut other_db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+    let utxos = other_db.unspent().unwrap();
+    // Leaves less than the dust limit for change so it all goes to the fee.
+    let mut payment = tx::build_payment(
+        &utxos,
+        &wallet,
+        &other_address(),
+        Amount::ONE_BTC - Amount::from_sat(400),
+        FeeRate::BROADCAST_MIN,
+        fee::DUST_RELAY_FEE,
+        CoinSelection::LargestFirst,
+    )
+    .unwrap();
+    payment.tx = sign(&payment, &key);
+    tx::broadcast(&chain, &mut other_db, &payment.tx)
+        .await
+        .unwrap();
+    assert_eq!(payment.tx.output.len(), 1);
+    chain.mine(None);
+
+    let summary = scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+    assert_eq!(summary.received.len(), 1);
+    assert_eq!(summary.spent.len(), 1);
+    assert_eq!(balance(&mut db), Amount::ZERO);
+}
+
+#[tokio::test]
+async fn send_fails_without_enough_funds() {
+    let wallet = keys::address(&wallet_key(), Chain::Regtest);
+    let chain = MockChain::new();
+    let mut db = Db::open_in_memory().unwrap();
+
+    chain.mine(vec![funding_tx(
+        &wallet.script_pubkey(),
+        &[Amount::from_sat(10_000)],
+    )]);
+    scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+
+    let utxos = db.unspent().unwrap();
+    let result = tx::build_payment(
+        &utxos,
+        &wallet,
+        &other_address(),
+        Amount::from_sat(10_000),
+        FeeRate::BROADCAST_MIN,
+        fee::DUST_RELAY_FEE,
+        CoinSelection::LargestFirst,
+    );
+    assert!(matches!(result, Err(SendError::InsufficientFunds { .. })));
+}
+
+#[tokio::test]
+async fn broadcast_rejects_unsigned_transactions() {
+    let key = wallet_key();
+    let wallet = keys::address(&key, Chain::Regtest);
+    let chain = MockChain::new();
+    let mut db = Db::open_in_memory().unwrap();
+
+    chain.mine(vec![funding_tx(
+        &wallet.script_pubkey(),
+        &[Amount::ONE_BTC],
+    )]);
+    scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+    let utxos = db.unspent().unwrap();
+    let mut payment = tx::build_payment(
+        &utxos,
+        &wallet,
+        &other_address(),
+        Amount::from_sat(50_000),
+        FeeRate::BROADCAST_MIN,
+        fee::DUST_RELAY_FEE,
+        CoinSelection::LargestFirst,
+    )
+    .unwrap();
+
+    let unsigned = tx::broadcast(&chain, &mut db, &payment.tx).await;
+    assert!(matches!(
+        unsigned,
+        Err(SendError::Broadcast(BackendError::Rejected(_)))
+    ));
+    // Nothing is marked as spent if the broadcast fails.
+    assert_eq!(balance(&mut db), Amount::ONE_BTC);
+
+    payment.tx = sign(&payment, &key);
+    tx::broadcast(&chain, &mut db, &payment.tx).await.unwrap();
+    assert_eq!(balance(&mut db), Amount::ZERO);
+}
+
+#[tokio::test]
+async fn broadcast_succeeds_if_the_node_already_has_the_transaction() {
+    let key = wallet_key();
+    let wallet = keys::address(&key, Chain::Regtest);
+    let chain = MockChain::new();
+    let mut db = Db::open_in_memory().unwrap();
+
+    chain.mine(vec![funding_tx(
+        &wallet.script_pubkey(),
+        &[Amount::ONE_BTC],
+    )]);
+    scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+    let payment = tx::build_payment(
+        &db.unspent().unwrap(),
+        &wallet,
+        &other_address(),
+        Amount::from_sat(50_000),
+        FeeRate::BROADCAST_MIN,
+        fee::DUST_RELAY_FEE,
+        CoinSelection::LargestFirst,
+    )
+    .unwrap();
+    let signed = sign(&payment, &key);
+
+    // A broadcast that reached the node but timed out before it answered, then the retry.
+    chain.broadcast(signed.clone()).await.unwrap();
+    let txid = tx::broadcast(&chain, &mut db, &signed).await.unwrap();
+
+    assert_eq!(txid, signed.txid());
+    assert_eq!(chain.mempool(), vec![signed.clone()]);
+    assert_eq!(balance(&mut db), Amount::ZERO);
+    assert_eq!(db.unconfirmed_broadcasts().unwrap(), vec![signed]);
+}
+
+#[tokio::test]
+async fn bump_fee_takes_the_fee_from_change() {
+    let key = wallet_key();
+    let wallet = keys::address(&key, Chain::Regtest);
+    let chain = MockChain::new();
+    let mut db = Db::open_in_memory().unwrap();
+
+    chain.mine(vec![funding_tx(
+        &wallet.script_pubkey(),
+        &[Amount::ONE_BTC],
+    )]);
+    scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+    let utxos = db.unspent().unwrap();
+    let payment = tx::build_payment(
+        &utxos,
+        &wallet,
+        &other_address(),
+        Amount::from_sat(50_000),
+        FeeRate::BROADCAST_MIN,
+        fee::DUST_RELAY_FEE,
+        CoinSelection::LargestFirst,
+    )
+    .unwrap();
+
+    let fee_rate = FeeRate::from_sat_per_vb(20).unwrap();
+    let mut bumped = tx::bump_fee(
+        &payment,
+        std::slice::from_ref(&wallet),
+        fee_rate,
+        fee::DUST_RELAY_FEE,
+    )
+    .unwrap();
+    bumped.tx = sign(&bumped, &key);
+
+    let spends = |tx: &Transaction| {
+        tx.input
+            .iter()
+            .map(|input| (input.previous_output, input.sequence))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(spends(&bumped.tx), spends(&payment.tx));
+    assert_eq!(bumped.tx.output[0], payment.tx.output[0]);
+    assert_eq!(
+        bumped.tx.output[1].value,
+        payment.tx.output[1].value - (bumped.fee - payment.fee).to_sat()
+    );
+    assert!(bumped.fee >= fee::fee_for(bumped.tx.weight(), fee_rate).unwrap());
+    tx::broadcast(&chain, &mut db, &bumped.tx).await.unwrap();
+}
+
+#[tokio::test]
+async fn replaced_transactions_are_not_rebroadcast() {
+    let key = wallet_key();
+    let wallet = keys::address(&key, Chain::Regtest);
+    let chain = MockChain::new();
+    let mut db = Db::open_in_memory().unwrap();
+
+    chain.mine(vec![funding_tx(
+        &wallet.script_pubkey(),
+        &[Amount::ONE_BTC],
+    )]);
+    scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+    let mut payment = tx::build_payment(
+        &db.unspent().unwrap(),
+        &wallet,
+        &other_address(),
+        Amount::from_sat(50_000),
+        FeeRate::BROADCAST_MIN,
+        fee::DUST_RELAY_FEE,
+        CoinSelection::LargestFirst,
+    )
+    .unwrap();
+    payment.tx = sign(&payment, &key);
+    tx::broadcast(&chain, &mut db, &payment.tx).await.unwrap();
+
+    let mut bumped = tx::bump_fee(
+        &payment,
+        std::slice::from_ref(&wallet),
+        FeeRate::from_sat_per_vb(20).unwrap(),
+        fee::DUST_RELAY_FEE,
+    )
+    .unwrap();
+    bumped.tx = sign(&bumped, &key);
+    tx::broadcast(&chain, &mut db, &bumped.tx).await.unwrap();
+
+    assert_eq!(
+        db.unconfirmed_broadcasts().unwrap(),
+        vec![bumped.tx.clone()]
+    );
+    let rebroadcast = tx::rebroadcast(&chain, &mut db).await.unwrap();
+    assert_eq!(
+        rebroadcast
+            .into_iter()
+            .map(|(txid, _)| txid)
+            .collect::<Vec<_>>(),
+        vec![bumped.tx.txid()]
+    );
+    assert_eq!(chain.mempool(), vec![bumped.tx]);
+}
+
+#[tokio::test]
+async fn payments_can_be_rebuilt_from_the_mempool() {
+    let key = wallet_key();
+    let wallet = keys::address(&key, Chain::Regtest);
+    let chain = MockChain::new();
+    let mut db = Db::open_in_memory().unwrap();
+
+    chain.mine(vec![funding_tx(
+        &wallet.script_pubkey(),
+        &[Amount::ONE_BTC],
+    )]);
+    scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+    let payment = tx::build_payment(
+        &db.unspent().unwrap(),
+        &wallet,
+        &other_address(),
+        Amount::from_sat(50_000),
+        FeeRate::BROADCAST_MIN,
+        fee::DUST_RELAY_FEE,
+        CoinSelection::LargestFirst,
+    )
+    .unwrap();
+    tx::broadcast(&chain, &mut db, &sign(&payment, &key))
+        .await
+        .unwrap();
+
+    let broadcast = chain.mempool().pop().unwrap();
+    let rebuilt = tx::rebuild_payment(&broadcast, &mut db, &wallet)
+        .unwrap()
+        .expect("all inputs are ours");
+    assert_eq!(rebuilt.tx, payment.tx);
+    assert_eq!(rebuilt.prevouts, payment.prevouts);
+    assert_eq!(rebuilt.fee, payment.fee);
+
+    // Not ours.
+    let other = funding_tx(&other_address().script_pubkey(), &[Amount::ONE_BTC]);
+    assert!(tx::rebuild_payment(&other, &mut db, &wallet)
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn scan_marks_replaced_transactions_conflicted() {
+    let key = wallet_key();
+    let wallet = keys::address(&key, Chain::Regtest);
+    let chain = MockChain::new();
+    let mut db = Db::open_in_memory().unwrap();
+
+    chain.mine(vec![funding_tx(
+        &wallet.script_pubkey(),
+        &[Amount::ONE_BTC],
+    )]);
+    scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+    let mut payment = tx::build_payment(
+        &db.unspent().unwrap(),
+        &wallet,
+        &other_address(),
+        Amount::from_sat(50_000),
+        FeeRate::BROADCAST_MIN,
+        fee::DUST_RELAY_FEE,
+        CoinSelection::LargestFirst,
+    )
+    .unwrap();
+    payment.tx = sign(&payment, &key);
+    tx::broadcast(&chain, &mut db, &payment.tx).await.unwrap();
+    let summary = scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+    assert!(summary.conflicted.is_empty());
+
+    let mut bumped = tx::bump_fee(
+        &payment,
+        std::slice::from_ref(&wallet),
+        FeeRate::from_sat_per_vb(20).unwrap(),
+        fee::DUST_RELAY_FEE,
+    )
+    .unwrap();
+    bumped.tx = sign(&bumped, &key);
+    tx::broadcast(&chain, &mut db, &bumped.tx).await.unwrap();
+    let summary = scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+    assert_eq!(summary.conflicted, vec![payment.tx.txid()]);
+    assert_eq!(summary.unconfirmed.len(), 1);
+    assert_eq!(summary.unconfirmed[0].txid, bumped.tx.txid());
+
+    // The replaced transaction stays conflicted, also once the replacement confirms.
+    chain.mine(None);
+    scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+    let history = db.history().unwrap();
+    let record = |txid| history.iter().find(|record| record.txid == txid).unwrap();
+    let replaced = record(payment.tx.txid());
+    assert!(replaced.conflicted);
+    assert_eq!(replaced.height, None);
+    let replacement = record(bumped.tx.txid());
+    assert!(!replacement.conflicted);
+    assert_eq!(replacement.height, Some(2));
+}
+
+#[tokio::test]
+async fn rebroadcast_resubmits_dropped_transactions_until_they_confirm() {
+    let key = wallet_key();
+    let wallet = keys::address(&key, Chain::Regtest);
+    let chain = MockChain::new();
+    let mut db = Db::open_in_memory().unwrap();
+
+    chain.mine(vec![funding_tx(
+        &wallet.script_pubkey(),
+        &[Amount::ONE_BTC],
+    )]);
+    scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+    let payment = tx::build_payment(
+        &db.unspent().unwrap(),
+        &wallet,
+        &other_address(),
+        Amount::from_sat(50_000),
+        FeeRate::BROADCAST_MIN,
+        fee::DUST_RELAY_FEE,
+        CoinSelection::LargestFirst,
+    )
+    .unwrap();
+    let signed = sign(&payment, &key);
+    let txid = tx::broadcast(&chain, &mut db, &signed).await.unwrap();
+
+    chain.clear_mempool();
+    let results = tx::rebroadcast(&chain, &mut db).await.unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].0, txid);
+    assert!(results[0].1.is_ok());
+    assert_eq!(chain.mempool(), vec![signed]);
+
+    chain.mine(None);
+    scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+    assert!(tx::rebroadcast(&chain, &mut db).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn scan_tracks_watched_addresses_apart_from_the_wallet() {
+    let wallet = keys::address(&wallet_key(), Chain::Regtest);
+    let watched = other_address();
+    let chain = MockChain::new();
+    let mut db = Db::open_in_memory().unwrap();
+
+    // Paid before the address was watched, not found.
+    chain.mine(vec![funding_tx(
+        &watched.script_pubkey(),
+        &[Amount::ONE_BTC],
+    )]);
+    scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+    assert!(db.add_watched(&watched.script_pubkey()).unwrap());
+    assert!(!db.add_watched(&watched.script_pubkey()).unwrap());
+
+    let paid = funding_tx(
+        &watched.script_pubkey(),
+        &[Amount::from_sat(20_000), Amount::from_sat(30_000)],
+    );
+    chain.mine(vec![
+        paid.clone(),
+        funding_tx(&wallet.script_pubkey(), &[Amount::ONE_BTC]),
+    ]);
+    let summary = scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+    assert_eq!(summary.watched.len(), 2);
+    assert_eq!(summary.received.len(), 1);
+    assert_eq!(balance(&mut db), Amount::ONE_BTC);
+
+    let mut spend = funding_tx(&wallet.script_pubkey(), &[Amount::from_sat(10_000)]);
+    spend.input[0].previous_output = OutPoint::new(paid.txid(), 0);
+    chain.mine(vec![spend]);
+    scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+    assert_eq!(
+        db.watched_unspent().unwrap(),
+        vec![OutPoint::new(paid.txid(), 1)]
+    );
+    assert_eq!(
+        db.watched_balances().unwrap(),
+        vec![(watched.script_pubkey(), 1, Amount::from_sat(30_000))]
+    );
+    // The spend paid the wallet but didn't spend any of its outputs.
+    assert_eq!(balance(&mut db), Amount::from_sat(100_010_000));
+}
+
+#[tokio::test]
+async fn rescan_finds_outputs_paid_before_the_address_was_watched() {
+    let wallet = keys::address(&wallet_key(), Chain::Regtest);
+    let watched = other_address();
+    let chain = MockChain::new();
+    let mut db = Db::open_in_memory().unwrap();
+
+    let early = funding_tx(&watched.script_pubkey(), &[Amount::ONE_BTC]);
+    chain.mine(vec![early.clone()]);
+    let paid = funding_tx(
+        &watched.script_pubkey(),
+        &[Amount::from_sat(20_000), Amount::from_sat(30_000)],
+    );
+    chain.mine(vec![paid.clone()]);
+    let mut spend = funding_tx(&wallet.script_pubkey(), &[Amount::from_sat(10_000)]);
+    spend.input[0].previous_output = OutPoint::new(paid.txid(), 0);
+    chain.mine(vec![spend]);
+    scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+    let height = db.get_last_height().unwrap();
+    assert!(db.add_watched(&watched.script_pubkey()).unwrap());
+
+    // From the block paying it the second time, the first payment is not found.
+    let found = scan::rescan_watched(&chain, &mut db, height - 1)
+        .await
+        .unwrap();
+    assert_eq!(found.len(), 2);
+    assert_eq!(
+        db.watched_balances().unwrap(),
+        vec![(watched.script_pubkey(), 1, Amount::from_sat(30_000))]
+    );
+
+    scan::rescan_watched(&chain, &mut db, 0).await.unwrap();
+    assert_eq!(
+        db.watched_balances().unwrap(),
+        vec![(
+            watched.script_pubkey(),
+            2,
+            Amount::ONE_BTC + Amount::from_sat(30_000)
+        )]
+    );
+    assert_eq!(db.get_last_height().unwrap(), height);
+    assert_eq!(balance(&mut db), Amount::from_sat(10_000));
+}
+
+fn recovery_key() -> PrivateKey {
+    let sk = SecretKey::from_slice(&[0x03; 32]).expect("valid secret key");
+    PrivateKey::new(sk, Network::Regtest)
+}
+
+#[tokio::test]
+async fn recovery_key_sweeps_expired_outputs() {
+    let recovery = Recovery {
+        key: recovery_key().inner.x_only_public_key(SECP256K1).0,
+        blocks: 10,
+    };
+    let spend_info = keys::spend_info(&wallet_key(), Some(&recovery));
+    let wallet = Address::p2tr_tweaked(spend_info.output_key(), Network::Regtest);
+    let chain = MockChain::new();
+    let mut db = Db::open_in_memory().unwrap();
+
+    chain.mine(vec![funding_tx(
+        &wallet.script_pubkey(),
+        &[Amount::from_sat(100_000), Amount::from_sat(50_000)],
+    )]);
+    scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+
+    // The wallet key still spends by key path.
+    let payment = tx::build_payment(
+        &db.unspent().unwrap(),
+        &wallet,
+        &other_address(),
+        Amount::from_sat(20_000),
+        FeeRate::from_sat_per_vb(2).unwrap(),
+        fee::DUST_RELAY_FEE,
+        CoinSelection::default(),
+    )
+    .unwrap();
+    let signer = KeySigner::with_merkle_root(wallet_key(), spend_info.merkle_root());
+    let signed = tx::finalize(tx::sign(&payment, &signer).unwrap()).unwrap();
+    tx::broadcast(&chain, &mut db, &signed).await.unwrap();
+    chain.mine_empty(9);
+    scan::scan(&chain, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+
+    let utxos = db.utxos().unwrap();
+    let tip = db.get_last_height().unwrap();
+    let (expired, waiting) = utxos
+        .iter()
+        .partition::<Vec<_>, _>(|utxo| recovery.is_expired(utxo.height.unwrap(), tip));
+    assert_eq!(expired.len(), 1, "the output not spent by the payment");
+    assert_eq!(waiting.len(), 1, "the change of the payment");
+
+    let expired = expired
+        .iter()
+        .map(|utxo| (utxo.outpoint, utxo.amount))
+        .collect::<Vec<_>>();
+    let sweep = recovery::build_sweep(
+        &expired,
+        &spend_info,
+        &recovery,
+        &other_address(),
+        FeeRate::from_sat_per_vb(2).unwrap(),
+        fee::DUST_RELAY_FEE,
+    )
+    .unwrap();
+    let result = recovery::sign_sweep(&sweep, &spend_info, &recovery, &wallet_key(), None);
+    assert!(matches!(result, Err(SignError::NotRecoveryKey)));
+
+    let signed =
+        recovery::sign_sweep(&sweep, &spend_info, &recovery, &recovery_key(), None).unwrap();
+    // The weight was predicted from the witness layout, check the fee rate is met.
+    assert!(
+        sweep.fee >= fee::fee_for(signed.weight(), FeeRate::from_sat_per_vb(2).unwrap()).unwrap()
+    );
+    tx::broadcast(&chain, &mut db, &signed).await.unwrap();
+}
@@ -0,0 +1,45 @@
+//! The operation log round trips entries through the file.
+
+use bitcoin::hashes::Hash;
+use bitcoin::Txid;
+use pico_wallet_core::oplog::{Op, OpLog};
+
+#[test]
+fn append_then_read() {
+    let path = std::env::temp_dir().join(format!("pico-oplog-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let log = OpLog::at(path.clone());
+    assert!(log.entries().unwrap().is_empty());
+
+    let ops = vec![
+        Op::Scan {
+            from_height: 1,
+            to_height: 101,
+            received: 2,
+            spent: 0,
+        },
+        Op::Send {
+            txid: Txid::all_zeros(),
+            recipient: "bcrt1qexample".to_owned(),
+            amount_sat: 50_000,
+            fee_sat: 153,
+        },
+    ];
+    for op in &ops {
+        log.append(op.clone()).unwrap();
+    }
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(contents.lines().count(), 2);
+    assert!(contents.lines().next().unwrap().contains(r#""op":"scan""#));
+
+    let entries = log.entries().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(
+        entries
+            .into_iter()
+            .map(|entry| entry.op)
+            .collect::<Vec<_>>(),
+        ops
+    );
+}
@@ -0,0 +1,152 @@
+//! Finding prices in what price sources return, and caching them.
+
+use std::cell::Cell;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::time::{Duration, Instant};
+
+use bitcoin::Amount;
+use pico_wallet_core::price::{self, Cached, PriceError, PriceProvider};
+
+#[test]
+fn price_is_found_under_the_currency() {
+    assert_eq!(price::parse_price(b"65000.5", "USD").unwrap(), 65000.5);
+    assert_eq!(
+        price::parse_price(br#"{"bitcoin":{"usd":65000}}"#, "USD").unwrap(),
+        65000.0
+    );
+    assert_eq!(
+        price::parse_price(br#"{"data":{"base":"BTC","EUR":"59000.25"}}"#, "EUR").unwrap(),
+        59000.25
+    );
+    assert!(matches!(
+        price::parse_price(br#"{"bitcoin":{"usd":65000}}"#, "EUR"),
+        Err(PriceError::NoPrice(_))
+    ));
+    assert!(matches!(
+        price::parse_price(b"<html>", "USD"),
+        Err(PriceError::NoPrice(_))
+    ));
+    assert!(matches!(
+        price::parse_price(b"-1", "USD"),
+        Err(PriceError::NoPrice(_))
+    ));
+}
+
+#[test]
+fn only_http_urls_are_supported() {
+    let timeout = Duration::from_secs(1);
+    assert!(
+        price::HttpProvider::new("http://127.0.0.1:8080/price/{currency}", None, timeout).is_ok()
+    );
+    assert!(matches!(
+        price::HttpProvider::new("https://example.com/price", None, timeout),
+        Err(PriceError::UnsupportedScheme(_))
+    ));
+    assert!(matches!(
+        price::HttpProvider::new("http://example.com:port/", None, timeout),
+        Err(PriceError::InvalidUrl(_))
+    ));
+}
+
+/// Counts how often it is asked.
+struct Fixed {
+    price: f64,
+    calls: Cell<usize>,
+}
+
+impl PriceProvider for &Fixed {
+    fn price(&self, _currency: &str) -> Result<f64, PriceError> {
+        self.calls.set(self.calls.get() + 1);
+        Ok(self.price)
+    }
+}
+
+#[test]
+fn cached_prices_are_reused_until_too_old() {
+    let path = std::env::temp_dir().join(format!("pico-price-cache-{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let provider = Fixed {
+        price: 50_000.0,
+        calls: Cell::new(0),
+    };
+
+    let cached = Cached::new(&provider, path.clone(), Duration::from_secs(600));
+    assert_eq!(cached.price("USD").unwrap(), 50_000.0);
+    assert_eq!(cached.price("USD").unwrap(), 50_000.0);
+    assert_eq!(provider.calls.get(), 1);
+    // Each currency is cached on its own.
+    cached.price("EUR").unwrap();
+    assert_eq!(provider.calls.get(), 2);
+
+    let expired = Cached::new(&provider, path.clone(), Duration::ZERO);
+    expired.price("USD").unwrap();
+    assert_eq!(provider.calls.get(), 3);
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        price::fiat_value(Amount::from_sat(50_000_000), 50_000.0),
+        25_000.0
+    );
+}
+
+#[test]
+fn prices_are_fetched_through_the_proxy() {
+    // A SOCKS5 proxy that checks it is asked for the price source by name, then plays it.
+    let proxy = TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy_addr = proxy.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = proxy.accept().unwrap();
+        let mut greeting = [0; 3];
+        stream.read_exact(&mut greeting).unwrap();
+        assert_eq!(greeting, [5, 1, 0]);
+        stream.write_all(&[5, 0]).unwrap();
+
+        let host = b"prices.onion";
+        let mut request = vec![0; 5 + host.len() + 2];
+        stream.read_exact(&mut request).unwrap();
+        assert_eq!(request[..5], [5, 1, 0, 3, host.len() as u8]);
+        assert_eq!(request[5..5 + host.len()], host[..]);
+        assert_eq!(request[5 + host.len()..], 8080u16.to_be_bytes());
+        stream.write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0]).unwrap();
+
+        let mut http = Vec::new();
+        let mut byte = [0; 1];
+        while !http.ends_with(b"\r\n\r\n") {
+            stream.read_exact(&mut byte).unwrap();
+            http.push(byte[0]);
+        }
+        assert!(http.starts_with(b"GET /price/USD HTTP/1.0\r\n"));
+        stream
+            .write_all(b"HTTP/1.0 200 OK\r\n\r\n{\"bitcoin\":{\"usd\":65000}}")
+            .unwrap();
+    });
+
+    let provider = price::HttpProvider::new(
+        "http://prices.onion:8080/price/{currency}",
+        Some(&format!("socks5://{}", proxy_addr)),
+        Duration::from_secs(5),
+    )
+    .unwrap();
+    assert_eq!(provider.price("USD").unwrap(), 65000.0);
+    server.join().unwrap();
+}
+
+#[test]
+fn a_stalled_proxy_times_out() {
+    // Accepts the connection but never answers, like a Tor circuit that does not get built.
+    let proxy = TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy_addr = proxy.local_addr().unwrap();
+    let server = std::thread::spawn(move || proxy.accept().unwrap());
+
+    let provider = price::HttpProvider::new(
+        "http://prices.onion/",
+        Some(&format!("socks5://{}", proxy_addr)),
+        Duration::from_millis(200),
+    )
+    .unwrap();
+    let start = Instant::now();
+    assert!(matches!(provider.price("USD"), Err(PriceError::Io(_))));
+    assert!(start.elapsed() < Duration::from_secs(5));
+    drop(server.join().unwrap());
+}
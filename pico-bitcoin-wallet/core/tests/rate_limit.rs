@@ -0,0 +1,19 @@
+//! The token bucket used to limit backend calls.
+
+use std::time::Duration;
+
+use pico_wallet_core::backend::RateLimiter;
+
+#[test]
+fn bursts_then_waits() {
+    let limiter = RateLimiter::new(10);
+    for _ in 0..10 {
+        assert_eq!(limiter.reserve(), Duration::ZERO);
+    }
+
+    // Each call past the burst waits one token (100ms) longer than the one before.
+    let first = limiter.reserve();
+    let second = limiter.reserve();
+    assert!(first > Duration::ZERO && first <= Duration::from_millis(100));
+    assert!(second > first && second <= Duration::from_millis(200));
+}
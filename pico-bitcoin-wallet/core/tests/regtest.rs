@@ -0,0 +1,304 @@
+//! End to end tests against a real bitcoind running in regtest mode.
+//!
+//! Skipped unless `BITCOIND_EXE` is set to the path of a `bitcoind` binary, e.g.
+//!
+//!     BITCOIND_EXE=$(which bitcoind) cargo test --workspace --test regtest
+//!
+//! Each test starts its own bitcoind in a temporary data directory and stops it when done.
+
+use std::collections::HashSet;
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+
+use bitcoin::secp256k1::SecretKey;
+use bitcoin::{
+    Address, Amount, FeeRate, Network, OutPoint, PrivateKey, ScriptBuf, Transaction, Txid,
+};
+use bitcoincore_rpc::RpcApi;
+use pico_wallet_core::backend::RpcBackend;
+use pico_wallet_core::config::{Auth, Chain, Config};
+use pico_wallet_core::db::Db;
+use pico_wallet_core::sign::KeySigner;
+use pico_wallet_core::tx::CoinSelection;
+use pico_wallet_core::{fee, keys, rpc, scan, tx};
+
+const RPC_USER: &str = "pico";
+const RPC_PASSWORD: &str = "pico";
+
+/// A bitcoind process that is killed, and its data directory removed, on drop.
+struct Bitcoind {
+    process: Child,
+    data_dir: PathBuf,
+    conf: Config,
+    client: rpc::Client,
+}
+
+impl Bitcoind {
+    /// Starts bitcoind if `BITCOIND_EXE` is set.
+    fn start() -> Option<Bitcoind> {
+        static INSTANCE: AtomicU32 = AtomicU32::new(0);
+
+        let exe = match std::env::var_os("BITCOIND_EXE") {
+            Some(exe) => exe,
+            None => {
+                eprintln!("BITCOIND_EXE not set, skipping regtest test");
+                return None;
+            }
+        };
+
+        let data_dir = std::env::temp_dir().join(format!(
+            "pico-regtest-{}-{}",
+            std::process::id(),
+            INSTANCE.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&data_dir).expect("failed to create data dir");
+        let rpc_port = free_port();
+
+        let process = Command::new(exe)
+            .arg("-regtest")
+            .arg(format!("-datadir={}", data_dir.display()))
+            .arg(format!("-rpcport={}", rpc_port))
+            .arg(format!("-port={}", free_port()))
+            .arg(format!("-rpcuser={}", RPC_USER))
+            .arg(format!("-rpcpassword={}", RPC_PASSWORD))
+            .arg("-listen=0")
+            .arg("-fallbackfee=0.0001")
+            .stdout(Stdio::null())
+            .spawn()
+            .expect("failed to start bitcoind");
+
+        let conf = Config {
+            chain: Chain::Regtest,
+            signet_challenge: None,
+            bitcoind_uris: vec![format!("http://127.0.0.1:{}", rpc_port)],
+            bitcoind_auth: Auth::UserPass(RPC_USER.to_owned(), RPC_PASSWORD.to_owned()),
+            bitcoind_wallet: None,
+            proxy: None,
+            rpc_timeout: Duration::from_secs(15),
+            rpc_retries: 0,
+            rpc_rate_limit: None,
+            dust_relay_fee: fee::DUST_RELAY_FEE,
+            recovery: None,
+            reserve: Amount::ZERO,
+            notify: false,
+            price_url: None,
+            price_cache: Duration::from_secs(600),
+        };
+
+        // bitcoind takes a moment to start answering RPC calls.
+        let start = Instant::now();
+        let client = loop {
+            match rpc::Client::new(&conf) {
+                Ok(client) => break client,
+                Err(error) if start.elapsed() > Duration::from_secs(30) => {
+                    panic!("bitcoind did not start: {}", error)
+                }
+                Err(_) => std::thread::sleep(Duration::from_millis(200)),
+            }
+        };
+
+        Some(Bitcoind {
+            process,
+            data_dir,
+            conf,
+            client,
+        })
+    }
+
+    fn backend(&self) -> RpcBackend {
+        RpcBackend::connect(&self.conf, 2).expect("failed to connect backend")
+    }
+
+    /// Mines `n` blocks to `address`.
+    fn mine(&self, n: u64, address: &Address) {
+        self.client
+            .generate_to_address(n, address)
+            .expect("failed to mine");
+    }
+
+    /// Returns the unspent outputs paying to `address` according to bitcoind.
+    fn utxos(&self, address: &Address) -> HashSet<(OutPoint, Amount)> {
+        let descriptor = serde_json::json!([{ "desc": format!("addr({})", address) }]);
+        let result: serde_json::Value = self
+            .client
+            .call("scantxoutset", &["start".into(), descriptor])
+            .expect("failed to scan utxo set");
+        result["unspents"]
+            .as_array()
+            .expect("unspents is an array")
+            .iter()
+            .map(|utxo| {
+                let txid = utxo["txid"].as_str().unwrap().parse::<Txid>().unwrap();
+                let vout = utxo["vout"].as_u64().unwrap() as u32;
+                let amount = Amount::from_btc(utxo["amount"].as_f64().unwrap()).unwrap();
+                (OutPoint::new(txid, vout), amount)
+            })
+            .collect()
+    }
+}
+
+impl Drop for Bitcoind {
+    fn drop(&mut self) {
+        let _ = self.client.stop();
+        if self.process.try_wait().ok().flatten().is_none() {
+            std::thread::sleep(Duration::from_secs(1));
+            let _ = self.process.kill();
+        }
+        let _ = self.process.wait();
+        let _ = std::fs::remove_dir_all(&self.data_dir);
+    }
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("failed to find a free port")
+        .port()
+}
+
+fn key(byte: u8) -> PrivateKey {
+    let sk = SecretKey::from_slice(&[byte; 32]).expect("valid secret key");
+    PrivateKey::new(sk, Network::Regtest)
+}
+
+/// An anyone-can-spend address that isn't ours, used to mature coinbase outputs.
+fn burn_address() -> Address {
+    let op_true = ScriptBuf::from(vec![bitcoin::opcodes::OP_TRUE.to_u8()]);
+    Address::p2wsh(&op_true, Network::Regtest)
+}
+
+/// Signs `payment` with `key`, returns the transaction ready to broadcast.
+fn sign(payment: &tx::Payment, key: &PrivateKey) -> Transaction {
+    let psbt = tx::sign(payment, &KeySigner::new(*key)).unwrap();
+    tx::finalize(psbt).unwrap()
+}
+
+fn db_utxos(db: &mut Db) -> HashSet<(OutPoint, Amount)> {
+    db.unspent().unwrap().into_iter().collect()
+}
+
+#[tokio::test]
+async fn fund_scan_send_bump() {
+    let bitcoind = match Bitcoind::start() {
+        Some(bitcoind) => bitcoind,
+        None => return,
+    };
+    let backend = bitcoind.backend();
+    let mut db = Db::open_in_memory().unwrap();
+    let key = key(0x01);
+    let wallet = keys::address(&key, Chain::Regtest);
+    let script_pubkey = wallet.script_pubkey();
+
+    // Fund: two coinbase outputs to us, matured by mining to someone else.
+    bitcoind.mine(2, &wallet);
+    bitcoind.mine(100, &burn_address());
+
+    let summary = scan::scan(&backend, &mut db, &script_pubkey).await.unwrap();
+    assert_eq!(summary.height, 102);
+    assert_eq!(summary.received.len(), 2);
+    assert_eq!(db_utxos(&mut db), bitcoind.utxos(&wallet));
+
+    // Send to someone else with change back to us.
+    let recipient = keys::address(&self::key(0x02), Chain::Regtest);
+    let amount = Amount::from_btc(12.5).unwrap();
+    let fee_rate = FeeRate::from_sat_per_vb(2).unwrap();
+    let utxos = db.unspent().unwrap();
+    let mut payment = tx::build_payment(
+        &utxos,
+        &wallet,
+        &recipient,
+        amount,
+        fee_rate,
+        fee::DUST_RELAY_FEE,
+        CoinSelection::LargestFirst,
+    )
+    .unwrap();
+    payment.tx = sign(&payment, &key);
+    let txid = tx::broadcast(&backend, &mut db, &payment.tx).await.unwrap();
+    assert_eq!(txid, payment.tx.txid());
+
+    // Bump the fee before it confirms, only the replacement makes it into a block.
+    let mut bumped = tx::bump_fee(
+        &payment,
+        std::slice::from_ref(&wallet),
+        FeeRate::from_sat_per_vb(10).unwrap(),
+        fee::DUST_RELAY_FEE,
+    )
+    .unwrap();
+    assert!(bumped.fee > payment.fee);
+    bumped.tx = sign(&bumped, &key);
+    let bumped_txid = tx::broadcast(&backend, &mut db, &bumped.tx).await.unwrap();
+    assert_ne!(bumped_txid, txid);
+
+    bitcoind.mine(1, &burn_address());
+    scan::scan(&backend, &mut db, &script_pubkey).await.unwrap();
+
+    let chain_utxos = bitcoind.utxos(&wallet);
+    assert_eq!(db_utxos(&mut db), chain_utxos);
+    assert_eq!(
+        bitcoind.utxos(&recipient),
+        [(OutPoint::new(bumped_txid, 0), amount)]
+            .iter()
+            .copied()
+            .collect()
+    );
+    let balance = chain_utxos
+        .iter()
+        .map(|(_, amount)| *amount)
+        .sum::<Amount>();
+    assert_eq!(
+        balance,
+        Amount::from_btc(100.0).unwrap() - amount - bumped.fee
+    );
+}
+
+#[tokio::test]
+async fn rescan_from_scratch_matches_chain() {
+    let bitcoind = match Bitcoind::start() {
+        Some(bitcoind) => bitcoind,
+        None => return,
+    };
+    let backend = bitcoind.backend();
+    let key = key(0x03);
+    let wallet = keys::address(&key, Chain::Regtest);
+
+    bitcoind.mine(1, &wallet);
+    bitcoind.mine(100, &burn_address());
+    let mut db = Db::open_in_memory().unwrap();
+    scan::scan(&backend, &mut db, &wallet.script_pubkey())
+        .await
+        .unwrap();
+
+    // Spend everything to ourselves a few times so there are spends to find.
+    for _ in 0..3 {
+        let utxos = db.unspent().unwrap();
+        let mut payment = tx::build_payment(
+            &utxos,
+            &wallet,
+            &wallet,
+            Amount::from_btc(1.0).unwrap(),
+            FeeRate::BROADCAST_MIN,
+            fee::DUST_RELAY_FEE,
+            CoinSelection::LargestFirst,
+        )
+        .unwrap();
+        payment.tx = sign(&payment, &key);
+        tx::broadcast(&backend, &mut db, &payment.tx).await.unwrap();
+        bitcoind.mine(1, &burn_address());
+        scan::scan(&backend, &mut db, &wallet.script_pubkey())
+            .await
+            .unwrap();
+    }
+
+    // A fresh database scanning the whole chain ends up in the same state.
+    let mut fresh = Db::open_in_memory().unwrap();
+    scan::scan(&backend, &mut fresh, &wallet.script_pubkey())
+        .await
+        .unwrap();
+    let chain_utxos = bitcoind.utxos(&wallet);
+    assert_eq!(db_utxos(&mut db), chain_utxos);
+    assert_eq!(db_utxos(&mut fresh), chain_utxos);
+}
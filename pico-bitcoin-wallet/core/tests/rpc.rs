@@ -0,0 +1,32 @@
+//! How the RPC client talks to bitcoind, against a listener standing in for it.
+
+use std::io::{BufRead, BufReader};
+use std::net::TcpListener;
+use std::path::Path;
+
+use pico_wallet_core::config;
+use pico_wallet_core::rpc::Client;
+
+#[test]
+fn wallet_name_is_percent_encoded() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let uri = format!("http://{}", listener.local_addr().unwrap());
+    let node = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut request_line = String::new();
+        BufReader::new(stream).read_line(&mut request_line).unwrap();
+        // Hanging up fails the health check, the request line is all we need.
+        request_line
+    });
+
+    let toml = format!(
+        "bitcoind_uri = \"{}\"\nbitcoind_wallet = \"my wallet/1?#%\"\nrpc_retries = 0\n",
+        uri
+    );
+    let conf = config::parse(Path::new("config.toml"), &toml).unwrap();
+    assert!(Client::connect_to(&conf, &uri).is_err());
+    assert_eq!(
+        node.join().unwrap().trim_end(),
+        "POST /wallet/my%20wallet%2F1%3F%23%25 HTTP/1.1"
+    );
+}
@@ -0,0 +1,385 @@
+//! Signing test vectors, known key and transaction give a known signature.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use bitcoin::consensus::encode::deserialize;
+use bitcoin::hashes::hex::FromHex;
+use bitcoin::psbt::{Psbt, PsbtSighashType};
+use bitcoin::secp256k1::{Message, XOnlyPublicKey, SECP256K1};
+use bitcoin::sighash::{EcdsaSighashType, Prevouts, SighashCache, TapSighashType};
+use bitcoin::{
+    Amount, Network, OutPoint, PrivateKey, PublicKey, ScriptBuf, Transaction, TxOut, Witness,
+};
+use pico_wallet_core::config::Chain;
+use pico_wallet_core::keys;
+use pico_wallet_core::sign::{self, InputSighash, KeySigner, PsbtOnly, SignError, Signer};
+use pico_wallet_core::tx::{self, SendError};
+
+fn hex(s: &str) -> Vec<u8> {
+    Vec::from_hex(s).expect("valid hex")
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The native P2WPKH example from BIP-143, input 1 spends a P2WPKH output.
+#[test]
+fn p2wpkh_bip143() {
+    let tx: Transaction = deserialize(&hex(
+        "0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f00000000\
+         00eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000\
+         ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093\
+         510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000",
+    ))
+    .unwrap();
+    let key = PrivateKey::from_slice(
+        &hex("619c335025c7f4012e556c2a58b2506e30b8511b53ade95ea316fd8c3286feb9"),
+        Network::Bitcoin,
+    )
+    .unwrap();
+    assert_eq!(
+        key.public_key(SECP256K1),
+        PublicKey::from_str("025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee6357")
+            .unwrap()
+    );
+
+    let mut cache = SighashCache::new(&tx);
+    let signature = sign::p2wpkh(
+        &mut cache,
+        1,
+        Amount::from_sat(600_000_000),
+        &key,
+        EcdsaSighashType::All,
+    )
+    .unwrap();
+
+    assert_eq!(
+        to_hex(&signature.to_vec()),
+        "304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c\
+         4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee01"
+    );
+}
+
+#[test]
+fn p2wpkh_rejects_uncompressed_key() {
+    let (tx, _, mut key) = taproot_fixture();
+    key.compressed = false;
+
+    let mut cache = SighashCache::new(&tx);
+    let result = sign::p2wpkh(&mut cache, 0, Amount::ONE_BTC, &key, EcdsaSighashType::All);
+    assert!(matches!(result, Err(SignError::UncompressedKey)));
+}
+
+#[test]
+fn uncompressed_keys_have_no_p2wpkh_address() {
+    let (_, _, mut key) = taproot_fixture();
+    assert!(keys::p2wpkh_address(&key, Chain::Regtest).is_some());
+    key.compressed = false;
+    assert_eq!(keys::p2wpkh_address(&key, Chain::Regtest), None);
+}
+
+/// Spends a made up 100000 sat output back to the same key with a 1000 sat fee.
+fn taproot_fixture() -> (Transaction, Vec<TxOut>, PrivateKey) {
+    let key = PrivateKey::from_slice(&[0x01; 32], Network::Regtest).unwrap();
+    let script_pubkey = keys::address(&key, Chain::Regtest).script_pubkey();
+    let tx = Transaction {
+        version: 2,
+        lock_time: bitcoin::absolute::LockTime::ZERO,
+        input: vec![bitcoin::TxIn {
+            previous_output: OutPoint::from_str(
+                "1111111111111111111111111111111111111111111111111111111111111111:0",
+            )
+            .unwrap(),
+            script_sig: Default::default(),
+            sequence: bitcoin::Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: 99_000,
+            script_pubkey: script_pubkey.clone(),
+        }],
+    };
+    let prevouts = vec![TxOut {
+        value: 100_000,
+        script_pubkey,
+    }];
+    (tx, prevouts, key)
+}
+
+/// BIP-340 signatures are deterministic given the auxiliary randomness, all zeros here.
+#[test]
+fn taproot_key_spend_vector() {
+    let (tx, prevouts, key) = taproot_fixture();
+    let mut cache = SighashCache::new(&tx);
+    let signature = sign::taproot_key_spend(
+        &mut cache,
+        0,
+        &prevouts,
+        &key,
+        None,
+        TapSighashType::Default,
+        &[0; 32],
+    )
+    .unwrap();
+
+    assert_eq!(
+        to_hex(&signature.to_vec()),
+        "d8ac31b6a24da6f2ab05143fe7bc4d917a266646725916ee1de8c160ef5c0391a85d168bb75c93ce5acc5488a2\
+         c07b5ac1d2a1ff197ec1aabb7712890f51bc2b"
+    );
+}
+
+#[test]
+fn taproot_key_spend_verifies() {
+    let (tx, prevouts, key) = taproot_fixture();
+    for hash_ty in [TapSighashType::Default, TapSighashType::AllPlusAnyoneCanPay] {
+        let mut cache = SighashCache::new(&tx);
+        let signature =
+            sign::taproot_key_spend(&mut cache, 0, &prevouts, &key, None, hash_ty, &[7; 32])
+                .unwrap();
+        assert_eq!(signature.hash_ty, hash_ty);
+
+        let sighash = cache
+            .taproot_key_spend_signature_hash(0, &Prevouts::All(&prevouts), hash_ty)
+            .unwrap();
+        let message = Message::from_slice(sighash.as_ref()).unwrap();
+        let output_key =
+            XOnlyPublicKey::from_slice(&prevouts[0].script_pubkey.as_bytes()[2..]).unwrap();
+        SECP256K1
+            .verify_schnorr(&signature.sig, &message, &output_key)
+            .unwrap();
+    }
+}
+
+#[test]
+fn taproot_key_spend_missing_input() {
+    let (tx, prevouts, key) = taproot_fixture();
+    let mut cache = SighashCache::new(&tx);
+    let result = sign::taproot_key_spend(
+        &mut cache,
+        1,
+        &prevouts,
+        &key,
+        None,
+        TapSighashType::Default,
+        &[0; 32],
+    );
+    assert!(matches!(result, Err(SignError::MissingInput(1))));
+}
+
+#[test]
+fn psbt_signers() {
+    let (tx, prevouts, key) = taproot_fixture();
+    let payment = tx::Payment {
+        tx,
+        prevouts,
+        fee: Amount::from_sat(1_000),
+        sighash_types: BTreeMap::new(),
+    };
+
+    let unsigned = tx::sign(&payment, &PsbtOnly).unwrap();
+    assert!(matches!(
+        tx::finalize(unsigned),
+        Err(SendError::Unsigned(0))
+    ));
+
+    let signed = tx::sign(&payment, &KeySigner::new(key)).unwrap();
+    let signed = tx::finalize(signed).unwrap();
+    assert_eq!(signed.input[0].witness.len(), 1);
+    assert_eq!(signed.txid(), payment.tx.txid());
+}
+
+/// A non-default sighash type is committed to and appended to the signature in the witness.
+#[test]
+fn per_input_sighash_types() {
+    let (mut tx, mut prevouts, key) = taproot_fixture();
+    let mut input = tx.input[0].clone();
+    input.previous_output.vout = 1;
+    tx.input.push(input);
+    prevouts.push(TxOut {
+        value: 100_000,
+        script_pubkey: keys::p2wpkh_address(&key, Chain::Regtest)
+            .unwrap()
+            .script_pubkey(),
+    });
+    let mut payment = tx::Payment {
+        tx,
+        prevouts,
+        fee: Amount::from_sat(101_000),
+        sighash_types: BTreeMap::new(),
+    };
+    payment
+        .set_sighash_type(0, TapSighashType::SinglePlusAnyoneCanPay.into())
+        .unwrap();
+    payment
+        .set_sighash_type(1, EcdsaSighashType::None.into())
+        .unwrap();
+
+    let signed = tx::finalize(tx::sign(&payment, &KeySigner::new(key)).unwrap()).unwrap();
+    let schnorr = &signed.input[0].witness[0];
+    assert_eq!(schnorr.len(), 65);
+    assert_eq!(schnorr[64], TapSighashType::SinglePlusAnyoneCanPay as u8);
+    let ecdsa = bitcoin::ecdsa::Signature::from_slice(&signed.input[1].witness[0]).unwrap();
+    assert_eq!(ecdsa.hash_ty, EcdsaSighashType::None);
+
+    // What was signed is what `sighashes` reports for the same PSBT.
+    let sighashes = sign::sighashes(&payment.psbt()).unwrap();
+    let signature = bitcoin::taproot::Signature::from_slice(schnorr).unwrap();
+    let output_key =
+        XOnlyPublicKey::from_slice(&payment.prevouts[0].script_pubkey.as_bytes()[2..]).unwrap();
+    match sighashes[..] {
+        [InputSighash::TaprootKeySpend { message, hash_ty }, InputSighash::P2wpkh {
+            message: ecdsa_message,
+            hash_ty: ecdsa_hash_ty,
+        }] => {
+            assert_eq!(hash_ty, TapSighashType::SinglePlusAnyoneCanPay);
+            SECP256K1
+                .verify_schnorr(&signature.sig, &message, &output_key)
+                .unwrap();
+            assert_eq!(ecdsa_hash_ty, EcdsaSighashType::None);
+            SECP256K1
+                .verify_ecdsa(&ecdsa_message, &ecdsa.sig, &key.public_key(SECP256K1).inner)
+                .unwrap();
+        }
+        ref other => panic!("unexpected sighashes {:?}", other),
+    }
+}
+
+#[test]
+fn sighash_types_must_suit_the_input() {
+    let (tx, prevouts, _) = taproot_fixture();
+    let mut payment = tx::Payment {
+        tx,
+        prevouts,
+        fee: Amount::from_sat(1_000),
+        sighash_types: BTreeMap::new(),
+    };
+    let invalid = PsbtSighashType::from_u32(0x04);
+    assert!(matches!(
+        payment.set_sighash_type(0, invalid),
+        Err(SendError::Sign(SignError::Sighash { index: 0, .. }))
+    ));
+    assert!(matches!(
+        payment.set_sighash_type(1, TapSighashType::All.into()),
+        Err(SendError::Sign(SignError::MissingInput(1)))
+    ));
+    assert!(payment.sighash_types.is_empty());
+
+    // A sighash type set directly on the PSBT, e.g. by whoever built it, is signed with too.
+    let (_, _, key) = taproot_fixture();
+    let mut psbt = payment.psbt();
+    psbt.inputs[0].sighash_type = Some(TapSighashType::All.into());
+    KeySigner::new(key)
+        .sign_taproot_key_spend(&mut psbt)
+        .unwrap();
+    let signed = tx::finalize(psbt).unwrap();
+    assert_eq!(signed.input[0].witness[0].len(), 65);
+}
+
+/// Inputs are signed in parallel, each signature must still end up on its own input.
+#[test]
+fn key_signer_signs_many_inputs_in_order() {
+    let (mut tx, mut prevouts, key) = taproot_fixture();
+    for vout in 1..32 {
+        let mut input = tx.input[0].clone();
+        input.previous_output.vout = vout;
+        tx.input.push(input);
+        prevouts.push(prevouts[0].clone());
+    }
+    let payment = tx::Payment {
+        tx,
+        prevouts,
+        fee: Amount::from_sat(1_000),
+        sighash_types: BTreeMap::new(),
+    };
+
+    let signed = tx::finalize(tx::sign(&payment, &KeySigner::new(key)).unwrap()).unwrap();
+    let output_key =
+        XOnlyPublicKey::from_slice(&payment.prevouts[0].script_pubkey.as_bytes()[2..]).unwrap();
+    let mut cache = SighashCache::new(&payment.tx);
+    for (index, input) in signed.input.iter().enumerate() {
+        let signature = bitcoin::taproot::Signature::from_slice(&input.witness[0]).unwrap();
+        let sighash = cache
+            .taproot_key_spend_signature_hash(
+                index,
+                &Prevouts::All(&payment.prevouts),
+                signature.hash_ty,
+            )
+            .unwrap();
+        let message = Message::from_slice(sighash.as_ref()).unwrap();
+        SECP256K1
+            .verify_schnorr(&signature.sig, &message, &output_key)
+            .unwrap();
+    }
+}
+
+/// The sighash of input 1 of the BIP-143 native P2WPKH example, input 0 is P2PK.
+#[test]
+fn sighashes_p2wpkh_bip143() {
+    let tx: Transaction = deserialize(&hex(
+        "0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f00000000\
+         00eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000\
+         ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093\
+         510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000",
+    ))
+    .unwrap();
+    let public_key =
+        PublicKey::from_str("025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee6357")
+            .unwrap();
+    let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+    psbt.inputs[1].witness_utxo = Some(TxOut {
+        value: 600_000_000,
+        script_pubkey: ScriptBuf::new_v0_p2wpkh(&public_key.wpubkey_hash().unwrap()),
+    });
+
+    let sighashes = sign::sighashes(&psbt).unwrap();
+    assert_eq!(sighashes[0], InputSighash::Unsupported);
+    match sighashes[1] {
+        InputSighash::P2wpkh { message, hash_ty } => {
+            assert_eq!(
+                message.to_string(),
+                "c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670"
+            );
+            assert_eq!(hash_ty, EcdsaSighashType::All);
+        }
+        other => panic!("unexpected sighash {:?}", other),
+    }
+}
+
+/// What the wallet signs is what `sighashes` says it does.
+#[test]
+fn sighashes_match_taproot_signatures() {
+    let (tx, prevouts, key) = taproot_fixture();
+    let payment = tx::Payment {
+        tx,
+        prevouts,
+        fee: Amount::from_sat(1_000),
+        sighash_types: BTreeMap::new(),
+    };
+    let unsigned = tx::sign(&payment, &PsbtOnly).unwrap();
+    let message = match sign::sighashes(&unsigned).unwrap()[..] {
+        [InputSighash::TaprootKeySpend { message, hash_ty }] => {
+            assert_eq!(hash_ty, TapSighashType::Default);
+            message
+        }
+        ref other => panic!("unexpected sighashes {:?}", other),
+    };
+
+    let signed = tx::sign(&payment, &KeySigner::new(key)).unwrap();
+    let signature = signed.inputs[0].tap_key_sig.unwrap();
+    let output_key =
+        XOnlyPublicKey::from_slice(&payment.prevouts[0].script_pubkey.as_bytes()[2..]).unwrap();
+    SECP256K1
+        .verify_schnorr(&signature.sig, &message, &output_key)
+        .unwrap();
+
+    // Taproot sighashes commit to all the outputs spent.
+    let mut incomplete = unsigned;
+    incomplete.inputs[0].witness_utxo = None;
+    assert_eq!(
+        sign::sighashes(&incomplete).unwrap(),
+        vec![InputSighash::Unsupported]
+    );
+}
@@ -0,0 +1,190 @@
+//! The `audit` command, checks the database against bitcoind's UTXO set.
+
+use anyhow::{bail, Context, Result};
+use bitcoin::{Address, Amount, OutPoint};
+use bitcoincore_rpc::json::ScanTxOutRequest;
+use bitcoincore_rpc::RpcApi;
+use pico_wallet_core::audit::{self, Audit};
+use pico_wallet_core::backend::RpcBackend;
+use pico_wallet_core::config::{self, Config};
+use pico_wallet_core::db::{self, Db};
+use pico_wallet_core::keys;
+
+use crate::{bitcoind_backend, ui};
+
+/// Compares the wallet's unspent outputs in the database with those bitcoind has, fails if they
+/// differ.
+pub fn run(mut args: impl Iterator<Item = String>) -> Result<()> {
+    if let Some(arg) = args.next() {
+        bail!("unexpected argument: {}", arg);
+    }
+    let conf = config::load()?;
+    let backend = bitcoind_backend(&conf)?;
+    let mut db = Db::open(conf.chain)?;
+
+    let addresses = wallet_addresses(&conf)?;
+    println!(
+        "Scanning bitcoind's UTXO set for {}, this takes a while",
+        addresses
+            .iter()
+            .map(Address::to_string)
+            .collect::<Vec<_>>()
+            .join(" and ")
+    );
+    let (chain, height) = chain_unspent(&backend, &addresses)?;
+    let ours = audit::confirmed_unspent(&mut db)?;
+    let scanned = db.get_last_height()?;
+    let audit = audit::compare(&ours, &chain);
+
+    println!(
+        "{} unspent outputs on chain at height {}, {} in the database scanned to height {}",
+        chain.len(),
+        height,
+        ours.len(),
+        scanned
+    );
+    if audit.is_clean() {
+        println!("{}", ui::green("The database agrees with the chain"));
+        return Ok(());
+    }
+    print(&audit, &mut ui::Terminal)?;
+    println!();
+    println!("See `help audit` for what these mean");
+    if scanned != height {
+        println!();
+        println!(
+            "The database is not scanned to the node's tip, run `scan` and audit again before \
+             worrying"
+        );
+    }
+    bail!(
+        "the database and the chain disagree about {} outputs",
+        audit.discrepancies()
+    )
+}
+
+/// Checks the balance `balance --verify` just printed against bitcoind's UTXO set, warning loudly
+/// and explaining how to rescan if the database is wrong.
+pub fn verify(conf: &Config, db: &mut Db, console: &mut dyn ui::Console) -> Result<()> {
+    let backend = bitcoind_backend(conf)?;
+    let addresses = wallet_addresses(conf)?;
+    console.print("")?;
+    console.print("Verifying against bitcoind's UTXO set, this takes a while")?;
+    let (chain, height) = chain_unspent(&backend, &addresses)?;
+    let ours = audit::confirmed_unspent(db)?;
+    let scanned = db.get_last_height()?;
+    let audit = audit::compare(&ours, &chain);
+
+    if audit.is_clean() {
+        console.print(&ui::green(format!(
+            "Verified: bitcoind agrees at height {}",
+            height
+        )))?;
+        return Ok(());
+    }
+
+    let total = |outputs: &[(OutPoint, Amount)]| outputs.iter().map(|(_, amount)| *amount).sum();
+    let (ours, chain): (Amount, Amount) = (total(&ours), total(&chain));
+    console.print(&ui::red(ui::bold(format!(
+        "WARNING: this balance is wrong, bitcoind has {} confirmed and unspent, the database {}",
+        ui::amount(chain),
+        ui::amount(ours)
+    ))))?;
+    print(&audit, console)?;
+    console.print("")?;
+    if scanned < height {
+        console.print(&format!(
+            "The database is scanned to height {} but the node is at {}, run `scan` to catch up.",
+            scanned, height
+        ))?;
+    } else {
+        console.print(&format!(
+            "The database is scanned to the node's tip ({}) and still disagrees: a reorg it did \
+             not notice or a spend from another wallet with the same key. Rescan from scratch by \
+             moving the database aside and running `scan`:",
+            height
+        ))?;
+        console.print("")?;
+        console.print(&format!(
+            "  $ mv {0} {0}.bak && pico-bitcoin-wallet scan",
+            db::database_file(conf.chain)?.display()
+        ))?;
+    }
+    bail!(
+        "the database and the chain disagree about {} outputs",
+        audit.discrepancies()
+    )
+}
+
+/// Returns the addresses the wallet key can spend from, worked out from the key rather than the
+/// database: the key path only address, with a recovery path configured the wallet address and the
+/// P2WPKH address.
+fn wallet_addresses(conf: &Config) -> Result<Vec<Address>> {
+    let mut key = keys::load_private_key(conf.chain)?;
+    let mut addresses = vec![keys::address(&key, conf.chain)];
+    addresses.extend(keys::p2wpkh_address(&key, conf.chain));
+    if let Some(recovery) = &conf.recovery {
+        let spend_info = keys::spend_info(&key, Some(recovery));
+        addresses.push(Address::p2tr_tweaked(
+            spend_info.output_key(),
+            conf.chain.network(),
+        ));
+    }
+    keys::erase(&mut key);
+    Ok(addresses)
+}
+
+/// Returns the unspent outputs paying to `addresses` in bitcoind's UTXO set and the height it is
+/// at, with `scantxoutset`.
+fn chain_unspent(
+    backend: &RpcBackend,
+    addresses: &[Address],
+) -> Result<(Vec<(OutPoint, Amount)>, u64)> {
+    let descriptors = addresses
+        .iter()
+        .map(|address| ScanTxOutRequest::Single(format!("addr({})", address)))
+        .collect::<Vec<_>>();
+    let result = backend
+        .client()
+        .scan_tx_out_set_blocking(&descriptors)
+        .context("failed to scan bitcoind's UTXO set")?;
+    if result.success == Some(false) {
+        bail!("bitcoind did not finish scanning its UTXO set");
+    }
+    let unspent = result
+        .unspents
+        .iter()
+        .map(|utxo| (OutPoint::new(utxo.txid, utxo.vout), utxo.amount))
+        .collect();
+    Ok((unspent, result.height.unwrap_or_default()))
+}
+
+/// Prints each output the database and the chain disagree about to `console`.
+fn print(audit: &Audit, console: &mut dyn ui::Console) -> Result<()> {
+    for (outpoint, amount) in &audit.missing {
+        console.print(&format!(
+            "{}  {}  {}",
+            ui::red("missing "),
+            outpoint,
+            ui::amount(*amount)
+        ))?;
+    }
+    for (outpoint, amount) in &audit.phantom {
+        console.print(&format!(
+            "{}  {}  {}",
+            ui::red("phantom "),
+            outpoint,
+            ui::amount(*amount)
+        ))?;
+    }
+    for (outpoint, ours, chain) in &audit.mismatched {
+        console.print(&format!(
+            "{}  {}  {} in the database, {} on chain",
+            ui::red("mismatch"),
+            outpoint,
+            ui::amount(*ours),
+            ui::amount(*chain)
+        ))?;
+    }
+    Ok(())
+}
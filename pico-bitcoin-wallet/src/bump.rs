@@ -0,0 +1,131 @@
+//! The `bump-all` command.
+//!
+//! Replaces every unconfirmed transaction of ours that signals replaceability with one paying a
+//! higher fee (BIP-125), for when the fee market moved while they were waiting to confirm.
+
+use anyhow::{anyhow, bail, Result};
+use bitcoin::{Address, Amount, Denomination, FeeRate, Txid};
+use pico_wallet_core::backend::{Backend, RpcBackend};
+use pico_wallet_core::config;
+use pico_wallet_core::db::Db;
+use pico_wallet_core::oplog::Op;
+use pico_wallet_core::tx::{self, Payment};
+
+use crate::{bitcoind_backend, block_on, record, sign_payments, take_option, ui, wallet_addresses};
+
+/// Bumps the fee of all our unconfirmed transactions to `--fee-rate <sat/vB>`.
+pub fn run(args: impl Iterator<Item = String>) -> Result<()> {
+    let mut args = args.collect::<Vec<_>>();
+    let fee_rate = take_option(&mut args, "--fee-rate")?
+        .ok_or_else(|| anyhow!("missing --fee-rate <sat/vB>"))?;
+    if let Some(arg) = args.first() {
+        bail!("unexpected argument: {}", arg);
+    }
+    let fee_rate = fee_rate
+        .parse::<u64>()
+        .ok()
+        .and_then(FeeRate::from_sat_per_vb)
+        .ok_or_else(|| anyhow!("invalid fee rate: {}", fee_rate))?;
+
+    let conf = config::load()?;
+    let wallet = wallet_addresses(&conf)?;
+    let backend = bitcoind_backend(&conf)?;
+    let mut db = Db::open(conf.chain)?;
+
+    // Outgoing means it spends wallet outputs, incoming payments are not ours to replace.
+    let pending = db
+        .history()?
+        .into_iter()
+        .filter(|record| {
+            record.height.is_none() && !record.conflicted && record.sent > Amount::ZERO
+        })
+        .collect::<Vec<_>>();
+    if pending.is_empty() {
+        println!("No unconfirmed outgoing transactions, run `scan` if you expected some");
+        return Ok(());
+    }
+
+    let mut replacements = Vec::new();
+    for record in &pending {
+        match replacement(
+            &backend,
+            &mut db,
+            &wallet,
+            record.txid,
+            fee_rate,
+            conf.dust_relay_fee,
+        ) {
+            Ok((old_fee, bumped)) => {
+                println!(
+                    "{}: fee {} -> {}",
+                    record.txid,
+                    btc(old_fee),
+                    btc(bumped.fee)
+                );
+                replacements.push((record.txid, bumped));
+            }
+            Err(error) => println!("{}: skipped, {:#}", record.txid, error),
+        }
+    }
+    if replacements.is_empty() {
+        return Ok(());
+    }
+    ui::confirm(&format!(
+        "broadcast {} replacement transaction(s)",
+        replacements.len()
+    ))?;
+
+    let signed = sign_payments(&conf, replacements.iter().map(|(_, bumped)| bumped))?
+        .into_iter()
+        .map(tx::finalize)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for ((replaced, bumped), signed) in replacements.iter().zip(signed) {
+        match block_on(tx::broadcast(&backend, &mut db, &signed)) {
+            Ok(txid) => {
+                record(
+                    conf.chain,
+                    Op::Bump {
+                        replaced: *replaced,
+                        txid,
+                        fee_sat: bumped.fee.to_sat(),
+                    },
+                );
+                println!("{}: replaced by {}", replaced, txid);
+            }
+            Err(error) => println!("{}: failed, {:#}", replaced, anyhow::Error::from(error)),
+        }
+    }
+    Ok(())
+}
+
+/// Builds the replacement of our transaction `txid`, returns its current fee and the replacement.
+fn replacement(
+    backend: &RpcBackend,
+    db: &mut Db,
+    wallet: &[Address],
+    txid: Txid,
+    fee_rate: FeeRate,
+    dust_relay_fee: FeeRate,
+) -> Result<(Amount, Payment)> {
+    let tx = match block_on(backend.mempool_tx(txid))? {
+        Some(tx) => tx,
+        None => bail!("no longer in the mempool"),
+    };
+    if !tx.is_explicitly_rbf() {
+        bail!("does not signal replaceability");
+    }
+    let payment = match tx::rebuild_payment(&tx, db, &wallet[0])? {
+        Some(payment) => payment,
+        None => bail!("spends the outputs of an unconfirmed transaction"),
+    };
+    let bumped = tx::bump_fee(&payment, wallet, fee_rate, dust_relay_fee)?;
+    Ok((payment.fee, bumped))
+}
+
+fn btc(amount: Amount) -> String {
+    amount
+        .display_in(Denomination::Bitcoin)
+        .show_denomination()
+        .to_string()
+}
@@ -0,0 +1,166 @@
+//! The `config check` command.
+//!
+//! Runs through everything that usually goes wrong when setting up the wallet (a typo in the config
+//! file, bitcoind not running, wrong credentials, wallet and node on different chains) and prints a
+//! hint on how to fix each failure.
+
+use std::fmt;
+use std::io::ErrorKind;
+
+use anyhow::{bail, Result};
+use bitcoincore_rpc::RpcApi;
+use jsonrpc::simple_http;
+
+use pico_wallet_core::config::{self, Auth, Config};
+use pico_wallet_core::rpc::{self, RpcError};
+
+use crate::ui;
+
+/// Checks the configuration file and every configured `bitcoind` endpoint.
+pub fn run() -> Result<()> {
+    let conf_file = config::config_file()?;
+    let mut failures = 0;
+
+    if !conf_file.exists() {
+        println!(
+            "No configuration file at {}, using the defaults",
+            conf_file.display()
+        );
+    }
+    let conf = match config::load() {
+        Ok(conf) => {
+            pass("configuration is valid");
+            conf
+        }
+        Err(error) => {
+            fail(
+                "configuration is invalid",
+                error,
+                format!(
+                    "fix {}, see the README for the available options",
+                    conf_file.display()
+                ),
+            );
+            bail!("configuration check failed");
+        }
+    };
+
+    for uri in &conf.bitcoind_uris {
+        let client = match rpc::Client::connect_to(&conf, uri) {
+            Ok(client) => {
+                pass(format!("connected to {}", uri));
+                client
+            }
+            Err(error) => {
+                let hint = connection_hint(&error, &conf, uri);
+                fail(format!("failed to connect to {}", uri), error, hint);
+                failures += 1;
+                continue;
+            }
+        };
+
+        match client.check_network(conf.chain, conf.signet_challenge.as_deref()) {
+            Ok(()) => pass(format!("{} is running on {}", uri, conf.chain)),
+            Err(error) => {
+                let hint = match error {
+                    RpcError::WrongChain { .. } => format!(
+                        "set `network` in the config file to match the node or start bitcoind with -chain={}",
+                        conf.chain.core_name()
+                    ),
+                    RpcError::WrongSignetChallenge { .. } => {
+                        "set `signet_challenge` in the config file to match the node or start bitcoind with -signetchallenge".to_owned()
+                    }
+                    _ => "check bitcoind is not still starting up (see its debug.log)".to_owned(),
+                };
+                fail(
+                    format!("failed to check the network of {}", uri),
+                    error,
+                    hint,
+                );
+                failures += 1;
+            }
+        }
+
+        if let Some(ref wallet) = conf.bitcoind_wallet {
+            match client.get_wallet_info() {
+                Ok(_) => pass(format!("wallet {} is loaded", wallet)),
+                Err(error) => {
+                    fail(
+                        format!("wallet {} is not available", wallet),
+                        error,
+                        format!(
+                            "load it with `bitcoin-cli loadwallet {}` or remove `bitcoind_wallet` from the config file",
+                            wallet
+                        ),
+                    );
+                    failures += 1;
+                }
+            }
+        }
+    }
+
+    if failures > 0 {
+        bail!("{} check(s) failed", failures);
+    }
+    println!();
+    println!("{}", ui::green("Everything looks good"));
+    Ok(())
+}
+
+fn pass(what: impl fmt::Display) {
+    println!("{}  {}", ui::green("ok  "), what);
+}
+
+fn fail(what: impl fmt::Display, error: impl Into<anyhow::Error>, hint: impl fmt::Display) {
+    println!("{}  {}", ui::red("FAIL"), what);
+    println!("      error: {:#}", error.into());
+    println!("      hint: {}", hint);
+}
+
+/// Works out what the user should do about a failure to connect to `uri`.
+fn connection_hint(error: &RpcError, conf: &Config, uri: &str) -> String {
+    let http_error = match error {
+        RpcError::Proxy(_) => {
+            return "check `proxy`, it should look like socks5://127.0.0.1:9050".to_owned()
+        }
+        RpcError::InvalidUri { .. } => {
+            return "check `bitcoind_uri`, it should look like http://127.0.0.1:18443".to_owned()
+        }
+        RpcError::Credentials(_) => match conf.bitcoind_auth {
+            Auth::CookieFile(ref path) => return format!(
+                "failed to read the cookie file {}, bitcoind creates it on startup so check it is running and that `bitcoind_cookie_path` is correct",
+                path.display()
+            ),
+            _ => None,
+        },
+        RpcError::HealthCheck(bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Transport(error))) => {
+            error.downcast_ref::<simple_http::Error>()
+        }
+        _ => None,
+    };
+    if let Some(ref proxy) = conf.proxy {
+        return format!(
+            "check the proxy at {} (e.g. Tor) is running and that {} is correct",
+            proxy, uri
+        );
+    }
+
+    match http_error {
+        Some(simple_http::Error::HttpErrorCode(401)) => match conf.bitcoind_auth {
+            Auth::CookieFile(_) => "bitcoind rejected the cookie, it changes every time bitcoind restarts so check `bitcoind_cookie_path` points at the cookie of this node".to_owned(),
+            Auth::UserPass(..) => "bitcoind rejected the credentials, check `bitcoind_username` and `bitcoind_password` match `rpcauth` or `rpcuser`/`rpcpassword` in bitcoin.conf".to_owned(),
+            Auth::None => "bitcoind requires authentication, set `bitcoind_cookie_path` or `bitcoind_username` and `bitcoind_password`".to_owned(),
+        },
+        Some(simple_http::Error::SocketError(error)) if error.kind() == ErrorKind::ConnectionRefused => format!(
+            "nothing is listening at {}, check bitcoind is running with -server and that the port is right (the default for {} is {})",
+            uri,
+            conf.chain,
+            conf.chain.default_rpc_port()
+        ),
+        Some(simple_http::Error::SocketError(error)) if matches!(error.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock) => format!(
+            "no response within {}s, check the host is reachable (firewall, `rpcallowip` and `rpcbind` in bitcoin.conf) or raise `rpc_timeout_secs`",
+            conf.rpc_timeout.as_secs()
+        ),
+        _ => format!("check `bitcoind_uri` ({}) and that bitcoind is running", uri),
+    }
+}
@@ -0,0 +1,260 @@
+//! The control socket of the daemon, so commands can run while it does.
+//!
+//! While `daemon` runs, `status`, `balance` and `send` are forwarded to it over a Unix socket in
+//! the data directory and run there with its open database and bitcoind connections, rather than
+//! both processes writing to the database. The protocol is line based: the client sends its
+//! arguments one per line and then an empty line, the daemon answers with lines starting with `>`
+//! (output) or `?` (a confirmation prompt, answered with `y` or `n`) and ends with `.` (done) or
+//! `!` and an error message.
+//!
+//! `send` spends from the wallet, so only the user the daemon runs as may connect: the socket is
+//! only accessible to them and clients of any other user are turned away.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use pico_wallet_core::config::Chain;
+use pico_wallet_core::db;
+
+use crate::ui::{self, Console};
+
+/// How long the daemon waits on a client, e.g. to answer a prompt, before giving up on it.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Runs `command` with `args` in the daemon if one is running for `chain`.
+///
+/// Returns false if there is no daemon, the command should then run here.
+pub fn forward(chain: Chain, command: &str, args: &[String]) -> Result<bool> {
+    let path = db::control_socket_file(chain)?;
+    let stream = match UnixStream::connect(&path) {
+        Ok(stream) => stream,
+        // No socket, or one left behind by a daemon that was killed.
+        Err(error)
+            if matches!(
+                error.kind(),
+                io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused
+            ) =>
+        {
+            return Ok(false)
+        }
+        Err(error) => {
+            return Err(error).with_context(|| format!("failed to connect to {}", path.display()))
+        }
+    };
+    let mut writer = stream.try_clone().context("failed to clone socket")?;
+    let mut request = String::new();
+    for arg in Some(command)
+        .into_iter()
+        .chain(args.iter().map(String::as_str))
+    {
+        if arg.is_empty() || arg.contains('\n') {
+            bail!("can't pass {:?} to the daemon", arg);
+        }
+        request += arg;
+        request += "\n";
+    }
+    request += "\n";
+    writer
+        .write_all(request.as_bytes())
+        .context("failed to send command to the daemon")?;
+
+    for line in BufReader::new(stream).lines() {
+        let line = line.context("failed to read from the daemon")?;
+        let mut chars = line.chars();
+        let kind = chars.next();
+        let text = chars.as_str().strip_prefix(' ').unwrap_or(chars.as_str());
+        match kind {
+            Some('>') => println!("{}", text),
+            Some('?') => {
+                let answer = ui::confirm(text);
+                writeln!(writer, "{}", if answer.is_ok() { "y" } else { "n" })
+                    .context("failed to answer the daemon")?;
+                answer?;
+            }
+            Some('.') => return Ok(true),
+            Some('!') => bail!("{}", text),
+            _ => bail!("unexpected reply from the daemon: {}", line),
+        }
+    }
+    Err(anyhow!("the daemon hung up"))
+}
+
+/// The listening end, the socket file is removed when dropped.
+pub struct Listener {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl Listener {
+    /// Starts listening for clients of the daemon of `chain`.
+    ///
+    /// Fails if another daemon is already listening.
+    pub fn bind(chain: Chain) -> Result<Self> {
+        let path = db::control_socket_file(chain)?;
+        if UnixStream::connect(&path).is_ok() {
+            bail!("a daemon is already running, see {}", path.display());
+        }
+        // Left behind by a daemon that did not stop cleanly.
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)
+            .with_context(|| format!("failed to listen on {}", path.display()))?;
+        let listener = Listener { listener, path };
+        // The data directory is usually readable by everyone. Clients of other users are also
+        // rejected in `handle`, in case one connects before this.
+        std::fs::set_permissions(&listener.path, std::fs::Permissions::from_mode(0o600))
+            .with_context(|| format!("failed to restrict {}", listener.path.display()))?;
+        Ok(listener)
+    }
+
+    /// Handles clients one at a time on a thread of its own, until the process exits.
+    ///
+    /// `run` is called with each command and its arguments, and a console talking to the client.
+    pub fn serve(
+        &self,
+        mut run: impl FnMut(&str, Vec<String>, &mut dyn Console) -> Result<()> + Send + 'static,
+    ) -> Result<()> {
+        let listener = self
+            .listener
+            .try_clone()
+            .context("failed to clone control socket")?;
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let result = stream
+                    .context("failed to accept client")
+                    .and_then(|stream| handle(stream, &mut run));
+                if let Err(error) = result {
+                    eprintln!("control socket: {:#}", error);
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Reads a command from `stream`, runs it and reports how it went.
+fn handle(
+    stream: UnixStream,
+    run: &mut impl FnMut(&str, Vec<String>, &mut dyn Console) -> Result<()>,
+) -> Result<()> {
+    let uid = peer_uid(&stream).context("failed to get the client's user")?;
+    // SAFETY: geteuid can't fail and has no preconditions.
+    let euid = unsafe { libc::geteuid() };
+    if uid != euid {
+        bail!(
+            "rejected a client of user {}, the daemon runs as {}",
+            uid,
+            euid
+        );
+    }
+
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut client = Client {
+        writer: stream.try_clone()?,
+        reader: BufReader::new(stream),
+    };
+    let mut args = Vec::new();
+    loop {
+        let line = client.read_line()?;
+        if line.is_empty() {
+            break;
+        }
+        args.push(line);
+    }
+    if args.is_empty() {
+        bail!("empty command");
+    }
+    let command = args.remove(0);
+
+    match run(&command, args, &mut client) {
+        Ok(()) => client.send('.', ""),
+        Err(error) => client.send('!', &format!("{:#}", error)),
+    }
+}
+
+/// Returns the user id of the process on the other end of `stream`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_uid(stream: &UnixStream) -> io::Result<libc::uid_t> {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    // SAFETY: `cred` and `len` are valid for writes and `len` is the size of `cred`.
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(cred.uid)
+}
+
+/// Returns the user id of the process on the other end of `stream`.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn peer_uid(stream: &UnixStream) -> io::Result<libc::uid_t> {
+    let mut uid = 0;
+    let mut gid = 0;
+    // SAFETY: `uid` and `gid` are valid for writes.
+    if unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(uid)
+}
+
+/// A connected client, as the console of the command it sent.
+struct Client {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl Client {
+    fn read_line(&mut self) -> Result<String> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            bail!("client hung up");
+        }
+        Ok(line.trim_end_matches('\n').to_owned())
+    }
+
+    /// Sends `text` as lines of `kind`.
+    fn send(&mut self, kind: char, text: &str) -> Result<()> {
+        let mut reply = String::new();
+        for line in text.split('\n') {
+            reply += &format!("{} {}\n", kind, line);
+        }
+        self.writer.write_all(reply.as_bytes())?;
+        Ok(())
+    }
+}
+
+impl Console for Client {
+    fn print(&mut self, text: &str) -> Result<()> {
+        self.send('>', text)
+    }
+
+    fn confirm(&mut self, action: &str) -> Result<()> {
+        self.send('?', action)?;
+        match self.read_line()?.as_str() {
+            "y" => Ok(()),
+            _ => bail!("aborted"),
+        }
+    }
+}
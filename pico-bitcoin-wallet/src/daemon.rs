@@ -0,0 +1,285 @@
+//! The `daemon` command, keeps the wallet up to date without anybody running commands.
+//!
+//! Every interval it does what you would otherwise run by hand (or from cron): `scan`,
+//! `schedule run` and `rebroadcast`. Blocks are polled for, bitcoind is not asked to push them.
+//! On Unix `status`, `balance` and `send` run in the daemon while it is up, see [`crate::control`].
+//! With `notify = true` in the config incoming payments are shown as desktop notifications.
+//! Changes to the config file are picked up within a few seconds, without dropping the connection
+//! to bitcoind.
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{bail, Result};
+use bitcoin::{Amount, Txid};
+use pico_wallet_core::backend::RpcBackend;
+use pico_wallet_core::config::{self, Config};
+use pico_wallet_core::db::Db;
+use pico_wallet_core::scan::{Summary, WalletTx};
+use pico_wallet_core::tx;
+
+use crate::schedule::{self, format_interval, parse_interval};
+use crate::{bitcoind_backend, block_on, notify, scan_with_stop, shutdown_signal, take_option, ui};
+
+/// How often to poll if `--interval` is not given.
+const DEFAULT_INTERVAL_SECS: u64 = 30;
+
+/// How often to check whether the config file changed.
+const CONFIG_POLL: Duration = Duration::from_secs(2);
+
+/// What the daemon is up to, for `status`.
+pub struct Status {
+    pub started: SystemTime,
+    pub interval_secs: u64,
+    /// When the last catch up finished and its error, if it failed.
+    pub last_catch_up: Option<(SystemTime, Option<String>)>,
+}
+
+/// Everything the daemon keeps open, shared by the catch up loop and the control socket.
+struct Daemon {
+    /// Replaced when the file changes, each catch up and command uses the one current when it starts.
+    conf: Mutex<Arc<Config>>,
+    backend: RpcBackend,
+    /// Only one of them uses the database at a time.
+    db: Mutex<Db>,
+    status: Mutex<Status>,
+}
+
+impl Daemon {
+    fn conf(&self) -> Arc<Config> {
+        Arc::clone(&lock(&self.conf))
+    }
+}
+
+/// Runs until Ctrl-C (or SIGTERM), catching up every `--interval` and straight after the config
+/// file changes.
+pub fn run(args: impl Iterator<Item = String>) -> Result<()> {
+    let mut args = args.collect::<Vec<_>>();
+    let interval_secs = match take_option(&mut args, "--interval")? {
+        Some(interval) => parse_interval(&interval)?,
+        None => DEFAULT_INTERVAL_SECS,
+    };
+    if let Some(arg) = args.first() {
+        bail!("unexpected argument: {}", arg);
+    }
+
+    let config_file = config::config_file()?;
+    let mut config_modified = modified(&config_file);
+    let conf = config::load()?;
+    let daemon = Arc::new(Daemon {
+        backend: bitcoind_backend(&conf)?,
+        db: Mutex::new(Db::open(conf.chain)?),
+        status: Mutex::new(Status {
+            started: SystemTime::now(),
+            interval_secs,
+            last_catch_up: None,
+        }),
+        conf: Mutex::new(Arc::new(conf)),
+    });
+    // Removes the socket when the daemon stops.
+    #[cfg(unix)]
+    let _listener = listen(&daemon)?;
+
+    // One handler for the whole run, a signal during any step stops the daemon once it is done.
+    let stop = Arc::new(AtomicBool::new(false));
+    let (wake, woken) = mpsc::channel();
+    {
+        let stop = Arc::clone(&stop);
+        std::thread::spawn(move || block_on(stop_on_signal(stop, wake)));
+    }
+
+    println!(
+        "Catching up every {}, press Ctrl-C to stop",
+        format_interval(interval_secs)
+    );
+    // Incoming payments notified while in the mempool, so they are not notified again.
+    let mut notified = HashSet::new();
+    'run: loop {
+        let conf = daemon.conf();
+        let result = catch_up(&conf, &daemon.backend, &mut lock(&daemon.db), &stop);
+        match &result {
+            Ok(summary) if conf.notify => notify_incoming(summary, &mut notified),
+            Ok(_) => {}
+            // bitcoind being unreachable for a while is no reason to stop.
+            Err(error) => eprintln!("error: {:#}", error),
+        }
+        lock(&daemon.status).last_catch_up = Some((
+            SystemTime::now(),
+            result.err().map(|error| format!("{:#}", error)),
+        ));
+
+        if stop.load(Ordering::Relaxed) {
+            break;
+        }
+        let next = Instant::now() + Duration::from_secs(interval_secs);
+        loop {
+            let wait = next
+                .saturating_duration_since(Instant::now())
+                .min(CONFIG_POLL);
+            match woken.recv_timeout(wait) {
+                Ok(()) => break 'run,
+                Err(RecvTimeoutError::Timeout) => {}
+                // Signals can't be handled, nothing will wake us.
+                Err(RecvTimeoutError::Disconnected) => std::thread::sleep(wait),
+            }
+            let modified = modified(&config_file);
+            if modified != config_modified {
+                config_modified = modified;
+                reload(&daemon);
+                break;
+            }
+            if Instant::now() >= next {
+                break;
+            }
+        }
+    }
+    println!("Stopped");
+    Ok(())
+}
+
+/// Starts serving the control socket, it is removed when the returned listener is dropped.
+#[cfg(unix)]
+fn listen(daemon: &Arc<Daemon>) -> Result<crate::control::Listener> {
+    let listener = crate::control::Listener::bind(daemon.conf().chain)?;
+    // Output goes to clients, which may not be terminals.
+    ui::init(true, false);
+
+    let daemon = Arc::clone(daemon);
+    listener.serve(move |command, args, console| {
+        let conf = &daemon.conf();
+        match command {
+            "status" => {
+                let status = lock(&daemon.status);
+                crate::status_with(&mut lock(&daemon.db), console, Some(&status))
+            }
+            "balance" => crate::balance_with(conf, &mut lock(&daemon.db), args, console),
+            "send" => crate::send_with(conf, &daemon.backend, &mut lock(&daemon.db), args, console),
+            _ => bail!("`{}` can't run in the daemon", command),
+        }
+    })?;
+    Ok(listener)
+}
+
+/// Sets `stop` and wakes the daemon on the first Ctrl-C (or SIGTERM), exits immediately on the
+/// second.
+async fn stop_on_signal(stop: Arc<AtomicBool>, wake: Sender<()>) {
+    if shutdown_signal().await.is_err() {
+        // No signal handling, the default of dying on the spot applies.
+        return;
+    }
+    eprintln!("Stopping once the current catch up is done, press Ctrl-C again to abort");
+    stop.store(true, Ordering::Relaxed);
+    let _ = wake.send(());
+
+    let _ = shutdown_signal().await;
+    std::process::exit(130);
+}
+
+/// When the config file was last modified, `None` if it doesn't exist (the defaults apply).
+fn modified(config_file: &Path) -> Option<SystemTime> {
+    std::fs::metadata(config_file)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Loads the config again, the old one stays if the file can't be loaded or changes settings the
+/// daemon can only pick up by restarting.
+fn reload(daemon: &Daemon) {
+    let result = config::load()
+        .map_err(anyhow::Error::from)
+        .and_then(|conf| match restart_needed(&daemon.conf(), &conf) {
+            Some(setting) => bail!("{} changed, restart the daemon to apply it", setting),
+            None => Ok(conf),
+        });
+    match result {
+        Ok(conf) => {
+            *lock(&daemon.conf) = Arc::new(conf);
+            println!("Reloaded the config");
+        }
+        Err(error) => eprintln!("error: failed to reload the config, {:#}", error),
+    }
+}
+
+/// Returns the first setting that differs between `old` and `new` which the daemon only reads on
+/// start up: the chain picks the database and control socket, the rest how bitcoind is reached.
+fn restart_needed(old: &Config, new: &Config) -> Option<&'static str> {
+    let changed = [
+        ("`network`", old.chain != new.chain),
+        ("`bitcoind_uri`", old.bitcoind_uris != new.bitcoind_uris),
+        (
+            "the bitcoind credentials",
+            old.bitcoind_auth != new.bitcoind_auth,
+        ),
+        (
+            "`bitcoind_wallet`",
+            old.bitcoind_wallet != new.bitcoind_wallet,
+        ),
+        ("`proxy`", old.proxy != new.proxy),
+        ("`rpc_timeout_secs`", old.rpc_timeout != new.rpc_timeout),
+        ("`rpc_retries`", old.rpc_retries != new.rpc_retries),
+        ("`rpc_rate_limit`", old.rpc_rate_limit != new.rpc_rate_limit),
+    ];
+    changed
+        .iter()
+        .find(|(_, changed)| *changed)
+        .map(|(setting, _)| *setting)
+}
+
+/// Scans new blocks, makes the scheduled payments that are due and rebroadcasts what is stuck.
+///
+/// The scan stops after the current block once `stop` is set, the rest is skipped. Returns what
+/// the scan found.
+fn catch_up(
+    conf: &Config,
+    backend: &RpcBackend,
+    db: &mut Db,
+    stop: &AtomicBool,
+) -> Result<Summary> {
+    let summary = scan_with_stop(conf, backend, db, Some(stop))?;
+    if stop.load(Ordering::Relaxed) {
+        return Ok(summary);
+    }
+    schedule::pay_due(conf, backend, db)?;
+    // Transactions still in the mempool are accepted again, only dropped ones are news.
+    for (txid, result) in block_on(tx::rebroadcast(backend, db))? {
+        if let Err(error) = result {
+            eprintln!(
+                "{}: rebroadcast rejected, {:#}",
+                txid,
+                anyhow::Error::from(error)
+            );
+        }
+    }
+    Ok(summary)
+}
+
+/// Shows a notification for each payment to the wallet in `summary`, once when it shows up in the
+/// mempool and once more only if it was not seen there before it confirmed.
+fn notify_incoming(summary: &Summary, notified: &mut HashSet<Txid>) {
+    // Our own transactions spend wallet outputs, what they pay back is change.
+    let incoming = |tx: &WalletTx| tx.received > Amount::ZERO && tx.spent.is_empty();
+    let mut new = Vec::new();
+    for tx in summary.txs.iter().filter(|tx| incoming(tx)) {
+        if !notified.remove(&tx.txid) {
+            new.push((tx, true));
+        }
+    }
+    for tx in summary.unconfirmed.iter().filter(|tx| incoming(tx)) {
+        if notified.insert(tx.txid) {
+            new.push((tx, false));
+        }
+    }
+    for (tx, confirmed) in new {
+        if let Err(error) = notify::payment(tx.received, tx.txid, confirmed) {
+            eprintln!("failed to show notification: {:#}", error);
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().expect("poisoned mutex")
+}
@@ -0,0 +1,216 @@
+//! The `tx` command.
+//!
+//! The database only knows the outputs of a wallet transaction and how much it moved in and out of
+//! the wallet. Everything else (the inputs, the fee, the block hash) is asked of bitcoind, if it
+//! can't be reached we show what the database knows.
+
+use anyhow::{anyhow, bail, Context, Result};
+use bitcoin::consensus::encode::deserialize;
+use bitcoin::{
+    Address, Amount, Block, BlockHash, Denomination, Network, Script, Transaction, TxOut, Txid,
+};
+use bitcoincore_rpc::RpcApi;
+use pico_wallet_core::backend::{Backend, RpcBackend};
+use pico_wallet_core::config;
+use pico_wallet_core::db::{Db, TxRecord};
+
+use crate::{bitcoind_backend, block_on, get_address, ui};
+
+/// Prints the details of the wallet transaction `<txid>`.
+pub fn run(mut args: impl Iterator<Item = String>) -> Result<()> {
+    let txid = args.next().ok_or_else(|| anyhow!("missing txid"))?;
+    let txid = txid
+        .parse::<Txid>()
+        .with_context(|| format!("invalid txid: {}", txid))?;
+
+    let conf = config::load()?;
+    let network = conf.chain.network();
+    let wallet = get_address(&conf)?.script_pubkey();
+    let mut db = Db::open(conf.chain)?;
+    let record = match db.history()?.into_iter().find(|record| record.txid == txid) {
+        Some(record) => record,
+        None => bail!(
+            "{} is not a wallet transaction, run `scan` if it is new",
+            txid
+        ),
+    };
+
+    let backend = match bitcoind_backend(&conf) {
+        Ok(backend) => Some(backend),
+        Err(error) => {
+            eprintln!("warning: {:#}", error);
+            eprintln!("warning: bitcoind is not reachable, only showing what the database knows");
+            None
+        }
+    };
+    let tip = match &backend {
+        Some(backend) => block_on(backend.block_count())?,
+        None => db.get_last_height()?,
+    };
+    let (block_hash, tx) = match &backend {
+        Some(backend) => fetch(backend, &record)?,
+        None => (None, None),
+    };
+
+    println!("Transaction {}", txid);
+    println!();
+    match record.height {
+        Some(height) => println!(
+            "  {:<12}confirmed at height {} ({} confirmations)",
+            "Status:",
+            height,
+            (tip + 1).saturating_sub(height)
+        ),
+        None if record.conflicted => println!(
+            "  {:<12}conflicted, double spent or replaced by another transaction",
+            "Status:"
+        ),
+        None => println!("  {:<12}unconfirmed", "Status:"),
+    }
+    if let Some(block_hash) = block_hash {
+        println!("  {:<12}{}", "Block:", block_hash);
+    }
+    println!("  {:<12}{}", "Net effect:", net_effect(&record));
+
+    let tx = match tx {
+        Some(tx) => tx,
+        None => {
+            // The outputs are all we have.
+            println!();
+            println!("Outputs:");
+            println!();
+            print_outputs(&db.tx_outputs(&txid)?, &wallet, network);
+            return Ok(());
+        }
+    };
+    let prevouts = tx
+        .input
+        .iter()
+        .map(|input| -> Result<Option<Amount>> {
+            if tx.is_coin_base() {
+                return Ok(None);
+            }
+            if let Some(amount) = db.txo_amount(&input.previous_output)? {
+                return Ok(Some(amount));
+            }
+            // Not ours, bitcoind only finds the transaction if it has `-txindex` or it is unspent.
+            let outpoint = input.previous_output;
+            Ok(backend
+                .as_ref()
+                .and_then(|backend| {
+                    backend
+                        .client()
+                        .get_raw_transaction(&outpoint.txid, None)
+                        .ok()
+                })
+                .and_then(|prev| prev.output.get(outpoint.vout as usize).cloned())
+                .map(|prevout| Amount::from_sat(prevout.value)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let fee = prevouts
+        .iter()
+        .copied()
+        .sum::<Option<Amount>>()
+        .and_then(|input| {
+            let output = tx.output.iter().map(|output| output.value).sum::<u64>();
+            input.checked_sub(Amount::from_sat(output))
+        });
+    match fee {
+        Some(fee) => println!(
+            "  {:<12}{} ({} vB)",
+            "Fee:",
+            btc(fee),
+            tx.weight().to_vbytes_ceil()
+        ),
+        None if tx.is_coin_base() => println!("  {:<12}none, coinbase", "Fee:"),
+        None => println!("  {:<12}unknown, not every input amount is known", "Fee:"),
+    }
+    println!(
+        "  {:<12}{}",
+        "RBF:",
+        if tx.is_explicitly_rbf() {
+            "signals replaceability (BIP-125)"
+        } else {
+            "does not signal replaceability"
+        }
+    );
+
+    println!();
+    println!("Inputs:");
+    println!();
+    for (input, prevout) in tx.input.iter().zip(&prevouts) {
+        let amount = match prevout {
+            Some(amount) => ui::amount(*amount),
+            None => format!("{:>width$}", "?", width = ui::AMOUNT_WIDTH),
+        };
+        let line = format!("  {}  {}", amount, input.previous_output);
+        if db.txo_amount(&input.previous_output)?.is_some() {
+            println!("{}  {}", ui::green(line), ui::bold("ours"));
+        } else {
+            println!("{}", line);
+        }
+    }
+    println!();
+    println!("Outputs:");
+    println!();
+    print_outputs(&tx.output, &wallet, network);
+    Ok(())
+}
+
+/// Returns the hash of the block `record` is in, if any, and the transaction itself.
+///
+/// The transaction is `None` if it has left the mempool since it was recorded.
+fn fetch(
+    backend: &RpcBackend,
+    record: &TxRecord,
+) -> Result<(Option<BlockHash>, Option<Transaction>)> {
+    block_on(async {
+        let height = match record.height {
+            Some(height) => height,
+            None => return Ok((None, backend.mempool_tx(record.txid).await?)),
+        };
+        let hash = backend.block_hash(height).await?;
+        let block: Block = deserialize(&backend.raw_block(hash).await?)
+            .with_context(|| format!("failed to decode block {}", hash))?;
+        let tx = block.txdata.into_iter().find(|tx| tx.txid() == record.txid);
+        Ok((Some(hash), tx))
+    })
+}
+
+/// Prints `outputs`, highlighting those paying to `wallet`.
+fn print_outputs(outputs: &[TxOut], wallet: &Script, network: Network) {
+    for (vout, output) in outputs.iter().enumerate() {
+        let recipient = match Address::from_script(&output.script_pubkey, network) {
+            Ok(address) => address.to_string(),
+            Err(_) => output.script_pubkey.to_hex_string(),
+        };
+        let line = format!(
+            "  {}  {}:{}",
+            ui::amount(Amount::from_sat(output.value)),
+            vout,
+            recipient
+        );
+        if output.script_pubkey.as_script() == wallet {
+            println!("{}  {}", ui::green(line), ui::bold("ours"));
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+/// Formats what `record` added to (+) or took from (-) the wallet balance.
+fn net_effect(record: &TxRecord) -> String {
+    if record.received >= record.sent {
+        format!("+{}", btc(record.received - record.sent))
+    } else {
+        format!("-{}", btc(record.sent - record.received))
+    }
+}
+
+fn btc(amount: Amount) -> String {
+    amount
+        .display_in(Denomination::Bitcoin)
+        .show_denomination()
+        .to_string()
+}
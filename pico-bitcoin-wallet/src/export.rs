@@ -0,0 +1,95 @@
+//! The `export` command, the wallet history in a form other tools can import.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+use anyhow::{bail, Context, Result};
+use bitcoin::{Address, Amount, Denomination};
+use bitcoincore_rpc::RpcApi;
+use pico_wallet_core::accounting;
+use pico_wallet_core::backend::Backend;
+use pico_wallet_core::config;
+use pico_wallet_core::db::Db;
+
+use crate::{bitcoind_backend, block_on, get_address, take_option, ui};
+
+/// Writes the confirmed wallet transactions to the CSV file given with `--accounting <file>`.
+///
+/// One row per transaction: the date of its block, txid, direction, amount, fee, the balance after
+/// it and, if it paid a saved payee, their name as the label. Block dates are asked of bitcoind.
+pub fn run(args: impl Iterator<Item = String>) -> Result<()> {
+    let mut args = args.collect::<Vec<_>>();
+    let path = match take_option(&mut args, "--accounting")? {
+        Some(path) => path,
+        None => bail!("missing --accounting <file>"),
+    };
+    if let Some(arg) = args.first() {
+        bail!("unexpected argument: {}", arg);
+    }
+
+    let conf = config::load()?;
+    let network = conf.chain.network();
+    let wallet = get_address(&conf)?.script_pubkey();
+    let mut db = Db::open(conf.chain)?;
+    let entries = accounting::entries(&mut db)?;
+    let mut payees = HashMap::new();
+    for (name, address) in db.payees()? {
+        payees.insert(address, name);
+    }
+
+    let backend = bitcoind_backend(&conf)?;
+    let mut times = HashMap::new();
+    for entry in &entries {
+        if times.contains_key(&entry.height) {
+            continue;
+        }
+        let hash = block_on(backend.block_hash(entry.height))?;
+        let header = backend
+            .client()
+            .get_block_header(&hash)
+            .with_context(|| format!("failed to get block header {}", hash))?;
+        times.insert(entry.height, header.time);
+    }
+
+    let mut csv = String::from("date,txid,direction,amount,fee,balance,label\n");
+    for entry in &entries {
+        // Payees are matched on what the wallet paid, not on change back to it.
+        let label = entry
+            .outputs
+            .iter()
+            .filter(|output| !entry.incoming && output.script_pubkey != wallet)
+            .filter_map(|output| Address::from_script(&output.script_pubkey, network).ok())
+            .find_map(|address| payees.get(&address.to_string()))
+            .map(String::as_str)
+            .unwrap_or("");
+        writeln!(
+            csv,
+            "{},{},{},{},{},{},{}",
+            ui::timestamp(times[&entry.height].into()),
+            entry.txid,
+            if entry.incoming { "in" } else { "out" },
+            btc(entry.amount),
+            entry.fee.map(btc).unwrap_or_default(),
+            btc(entry.balance),
+            field(label)
+        )
+        .expect("writing to a string does not fail");
+    }
+    std::fs::write(&path, csv).with_context(|| format!("failed to write {}", path))?;
+    println!("Exported {} transactions to {}", entries.len(), path);
+    Ok(())
+}
+
+/// Formats `amount` in BTC without a unit, which spreadsheets read as a number.
+fn btc(amount: Amount) -> String {
+    amount.to_string_in(Denomination::Bitcoin)
+}
+
+/// Quotes `s` as a CSV field if it needs to be.
+fn field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
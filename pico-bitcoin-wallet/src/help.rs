@@ -0,0 +1,641 @@
+//! The help menu and extended per-command help.
+
+use anyhow::{bail, Result};
+
+use crate::{config, db, ui};
+
+/// Help text for a single command.
+struct Command {
+    name: &'static str,
+    usage: &'static str,
+    summary: &'static str,
+    details: &'static str,
+}
+
+const COMMANDS: &[Command] = &[
+    Command {
+        name: "init",
+        usage: "init",
+        summary: "Create the wallet key.",
+        details: "\
+Creates the wallet's private key in the data directory and prints the wallet address. Fails if
+there already is a key.
+
+Other commands create a random key the first time they need one, `init` is only needed to choose
+it: with `--deterministic <seed>` the key is derived from <seed>, so test runs (e.g. grading a
+workshop) build the same transactions every time. Pass the same `--deterministic <seed>` to the
+commands that sign to make their signatures reproducible too. Not available on mainnet, anybody
+who knows the seed has the key.
+
+Example:
+
+  $ pico-bitcoin-wallet --deterministic workshop init
+  bcrt1p...",
+    },
+    Command {
+        name: "address",
+        usage: "address [--p2wpkh]",
+        summary: "Get the wallet address.",
+        details: "\
+Prints the address derived from the wallet's private key. The key is created the first time it is
+needed and stored in the data directory.
+
+The same address is returned every time, a real wallet would never reuse addresses like this.
+
+With `--p2wpkh` prints the P2WPKH (segwit v0) address of the same key instead, for being paid by
+wallets that can not pay to taproot addresses. `scan` finds payments to both, `send` spends them
+alike (with an ECDSA signature for P2WPKH outputs) and change always goes to the taproot address.
+Outputs paying to the P2WPKH address have no recovery path.
+
+Example:
+
+  $ pico-bitcoin-wallet address
+  bcrt1p...",
+    },
+    Command {
+        name: "verify-address",
+        usage: "verify-address <address>",
+        summary: "Show what an address is and whether it is ours.",
+        details: "\
+Prints the type of <address> (p2pkh, p2sh, p2wpkh, p2wsh or p2tr), the networks it is valid on, the
+scriptPubKey it pays to and whether it belongs to the wallet. Useful to learn how addresses map to
+scripts, and to check an address before sending to it.
+
+Works without bitcoind.
+
+Example:
+
+  $ pico-bitcoin-wallet verify-address bcrt1p...",
+    },
+    Command {
+        name: "spend-info",
+        usage: "spend-info [txid:vout]",
+        summary: "Show the taproot data needed to spend wallet outputs.",
+        details: "\
+Prints what an external tool (or you, by hand) needs to spend the wallet's taproot outputs: the
+internal key, the merkle root of the script tree, the tweak, the tweaked output key and its parity,
+and for each script leaf the script and its control block. The wallet outputs all pay to the same
+key so this is the same for all of them, pass an outpoint to check it is a wallet output.
+
+Examples:
+
+  $ pico-bitcoin-wallet spend-info
+  $ pico-bitcoin-wallet spend-info 4a5e...:0",
+    },
+    Command {
+        name: "status",
+        usage: "status",
+        summary: "Show whether the daemon is running and what is pending.",
+        details: "\
+Prints whether `daemon` is running, and if so when it last caught up and whether that failed, the
+height the wallet is scanned to, how many of our transactions are unconfirmed and how many payments
+are scheduled.",
+    },
+    Command {
+        name: "balance",
+        usage: "balance [options]",
+        summary: "Get the current balance.",
+        details: "\
+Prints the sum of all wallet outputs in the database, broken down into:
+
+  Confirmed             Outputs that `send` can spend right now.
+  Unconfirmed incoming  Payments to the wallet that are still in the mempool.
+  Unconfirmed change    Change from our own transactions that are still in the mempool.
+  Immature              Coinbase outputs with fewer than 100 confirmations.
+  Frozen                Outputs frozen with `freeze`.
+
+Options:
+
+  --by-address          Print each address instead, see below.
+  --fiat <currency>     Follow amounts with their value in <currency>, see below.
+  --verify              Check the balance against bitcoind's UTXO set, see below.
+
+With `--by-address` prints how many unspent outputs each address has and their total instead, so
+address reuse is easy to spot.
+
+With `--fiat <currency>`, e.g. `--fiat USD`, amounts are followed by roughly what they are worth
+at the current price. This needs `price_url` in the config file and is off by default, the wallet
+makes no requests other than to bitcoind without it. Prices are cached for `price_cache_secs`.
+
+With `--verify` the confirmed outputs in the database are checked against bitcoind's UTXO set with
+`scantxoutset`, as `audit` does. If they differ (a stale scan, a reorg, a spend from elsewhere) the
+balance is wrong: prints a warning, what differs and how to rescan, and fails.
+
+The database is only updated by `scan` so run that first if you expect to see new coins.
+
+Examples:
+
+  $ pico-bitcoin-wallet scan && pico-bitcoin-wallet balance
+  $ pico-bitcoin-wallet balance --by-address
+  $ pico-bitcoin-wallet balance --fiat EUR",
+    },
+    Command {
+        name: "history",
+        usage: "history [filters]",
+        summary: "List the wallet's transactions.",
+        details: "\
+Prints every transaction paying to or spending from the wallet found by `scan`, oldest first, with
+its block height and the net amount it added to (in) or took from (out) the balance. Transactions
+still in the mempool come last, as pending. The totals of the listed transactions are printed at
+the bottom, leaving out conflicted transactions: pending ones that were double spent or replaced
+(e.g. by `bump-all`) and will never confirm.
+
+Filters:
+
+  --since <height>      Only transactions at or above <height>.
+  --direction in|out    Only transactions that added to (in) or took from (out) the balance.
+  --address <address>   Only transactions with an output paying to <address>.
+  --limit <n>           Only the most recent <n> transactions left after the other filters.
+
+With `--fiat <currency>` amounts are followed by roughly what they are worth at the current price
+(not the price at the time), see `balance`.
+
+Transactions are recorded as blocks are scanned, outputs scanned by older versions of the wallet do
+not show up here.
+
+Example:
+
+  $ pico-bitcoin-wallet history --direction out --limit 10",
+    },
+    Command {
+        name: "export",
+        usage: "export --accounting <file>",
+        summary: "Export the transactions to a CSV file for accounting.",
+        details: "\
+Writes a CSV file, e.g. to import into a spreadsheet or tax tool, with a row for each confirmed
+wallet transaction, oldest first. The columns are:
+
+  date        When the transaction's block was mined (UTC), asked of bitcoind.
+  txid        The transaction id.
+  direction   `in` if it paid the wallet, `out` if the wallet paid somebody else.
+  amount      What the wallet received, or paid not counting the fee, in BTC.
+  fee         The fee the wallet paid, in BTC. Empty for `in` or if an input amount is unknown, the
+              amount then includes it.
+  balance     The confirmed balance after the transaction, in BTC.
+  label       The name of the payee paid, if saved with `payee add`.
+
+Unconfirmed transactions are left out until they confirm. An existing file is overwritten.
+
+Example:
+
+  $ pico-bitcoin-wallet export --accounting wallet.csv",
+    },
+    Command {
+        name: "audit",
+        usage: "audit",
+        summary: "Check the database against bitcoind's UTXO set.",
+        details: "\
+Works out the wallet's addresses from the key (not the database), asks bitcoind for the outputs
+paying to them in its UTXO set with `scantxoutset` and compares them with the unspent outputs in
+the database. Prints each output they disagree about and fails if there are any:
+
+  missing   Unspent on chain but not in the database, e.g. a block was not scanned.
+  phantom   Unspent in the database but not on chain, e.g. spent elsewhere or reorged out.
+  mismatch  Unspent in both but for a different amount.
+
+The UTXO set knows nothing of the mempool, so outputs of our unconfirmed transactions are left out
+and the ones they spend are counted as unspent. Run `scan` first, a database behind the node's tip
+is missing whatever was paid since. Scanning the UTXO set takes a minute or so on mainnet.
+
+Example:
+
+  $ pico-bitcoin-wallet scan && pico-bitcoin-wallet audit",
+    },
+    Command {
+        name: "tx",
+        usage: "tx <txid>",
+        summary: "Show the details of a wallet transaction.",
+        details: "\
+Prints the wallet transaction <txid>: whether it is confirmed and how deep, the block it is in, the
+net amount it added to or took from the balance, its fee and whether it signals replaceability
+(BIP-125). Its inputs and outputs are listed too, those belonging to the wallet highlighted.
+
+The database only knows the outputs of a transaction, everything else is fetched from bitcoind. If
+bitcoind can't be reached only what the database knows is shown. The amounts of inputs that are
+not ours are only known if bitcoind runs with `-txindex`, without them the fee is unknown.
+
+Example:
+
+  $ pico-bitcoin-wallet tx 4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b",
+    },
+    Command {
+        name: "utxos",
+        usage: "utxos [--sort <order>]",
+        summary: "List the unspent outputs.",
+        details: "\
+Prints every unspent wallet output with its amount, number of confirmations, whether it is an
+immature coinbase output (these need 100 confirmations before they can be spent), whether it is
+frozen and the address it pays. <order> is one of `age` (oldest first, the default), `amount`
+(largest first) or `address`.
+
+Use the outpoints (<txid>:<vout>) with `freeze` to keep coins out of `send`.
+
+Example:
+
+  $ pico-bitcoin-wallet utxos --sort amount",
+    },
+    Command {
+        name: "freeze",
+        usage: "freeze <txid:vout>",
+        summary: "Stop an unspent output from being spent.",
+        details: "\
+Marks the unspent output <txid:vout> as frozen, `send` never spends frozen outputs. Handy to keep
+coins you want to treat separately (e.g. from a particular payer) from being mixed with the rest.
+Run `utxos` to see the outputs and whether they are frozen.
+
+Example:
+
+  $ pico-bitcoin-wallet freeze 4a5e1e4b...:0",
+    },
+    Command {
+        name: "unfreeze",
+        usage: "unfreeze <txid:vout>",
+        summary: "Allow a frozen output to be spent again.",
+        details: "\
+Undoes `freeze`, <txid:vout> can be spent by `send` again.
+
+Example:
+
+  $ pico-bitcoin-wallet unfreeze 4a5e1e4b...:0",
+    },
+    Command {
+        name: "scan",
+        usage: "scan",
+        summary: "Scan all blocks looking for relevant transactions.",
+        details: "\
+Requests every block since the last scanned height from bitcoind and stores outputs paying to the
+wallet address in the database. Run it each time new blocks have been mined. Once at the chain tip
+wallet transactions in the mempool are recorded too, as unconfirmed. Unconfirmed transactions that
+were double spent or replaced since the last scan are marked as conflicted.
+
+Ctrl-C (or SIGTERM) stops the scan after the current block, run `scan` again to carry on from
+there. Press Ctrl-C a second time to abort immediately.
+
+Example:
+
+  $ bitcoin-cli -regtest generatetoaddress 1 $(pico-bitcoin-wallet address)
+  $ pico-bitcoin-wallet scan",
+    },
+    Command {
+        name: "send",
+        usage: "send [options] <address> <amount>",
+        summary: "Send a given amount to the address provided.",
+        details: "\
+Builds a transaction spending wallet outputs to pay <amount> BTC to <address>, sends any change
+back to the wallet address, signs it and broadcasts it via bitcoind. Only confirmed outputs are
+spent, never frozen or immature coinbase ones. <address> can also be the name of a payee saved with
+`payee add`.
+
+A summary of the transaction is shown and you are asked to confirm before it is broadcast, pass
+`--yes` to skip the prompt (e.g. in scripts).
+
+Options are `--psbt`, `--coin-select <strategy>` and `--use-reserve`.
+
+With `--psbt` nothing is signed or broadcast, the unsigned transaction is printed as a base64 PSBT
+instead so it can be signed elsewhere (e.g. on a hardware wallet).
+
+`--coin-select` picks which outputs are spent:
+
+  largest-first  the largest first, fewest inputs so the lowest fee now (the default)
+  oldest-first   the oldest first, consolidates old coins
+  bnb            a set adding up to the payment without change if there is one, saves the change
+                 output now and spending it later
+  privacy        no change output if paying to another address type (the change would stand out),
+                 otherwise a single output if one is enough, spending several together links them.
+                 Never spends taproot and P2WPKH outputs together
+
+With `reserve_sat` in the config file that much of the confirmed balance is kept back, e.g. to bump
+fees with, and only the rest is spent. `--use-reserve` spends it too.
+
+Examples:
+
+  $ pico-bitcoin-wallet send bcrt1q... 0.5
+  $ pico-bitcoin-wallet send alice 0.5
+  $ pico-bitcoin-wallet send --coin-select bnb alice 0.5",
+    },
+    Command {
+        name: "watch",
+        usage: "watch [options] [address]",
+        summary: "Track the outputs of addresses we don't control.",
+        details: "\
+`watch <address>` makes `scan` also track outputs paying to <address>, e.g. a cold storage or a
+friend's address. They are watch-only: never spent and not part of the balance. Only blocks scanned
+after adding the address are searched, unless `--from-height` is given.
+
+Options:
+
+  --from-height <height>  Search the blocks scanned already again from <height>, needs bitcoind.
+
+A watched address can be given again with `--from-height` to search further back.
+
+`watch` with no address lists the watched addresses with their unspent outputs.
+
+Examples:
+
+  $ pico-bitcoin-wallet watch bcrt1q...
+  $ pico-bitcoin-wallet watch --from-height 800000 bc1q...
+  $ pico-bitcoin-wallet scan
+  $ pico-bitcoin-wallet watch",
+    },
+    Command {
+        name: "psbt",
+        usage: "psbt sighashes <file>",
+        summary: "Show what the inputs of a PSBT commit to.",
+        details: "\
+`psbt sighashes <file>` reads a base64 PSBT, e.g. one printed by `send --psbt`, and prints for each
+input the message (sighash) a signing device should sign and the sighash type. Check them against
+what an air-gapped device displays before approving.
+
+Taproot sighashes commit to every output spent, so the PSBT must have all of them. The sighash type
+is the one in the input's sighash type field, SIGHASH_DEFAULT for taproot and SIGHASH_ALL for
+P2WPKH without one, the same the wallet signs with.
+
+Example:
+
+  $ pico-bitcoin-wallet send --psbt alice 0.5 > payment.psbt
+  $ pico-bitcoin-wallet psbt sighashes payment.psbt",
+    },
+    Command {
+        name: "recover",
+        usage: "recover --key <file> <address>",
+        summary: "Sweep old wallet outputs with the recovery key.",
+        details: "\
+With `recovery_key` and `recovery_blocks` in the config file every wallet output can also be spent
+by the recovery key once it has been confirmed for `recovery_blocks` blocks, e.g. by an heir or by
+you after losing the wallet key. Setting or changing them changes the wallet address.
+
+`recover` scans, then spends every output old enough that way to <address>, signing with the WIF
+private key in <file>. The wallet key is only read for its public key. Outputs not old enough yet
+are left alone, spending an output (e.g. to yourself) restarts its clock.
+
+Example:
+
+  $ pico-bitcoin-wallet recover --key recovery.wif bcrt1q...",
+    },
+    Command {
+        name: "bump-all",
+        usage: "bump-all --fee-rate <n>",
+        summary: "Bump the fee of all stuck transactions.",
+        details: "\
+Replaces every unconfirmed transaction sending from the wallet that signals replaceability
+(BIP-125, `send` always does) with one paying <n> sat/vB, or the least BIP-125 allows if that is
+more. The extra fee comes out of the change. Prints the old and new fee of each transaction, or why
+it was skipped, and asks to confirm before broadcasting the replacements.
+
+Only transactions recorded by `scan` are considered, so scan first. A transaction spending the
+change of another unconfirmed one is skipped until that one confirms.
+
+Example:
+
+  $ pico-bitcoin-wallet bump-all --fee-rate 20",
+    },
+    Command {
+        name: "rebroadcast",
+        usage: "rebroadcast",
+        summary: "Broadcast our unconfirmed transactions again.",
+        details: "\
+Every transaction broadcast by `send`, `bump-all` or `schedule run` is kept until it confirms. This
+broadcasts the ones that have not confirmed yet again, so they are not lost if bitcoind drops them
+from its mempool (e.g. when restarted during a long session). Conflicted transactions are skipped,
+those that are no longer valid are rejected by bitcoind and reported as such.
+
+Example:
+
+  $ pico-bitcoin-wallet rebroadcast",
+    },
+    Command {
+        name: "daemon",
+        usage: "daemon [--interval <interval>]",
+        summary: "Keep running, scanning and paying what is due.",
+        details: "\
+Runs until stopped with Ctrl-C (or SIGTERM). Every <interval>, 30s unless given (e.g. `10m`), it
+scans new blocks, makes the scheduled payments that are due (as `schedule run` does) and broadcasts
+our unconfirmed transactions again (as `rebroadcast` does), printing what it did. Errors, e.g.
+bitcoind being unreachable, are printed and tried again next interval.
+
+On Unix, while the daemon runs `status`, `balance` and `send` are passed to it over a socket in the
+data directory and run there, with its open database and bitcoind connection. Their output and
+prompts still show where you ran them. Only one daemon runs per chain.
+
+With `notify = true` in the config file each payment to the wallet is shown as a desktop
+notification when it shows up in the mempool, or in a block if it was not seen in the mempool
+first. On Linux this needs a notification service on the session D-Bus, as desktops have.
+
+Example:
+
+  $ pico-bitcoin-wallet daemon --interval 1m",
+    },
+    Command {
+        name: "payee",
+        usage: "payee add|list",
+        summary: "Manage the address book of saved payees.",
+        details: "\
+`payee add <name> <address>` saves <address> as <name>, so you can `send <name> <amount>` instead
+of pasting the address every time. Names must be unique and can't be addresses themselves.
+
+`payee list` prints the saved payees.
+
+Examples:
+
+  $ pico-bitcoin-wallet payee add alice bcrt1q...
+  $ pico-bitcoin-wallet send alice 0.5",
+    },
+    Command {
+        name: "schedule",
+        usage: "schedule add|list|cancel|run",
+        summary: "Manage recurring payments.",
+        details: "\
+`schedule add <payee> <amount> <interval>` pays <amount> BTC to <payee> (a payee name or an
+address) every <interval>, e.g. `30s`, `10m`, `12h`, `1d` or `2w`. The first payment is due one
+interval from now.
+
+`schedule list` prints the scheduled payments with their ids and when they are due next, `schedule
+cancel <id>` stops one.
+
+`schedule run` scans, then makes every payment that is due without asking for confirmation. A
+payment is put off until the next run if there aren't enough confirmed coins to make it, and one
+missed for several intervals is only made once. Run it periodically e.g. from cron, or leave
+`daemon` running.
+
+Examples:
+
+  $ pico-bitcoin-wallet schedule add alice 0.01 1d
+  $ pico-bitcoin-wallet schedule run",
+    },
+    Command {
+        name: "estimate-fee",
+        usage: "estimate-fee [target_blocks]",
+        summary: "Print fee rate estimates.",
+        details: "\
+Asks bitcoind to estimate the fee rate needed to confirm within [target_blocks] blocks, or within a
+range of common targets if none is given. For each estimate also prints what a typical spend from
+this wallet (one taproot input, two outputs) would cost.
+
+bitcoind needs to have seen a fair number of transactions before it can estimate anything, on a
+fresh regtest chain you will likely only see errors.
+
+Examples:
+
+  $ pico-bitcoin-wallet estimate-fee
+  $ pico-bitcoin-wallet estimate-fee 6",
+    },
+    Command {
+        name: "mempool",
+        usage: "mempool",
+        summary: "Summarize the mempool and suggest fee rates.",
+        details: "\
+Prints the size of bitcoind's mempool and its minimum fee rate, a histogram of how many
+transactions (and vbytes) pay each range of fee rates, and two fee rates to pick from: what gets a
+transaction into the next block if miners take the highest paying transactions first, and
+bitcoind's economical estimate for confirming within a day.
+
+A transaction's fee rate is its own, a low fee parent with a high fee child counts as low.
+
+Example:
+
+  $ pico-bitcoin-wallet mempool",
+    },
+    Command {
+        name: "generate",
+        usage: "generate <n>",
+        summary: "Mine n blocks to the wallet address and scan them (regtest only).",
+        details: "\
+Mines <n> blocks paying the coinbase to the wallet address then runs `scan`. Coinbase outputs need
+100 confirmations before they can be spent, use `fund` if you just want spendable coins.
+
+Example:
+
+  $ pico-bitcoin-wallet generate 1",
+    },
+    Command {
+        name: "fund",
+        usage: "fund [amount]",
+        summary: "Mine and mature [amount] BTC (default 1) to the wallet (regtest only).",
+        details: "\
+Mines enough blocks to pay at least [amount] BTC to the wallet address, mines another 100 blocks
+(paying someone else) so the coins are spendable, then runs `scan`.
+
+Example:
+
+  $ pico-bitcoin-wallet fund 5",
+    },
+    Command {
+        name: "log",
+        usage: "log [scan|send|bump]",
+        summary: "Print the operation log.",
+        details: "\
+Every scan and broadcast is appended to `ops.jsonl` in the data directory together with a
+timestamp and the resulting txid. Prints the log oldest first, optionally only the operations of
+one kind.
+
+Example:
+
+  $ pico-bitcoin-wallet log send",
+    },
+    Command {
+        name: "config",
+        usage: "config check",
+        summary: "Check the configuration and the connection to bitcoind.",
+        details: "\
+Parses the configuration file, then for each configured bitcoind URI checks that we can connect and
+authenticate, that the node is on the configured network (and signet challenge), and that the
+configured bitcoind wallet is loaded. Each failure is printed along with a hint on how to fix it.
+
+Run this first if any other command fails to talk to bitcoind.
+
+Example:
+
+  $ pico-bitcoin-wallet config check",
+    },
+    Command {
+        name: "version",
+        usage: "version",
+        summary: "Print version and build information.",
+        details: "\
+Prints the wallet version, the git commit it was built from, the rust-bitcoin and rustc versions,
+the available backends and the supported networks. Please include this when reporting problems.",
+    },
+    Command {
+        name: "help",
+        usage: "help [command]",
+        summary: "Print this help menu, or detailed help for a command.",
+        details: "\
+Without arguments prints the list of commands. With a command name prints detailed usage and
+examples for that command, as does passing `--help` to the command.
+
+Examples:
+
+  $ pico-bitcoin-wallet help send
+  $ pico-bitcoin-wallet send --help",
+    },
+];
+
+/// Prints the help menu.
+pub fn menu() -> Result<()> {
+    let width = COMMANDS
+        .iter()
+        .map(|command| command.usage.len())
+        .max()
+        .unwrap_or_default();
+
+    println!();
+    println!("Usage: pico-bitcoin-wallet [--plain] [--yes] [--deterministic <seed>] COMMAND");
+    println!();
+    println!("Commands:");
+    println!();
+    for command in COMMANDS {
+        // Pad before painting, the escape codes would otherwise throw the alignment off.
+        let usage = format!("{:<width$}", command.usage, width = width);
+        println!(" {} : {}", ui::bold(usage), command.summary);
+    }
+    println!();
+    println!("Options:");
+    println!();
+    println!(
+        " {:<width$} : Disable colored output (also disabled when not writing to a terminal).",
+        "--plain",
+        width = width
+    );
+    println!(
+        " {:<width$} : Do not ask for confirmation before destructive actions.",
+        "--yes",
+        width = width
+    );
+    println!(
+        " {:<width$} : With `init` derive the key from <seed>, with commands that sign their signatures.",
+        "--deterministic <seed>",
+        width = width
+    );
+    println!();
+    println!("Run `pico-bitcoin-wallet help COMMAND` for more information on a command.");
+    println!();
+
+    let data_dir = db::data_dir()?;
+    let config_file = config::config_file()?;
+
+    println!("Some paths you might need:");
+    println!();
+    println!("data directory: {}", data_dir.display());
+    println!("configuration file: {}", config_file.display());
+    println!();
+
+    Ok(())
+}
+
+/// Prints detailed help for `name`.
+pub fn command(name: &str) -> Result<()> {
+    let command = match COMMANDS.iter().find(|command| command.name == name) {
+        Some(command) => command,
+        None => bail!("Unknown command: `{}`", name),
+    };
+
+    println!();
+    println!("Usage: pico-bitcoin-wallet {}", ui::bold(command.usage));
+    println!();
+    println!("{}", command.summary);
+    println!();
+    println!("{}", command.details);
+    println!();
+
+    Ok(())
+}
@@ -0,0 +1,89 @@
+//! The `mempool` command, a look at the fee market before picking a fee rate.
+
+use anyhow::{Context, Result};
+use bitcoin::{Amount, FeeRate};
+use bitcoincore_rpc::json::EstimateMode;
+use bitcoincore_rpc::RpcApi;
+use pico_wallet_core::config;
+use pico_wallet_core::fee;
+
+use crate::{bitcoind_backend, ui};
+
+/// Aim to confirm within a day when saving on fees.
+const ECONOMICAL_TARGET: u16 = 144;
+
+/// Prints the size of the mempool, how much of it pays which fee rate and the fee rates to pay.
+pub fn run() -> Result<()> {
+    let conf = config::load()?;
+    let backend = bitcoind_backend(&conf)?;
+    let client = backend.client();
+
+    let info = client
+        .get_mempool_info()
+        .context("failed to get mempool info")?;
+    let txs = client
+        .get_raw_mempool_verbose()
+        .context("failed to get mempool transactions")?
+        .into_values()
+        .map(|entry| (entry.vsize, entry.fees.base))
+        .collect::<Vec<_>>();
+    let min_fee = fee_rate(info.mempool_min_fee);
+
+    println!(
+        "{} transactions, {} vB, paying {} BTC in fees",
+        info.size,
+        txs.iter().map(|(vsize, _)| vsize).sum::<u64>(),
+        info.total_fee.to_btc()
+    );
+    println!(
+        "Minimum fee rate {} (relay {})",
+        sat_per_vb(min_fee),
+        sat_per_vb(fee_rate(info.min_relay_tx_fee))
+    );
+    println!();
+    println!("{:>12}  {:>8}  {:>12}", "sat/vB", "txs", "vB");
+    let buckets = fee::mempool_histogram(&txs);
+    let mut upper = None;
+    for bucket in buckets.iter().rev() {
+        let min = bucket.min.to_sat_per_vb_floor();
+        let range = match upper {
+            Some(upper) => format!("{}-{}", min, upper),
+            None => format!("{}+", min),
+        };
+        upper = Some(min);
+        println!("{:>12}  {:>8}  {:>12}", range, bucket.count, bucket.vsize);
+    }
+    println!();
+
+    let next_block = fee::next_block_fee_rate(&txs, min_fee);
+    let economical = client
+        .estimate_smart_fee(ECONOMICAL_TARGET, Some(EstimateMode::Economical))
+        .context("failed to estimate fee")?
+        .fee_rate
+        .map(fee_rate);
+    println!("{:<12}{}", "Next block:", sat_per_vb(next_block));
+    match economical {
+        Some(rate) => println!(
+            "{:<12}{} (within {} blocks)",
+            "Economical:",
+            sat_per_vb(rate.max(min_fee)),
+            ECONOMICAL_TARGET
+        ),
+        None => println!(
+            "{:<12}{}",
+            "Economical:",
+            ui::red("no estimate, bitcoind has not seen enough transactions")
+        ),
+    }
+    Ok(())
+}
+
+/// Converts a fee rate as bitcoind reports them, BTC/kvB.
+fn fee_rate(per_kvb: Amount) -> FeeRate {
+    // A kvB is 4000 weight units.
+    FeeRate::from_sat_per_kwu(per_kvb.to_sat() / 4)
+}
+
+fn sat_per_vb(rate: FeeRate) -> String {
+    format!("{:.3} sat/vB", rate.to_sat_per_kwu() as f64 / 250.0)
+}
@@ -0,0 +1,29 @@
+//! Desktop notifications of incoming payments, for `daemon` with `notify = true` in the config.
+//!
+//! Shown with `notify-rust`, which talks to the notification service over D-Bus on Linux and the
+//! BSDs and uses the platform's own notifications on macOS and Windows.
+
+use anyhow::Result;
+use bitcoin::{Amount, Denomination, Txid};
+use notify_rust::Notification;
+
+/// Shows a notification of `amount` received in `txid`, `confirmed` if it was found in a block
+/// rather than the mempool.
+pub fn payment(amount: Amount, txid: Txid, confirmed: bool) -> Result<()> {
+    let title = if confirmed {
+        "Payment confirmed"
+    } else {
+        "Incoming payment"
+    };
+    let body = format!(
+        "{} in {}",
+        amount.display_in(Denomination::Bitcoin).show_denomination(),
+        txid
+    );
+    Notification::new()
+        .appname("pico-bitcoin-wallet")
+        .summary(title)
+        .body(&body)
+        .show()?;
+    Ok(())
+}
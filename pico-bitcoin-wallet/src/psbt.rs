@@ -0,0 +1,55 @@
+//! The `psbt` command, for working with PSBTs signed elsewhere.
+//!
+//! `send --psbt` prints an unsigned PSBT for an external signing device. The device shows what it
+//! is about to sign, these commands show what it should be.
+
+use anyhow::{anyhow, bail, Context, Result};
+use bitcoin::psbt::Psbt;
+use pico_wallet_core::sign::{self, InputSighash};
+
+/// Runs the `psbt` subcommand in `args`.
+pub fn run(mut args: impl Iterator<Item = String>) -> Result<()> {
+    match args.next().as_deref() {
+        Some("sighashes") => {
+            let file = args.next().ok_or_else(|| anyhow!("missing PSBT file"))?;
+            sighashes(&read(&file)?)
+        }
+        Some(subcommand) => bail!("Unknown psbt command: `{}`", subcommand),
+        None => bail!("psbt subcommand missing, try `psbt sighashes <file>`"),
+    }
+}
+
+/// Prints the message each input of `psbt` commits to.
+fn sighashes(psbt: &Psbt) -> Result<()> {
+    let sighashes = sign::sighashes(psbt)?;
+    for (index, (input, sighash)) in psbt.unsigned_tx.input.iter().zip(&sighashes).enumerate() {
+        println!("Input {} ({}):", index, input.previous_output);
+        match sighash {
+            InputSighash::TaprootKeySpend { message, hash_ty } => {
+                println!("  {:<10}taproot key path, {}", "Spend:", hash_ty);
+                println!("  {:<10}{}", "Sighash:", message);
+            }
+            InputSighash::P2wpkh { message, hash_ty } => {
+                println!("  {:<10}P2WPKH, {}", "Spend:", hash_ty);
+                println!("  {:<10}{}", "Sighash:", message);
+            }
+            InputSighash::Unsupported => {
+                println!(
+                    "  {:<10}unknown, the output spent is missing or not a type the wallet signs",
+                    "Spend:"
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads a base64 PSBT, as printed by `send --psbt`, from `file`.
+fn read(file: &str) -> Result<Psbt> {
+    let base64 =
+        std::fs::read_to_string(file).with_context(|| format!("failed to read {}", file))?;
+    base64
+        .trim()
+        .parse::<Psbt>()
+        .with_context(|| format!("{} is not a base64 PSBT", file))
+}
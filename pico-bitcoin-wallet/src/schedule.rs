@@ -0,0 +1,220 @@
+//! The `schedule` command, recurring payments.
+//!
+//! Scheduled payments are kept in the database and made by [`pay_due`] whenever one is due and
+//! there are enough confirmed coins to pay for it. `schedule run` makes the payments that are due,
+//! run it periodically (e.g. from cron) or leave `daemon` running to do it.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, bail, Context, Result};
+use bitcoin::{Amount, Denomination};
+use pico_wallet_core::backend::RpcBackend;
+use pico_wallet_core::config::{self, Config};
+use pico_wallet_core::db::{Db, ScheduledPayment};
+use pico_wallet_core::oplog::Op;
+use pico_wallet_core::tx::{self, CoinSelection, SendError};
+
+use crate::{
+    bitcoind_backend, block_on, fee_rate, recipient_address, record, scan_with, sign_payments,
+    spendable_utxos, ui, wallet_addresses, with_scripts,
+};
+
+/// Runs the `schedule` subcommand in `args`.
+pub fn run(mut args: impl Iterator<Item = String>) -> Result<()> {
+    let conf = config::load()?;
+    match args.next().as_deref() {
+        Some("add") => add(&conf, args),
+        Some("list") => list(&conf),
+        Some("cancel") => {
+            let id = args.next().ok_or_else(|| anyhow!("missing payment id"))?;
+            let id = id
+                .parse::<u64>()
+                .with_context(|| format!("invalid payment id: {}", id))?;
+            if !Db::open(conf.chain)?.cancel_scheduled(id)? {
+                bail!("there is no scheduled payment {}", id);
+            }
+            println!("Cancelled scheduled payment {}", id);
+            Ok(())
+        }
+        Some("run") => {
+            let backend = bitcoind_backend(&conf)?;
+            // Make sure coins received (and payments confirmed) since the last run are known.
+            let mut db = Db::open(conf.chain)?;
+            scan_with(&conf, &backend, &mut db)?;
+            pay_due(&conf, &backend, &mut db)
+        }
+        Some(subcommand) => bail!("Unknown schedule command: `{}`", subcommand),
+        None => bail!("schedule subcommand missing, try `schedule list`"),
+    }
+}
+
+/// Schedules paying `<amount>` BTC to `<payee>` every `<interval>`.
+fn add(conf: &Config, mut args: impl Iterator<Item = String>) -> Result<()> {
+    let payee = args
+        .next()
+        .ok_or_else(|| anyhow!("missing address or payee"))?;
+    let amount = args.next().ok_or_else(|| anyhow!("missing amount"))?;
+    let interval = args.next().ok_or_else(|| anyhow!("missing interval"))?;
+
+    let mut db = Db::open(conf.chain)?;
+    // Checked now rather than when the payment is first due.
+    recipient_address(conf, &mut db, &payee)?;
+    let amount = Amount::from_str_in(&amount, Denomination::Bitcoin)
+        .with_context(|| format!("invalid amount: {}", amount))?;
+    let interval_secs = parse_interval(&interval)?;
+
+    let id = db.add_scheduled(
+        &payee,
+        amount,
+        interval_secs,
+        now().saturating_add(interval_secs),
+    )?;
+    println!(
+        "Scheduled payment {}: {} to {} every {}",
+        id,
+        amount.display_in(Denomination::Bitcoin).show_denomination(),
+        payee,
+        format_interval(interval_secs)
+    );
+    Ok(())
+}
+
+/// Prints the scheduled payments.
+fn list(conf: &Config) -> Result<()> {
+    let payments = Db::open(conf.chain)?.scheduled()?;
+    if payments.is_empty() {
+        println!("No scheduled payments, add one with `schedule add <payee> <amount> <interval>`");
+        return Ok(());
+    }
+    for payment in &payments {
+        println!(
+            "{:>4}  {}  every {:<4}  next {}  {}",
+            payment.id,
+            ui::amount(payment.amount),
+            format_interval(payment.interval_secs),
+            ui::timestamp(payment.next_due),
+            payment.payee
+        );
+    }
+    Ok(())
+}
+
+/// Makes every scheduled payment that is due.
+///
+/// A payment that can't be made yet because there aren't enough confirmed coins stays due and is
+/// tried again next time. A payment missed for several intervals is only made once.
+pub fn pay_due(conf: &Config, backend: &RpcBackend, db: &mut Db) -> Result<()> {
+    let now = now();
+    for payment in db.scheduled()? {
+        if payment.next_due > now {
+            continue;
+        }
+        match pay(conf, backend, db, &payment) {
+            Ok(()) => {
+                let missed = (now - payment.next_due) / payment.interval_secs;
+                db.set_next_due(
+                    payment.id,
+                    payment.next_due + (missed + 1) * payment.interval_secs,
+                )?;
+            }
+            Err(error) => match error.downcast_ref::<SendError>() {
+                Some(SendError::InsufficientFunds { .. }) => println!(
+                    "Scheduled payment {} is due but there are not enough confirmed coins, will \
+                     try again",
+                    payment.id
+                ),
+                _ => eprintln!("Scheduled payment {} failed: {:#}", payment.id, error),
+            },
+        }
+    }
+    Ok(())
+}
+
+/// Makes a single scheduled payment, the user agreed to it when scheduling it so no questions.
+fn pay(conf: &Config, backend: &RpcBackend, db: &mut Db, payment: &ScheduledPayment) -> Result<()> {
+    let recipient = recipient_address(conf, db, &payment.payee)?;
+    let wallet = wallet_addresses(conf)?;
+    let fee_rate = fee_rate(backend)?;
+    let utxos = tx::without_reserve(&spendable_utxos(db)?, conf.reserve);
+    let built = tx::build_payment_from(
+        &with_scripts(db, &wallet[0], &utxos)?,
+        &wallet,
+        &recipient,
+        payment.amount,
+        fee_rate,
+        conf.dust_relay_fee,
+        CoinSelection::default(),
+    )?;
+    let signed = tx::finalize(sign_payments(conf, [&built])?.remove(0))?;
+
+    let txid = block_on(tx::broadcast(backend, db, &signed))?;
+    record(
+        conf.chain,
+        Op::Send {
+            txid,
+            recipient: recipient.to_string(),
+            amount_sat: payment.amount.to_sat(),
+            fee_sat: built.fee.to_sat(),
+        },
+    );
+    println!(
+        "Scheduled payment {}: paid {} to {} in {}",
+        payment.id,
+        payment
+            .amount
+            .display_in(Denomination::Bitcoin)
+            .show_denomination(),
+        payment.payee,
+        txid
+    );
+    Ok(())
+}
+
+/// Parses an interval such as `30s`, `10m`, `12h`, `1d` or `2w` into seconds.
+pub fn parse_interval(s: &str) -> Result<u64> {
+    let invalid = || {
+        anyhow!(
+            "invalid interval {}, expected e.g. 30s, 10m, 12h, 1d or 2w",
+            s
+        )
+    };
+    let unit = match s.chars().last() {
+        Some('s') => 1,
+        Some('m') => 60,
+        Some('h') => 60 * 60,
+        Some('d') => 24 * 60 * 60,
+        Some('w') => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    let count = s[..s.len() - 1]
+        .parse::<u64>()
+        .ok()
+        .filter(|count| *count > 0)
+        .ok_or_else(invalid)?;
+    count
+        .checked_mul(unit)
+        .ok_or_else(|| anyhow!("interval {} is too long", s))
+}
+
+/// Formats `secs` in the largest unit [`parse_interval`] knows that divides it.
+pub fn format_interval(secs: u64) -> String {
+    for (unit, suffix) in [
+        (7 * 24 * 60 * 60, 'w'),
+        (24 * 60 * 60, 'd'),
+        (60 * 60, 'h'),
+        (60, 'm'),
+    ] {
+        if secs.is_multiple_of(unit) {
+            return format!("{}{}", secs / unit, suffix);
+        }
+    }
+    format!("{}s", secs)
+}
+
+/// Returns the current time in seconds since the UNIX epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock is after 1970")
+        .as_secs()
+}
@@ -0,0 +1,168 @@
+//! Helpers for printing human friendly output.
+//!
+//! Output is colored when stdout is a terminal, pass `--plain` (or set `NO_COLOR`) to disable.
+//! Destructive actions ask for confirmation, pass `--yes` to skip the prompt in scripts.
+
+use std::fmt;
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::{bail, Context, Result};
+use bitcoin::Amount;
+use pico_wallet_core::price;
+
+static COLOR: AtomicBool = AtomicBool::new(false);
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+
+/// Width of the amount column, fits a 21 million BTC amount with 8 decimal places.
+pub const AMOUNT_WIDTH: usize = 21;
+
+/// Enables colored output unless `plain` is set or stdout is not a terminal.
+///
+/// If `yes` is set confirmation prompts are answered automatically.
+pub fn init(plain: bool, yes: bool) {
+    let color = !plain && std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal();
+    COLOR.store(color, Ordering::Relaxed);
+    ASSUME_YES.store(yes, Ordering::Relaxed);
+}
+
+/// Asks the user to confirm `action`, returns an error if they decline.
+///
+/// Call this after printing a summary of what is about to happen.
+pub fn confirm(action: &str) -> Result<()> {
+    if ASSUME_YES.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    if !std::io::stdin().is_terminal() {
+        bail!(
+            "refusing to {} without confirmation, pass --yes to skip the prompt",
+            action
+        );
+    }
+
+    print!(
+        "{} [y/N] ",
+        bold(format!("Are you sure you want to {}?", action))
+    );
+    std::io::stdout()
+        .flush()
+        .context("failed to flush stdout")?;
+
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .context("failed to read answer")?;
+    match answer.trim() {
+        "y" | "Y" | "yes" => Ok(()),
+        _ => bail!("aborted"),
+    }
+}
+
+/// Where a command prints to and who confirms what it is about to do.
+///
+/// Usually the [`Terminal`], or a client of the daemon when the command runs there.
+pub trait Console {
+    /// Prints `text` followed by a newline.
+    fn print(&mut self, text: &str) -> Result<()>;
+
+    /// Asks to confirm `action` as [`confirm`] does.
+    fn confirm(&mut self, action: &str) -> Result<()>;
+}
+
+/// This process' stdout, with the prompts answered on stdin.
+pub struct Terminal;
+
+impl Console for Terminal {
+    fn print(&mut self, text: &str) -> Result<()> {
+        println!("{}", text);
+        Ok(())
+    }
+
+    fn confirm(&mut self, action: &str) -> Result<()> {
+        confirm(action)
+    }
+}
+
+fn paint(color: &str, s: impl fmt::Display) -> String {
+    if COLOR.load(Ordering::Relaxed) {
+        format!("{}{}{}", color, s, RESET)
+    } else {
+        s.to_string()
+    }
+}
+
+pub fn bold(s: impl fmt::Display) -> String {
+    paint(BOLD, s)
+}
+
+pub fn green(s: impl fmt::Display) -> String {
+    paint(GREEN, s)
+}
+
+pub fn red(s: impl fmt::Display) -> String {
+    paint(RED, s)
+}
+
+/// Formats `amount` right aligned in BTC, always showing all 8 decimal places.
+pub fn amount(amount: Amount) -> String {
+    format!(
+        "{:>width$} BTC",
+        btc(amount.to_sat()),
+        width = AMOUNT_WIDTH - 4
+    )
+}
+
+/// A fiat currency and the price of a bitcoin in it, for `--fiat`.
+pub struct Fiat {
+    pub currency: String,
+    pub price: f64,
+}
+
+/// Formats what `amount` is worth in `fiat` to follow it on a line, empty without `fiat`.
+pub fn fiat(amount: Amount, fiat: Option<&Fiat>) -> String {
+    match fiat {
+        Some(fiat) => format!(
+            "  ~{:>12.2} {}",
+            price::fiat_value(amount, fiat.price),
+            fiat.currency
+        ),
+        None => String::new(),
+    }
+}
+
+fn btc(sat: u64) -> String {
+    format!("{}.{:08}", sat / 100_000_000, sat % 100_000_000)
+}
+
+/// Formats `secs` since the UNIX epoch as a UTC date and time e.g., `2024-05-01 12:00:00`.
+pub fn timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let secs = secs % 86_400;
+
+    // Converts days since the epoch to a civil date, see
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        secs / 3_600,
+        secs % 3_600 / 60,
+        secs % 60
+    )
+}
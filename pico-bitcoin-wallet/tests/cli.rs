@@ -0,0 +1,478 @@
+//! Snapshot tests for the command line output.
+//!
+//! Each test runs the binary against a fixture data directory and compares what it prints with
+//! `tests/snapshots/<name>.txt`, so changes to the output format show up in review. After a
+//! deliberate change regenerate the snapshots with:
+//!
+//!     UPDATE_SNAPSHOTS=1 cargo test --test cli
+//!
+//! The data and config directories are redirected with the XDG variables, which `dirs` only
+//! honours on Linux.
+#![cfg(target_os = "linux")]
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use bitcoin::hashes::{hash160, Hash};
+use bitcoin::secp256k1::SecretKey;
+use bitcoin::{Address, Amount, FeeRate, Network, OutPoint, PrivateKey, TxOut, Txid};
+use pico_wallet_core::config::Chain;
+use pico_wallet_core::db::Db;
+use pico_wallet_core::scan::WalletTx;
+use pico_wallet_core::sign::PsbtOnly;
+use pico_wallet_core::tx::{self, CoinSelection};
+use pico_wallet_core::{fee, keys};
+
+/// A temporary home with a regtest config file and a database holding a few outputs.
+struct Fixture {
+    root: PathBuf,
+}
+
+impl Fixture {
+    fn new() -> Fixture {
+        static INSTANCE: AtomicU32 = AtomicU32::new(0);
+
+        let root = std::env::temp_dir().join(format!(
+            "pico-cli-{}-{}",
+            std::process::id(),
+            INSTANCE.fetch_add(1, Ordering::Relaxed)
+        ));
+        let config_dir = root.join("config").join("pico-bitcoin-wallet");
+        let data_dir = root.join("data").join("pico-bitcoin-wallet");
+        std::fs::create_dir_all(&config_dir).unwrap();
+        std::fs::create_dir_all(&data_dir).unwrap();
+
+        std::fs::write(
+            config_dir.join("config.toml"),
+            concat!(
+                "network = \"regtest\"\n",
+                "bitcoind_username = \"pico\"\n",
+                "bitcoind_password = \"pico\"\n",
+                // Nothing listens here, commands that can do without bitcoind must not wait for it.
+                "bitcoind_uri = \"http://127.0.0.1:1\"\n",
+                "rpc_retries = 0\n",
+            ),
+        )
+        .unwrap();
+
+        // The wallet key is `address(0x01)`'s so the fixture can pay to it.
+        let key = PrivateKey::new(
+            SecretKey::from_slice(&[0x01; 32]).unwrap(),
+            Network::Regtest,
+        );
+        std::fs::write(data_dir.join("private.key"), key.to_wif()).unwrap();
+
+        let outpoint = |s: &str| s.parse::<OutPoint>().unwrap();
+        let txid = |s: &str| s.parse::<Txid>().unwrap();
+        let output = |address: &Address, sat| TxOut {
+            value: sat,
+            script_pubkey: address.script_pubkey(),
+        };
+        let ours = address(0x01);
+        let theirs = address(0x02);
+        let mut db = Db::open_at(data_dir.join("data.db")).unwrap();
+        db.store_block(
+            101,
+            &[
+                (
+                    outpoint("1111111111111111111111111111111111111111111111111111111111111111:0"),
+                    Amount::from_sat(5_000_000_000),
+                ),
+                (
+                    outpoint("2222222222222222222222222222222222222222222222222222222222222222:1"),
+                    Amount::from_sat(12_345),
+                ),
+                (
+                    outpoint("3333333333333333333333333333333333333333333333333333333333333333:0"),
+                    Amount::from_sat(70_000),
+                ),
+            ],
+            &[],
+            &[
+                WalletTx {
+                    txid: txid("1111111111111111111111111111111111111111111111111111111111111111"),
+                    coinbase: true,
+                    received: Amount::from_sat(5_000_000_000),
+                    spent: vec![],
+                    inputs: vec![],
+                    outputs: vec![output(&ours, 5_000_000_000)],
+                },
+                WalletTx {
+                    txid: txid("2222222222222222222222222222222222222222222222222222222222222222"),
+                    coinbase: false,
+                    received: Amount::from_sat(12_345),
+                    spent: vec![],
+                    inputs: vec![],
+                    outputs: vec![output(&theirs, 100_000), output(&ours, 12_345)],
+                },
+                WalletTx {
+                    txid: txid("3333333333333333333333333333333333333333333333333333333333333333"),
+                    coinbase: false,
+                    received: Amount::from_sat(70_000),
+                    spent: vec![],
+                    inputs: vec![],
+                    outputs: vec![output(&ours, 70_000)],
+                },
+            ],
+        )
+        .unwrap();
+        let spent = outpoint("3333333333333333333333333333333333333333333333333333333333333333:0");
+        db.store_block(
+            102,
+            &[],
+            &[spent],
+            &[WalletTx {
+                txid: txid("4444444444444444444444444444444444444444444444444444444444444444"),
+                coinbase: false,
+                received: Amount::ZERO,
+                spent: vec![spent],
+                inputs: vec![spent],
+                outputs: vec![output(&theirs, 69_846)],
+            }],
+        )
+        .unwrap();
+        db.store_unconfirmed(
+            &[WalletTx {
+                txid: txid("5555555555555555555555555555555555555555555555555555555555555555"),
+                coinbase: false,
+                received: Amount::from_sat(100_000),
+                spent: vec![],
+                inputs: vec![],
+                outputs: vec![output(&ours, 100_000), output(&theirs, 900_000)],
+            }],
+            &[],
+        )
+        .unwrap();
+
+        std::fs::write(
+            data_dir.join("ops.jsonl"),
+            concat!(
+                r#"{"time":1700000000,"op":"scan","from_height":1,"to_height":102,"received":3,"spent":1}"#,
+                "\n",
+                r#"{"time":1700000100,"op":"send","txid":"4444444444444444444444444444444444444444444444444444444444444444","recipient":"bcrt1qexample","amount_sat":50000,"fee_sat":154}"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+
+        Fixture { root }
+    }
+
+    /// Runs the wallet with `args`, returns stdout with the fixture path replaced by `[ROOT]`.
+    fn run(&self, args: &[&str]) -> String {
+        let output = Command::new(env!("CARGO_BIN_EXE_pico-bitcoin-wallet"))
+            .args(args)
+            .env("HOME", &self.root)
+            .env("XDG_CONFIG_HOME", self.root.join("config"))
+            .env("XDG_DATA_HOME", self.root.join("data"))
+            .env("NO_COLOR", "1")
+            .output()
+            .expect("failed to run pico-bitcoin-wallet");
+        assert!(
+            output.status.success(),
+            "{:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout)
+            .expect("output is UTF-8")
+            .replace(&self.root.display().to_string(), "[ROOT]")
+    }
+
+    /// Runs the wallet with `args` expecting it to fail, returns stderr.
+    fn run_failing(&self, args: &[&str]) -> String {
+        let output = Command::new(env!("CARGO_BIN_EXE_pico-bitcoin-wallet"))
+            .args(args)
+            .env("HOME", &self.root)
+            .env("XDG_CONFIG_HOME", self.root.join("config"))
+            .env("XDG_DATA_HOME", self.root.join("data"))
+            .env("NO_COLOR", "1")
+            .output()
+            .expect("failed to run pico-bitcoin-wallet");
+        assert!(!output.status.success(), "{:?} succeeded", args);
+        String::from_utf8(output.stderr).expect("output is UTF-8")
+    }
+
+    /// Replaces the wallet key with the same key in uncompressed form.
+    fn uncompress_key(&self) {
+        let mut key = PrivateKey::new(
+            SecretKey::from_slice(&[0x01; 32]).unwrap(),
+            Network::Regtest,
+        );
+        key.compressed = false;
+        let file = self
+            .root
+            .join("data")
+            .join("pico-bitcoin-wallet")
+            .join("private.key");
+        std::fs::write(file, key.to_wif()).unwrap();
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Returns the regtest address of the key with every byte set to `byte`.
+fn address(byte: u8) -> Address {
+    let sk = SecretKey::from_slice(&[byte; 32]).expect("valid secret key");
+    keys::address(&PrivateKey::new(sk, Network::Regtest), Chain::Regtest)
+}
+
+/// Compares `actual` with the snapshot called `name`, or overwrites it if `UPDATE_SNAPSHOTS` is set.
+fn assert_snapshot(name: &str, actual: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("snapshots")
+        .join(format!("{}.txt", name));
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|_| panic!("missing snapshot {}, run with UPDATE_SNAPSHOTS=1", name));
+    assert!(
+        expected == actual,
+        "output does not match snapshot {}\n\nexpected:\n{}\nactual:\n{}",
+        name,
+        expected,
+        actual
+    );
+}
+
+#[test]
+fn help() {
+    assert_snapshot("help", &Fixture::new().run(&["help"]));
+}
+
+#[test]
+fn help_send() {
+    assert_snapshot("help_send", &Fixture::new().run(&["help", "send"]));
+}
+
+#[test]
+fn balance() {
+    assert_snapshot("balance", &Fixture::new().run(&["balance"]));
+}
+
+#[test]
+fn balance_by_address() {
+    assert_snapshot(
+        "balance_by_address",
+        &Fixture::new().run(&["balance", "--by-address"]),
+    );
+}
+
+#[test]
+fn log() {
+    assert_snapshot("log", &Fixture::new().run(&["log"]));
+}
+
+#[test]
+fn history() {
+    assert_snapshot("history", &Fixture::new().run(&["history"]));
+}
+
+#[test]
+fn history_filtered() {
+    let theirs = address(0x02).to_string();
+    assert_snapshot(
+        "history_filtered",
+        &Fixture::new().run(&["history", "--address", &theirs, "--direction", "in"]),
+    );
+}
+
+#[test]
+fn utxos() {
+    let fixture = Fixture::new();
+    fixture.run(&[
+        "freeze",
+        "2222222222222222222222222222222222222222222222222222222222222222:1",
+    ]);
+    assert_snapshot("utxos", &fixture.run(&["utxos", "--sort", "amount"]));
+}
+
+#[test]
+fn tx_without_bitcoind() {
+    assert_snapshot(
+        "tx_without_bitcoind",
+        &Fixture::new().run(&[
+            "tx",
+            "5555555555555555555555555555555555555555555555555555555555555555",
+        ]),
+    );
+}
+
+#[test]
+fn payees() {
+    let fixture = Fixture::new();
+    assert_snapshot("payees_empty", &fixture.run(&["payee", "list"]));
+    fixture.run(&["payee", "add", "bob", &address(0x03).to_string()]);
+    fixture.run(&["payee", "add", "alice", &address(0x02).to_string()]);
+    assert_snapshot("payees", &fixture.run(&["payee", "list"]));
+}
+
+#[test]
+fn verify_address() {
+    let fixture = Fixture::new();
+    fixture.run(&["watch", &address(0x03).to_string()]);
+    let output = [
+        address(0x01).to_string(),
+        address(0x02).to_string(),
+        address(0x03).to_string(),
+        "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2".to_owned(),
+    ]
+    .iter()
+    .map(|address| fixture.run(&["verify-address", address]))
+    .collect::<Vec<_>>()
+    .join("\n");
+    assert_snapshot("verify_address", &output);
+}
+
+#[test]
+fn spend_info() {
+    let fixture = Fixture::new();
+    assert_snapshot(
+        "spend_info",
+        &fixture.run(&[
+            "spend-info",
+            "1111111111111111111111111111111111111111111111111111111111111111:0",
+        ]),
+    );
+}
+
+#[test]
+fn psbt_sighashes() {
+    let fixture = Fixture::new();
+    let utxos = [(
+        "1111111111111111111111111111111111111111111111111111111111111111:0"
+            .parse::<OutPoint>()
+            .unwrap(),
+        Amount::from_sat(5_000_000_000),
+    )];
+    let payment = tx::build_payment(
+        &utxos,
+        &address(0x01),
+        &address(0x02),
+        Amount::from_sat(100_000),
+        FeeRate::BROADCAST_MIN,
+        fee::DUST_RELAY_FEE,
+        CoinSelection::LargestFirst,
+    )
+    .unwrap();
+    let psbt = tx::sign(&payment, &PsbtOnly).unwrap();
+    let file = fixture.root.join("payment.psbt");
+    std::fs::write(&file, psbt.to_string()).unwrap();
+
+    assert_snapshot(
+        "psbt_sighashes",
+        &fixture.run(&["psbt", "sighashes", file.to_str().unwrap()]),
+    );
+}
+
+#[test]
+fn watch() {
+    let fixture = Fixture::new();
+    assert_snapshot("watch_empty", &fixture.run(&["watch"]));
+    fixture.run(&["watch", &address(0x02).to_string()]);
+    fixture.run(&["watch", &address(0x03).to_string()]);
+    assert_snapshot("watch", &fixture.run(&["watch"]));
+}
+
+#[test]
+fn watch_rejects_wallet_addresses() {
+    let fixture = Fixture::new();
+    let key = PrivateKey::new(
+        SecretKey::from_slice(&[0x01; 32]).unwrap(),
+        Network::Regtest,
+    );
+    let p2wpkh = keys::p2wpkh_address(&key, Chain::Regtest).unwrap();
+    for address in [address(0x01), p2wpkh] {
+        let error = fixture.run_failing(&["watch", &address.to_string()]);
+        assert!(error.contains("is a wallet address"), "{}", error);
+    }
+    assert_snapshot("watch_empty", &fixture.run(&["watch"]));
+}
+
+/// An uncompressed key has no P2WPKH address, the wallet carries on with the taproot one.
+#[test]
+fn uncompressed_key() {
+    let fixture = Fixture::new();
+    fixture.uncompress_key();
+    assert_eq!(fixture.run(&["address"]).trim(), address(0x01).to_string());
+    let error = fixture.run_failing(&["address", "--p2wpkh"]);
+    assert!(error.contains("no P2WPKH address"), "{}", error);
+    let output = fixture.run(&["verify-address", &address(0x01).to_string()]);
+    assert!(output.contains("yes, the wallet address"), "{}", output);
+    fixture.run(&["watch", &address(0x02).to_string()]);
+}
+
+/// `--deterministic` only writes a key with `init`, other commands that don't sign reject it.
+#[test]
+fn deterministic() {
+    let fixture = Fixture::new();
+    let error = fixture.run_failing(&["--deterministic", "seed", "balance"]);
+    assert!(error.contains("only applies to `init`"), "{}", error);
+    let error = fixture.run_failing(&["--deterministic", "seed", "init"]);
+    assert!(error.contains("already has a key"), "{}", error);
+
+    let key_file = fixture
+        .root
+        .join("data")
+        .join("pico-bitcoin-wallet")
+        .join("private.key");
+    std::fs::remove_file(&key_file).unwrap();
+    let expected = keys::address(&keys::from_seed("seed", Chain::Regtest), Chain::Regtest);
+    assert_eq!(
+        fixture.run(&["--deterministic", "seed", "init"]).trim(),
+        expected.to_string()
+    );
+    let error = fixture.run_failing(&["--deterministic", "other", "send"]);
+    assert!(error.contains("not derived from this seed"), "{}", error);
+}
+
+/// Each custom signet keeps its keys and database apart, named after its challenge.
+#[test]
+fn custom_signet_data_dir() {
+    let fixture = Fixture::new();
+    let config = fixture
+        .root
+        .join("config")
+        .join("pico-bitcoin-wallet")
+        .join("config.toml");
+    for challenge in ["51", "52"] {
+        std::fs::write(
+            &config,
+            format!(
+                "network = \"signet\"\nsignet_challenge = \"{}\"\nbitcoind_uri = \"http://127.0.0.1:1\"\n",
+                challenge
+            ),
+        )
+        .unwrap();
+        fixture.run(&["history"]);
+    }
+    let dir = fixture.root.join("data").join("pico-bitcoin-wallet");
+    for challenge in [[0x51], [0x52]] {
+        let hash = hash160::Hash::hash(&challenge);
+        assert!(dir
+            .join(format!("signet-{}", hash))
+            .join("data.db")
+            .exists());
+    }
+    assert!(!dir.join("signet").exists());
+}
+
+#[test]
+fn schedule() {
+    let fixture = Fixture::new();
+    fixture.run(&["payee", "add", "alice", &address(0x02).to_string()]);
+    assert_snapshot(
+        "schedule_add",
+        &fixture.run(&["schedule", "add", "alice", "0.01", "1d"]),
+    );
+    fixture.run(&["schedule", "cancel", "1"]);
+    assert_snapshot("schedule_cancelled", &fixture.run(&["schedule", "list"]));
+}
@@ -0,0 +1,15 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The exercise, replace the `todo!()` with your solution.
+
+use bitcoin::secp256k1::{Secp256k1, Verification};
+use bitcoin::Transaction;
+
+/// Builds the witness from the partial signature and extracts the signed transaction.
+///
+/// A p2wpkh witness is `[sig, pubkey]`, set `final_script_witness` and clear the fields nobody
+/// needs once the input is final. Verify the signature before extracting the transaction.
+#[allow(unused_variables)]
+pub fn finalizer<C: Verification>(secp: &Secp256k1<C>, psbt: &[u8]) -> Transaction {
+    todo!("finalize the input and extract the transaction")
+}
@@ -0,0 +1,16 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Hints for the exercise, from least to most specific.
+
+pub const HINTS: &[&str] = &[
+    "Finalizing turns the partial signature into the final witness. Deserialize with \
+     `Psbt::deserialize` and look at `psbt.inputs[0].partial_sigs`, it maps public keys to \
+     signatures.",
+    "A p2wpkh witness is [sig, pubkey], set `final_script_witness` to \
+     `Witness::from_slice(&[sig.to_vec(), pk.to_bytes()])`. Then clear `partial_sigs`, \
+     `bip32_derivation` and `sighash_type`, only `witness_utxo` and `final_script_witness` are \
+     left.",
+    "Before extracting, verify the signature: compute the sighash with `segwit_signature_hash` \
+     and the script code of `witness_utxo`, then call `secp.verify_ecdsa(&msg, &sig.sig, \
+     &pk.inner)`. `psbt.extract_tx()` gives the signed transaction.",
+];
@@ -0,0 +1,234 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Pass a PSBT between the parties involved in spending a p2wpkh output (BIP-174).
+//!
+//! The roles are played by three functions that only share serialized PSBTs, as if they ran on
+//! different machines:
+//!
+//! - The funder (BIP-174 creator and updater) knows the xpub and builds the transaction.
+//! - The signer holds the xpriv, e.g. a hardware wallet.
+//! - The finalizer (BIP-174 input finalizer and transaction extractor) builds the witness and gets
+//!   the network serialized transaction.
+//!
+//! After each step we check that exactly the fields we expect are set.
+
+use std::str::FromStr;
+
+use bitcoin::bip32::{DerivationPath, ExtendedPrivKey, ExtendedPubKey, Fingerprint, KeySource};
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::locktime::absolute;
+use bitcoin::psbt::{self, Psbt};
+use bitcoin::secp256k1::{Secp256k1, Signing, Verification};
+use bitcoin::{Network, PublicKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness};
+use workshop_common::segwit_v0::receivers_address;
+use workshop_common::{CHANGE_AMOUNT, SPEND_AMOUNT};
+
+// The exercise is `finalizer` in `src/exercise.rs`, building with `--features solution` swaps in
+// the reference solution from `src/solution.rs`.
+#[cfg(not(feature = "solution"))]
+mod exercise;
+#[cfg(feature = "solution")]
+#[path = "solution.rs"]
+mod exercise;
+
+use exercise::finalizer;
+
+mod hints;
+
+/// The BIP-84 account the wallet uses.
+const ACCOUNT_PATH: &str = "m/84'/0'/0'";
+
+fn main() {
+    // Stuck? Run `cargo run -- hint 1`.
+    workshop_common::hint_command(hints::HINTS);
+
+    // We need a signing secp256k1 context, if you have not seen this before just pass it in when
+    // needed and otherwise ignore it.
+    let secp = Secp256k1::new();
+
+    // The signer owns the master key, the funder only gets the account xpub (and where it came
+    // from) so it can build transactions but not sign them.
+    let master = signers_master_key();
+    let account = Account::new(&secp, &master);
+
+    let psbt = funder(&secp, &account);
+    let psbt = signer(&secp, &psbt, &master);
+    // Finalize and extract the transaction, this is the exercise.
+    let tx = finalizer(&secp, &psbt);
+
+    // BOOM! Transaction signed and ready to broadcast.
+    let prevouts = Psbt::deserialize(&psbt)
+        .expect("valid PSBT")
+        .inputs
+        .into_iter()
+        .map(|input| input.witness_utxo.expect("witness_utxo is set"))
+        .collect::<Vec<_>>();
+    workshop_common::print_prevouts(&prevouts);
+    println!("{}", serialize_hex(&tx));
+}
+
+/// Creates the PSBT and adds everything a signer needs to sign it.
+///
+/// Returns the PSBT serialized, ready to be sent to the signer.
+fn funder<C: Verification>(secp: &Secp256k1<C>, account: &Account) -> Vec<u8> {
+    // Get an unspent output that is locked to the first receive address of the account.
+    // In a real application these would come from the chain.
+    let (pk, key_source) = account.derive(secp, "0/0");
+    let (dummy_out_point, dummy_utxo) = workshop_common::dummy_utxo(
+        0,
+        ScriptBuf::new_v0_p2wpkh(&pk.wpubkey_hash().expect("key is compressed")),
+    );
+
+    // Change goes to the first change address.
+    let (change_pk, change_key_source) = account.derive(secp, "1/0");
+
+    // Creator: the unsigned transaction, this is all the PSBT holds to start with.
+    let input = TxIn {
+        previous_output: dummy_out_point, // The dummy output we are spending.
+        script_sig: ScriptBuf::default(), // For a p2wpkh script_sig is empty.
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        witness: Witness::default(), // Must be empty in a PSBT.
+    };
+    let spend = TxOut {
+        value: SPEND_AMOUNT,
+        script_pubkey: receivers_address().script_pubkey(),
+    };
+    let change = TxOut {
+        value: CHANGE_AMOUNT,
+        script_pubkey: ScriptBuf::new_v0_p2wpkh(&change_pk.wpubkey_hash().expect("compressed")),
+    };
+    let unsigned_tx = Transaction {
+        version: 2,                          // Post BIP-68.
+        lock_time: absolute::LockTime::ZERO, // Ignore the locktime.
+        input: vec![input],                  // Input goes into index 0.
+        output: vec![spend, change],         // Outputs, order does not matter.
+    };
+    let mut psbt = Psbt::from_unsigned_tx(unsigned_tx).expect("unsigned transaction");
+    assert_eq!(input_fields(&psbt.inputs[0]), Vec::<&str>::new());
+
+    // Updater: the output being spent (segwit signatures commit to its value) and which key signs
+    // for it. The change output gets its key too so the signer can check it is really change.
+    psbt.inputs[0].witness_utxo = Some(dummy_utxo);
+    psbt.inputs[0].bip32_derivation.insert(pk.inner, key_source);
+    psbt.outputs[1]
+        .bip32_derivation
+        .insert(change_pk.inner, change_key_source);
+
+    assert_eq!(
+        input_fields(&psbt.inputs[0]),
+        ["witness_utxo", "bip32_derivation"]
+    );
+    assert_eq!(output_fields(&psbt.outputs[0]), Vec::<&str>::new());
+    assert_eq!(output_fields(&psbt.outputs[1]), ["bip32_derivation"]);
+
+    psbt.serialize()
+}
+
+/// Signs every input it has keys for.
+///
+/// Returns the PSBT serialized, ready to be sent to the finalizer.
+fn signer<C: Signing>(secp: &Secp256k1<C>, psbt: &[u8], master: &ExtendedPrivKey) -> Vec<u8> {
+    let mut psbt = Psbt::deserialize(psbt).expect("valid PSBT");
+
+    // A real signer would show the user what they are signing before going ahead.
+    let fee = psbt.fee().expect("witness_utxo is set");
+    println!("Signing, paying a {} fee", fee);
+
+    // Signs each input with the keys in `bip32_derivation` that derive from `master`.
+    let used = psbt.sign(master, secp).expect("failed to sign");
+    assert_eq!(used[&0].len(), 1);
+
+    assert_eq!(
+        input_fields(&psbt.inputs[0]),
+        ["witness_utxo", "partial_sigs", "bip32_derivation"]
+    );
+
+    psbt.serialize()
+}
+
+/// What the funder knows about the signer's keys.
+struct Account {
+    /// The fingerprint of the master key, tells the signer which key the paths start from.
+    fingerprint: Fingerprint,
+    path: DerivationPath,
+    xpub: ExtendedPubKey,
+}
+
+impl Account {
+    fn new<C: Signing>(secp: &Secp256k1<C>, master: &ExtendedPrivKey) -> Self {
+        let path = DerivationPath::from_str(ACCOUNT_PATH).expect("valid path");
+        let xpriv = master.derive_priv(secp, &path).expect("valid path");
+        Account {
+            fingerprint: master.fingerprint(secp),
+            path,
+            xpub: ExtendedPubKey::from_priv(secp, &xpriv),
+        }
+    }
+
+    /// Returns the public key at `child` (relative to the account) and its full derivation.
+    fn derive<C: Verification>(&self, secp: &Secp256k1<C>, child: &str) -> (PublicKey, KeySource) {
+        let child = DerivationPath::from_str(&format!("m/{}", child)).expect("valid path");
+        let xpub = self.xpub.derive_pub(secp, &child).expect("unhardened path");
+        let path = self.path.extend(child);
+        (PublicKey::new(xpub.public_key), (self.fingerprint, path))
+    }
+}
+
+/// An example of the signer's master key.
+///
+/// In a real application this would be created from an actual secret seed.
+fn signers_master_key() -> ExtendedPrivKey {
+    let seed = workshop_common::secret_key(0).secret_bytes();
+    ExtendedPrivKey::new_master(Network::Bitcoin, &seed).expect("valid seed")
+}
+
+/// Returns the names of the fields set in `input`.
+fn input_fields(input: &psbt::Input) -> Vec<&'static str> {
+    let fields = [
+        ("non_witness_utxo", input.non_witness_utxo.is_some()),
+        ("witness_utxo", input.witness_utxo.is_some()),
+        ("partial_sigs", !input.partial_sigs.is_empty()),
+        ("sighash_type", input.sighash_type.is_some()),
+        ("redeem_script", input.redeem_script.is_some()),
+        ("witness_script", input.witness_script.is_some()),
+        ("bip32_derivation", !input.bip32_derivation.is_empty()),
+        ("final_script_sig", input.final_script_sig.is_some()),
+        ("final_script_witness", input.final_script_witness.is_some()),
+        ("ripemd160_preimages", !input.ripemd160_preimages.is_empty()),
+        ("sha256_preimages", !input.sha256_preimages.is_empty()),
+        ("hash160_preimages", !input.hash160_preimages.is_empty()),
+        ("hash256_preimages", !input.hash256_preimages.is_empty()),
+        ("tap_key_sig", input.tap_key_sig.is_some()),
+        ("tap_script_sigs", !input.tap_script_sigs.is_empty()),
+        ("tap_scripts", !input.tap_scripts.is_empty()),
+        ("tap_key_origins", !input.tap_key_origins.is_empty()),
+        ("tap_internal_key", input.tap_internal_key.is_some()),
+        ("tap_merkle_root", input.tap_merkle_root.is_some()),
+        ("proprietary", !input.proprietary.is_empty()),
+        ("unknown", !input.unknown.is_empty()),
+    ];
+    fields
+        .iter()
+        .filter(|(_, set)| *set)
+        .map(|(name, _)| *name)
+        .collect()
+}
+
+/// Returns the names of the fields set in `output`.
+fn output_fields(output: &psbt::Output) -> Vec<&'static str> {
+    let fields = [
+        ("redeem_script", output.redeem_script.is_some()),
+        ("witness_script", output.witness_script.is_some()),
+        ("bip32_derivation", !output.bip32_derivation.is_empty()),
+        ("tap_internal_key", output.tap_internal_key.is_some()),
+        ("tap_tree", output.tap_tree.is_some()),
+        ("tap_key_origins", !output.tap_key_origins.is_empty()),
+        ("proprietary", !output.proprietary.is_empty()),
+        ("unknown", !output.unknown.is_empty()),
+    ];
+    fields
+        .iter()
+        .filter(|(_, set)| *set)
+        .map(|(name, _)| *name)
+        .collect()
+}
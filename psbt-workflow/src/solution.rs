@@ -0,0 +1,57 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The reference solution, built with `--features solution`.
+
+use bitcoin::hashes::Hash;
+use bitcoin::psbt::Psbt;
+use bitcoin::secp256k1::{Message, Secp256k1, Verification};
+use bitcoin::sighash::{EcdsaSighashType, SighashCache};
+use bitcoin::{Transaction, Witness};
+
+use super::input_fields;
+
+/// Builds the witness from the partial signature and extracts the signed transaction.
+pub fn finalizer<C: Verification>(secp: &Secp256k1<C>, psbt: &[u8]) -> Transaction {
+    let mut psbt = Psbt::deserialize(psbt).expect("valid PSBT");
+
+    // Input finalizer: a p2wpkh witness is [sig, pubkey]. Once the input is final everything but
+    // the UTXO is cleared, nobody needs it any more.
+    let input = &mut psbt.inputs[0];
+    let (pk, sig) = input
+        .partial_sigs
+        .iter()
+        .next()
+        .map(|(pk, sig)| (*pk, *sig))
+        .expect("input is signed");
+    input.final_script_witness = Some(Witness::from_slice(&[sig.to_vec(), pk.to_bytes()]));
+    input.partial_sigs.clear();
+    input.bip32_derivation.clear();
+    input.sighash_type = None;
+
+    assert_eq!(
+        input_fields(&psbt.inputs[0]),
+        ["witness_utxo", "final_script_witness"]
+    );
+
+    // Check the signature before we extract, nobody should broadcast a transaction they have not
+    // verified.
+    let spent = psbt.inputs[0]
+        .witness_utxo
+        .clone()
+        .expect("witness_utxo is set");
+    let script_code = spent
+        .script_pubkey
+        .p2wpkh_script_code()
+        .expect("valid p2wpkh script pubkey");
+    let sighash = SighashCache::new(&psbt.unsigned_tx)
+        .segwit_signature_hash(0, &script_code, spent.value, EcdsaSighashType::All)
+        .expect("failed to create sighash");
+    let msg = Message::from_slice(sighash.as_byte_array()).expect("32 byte sighash");
+    secp.verify_ecdsa(&msg, &sig.sig, &pk.inner)
+        .expect("signature verifies against the public key");
+
+    // Transaction extractor.
+    let tx = psbt.extract_tx();
+    assert_eq!(tx.input[0].witness.len(), 2);
+    tx
+}
@@ -0,0 +1,24 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The exercise, replace the `todo!()` with your solution.
+
+use bitcoin::key::KeyPair;
+use bitcoin::secp256k1::{Secp256k1, SecretKey, Signing, Verification};
+use bitcoin::{Transaction, TxOut};
+
+/// Signs the segwit v0 input with `sk` and the taproot input with `keypair`, both with
+/// SIGHASH_SINGLE|ANYONECANPAY.
+///
+/// `prevouts` are the outputs being spent, in input order. The segwit v0 signature has the sighash
+/// type appended as for SIGHASH_ALL, the taproot signature is 65 bytes for any type but
+/// SIGHASH_DEFAULT. With ANYONECANPAY the taproot sighash only needs the output being spent.
+#[allow(unused_variables)]
+pub fn sign<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    unsigned_tx: Transaction,
+    sk: &SecretKey,
+    keypair: &KeyPair,
+    prevouts: &[TxOut],
+) -> Transaction {
+    todo!("sign both inputs with SIGHASH_SINGLE|ANYONECANPAY")
+}
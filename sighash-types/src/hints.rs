@@ -0,0 +1,16 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Hints for the exercise, from least to most specific.
+
+pub const HINTS: &[&str] = &[
+    "Both inputs are signed as usual, only the sighash type changes. The segwit v0 input uses \
+     `EcdsaSighashType::SinglePlusAnyoneCanPay`, the taproot input \
+     `TapSighashType::SinglePlusAnyoneCanPay`.",
+    "For the segwit v0 input the script code is \
+     `prevouts[SEGWIT_INPUT].script_pubkey.p2wpkh_script_code()`, the witness is [sig, pubkey]. \
+     With ANYONECANPAY the taproot sighash only needs `Prevouts::One(TAPROOT_INPUT, \
+     prevouts[TAPROOT_INPUT].clone())`.",
+    "Use one `SighashCache` for both inputs. Sign the taproot sighash with \
+     `keypair.tap_tweak(secp, None)`, its witness is just `taproot::Signature { sig, hash_ty \
+     }.to_vec()`, which is 65 bytes because the sighash type is not the default.",
+];
@@ -0,0 +1,261 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Demonstrate signing with the non-default sighash types SIGHASH_SINGLE|ANYONECANPAY and
+//! SIGHASH_NONE, on both a segwit v0 and a taproot input.
+//!
+//! The sighash type chooses which parts of the transaction a signature commits to:
+//!
+//! - ALL: every input and every output, the transaction can not be changed without re-signing.
+//! - NONE: every input and none of the outputs, anyone can decide where the coins go.
+//! - SINGLE: every input and only the output with the same index as the input being signed.
+//! - ANYONECANPAY (a modifier): only the input being signed, anyone can add more inputs.
+//!
+//! SINGLE|ANYONECANPAY lets a signer say "I put in this coin on the condition that this output is
+//! paid", other parties can then add their own inputs and outputs, e.g. to fund a collaborative
+//! transaction or to bump the fee. NONE is a blank cheque, it is only safe if you do not care who
+//! ends up with the coins.
+
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::hashes::Hash;
+use bitcoin::key::KeyPair;
+use bitcoin::locktime::absolute;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::sighash::{
+    EcdsaSighashType, Prevouts, SegwitV0Sighash, SighashCache, TapSighash, TapSighashType,
+};
+use bitcoin::{
+    OutPoint, PublicKey, Script, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, WPubkeyHash,
+    Witness,
+};
+use workshop_common::taproot::receivers_address;
+use workshop_common::{DUMMY_UTXO_AMOUNT, FEE, SPEND_AMOUNT};
+
+// The exercise is `sign` in `src/exercise.rs`, building with `--features solution` swaps in the
+// reference solution from `src/solution.rs`.
+#[cfg(not(feature = "solution"))]
+mod exercise;
+#[cfg(feature = "solution")]
+#[path = "solution.rs"]
+mod exercise;
+
+use exercise::sign;
+
+mod hints;
+
+/// We spend two dummy utxos.
+const CHANGE_AMOUNT: u64 = 2 * DUMMY_UTXO_AMOUNT - SPEND_AMOUNT - FEE;
+
+/// The index of the segwit v0 input, it is paired with the spend output.
+const SEGWIT_INPUT: usize = 0;
+/// The index of the taproot input, it is paired with the change output.
+const TAPROOT_INPUT: usize = 1;
+
+fn main() {
+    // Stuck? Run `cargo run -- hint 1`.
+    workshop_common::hint_command(hints::HINTS);
+
+    // We need a signing secp256k1 context, if you have not seen this before just pass it in when
+    // needed and otherwise ignore it.
+    let secp = Secp256k1::new();
+
+    // Get the keys we control, one for each input. In a real application these would come from a
+    // stored secret.
+    let sk = workshop_common::secret_key(0);
+    let pk = PublicKey::new(sk.public_key(&secp));
+    let keypair = KeyPair::from_secret_key(&secp, &workshop_common::secret_key(1));
+    let (internal_key, _parity) = keypair.x_only_public_key();
+
+    // Get two unspent outputs, one p2wpkh and one p2tr, locked to the keys above.
+    // In a real application these would come from the chain.
+    let wpkh = pk.wpubkey_hash().expect("key is compressed");
+    let (segwit_out_point, segwit_utxo) =
+        workshop_common::dummy_utxo(0, ScriptBuf::new_v0_p2wpkh(&wpkh));
+    let (taproot_out_point, taproot_utxo) =
+        workshop_common::dummy_utxo(1, ScriptBuf::new_v1_p2tr(&secp, internal_key, None));
+
+    // Get an address to send to.
+    let address = receivers_address();
+
+    // The inputs for the transaction we are constructing.
+    let segwit_input = TxIn {
+        previous_output: segwit_out_point, // The dummy p2wpkh output we are spending.
+        script_sig: ScriptBuf::default(),  // For a p2wpkh script_sig is empty.
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        witness: Witness::default(), // Filled in after signing.
+    };
+    let taproot_input = TxIn {
+        previous_output: taproot_out_point, // The dummy p2tr output we are spending.
+        script_sig: ScriptBuf::default(),   // For a p2tr script_sig is empty.
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        witness: Witness::default(), // Filled in after signing.
+    };
+
+    // The spend output is locked to a key controlled by the receiver.
+    let spend = TxOut {
+        value: SPEND_AMOUNT,
+        script_pubkey: address.script_pubkey(),
+    };
+
+    // The change output is locked to a key controlled by us.
+    let change = TxOut {
+        value: CHANGE_AMOUNT,
+        script_pubkey: ScriptBuf::new_v1_p2tr(&secp, internal_key, None), // Change comes back to us.
+    };
+
+    // The transaction we want to sign and broadcast. With SIGHASH_SINGLE the output index matters,
+    // each input is paired with the output at the same index.
+    let unsigned_tx = Transaction {
+        version: 2,                               // Post BIP-68.
+        lock_time: absolute::LockTime::ZERO,      // Ignore the locktime.
+        input: vec![segwit_input, taproot_input], // Segwit v0 input at index 0, taproot at 1.
+        output: vec![spend, change],              // Spend at index 0, change at 1.
+    };
+
+    // Two modified versions of the transaction to compare sighashes against: one with an input and
+    // an output added by someone else, and one with the spend output sent somewhere else.
+    let extended_tx = extend(&unsigned_tx);
+    let redirected_tx = redirect_spend(&unsigned_tx);
+
+    // For segwit v0 the sighash algorithm is from BIP-143, the script code for p2wpkh is the p2pkh
+    // script for the same key hash.
+    let script_code = segwit_utxo
+        .script_pubkey
+        .p2wpkh_script_code()
+        .expect("valid p2wpkh script pubkey");
+    let segwit_sighash = |tx: &Transaction, ty: EcdsaSighashType| {
+        segwit_v0_sighash(tx, &script_code, segwit_utxo.value, ty)
+    };
+
+    // SIGHASH_ALL commits to everything, any change means a different sighash.
+    let all = EcdsaSighashType::All;
+    assert_ne!(
+        segwit_sighash(&unsigned_tx, all),
+        segwit_sighash(&extended_tx, all)
+    );
+    assert_ne!(
+        segwit_sighash(&unsigned_tx, all),
+        segwit_sighash(&redirected_tx, all)
+    );
+
+    // SIGHASH_SINGLE|ANYONECANPAY commits to our input and the spend output only. Others can add
+    // inputs and outputs but can not touch the spend output.
+    let single_acp = EcdsaSighashType::SinglePlusAnyoneCanPay;
+    assert_eq!(
+        segwit_sighash(&unsigned_tx, single_acp),
+        segwit_sighash(&extended_tx, single_acp)
+    );
+    assert_ne!(
+        segwit_sighash(&unsigned_tx, single_acp),
+        segwit_sighash(&redirected_tx, single_acp)
+    );
+
+    // SIGHASH_NONE commits to all the inputs and none of the outputs. The spend output can be sent
+    // anywhere but no inputs can be added.
+    let none = EcdsaSighashType::None;
+    assert_ne!(
+        segwit_sighash(&unsigned_tx, none),
+        segwit_sighash(&extended_tx, none)
+    );
+    assert_eq!(
+        segwit_sighash(&unsigned_tx, none),
+        segwit_sighash(&redirected_tx, none)
+    );
+
+    // For taproot the sighash algorithm is from BIP-341. Unless ANYONECANPAY is used it commits to
+    // the script pubkeys and values of every output being spent, so all of them are needed.
+    let all_prevouts = [segwit_utxo.clone(), taproot_utxo.clone()];
+    let all_prevouts = Prevouts::All(&all_prevouts);
+    // With ANYONECANPAY only the output being spent by this input is needed.
+    let one_prevout = Prevouts::One(TAPROOT_INPUT, taproot_utxo.clone());
+
+    // SIGHASH_ALL commits to everything, as for segwit v0.
+    let all = TapSighashType::All;
+    assert_ne!(
+        taproot_sighash(&unsigned_tx, &all_prevouts, all),
+        taproot_sighash(&redirected_tx, &all_prevouts, all)
+    );
+
+    // SIGHASH_SINGLE|ANYONECANPAY commits to our input and the change output only. The spend
+    // output is paired with the other input so it can be changed, as can the inputs and outputs
+    // added by someone else.
+    let single_acp = TapSighashType::SinglePlusAnyoneCanPay;
+    let sighash = taproot_sighash(&unsigned_tx, &one_prevout, single_acp);
+    assert_eq!(
+        sighash,
+        taproot_sighash(&extended_tx, &one_prevout, single_acp)
+    );
+    assert_eq!(
+        sighash,
+        taproot_sighash(&redirected_tx, &one_prevout, single_acp)
+    );
+
+    // SIGHASH_NONE commits to all the inputs and none of the outputs.
+    let none = TapSighashType::None;
+    assert_eq!(
+        taproot_sighash(&unsigned_tx, &all_prevouts, none),
+        taproot_sighash(&redirected_tx, &all_prevouts, none)
+    );
+
+    // Finally sign both inputs with SIGHASH_SINGLE|ANYONECANPAY, leaving the transaction open for
+    // others to add inputs and outputs at higher indices. This is the exercise.
+    let prevouts = [segwit_utxo, taproot_utxo];
+    let tx = sign(&secp, unsigned_tx, &sk, &keypair, &prevouts);
+
+    // BOOM! Transaction signed and ready to broadcast (or to be extended by someone else).
+    workshop_common::print_prevouts(&prevouts);
+    println!("{}", serialize_hex(&tx));
+}
+
+/// Returns the BIP-143 sighash for the segwit v0 input of `tx`.
+fn segwit_v0_sighash(
+    tx: &Transaction,
+    script_code: &Script,
+    value: u64,
+    sighash_type: EcdsaSighashType,
+) -> SegwitV0Sighash {
+    SighashCache::new(tx)
+        .segwit_signature_hash(SEGWIT_INPUT, script_code, value, sighash_type)
+        .expect("failed to create sighash")
+}
+
+/// Returns the BIP-341 key path sighash for the taproot input of `tx`.
+fn taproot_sighash(
+    tx: &Transaction,
+    prevouts: &Prevouts<TxOut>,
+    sighash_type: TapSighashType,
+) -> TapSighash {
+    SighashCache::new(tx)
+        .taproot_key_spend_signature_hash(TAPROOT_INPUT, prevouts, sighash_type)
+        .expect("failed to construct sighash")
+}
+
+/// Returns `tx` with an input and an output, belonging to someone else, added at the end.
+fn extend(tx: &Transaction) -> Transaction {
+    let mut tx = tx.clone();
+    tx.input.push(TxIn {
+        previous_output: OutPoint {
+            txid: Txid::all_zeros(), // Obviously invalid.
+            vout: 2,
+        },
+        script_sig: ScriptBuf::default(),
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        witness: Witness::default(),
+    });
+    tx.output.push(TxOut {
+        value: 1_000_000,
+        script_pubkey: someone_elses_script_pubkey(),
+    });
+    tx
+}
+
+/// Returns `tx` with the spend output paid to someone else.
+fn redirect_spend(tx: &Transaction) -> Transaction {
+    let mut tx = tx.clone();
+    tx.output[0].script_pubkey = someone_elses_script_pubkey();
+    tx
+}
+
+/// A script pubkey that neither we nor the receiver control.
+fn someone_elses_script_pubkey() -> ScriptBuf {
+    ScriptBuf::new_v0_p2wpkh(&WPubkeyHash::all_zeros())
+}
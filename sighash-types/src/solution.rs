@@ -0,0 +1,68 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The reference solution, built with `--features solution`.
+
+use bitcoin::hashes::Hash;
+use bitcoin::key::{KeyPair, TapTweak, TweakedKeyPair};
+use bitcoin::secp256k1::{Message, Secp256k1, SecretKey, Signing, Verification};
+use bitcoin::sighash::{EcdsaSighashType, Prevouts, SighashCache, TapSighashType};
+use bitcoin::{ecdsa, taproot, PublicKey, Transaction, TxOut};
+
+use super::{SEGWIT_INPUT, TAPROOT_INPUT};
+
+/// Signs the segwit v0 input with `sk` and the taproot input with `keypair`, both with
+/// SIGHASH_SINGLE|ANYONECANPAY.
+///
+/// `prevouts` are the outputs being spent, in input order.
+pub fn sign<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    unsigned_tx: Transaction,
+    sk: &SecretKey,
+    keypair: &KeyPair,
+    prevouts: &[TxOut],
+) -> Transaction {
+    let mut sighasher = SighashCache::new(unsigned_tx);
+
+    // Sign the segwit v0 input. The sighash type is appended to the DER encoded signature.
+    let spent = &prevouts[SEGWIT_INPUT];
+    let script_code = spent
+        .script_pubkey
+        .p2wpkh_script_code()
+        .expect("valid p2wpkh script pubkey");
+    let sighash_type = EcdsaSighashType::SinglePlusAnyoneCanPay;
+    let sighash = sighasher
+        .segwit_signature_hash(SEGWIT_INPUT, &script_code, spent.value, sighash_type)
+        .expect("failed to create sighash");
+    let msg = Message::from_slice(sighash.as_byte_array()).expect("32 byte sighash");
+    let signature = ecdsa::Signature {
+        sig: secp.sign_ecdsa(&msg, sk),
+        hash_ty: sighash_type,
+    };
+    let witness = sighasher
+        .witness_mut(SEGWIT_INPUT)
+        .expect("getting mutable witness reference should work");
+    witness.push(signature.to_vec());
+    witness.push(PublicKey::new(sk.public_key(secp)).to_bytes());
+
+    // Sign the taproot input with the tweaked key. With SIGHASH_DEFAULT a taproot signature is 64
+    // bytes, for any other type the sighash type is appended as a 65th byte. With ANYONECANPAY
+    // only the output being spent by this input is needed.
+    let sighash_type = TapSighashType::SinglePlusAnyoneCanPay;
+    let prevout = Prevouts::One(TAPROOT_INPUT, prevouts[TAPROOT_INPUT].clone());
+    let sighash = sighasher
+        .taproot_key_spend_signature_hash(TAPROOT_INPUT, &prevout, sighash_type)
+        .expect("failed to construct sighash");
+    let tweaked: TweakedKeyPair = keypair.tap_tweak(secp, None);
+    let msg = Message::from_slice(sighash.as_byte_array()).expect("32 byte sighash");
+    let signature = taproot::Signature {
+        sig: secp.sign_schnorr(&msg, &tweaked.to_inner()),
+        hash_ty: sighash_type,
+    };
+    assert_eq!(signature.to_vec().len(), 65);
+    sighasher
+        .witness_mut(TAPROOT_INPUT)
+        .expect("getting mutable witness reference should work")
+        .push(signature.to_vec());
+
+    sighasher.into_transaction()
+}
@@ -0,0 +1,16 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Hints for the exercise, from least to most specific.
+
+pub const HINTS: &[&str] = &[
+    "A legacy signature commits to a sighash computed from a copy of the transaction, look at \
+     `SighashCache::legacy_signature_hash`. There is no witness, the signature goes in the \
+     input's scriptSig.",
+    "Call `legacy_signature_hash(input_index, script_pubkey, EcdsaSighashType::All.to_u32())`, \
+     the script pubkey being spent stands in for the scriptSig. Make a `Message` with \
+     `Message::from_slice(sighash.as_byte_array())` and sign it with `secp.sign_ecdsa(&msg, sk)`.",
+    "The scriptSig is `<sig> <pubkey>`. Build a `bitcoin::ecdsa::Signature { sig, hash_ty }`, \
+     turn `signature.to_vec()` into a `PushBytesBuf`, and set `tx.input[input_index].script_sig` \
+     to `Builder::new().push_slice(signature).push_key(&pk).into_script()` where `pk` is \
+     `PublicKey::new(sk.public_key(secp))`.",
+];
@@ -0,0 +1,93 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Sign a transaction that spends a legacy p2pkh unspent output.
+//!
+//! Compare with `sign-segwit-v0`: before segwit the signature goes in the scriptSig, which is part
+//! of the txid, and the sighash algorithm does not commit to the value being spent.
+
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::locktime::absolute;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::{PublicKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness};
+use workshop_common::segwit_v0::receivers_address;
+use workshop_common::{CHANGE_AMOUNT, SPEND_AMOUNT};
+
+// The exercise is `sign` in `src/exercise.rs`, building with `--features solution` swaps in the
+// reference solution from `src/solution.rs`.
+#[cfg(not(feature = "solution"))]
+mod exercise;
+#[cfg(feature = "solution")]
+#[path = "solution.rs"]
+mod exercise;
+
+use exercise::sign;
+
+mod hints;
+
+fn main() {
+    // Stuck? Run `cargo run -- hint 1`.
+    workshop_common::hint_command(hints::HINTS);
+
+    // We need a signing secp256k1 context, if you have not seen this before just pass it in when
+    // needed and otherwise ignore it.
+    let secp = Secp256k1::new();
+
+    // Get a secret key we control and the pubkeyhash of the associated pubkey.
+    // In a real application these would come from a stored secret.
+    let sk = workshop_common::secret_key(0);
+    let pk = PublicKey::new(sk.public_key(&secp));
+    let pkh = pk.pubkey_hash();
+
+    // Get an address to send to.
+    let address = receivers_address();
+
+    // Get an unspent output that is locked to the key above that we control.
+    // In a real application these would come from the chain.
+    let (dummy_out_point, dummy_utxo) = workshop_common::dummy_utxo(0, ScriptBuf::new_p2pkh(&pkh));
+
+    // The input for the transaction we are constructing.
+    let input = TxIn {
+        previous_output: dummy_out_point, // The dummy output we are spending.
+        script_sig: ScriptBuf::default(), // Filled in after signing.
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        witness: Witness::default(), // Legacy inputs have no witness.
+    };
+
+    // The spend output is locked to a key controlled by the receiver.
+    let spend = TxOut {
+        value: SPEND_AMOUNT,
+        script_pubkey: address.script_pubkey(),
+    };
+
+    // The change output is locked to a key controlled by us.
+    let change = TxOut {
+        value: CHANGE_AMOUNT,
+        script_pubkey: ScriptBuf::new_p2pkh(&pkh), // Change comes back to us.
+    };
+
+    // The transaction we want to sign and broadcast.
+    let unsigned_tx = Transaction {
+        version: 2,                          // Post BIP-68.
+        lock_time: absolute::LockTime::ZERO, // Ignore the locktime.
+        input: vec![input],                  // Input goes into index 0.
+        output: vec![spend, change],         // Outputs, order does not matter.
+    };
+    let input_index = 0;
+    let unsigned_txid = unsigned_tx.txid();
+
+    // Sign the input, this is the exercise.
+    let tx = sign(
+        &secp,
+        &sk,
+        unsigned_tx,
+        input_index,
+        &dummy_utxo.script_pubkey,
+    );
+
+    // Unlike a segwit spend, signing changed the txid.
+    assert_ne!(tx.txid(), unsigned_txid);
+
+    // BOOM! Transaction signed and ready to broadcast.
+    workshop_common::print_prevouts(&[dummy_utxo]);
+    println!("{}", serialize_hex(&tx));
+}
@@ -0,0 +1,53 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The reference solution, built with `--features solution`.
+
+use bitcoin::hashes::Hash;
+use bitcoin::script::{Builder, PushBytesBuf};
+use bitcoin::secp256k1::{Message, Secp256k1, SecretKey, Signing, Verification};
+use bitcoin::sighash::{EcdsaSighashType, SighashCache};
+use bitcoin::{ecdsa, PublicKey, Script, Transaction};
+
+/// Signs input `input_index` of `unsigned_tx`, it spends a p2pkh output with `script_pubkey`
+/// locked to `sk`.
+pub fn sign<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    sk: &SecretKey,
+    unsigned_tx: Transaction,
+    input_index: usize,
+    script_pubkey: &Script,
+) -> Transaction {
+    let mut tx = unsigned_tx;
+
+    // Get the sighash to sign. The legacy algorithm serializes a copy of the transaction with the
+    // script pubkey being spent in place of this input's scriptSig (and the other scriptSigs
+    // emptied) then hashes it. The value of the output being spent is not included, a signer has
+    // to see the previous transaction to know what they are spending.
+    let sighash_type = EcdsaSighashType::All;
+    let sighash = SighashCache::new(&tx)
+        .legacy_signature_hash(input_index, script_pubkey, sighash_type.to_u32())
+        .expect("failed to create sighash");
+
+    // Sign the sighash using the secp256k1 library (exported by rust-bitcoin).
+    let msg = Message::from_slice(sighash.as_byte_array()).expect("32 byte sighash");
+    let sig = secp.sign_ecdsa(&msg, sk);
+
+    // The signature must verify against our public key.
+    let pk = PublicKey::new(sk.public_key(secp));
+    secp.verify_ecdsa(&msg, &sig, &pk.inner)
+        .expect("signature verifies against our public key");
+
+    // The scriptSig is <sig> <pubkey>, the signature is DER encoded with the sighash type appended
+    // as a single byte.
+    let signature = ecdsa::Signature {
+        sig,
+        hash_ty: sighash_type,
+    };
+    let signature = PushBytesBuf::try_from(signature.to_vec()).expect("signature is short");
+    tx.input[input_index].script_sig = Builder::new()
+        .push_slice(signature)
+        .push_key(&pk)
+        .into_script();
+
+    tx
+}
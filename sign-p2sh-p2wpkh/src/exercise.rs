@@ -0,0 +1,23 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The exercise, replace the `todo!()` with your solution.
+
+use bitcoin::secp256k1::{Secp256k1, SecretKey, Signing, Verification};
+use bitcoin::{Script, Transaction};
+
+/// Signs input `input_index` of `unsigned_tx`, it spends a p2sh output worth `value` locked to
+/// `redeem_script`, the p2wpkh script pubkey for `sk`.
+///
+/// Sign as for native p2wpkh, with the script code from the redeem script, then put the redeem
+/// script in the scriptSig.
+#[allow(unused_variables)]
+pub fn sign<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    sk: &SecretKey,
+    unsigned_tx: Transaction,
+    input_index: usize,
+    redeem_script: &Script,
+    value: u64,
+) -> Transaction {
+    todo!("sign the input and fill in its witness and scriptSig")
+}
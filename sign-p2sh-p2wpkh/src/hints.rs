@@ -0,0 +1,16 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Hints for the exercise, from least to most specific.
+
+pub const HINTS: &[&str] = &[
+    "Nested segwit signs exactly like native p2wpkh, the difference is where the script code \
+     comes from. It is derived from the redeem script, not from the p2sh script pubkey being \
+     spent.",
+    "Get the script code with `redeem_script.to_owned().p2wpkh_script_code()`, then the sighash \
+     with `segwit_signature_hash(input_index, &script_code, value, EcdsaSighashType::All)`. Sign \
+     it with `secp.sign_ecdsa(&msg, sk)`.",
+    "The witness is [sig, pubkey] as for native p2wpkh, push both onto \
+     `sighasher.witness_mut(input_index)`. Then set the scriptSig of the transaction from \
+     `sighasher.into_transaction()` to a single push of the redeem script, \
+     `PushBytesBuf::try_from(redeem_script.to_bytes())` and `Builder::push_slice` do that.",
+];
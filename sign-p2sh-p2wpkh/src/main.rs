@@ -0,0 +1,104 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Sign a transaction that spends a nested segwit (p2sh-p2wpkh) unspent output.
+//!
+//! The p2wpkh script pubkey is wrapped in p2sh so that wallets that predate segwit can pay to it.
+//! The spend needs both a scriptSig (the redeem script) and a witness (signature and pubkey).
+
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::locktime::absolute;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::{
+    Address, Network, PublicKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness,
+};
+use workshop_common::segwit_v0::receivers_address;
+use workshop_common::{CHANGE_AMOUNT, SPEND_AMOUNT};
+
+// The exercise is `sign` in `src/exercise.rs`, building with `--features solution` swaps in the
+// reference solution from `src/solution.rs`.
+#[cfg(not(feature = "solution"))]
+mod exercise;
+#[cfg(feature = "solution")]
+#[path = "solution.rs"]
+mod exercise;
+
+use exercise::sign;
+
+mod hints;
+
+fn main() {
+    // Stuck? Run `cargo run -- hint 1`.
+    workshop_common::hint_command(hints::HINTS);
+
+    // We need a signing secp256k1 context, if you have not seen this before just pass it in when
+    // needed and otherwise ignore it.
+    let secp = Secp256k1::new();
+
+    // Get a secret key we control and the associated pubkey.
+    // In a real application these would come from a stored secret.
+    let sk = workshop_common::secret_key(0);
+    let pk = PublicKey::new(sk.public_key(&secp));
+
+    // The redeem script is the p2wpkh script pubkey `OP_0 <20 byte key hash>`. The p2sh output
+    // commits to the HASH160 of the redeem script.
+    let redeem_script = ScriptBuf::new_v0_p2wpkh(&pk.wpubkey_hash().expect("key is compressed"));
+
+    // This is the address format that starts with a 3 on mainnet.
+    let nested_address = Address::p2shwpkh(&pk, Network::Bitcoin).expect("key is compressed");
+    assert_eq!(
+        nested_address.script_pubkey(),
+        ScriptBuf::new_p2sh(&redeem_script.script_hash())
+    );
+    println!("p2sh-p2wpkh address: {}", nested_address);
+
+    // Get an unspent output that is locked to the redeem script above.
+    // In a real application these would come from the chain.
+    let script_pubkey = ScriptBuf::new_p2sh(&redeem_script.script_hash());
+    let (dummy_out_point, dummy_utxo) = workshop_common::dummy_utxo(0, script_pubkey);
+
+    // Get an address to send to.
+    let address = receivers_address();
+
+    // The input for the transaction we are constructing.
+    let input = TxIn {
+        previous_output: dummy_out_point, // The dummy output we are spending.
+        script_sig: ScriptBuf::default(), // Filled in with the redeem script below.
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        witness: Witness::default(), // Filled in after signing.
+    };
+
+    // The spend output is locked to a key controlled by the receiver.
+    let spend = TxOut {
+        value: SPEND_AMOUNT,
+        script_pubkey: address.script_pubkey(),
+    };
+
+    // The change output is locked to a key controlled by us.
+    let change = TxOut {
+        value: CHANGE_AMOUNT,
+        script_pubkey: nested_address.script_pubkey(), // Change comes back to us.
+    };
+
+    // The transaction we want to sign and broadcast.
+    let unsigned_tx = Transaction {
+        version: 2,                          // Post BIP-68.
+        lock_time: absolute::LockTime::ZERO, // Ignore the locktime.
+        input: vec![input],                  // Input goes into index 0.
+        output: vec![spend, change],         // Outputs, order does not matter.
+    };
+    let input_index = 0;
+
+    // Sign the input, this is the exercise.
+    let tx = sign(
+        &secp,
+        &sk,
+        unsigned_tx,
+        input_index,
+        &redeem_script,
+        dummy_utxo.value,
+    );
+
+    // BOOM! Transaction signed and ready to broadcast.
+    workshop_common::print_prevouts(&[dummy_utxo]);
+    println!("{}", serialize_hex(&tx));
+}
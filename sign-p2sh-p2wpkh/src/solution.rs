@@ -0,0 +1,65 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The reference solution, built with `--features solution`.
+
+use bitcoin::hashes::Hash;
+use bitcoin::script::{Builder, PushBytesBuf};
+use bitcoin::secp256k1::{Message, Secp256k1, SecretKey, Signing, Verification};
+use bitcoin::sighash::{EcdsaSighashType, SighashCache};
+use bitcoin::{ecdsa, PublicKey, Script, Transaction};
+
+/// Signs input `input_index` of `unsigned_tx`, it spends a p2sh output worth `value` locked to
+/// `redeem_script`, the p2wpkh script pubkey for `sk`.
+pub fn sign<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    sk: &SecretKey,
+    unsigned_tx: Transaction,
+    input_index: usize,
+    redeem_script: &Script,
+    value: u64,
+) -> Transaction {
+    // The script code comes from the redeem script, not from the p2sh script pubkey of the output
+    // being spent. Using the wrong one is the classic mistake with nested segwit.
+    let script_code = redeem_script
+        .to_owned()
+        .p2wpkh_script_code()
+        .expect("valid p2wpkh redeem script");
+
+    // Get the sighash to sign. As for native segwit v0 this commits to the value being spent.
+    let sighash_type = EcdsaSighashType::All;
+    let mut sighasher = SighashCache::new(unsigned_tx);
+    let sighash = sighasher
+        .segwit_signature_hash(input_index, &script_code, value, sighash_type)
+        .expect("failed to create sighash");
+
+    // Sign the sighash using the secp256k1 library (exported by rust-bitcoin).
+    let msg = Message::from_slice(sighash.as_byte_array()).expect("32 byte sighash");
+    let sig = secp.sign_ecdsa(&msg, sk);
+
+    // The signature must verify against our public key.
+    let pk = PublicKey::new(sk.public_key(secp));
+    secp.verify_ecdsa(&msg, &sig, &pk.inner)
+        .expect("signature verifies against our public key");
+
+    // Update the witness stack, it is [sig, pubkey] exactly as for native p2wpkh.
+    let signature = ecdsa::Signature {
+        sig,
+        hash_ty: sighash_type,
+    };
+    let witness = sighasher
+        .witness_mut(input_index)
+        .expect("getting mutable witness reference should work");
+    witness.push(signature.to_vec());
+    witness.push(pk.to_bytes());
+
+    // Get the signed transaction.
+    let mut tx = sighasher.into_transaction();
+
+    // The scriptSig is a single push of the redeem script. It is not signed (no signature can
+    // commit to the scriptSig) but it is fixed by the p2sh hash so it can not be malleated.
+    let redeem_script_bytes =
+        PushBytesBuf::try_from(redeem_script.to_bytes()).expect("redeem script is short");
+    tx.input[input_index].script_sig = Builder::new().push_slice(redeem_script_bytes).into_script();
+
+    tx
+}
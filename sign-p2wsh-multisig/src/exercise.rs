@@ -0,0 +1,23 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The exercise, replace the `todo!()` with your solution.
+
+use bitcoin::secp256k1::{Secp256k1, SecretKey, Signing, Verification};
+use bitcoin::{Script, Transaction};
+
+/// Signs input `input_index` of `unsigned_tx` with each of `signers`, it spends a p2wsh output
+/// worth `value` locked to the multisig `witness_script`.
+///
+/// `signers` are in the same order as their public keys appear in `witness_script`. Get the
+/// BIP-143 sighash, sign it with every key, and build the witness OP_CHECKMULTISIG expects.
+#[allow(unused_variables)]
+pub fn sign<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    signers: &[SecretKey],
+    unsigned_tx: Transaction,
+    input_index: usize,
+    witness_script: &Script,
+    value: u64,
+) -> Transaction {
+    todo!("sign the input with every signer and fill in its witness")
+}
@@ -0,0 +1,15 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Hints for the exercise, from least to most specific.
+
+pub const HINTS: &[&str] = &[
+    "For p2wsh the script code is the witness script itself, so every signer signs the same \
+     `segwit_signature_hash(input_index, witness_script, value, EcdsaSighashType::All)`.",
+    "Sign the `Message` with each of `signers` using `secp.sign_ecdsa(&msg, sk)`, and wrap each \
+     signature in a `bitcoin::ecdsa::Signature { sig, hash_ty }` so the sighash type byte gets \
+     appended.",
+    "OP_CHECKMULTISIG pops one element too many, so the witness starts with an empty element. \
+     Push `[]`, then each `signature.to_vec()` in the order of `signers`, then \
+     `witness_script.as_bytes()`, and get the transaction back with \
+     `sighasher.into_transaction()`.",
+];
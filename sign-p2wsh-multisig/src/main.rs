@@ -0,0 +1,116 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Sign a transaction that spends a 2-of-3 p2wsh multisig output.
+
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::locktime::absolute;
+use bitcoin::opcodes::all::{OP_CHECKMULTISIG, OP_PUSHNUM_2, OP_PUSHNUM_3};
+use bitcoin::script::Builder;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::{
+    Address, Network, PublicKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness,
+};
+use workshop_common::segwit_v0::receivers_address;
+use workshop_common::{CHANGE_AMOUNT, SPEND_AMOUNT};
+
+// The exercise is `sign` in `src/exercise.rs`, building with `--features solution` swaps in the
+// reference solution from `src/solution.rs`.
+#[cfg(not(feature = "solution"))]
+mod exercise;
+#[cfg(feature = "solution")]
+#[path = "solution.rs"]
+mod exercise;
+
+use exercise::sign;
+
+mod hints;
+
+fn main() {
+    // Stuck? Run `cargo run -- hint 1`.
+    workshop_common::hint_command(hints::HINTS);
+
+    // We need a signing secp256k1 context, if you have not seen this before just pass it in when
+    // needed and otherwise ignore it.
+    let secp = Secp256k1::new();
+
+    // Get three keys, e.g. held by three different people or on three different devices.
+    // In a real application these would come from stored secrets.
+    let keys = [0, 1, 2].map(|n| {
+        let sk = workshop_common::secret_key(n);
+        (sk, PublicKey::new(sk.public_key(&secp)))
+    });
+    let pubkeys = [keys[0].1, keys[1].1, keys[2].1];
+
+    // The witness script: any two signatures for these three keys.
+    let witness_script = multisig_2_of_3(&pubkeys);
+
+    // The p2wsh address commits to the SHA256 hash of the witness script, the script itself is
+    // only revealed when the output is spent.
+    let multisig_address = Address::p2wsh(&witness_script, Network::Bitcoin);
+    println!("2-of-3 multisig address: {}", multisig_address);
+
+    // Get an unspent output that is locked to the witness script above.
+    // In a real application these would come from the chain.
+    let (dummy_out_point, dummy_utxo) =
+        workshop_common::dummy_utxo(0, ScriptBuf::new_v0_p2wsh(&witness_script.wscript_hash()));
+
+    // Get an address to send to.
+    let address = receivers_address();
+
+    // The input for the transaction we are constructing.
+    let input = TxIn {
+        previous_output: dummy_out_point, // The dummy output we are spending.
+        script_sig: ScriptBuf::default(), // For a p2wsh script_sig is empty.
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        witness: Witness::default(), // Filled in after signing.
+    };
+
+    // The spend output is locked to a key controlled by the receiver.
+    let spend = TxOut {
+        value: SPEND_AMOUNT,
+        script_pubkey: address.script_pubkey(),
+    };
+
+    // The change output goes back to the multisig.
+    let change = TxOut {
+        value: CHANGE_AMOUNT,
+        script_pubkey: multisig_address.script_pubkey(),
+    };
+
+    // The transaction we want to sign and broadcast.
+    let unsigned_tx = Transaction {
+        version: 2,                          // Post BIP-68.
+        lock_time: absolute::LockTime::ZERO, // Ignore the locktime.
+        input: vec![input],                  // Input goes into index 0.
+        output: vec![spend, change],         // Outputs, order does not matter.
+    };
+    let input_index = 0;
+
+    // Sign with the first and the third key, the second key holder is not around. This is the
+    // exercise.
+    let signers = [keys[0].0, keys[2].0];
+    let tx = sign(
+        &secp,
+        &signers,
+        unsigned_tx,
+        input_index,
+        &witness_script,
+        dummy_utxo.value,
+    );
+
+    // BOOM! Transaction signed and ready to broadcast.
+    workshop_common::print_prevouts(&[dummy_utxo]);
+    println!("{}", serialize_hex(&tx));
+}
+
+/// Returns the witness script `OP_2 <pk1> <pk2> <pk3> OP_3 OP_CHECKMULTISIG`.
+fn multisig_2_of_3(pubkeys: &[PublicKey; 3]) -> ScriptBuf {
+    Builder::new()
+        .push_opcode(OP_PUSHNUM_2)
+        .push_key(&pubkeys[0])
+        .push_key(&pubkeys[1])
+        .push_key(&pubkeys[2])
+        .push_opcode(OP_PUSHNUM_3)
+        .push_opcode(OP_CHECKMULTISIG)
+        .into_script()
+}
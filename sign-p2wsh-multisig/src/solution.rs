@@ -0,0 +1,60 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The reference solution, built with `--features solution`.
+
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::{Message, Secp256k1, SecretKey, Signing, Verification};
+use bitcoin::sighash::{EcdsaSighashType, SighashCache};
+use bitcoin::{ecdsa, Script, Transaction};
+
+/// Signs input `input_index` of `unsigned_tx` with each of `signers`, it spends a p2wsh output
+/// worth `value` locked to the multisig `witness_script`.
+///
+/// `signers` are in the same order as their public keys appear in `witness_script`.
+pub fn sign<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    signers: &[SecretKey],
+    unsigned_tx: Transaction,
+    input_index: usize,
+    witness_script: &Script,
+    value: u64,
+) -> Transaction {
+    // Get the sighash to sign. For p2wsh the script code is the witness script itself.
+    let sighash_type = EcdsaSighashType::All;
+    let mut sighasher = SighashCache::new(unsigned_tx);
+    let sighash = sighasher
+        .segwit_signature_hash(input_index, witness_script, value, sighash_type)
+        .expect("failed to create sighash");
+    let msg = Message::from_slice(sighash.as_byte_array()).expect("32 byte sighash");
+
+    // Every signer signs the same sighash.
+    let signatures = signers
+        .iter()
+        .map(|sk| {
+            let sig = secp.sign_ecdsa(&msg, sk);
+            secp.verify_ecdsa(&msg, &sig, &sk.public_key(secp))
+                .expect("signature verifies against the public key");
+            ecdsa::Signature {
+                sig,
+                hash_ty: sighash_type,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // Update the witness stack. OP_CHECKMULTISIG pops one element more than it should (an
+    // off-by-one bug in the original implementation that is now consensus) so the stack starts
+    // with an empty element. The signatures must be in the same order as their public keys appear
+    // in the script, OP_CHECKMULTISIG only walks forward through the keys. The witness script
+    // comes last.
+    let witness = sighasher
+        .witness_mut(input_index)
+        .expect("getting mutable witness reference should work");
+    witness.push([]);
+    for signature in &signatures {
+        witness.push(signature.to_vec());
+    }
+    witness.push(witness_script.as_bytes());
+
+    // Get the signed transaction.
+    sighasher.into_transaction()
+}
@@ -0,0 +1,22 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The exercise, replace the `todo!()` with your solution.
+
+use bitcoin::secp256k1::{Secp256k1, SecretKey, Signing, Verification};
+use bitcoin::{Script, Transaction};
+
+/// Signs input `input_index` of `unsigned_tx`, it spends a p2wpkh output worth `value` locked to
+/// `sk`.
+///
+/// Get the BIP-143 sighash, sign it, and put the signature and our public key in the witness.
+#[allow(unused_variables)]
+pub fn sign<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    sk: &SecretKey,
+    unsigned_tx: Transaction,
+    input_index: usize,
+    script_code: &Script,
+    value: u64,
+) -> Transaction {
+    todo!("sign the input and fill in its witness")
+}
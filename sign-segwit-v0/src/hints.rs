@@ -0,0 +1,16 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Hints for the exercise, from least to most specific.
+
+pub const HINTS: &[&str] = &[
+    "The signature commits to the sighash of the input, not to the raw transaction. \
+     `SighashCache` computes sighashes, look at `segwit_signature_hash`.",
+    "`SighashCache::new(unsigned_tx)` takes the transaction, then \
+     `segwit_signature_hash(input_index, script_code, value, EcdsaSighashType::All)` gives the \
+     sighash. Make a `Message` with `Message::from_slice(sighash.as_byte_array())` and sign it \
+     with `secp.sign_ecdsa(&msg, sk)`.",
+    "The p2wpkh witness is [sig, pubkey]. The sig is `sig.serialize_der()` with the sighash type \
+     appended as one byte (`EcdsaSighashType::All.to_u32() as u8`), the pubkey is \
+     `sk.public_key(secp).serialize()`. Push both onto `sighasher.witness_mut(input_index)` and \
+     get the signed transaction back with `sighasher.into_transaction()`.",
+];
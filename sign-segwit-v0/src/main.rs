@@ -2,22 +2,31 @@
 
 //! Sign a transaction that spends an p2wpkh unspent output.
 
-use std::str::FromStr;
-
-use bitcoin::hashes::Hash;
+use bitcoin::consensus::encode::serialize_hex;
 use bitcoin::locktime::absolute;
-use bitcoin::secp256k1::{rand, Message, Secp256k1, SecretKey, Signing};
-use bitcoin::sighash::{EcdsaSighashType, SighashCache};
-use bitcoin::{
-    Address, Network, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, WPubkeyHash,
-    Witness,
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::{ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness};
+use workshop_common::segwit_v0::{
+    dummy_unspent_transaction_output, receivers_address, senders_keys,
 };
+use workshop_common::{CHANGE_AMOUNT, DUMMY_UTXO_AMOUNT, SPEND_AMOUNT};
+
+// The exercise is `sign` in `src/exercise.rs`, building with `--features solution` swaps in the
+// reference solution from `src/solution.rs`.
+#[cfg(not(feature = "solution"))]
+mod exercise;
+#[cfg(feature = "solution")]
+#[path = "solution.rs"]
+mod exercise;
+
+use exercise::sign;
 
-const DUMMY_UTXO_AMOUNT: u64 = 20_000_000;
-const SPEND_AMOUNT: u64 = 5_000_000;
-const CHANGE_AMOUNT: u64 = 14_999_000; // 1000 sat fee.
+mod hints;
 
 fn main() {
+    // Stuck? Run `cargo run -- hint 1`.
+    workshop_common::hint_command(hints::HINTS);
+
     // We need a signing secp256k1 context, if you have not seen this before just pass it in when
     // needed and otherwise ignore it.
     let secp = Secp256k1::new();
@@ -33,69 +42,53 @@ fn main() {
     // In a real application these would come from the chain.
     let (dummy_out_point, dummy_utxo) = dummy_unspent_transaction_output(&wpkh);
 
-    // The script code required to spend a p2wpkh output.
-    let script_code = todo!();
+    // The script code required to spend a p2wpkh output, this is the p2pkh script for the same
+    // key hash (BIP-143).
+    let script_code = dummy_utxo
+        .script_pubkey
+        .p2wpkh_script_code()
+        .expect("valid p2wpkh script pubkey");
 
     // The input for the transaction we are constructing.
-    let input = todo!();
+    let input = TxIn {
+        previous_output: dummy_out_point, // The dummy output we are spending.
+        script_sig: ScriptBuf::default(), // For a p2wpkh script_sig is empty.
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        witness: Witness::default(), // Filled in after signing.
+    };
 
     // The spend output is locked to a key controlled by the receiver.
-    let spend = todo!();
+    let spend = TxOut {
+        value: SPEND_AMOUNT,
+        script_pubkey: address.script_pubkey(),
+    };
 
     // The change output is locked to a key controlled by us.
-    let change = todo!();
-
-    // The transaction we want to sign and broadcast.
-    let mut unsigned_tx = todo!();
-
-    //
-    // TODO: Sign the unsigned transaction.
-    //
-}
-
-/// An example of keys controlled by the transaction sender.
-///
-/// In a real application these would be actual secrets.
-fn senders_keys<C: Signing>(secp: &Secp256k1<C>) -> (SecretKey, WPubkeyHash) {
-    let sk = SecretKey::new(&mut rand::thread_rng());
-    let pk = bitcoin::PublicKey::new(sk.public_key(secp));
-    let wpkh = pk.wpubkey_hash().expect("key is compressed");
-
-    (sk, wpkh)
-}
-
-/// A dummy address for the receiver.
-///
-/// We lock the spend output to the key associated with this address.
-///
-/// (FWIW this is an arbitrary mainnet address.)
-fn receivers_address() -> Address {
-    Address::from_str("bc1q7cyrfmck2ffu2ud3rn5l5a8yv6f0chkp0zpemf")
-        .expect("a valid address")
-        .require_network(Network::Bitcoin)
-        .expect("valid address for mainnet")
-}
-
-/// Creates a p2wpkh output locked to the key associated with `wpkh`.
-///
-/// An utxo is described by the `OutPoint` (txid and index within the transaction that it was
-/// created). Using the out point one can get the transaction by `txid` and using the `vout` get the
-/// transaction value and script pubkey (`TxOut`) of the utxo.
-///
-/// This output is locked to keys that we control, in a real application this would be a valid
-/// output taken from a transaction that appears in the chain.
-fn dummy_unspent_transaction_output(wpkh: &WPubkeyHash) -> (OutPoint, TxOut) {
-    let script_pubkey = ScriptBuf::new_v0_p2wpkh(wpkh);
-
-    let out_point = OutPoint {
-        txid: Txid::all_zeros(), // Obviously invalid.
-        vout: 0,
+    let change = TxOut {
+        value: CHANGE_AMOUNT,
+        script_pubkey: ScriptBuf::new_v0_p2wpkh(&wpkh), // Change comes back to us.
     };
 
-    let utxo = TxOut {
-        value: DUMMY_UTXO_AMOUNT,
-        script_pubkey,
+    // The transaction we want to sign and broadcast.
+    let unsigned_tx = Transaction {
+        version: 2,                          // Post BIP-68.
+        lock_time: absolute::LockTime::ZERO, // Ignore the locktime.
+        input: vec![input],                  // Input goes into index 0.
+        output: vec![spend, change],         // Outputs, order does not matter.
     };
-
-    (out_point, utxo)
+    let input_index = 0;
+
+    // Sign the input, this is the exercise.
+    let tx = sign(
+        &secp,
+        &sk,
+        unsigned_tx,
+        input_index,
+        &script_code,
+        DUMMY_UTXO_AMOUNT,
+    );
+
+    // BOOM! Transaction signed and ready to broadcast.
+    workshop_common::print_prevouts(&[dummy_utxo]);
+    println!("{}", serialize_hex(&tx));
 }
@@ -0,0 +1,48 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The reference solution, built with `--features solution`.
+
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::{Message, Secp256k1, SecretKey, Signing, Verification};
+use bitcoin::sighash::{EcdsaSighashType, SighashCache};
+use bitcoin::{Script, Transaction};
+
+/// Signs input `input_index` of `unsigned_tx`, it spends a p2wpkh output worth `value` locked to
+/// `sk`.
+pub fn sign<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    sk: &SecretKey,
+    unsigned_tx: Transaction,
+    input_index: usize,
+    script_code: &Script,
+    value: u64,
+) -> Transaction {
+    // Get the sighash to sign. Segwit v0 sighashes commit to the value of the output being spent.
+    let sighash_type = EcdsaSighashType::All;
+    let mut sighasher = SighashCache::new(unsigned_tx);
+    let sighash = sighasher
+        .segwit_signature_hash(input_index, script_code, value, sighash_type)
+        .expect("failed to create sighash");
+
+    // Sign the sighash using the secp256k1 library (exported by rust-bitcoin).
+    let msg = Message::from_slice(sighash.as_byte_array()).expect("32 byte sighash");
+    let sig = secp.sign_ecdsa(&msg, sk);
+
+    // The signature must verify against our public key.
+    let pk = sk.public_key(secp);
+    secp.verify_ecdsa(&msg, &sig, &pk)
+        .expect("signature verifies against our public key");
+
+    // Update the witness stack, it is [sig, pubkey]. The signature is DER encoded with the sighash
+    // type appended as a single byte.
+    let mut sig_with_flag = sig.serialize_der().to_vec();
+    sig_with_flag.push(sighash_type.to_u32() as u8);
+    let witness = sighasher
+        .witness_mut(input_index)
+        .expect("getting mutable witness reference should work");
+    witness.push(sig_with_flag);
+    witness.push(pk.serialize());
+
+    // Get the signed transaction.
+    sighasher.into_transaction()
+}
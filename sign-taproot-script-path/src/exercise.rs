@@ -0,0 +1,26 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The exercise, replace the `todo!()` with your solution.
+
+use bitcoin::key::KeyPair;
+use bitcoin::secp256k1::{Secp256k1, Signing, Verification};
+use bitcoin::taproot::TaprootSpendInfo;
+use bitcoin::{Script, Transaction, TxOut};
+
+/// Signs input `input_index` of `unsigned_tx` with a script path spend of `leaf_script`, a leaf of
+/// the tree in `spend_info`. `prevouts` are the outputs spent by all the inputs.
+///
+/// The sighash commits to the leaf hash, sign it with the untweaked leaf key. The witness is the
+/// signature, then the script, then the control block from `spend_info`.
+#[allow(unused_variables)]
+pub fn sign<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    leaf_keypair: &KeyPair,
+    unsigned_tx: Transaction,
+    input_index: usize,
+    prevouts: &[TxOut],
+    spend_info: &TaprootSpendInfo,
+    leaf_script: &Script,
+) -> Transaction {
+    todo!("sign the input and fill in its witness")
+}
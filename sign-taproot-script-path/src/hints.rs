@@ -0,0 +1,14 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Hints for the exercise, from least to most specific.
+
+pub const HINTS: &[&str] = &[
+    "A script path sighash commits to the leaf being executed. Get the leaf hash with \
+     `TapLeafHash::from_script(leaf_script, LeafVersion::TapScript)` and look at \
+     `SighashCache::taproot_script_spend_signature_hash`.",
+    "OP_CHECKSIG checks the signature against the key in the script as is, so there is no tweak. \
+     Sign with `secp.sign_schnorr(&msg, leaf_keypair)`.",
+    "The witness is the signature, then the script, then the control block. Get the control block \
+     with `spend_info.control_block(&(leaf_script.to_owned(), LeafVersion::TapScript))` and push \
+     `control_block.serialize()` last.",
+];
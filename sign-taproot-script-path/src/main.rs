@@ -0,0 +1,115 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Demonstrate spending a p2tr output via the script path.
+
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::key::{KeyPair, XOnlyPublicKey};
+use bitcoin::locktime::absolute;
+use bitcoin::opcodes::all::OP_CHECKSIG;
+use bitcoin::script::Builder;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::taproot::TaprootBuilder;
+use bitcoin::{ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness};
+use workshop_common::taproot::receivers_address;
+use workshop_common::{CHANGE_AMOUNT, SPEND_AMOUNT};
+
+// The exercise is `sign` in `src/exercise.rs`, building with `--features solution` swaps in the
+// reference solution from `src/solution.rs`.
+#[cfg(not(feature = "solution"))]
+mod exercise;
+#[cfg(feature = "solution")]
+#[path = "solution.rs"]
+mod exercise;
+
+use exercise::sign;
+
+mod hints;
+
+fn main() {
+    // Stuck? Run `cargo run -- hint 1`.
+    workshop_common::hint_command(hints::HINTS);
+
+    // We need a signing secp256k1 context, if you have not seen this before just pass it in when
+    // needed and otherwise ignore it.
+    let secp = Secp256k1::new();
+
+    // Get the keys we control. The internal key could spend via the key path, here we only use
+    // the key in the leaf script. In a real application the internal key could instead be provably
+    // unspendable, if the key path should be disabled.
+    let internal_keypair = KeyPair::from_secret_key(&secp, &workshop_common::secret_key(0));
+    let (internal_key, _parity) = internal_keypair.x_only_public_key();
+    let leaf_keypair = KeyPair::from_secret_key(&secp, &workshop_common::secret_key(1));
+    let (leaf_key, _parity) = leaf_keypair.x_only_public_key();
+
+    // The one and only leaf of the script tree: `<leaf_key> OP_CHECKSIG`.
+    let leaf_script = checksig_script(&leaf_key);
+
+    // Commit to the script tree. A single leaf sits at depth 0, the output key is the internal key
+    // tweaked with the root of the tree (the leaf hash in this case).
+    let spend_info = TaprootBuilder::new()
+        .add_leaf(0, leaf_script.clone())
+        .expect("valid depth for a single leaf")
+        .finalize(&secp, internal_key)
+        .expect("the tree is complete");
+
+    // Get an unspent output that is locked to the script tree above.
+    // In a real application these would come from the chain.
+    let (dummy_out_point, dummy_utxo) =
+        workshop_common::dummy_utxo(0, ScriptBuf::new_v1_p2tr_tweaked(spend_info.output_key()));
+
+    // Get an address to send to.
+    let address = receivers_address();
+
+    // The input for the transaction we are constructing.
+    let input = TxIn {
+        previous_output: dummy_out_point, // The dummy output we are spending.
+        script_sig: ScriptBuf::default(), // For a p2tr script_sig is empty.
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        witness: Witness::default(), // Filled in after signing.
+    };
+
+    // The spend output is locked to a key controlled by the receiver.
+    let spend = TxOut {
+        value: SPEND_AMOUNT,
+        script_pubkey: address.script_pubkey(),
+    };
+
+    // The change output goes back to the same script tree.
+    let change = TxOut {
+        value: CHANGE_AMOUNT,
+        script_pubkey: ScriptBuf::new_v1_p2tr_tweaked(spend_info.output_key()),
+    };
+
+    // The transaction we want to sign and broadcast.
+    let unsigned_tx = Transaction {
+        version: 2,                          // Post BIP-68.
+        lock_time: absolute::LockTime::ZERO, // Ignore the locktime.
+        input: vec![input],                  // Input goes into index 0.
+        output: vec![spend, change],         // Outputs, order does not matter.
+    };
+    let input_index = 0;
+
+    // Sign the input, this is the exercise.
+    let prevouts = [dummy_utxo];
+    let tx = sign(
+        &secp,
+        &leaf_keypair,
+        unsigned_tx,
+        input_index,
+        &prevouts,
+        &spend_info,
+        &leaf_script,
+    );
+
+    // BOOM! Transaction signed and ready to broadcast.
+    workshop_common::print_prevouts(&prevouts);
+    println!("{}", serialize_hex(&tx));
+}
+
+/// Returns a tapscript that can be satisfied by a signature from `key`.
+fn checksig_script(key: &XOnlyPublicKey) -> ScriptBuf {
+    Builder::new()
+        .push_x_only_key(key)
+        .push_opcode(OP_CHECKSIG)
+        .into_script()
+}
@@ -0,0 +1,69 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The reference solution, built with `--features solution`.
+
+use bitcoin::hashes::Hash;
+use bitcoin::key::KeyPair;
+use bitcoin::secp256k1::{Message, Secp256k1, Signing, Verification};
+use bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
+use bitcoin::taproot::{LeafVersion, TapLeafHash, TaprootSpendInfo};
+use bitcoin::{Script, Transaction, TxOut};
+
+/// Signs input `input_index` of `unsigned_tx` with a script path spend of `leaf_script`, a leaf of
+/// the tree in `spend_info`. `prevouts` are the outputs spent by all the inputs.
+pub fn sign<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    leaf_keypair: &KeyPair,
+    unsigned_tx: Transaction,
+    input_index: usize,
+    prevouts: &[TxOut],
+    spend_info: &TaprootSpendInfo,
+    leaf_script: &Script,
+) -> Transaction {
+    // A script path sighash commits to the leaf being executed, identified by its leaf hash.
+    let leaf_hash = TapLeafHash::from_script(leaf_script, LeafVersion::TapScript);
+
+    // Get the sighash to sign.
+    let sighash_type = TapSighashType::Default;
+    let prevouts = Prevouts::All(prevouts);
+
+    let mut sighasher = SighashCache::new(unsigned_tx);
+    let sighash = sighasher
+        .taproot_script_spend_signature_hash(input_index, &prevouts, leaf_hash, sighash_type)
+        .expect("failed to construct sighash");
+
+    // Sign with the leaf key. Unlike a key path spend there is no tweak, OP_CHECKSIG checks the
+    // signature against the key in the script as is.
+    let msg = Message::from_slice(sighash.as_byte_array()).expect("32 byte sighash");
+    let sig = secp.sign_schnorr(&msg, leaf_keypair);
+    let (leaf_key, _parity) = leaf_keypair.x_only_public_key();
+    secp.verify_schnorr(&sig, &msg, &leaf_key)
+        .expect("signature verifies against the leaf key");
+
+    // The control block proves the leaf is in the tree committed to by the output key. It holds
+    // the leaf version, the parity of the output key, the internal key and the merkle path (empty
+    // for a single leaf).
+    let control_block = spend_info
+        .control_block(&(leaf_script.to_owned(), LeafVersion::TapScript))
+        .expect("leaf is in the tree");
+    assert!(control_block.verify_taproot_commitment(
+        secp,
+        spend_info.output_key().to_inner(),
+        leaf_script
+    ));
+
+    // Update the witness stack: the inputs to the script, then the script, then the control block.
+    let signature = bitcoin::taproot::Signature {
+        sig,
+        hash_ty: sighash_type,
+    };
+    let witness = sighasher
+        .witness_mut(input_index)
+        .expect("getting mutable witness reference should work");
+    witness.push(signature.to_vec());
+    witness.push(leaf_script.as_bytes());
+    witness.push(control_block.serialize());
+
+    // Get the signed transaction.
+    sighasher.into_transaction()
+}
@@ -0,0 +1,22 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The exercise, replace the `todo!()` with your solution.
+
+use bitcoin::key::KeyPair;
+use bitcoin::secp256k1::{Secp256k1, Signing, Verification};
+use bitcoin::{Transaction, TxOut};
+
+/// Signs input `input_index` of `unsigned_tx` with a key path spend, `prevouts` are the outputs
+/// spent by all the inputs.
+///
+/// Get the BIP-341 sighash, sign it with the tweaked key, and put the signature in the witness.
+#[allow(unused_variables)]
+pub fn sign<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    keypair: &KeyPair,
+    unsigned_tx: Transaction,
+    input_index: usize,
+    prevouts: &[TxOut],
+) -> Transaction {
+    todo!("sign the input and fill in its witness")
+}
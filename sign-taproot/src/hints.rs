@@ -0,0 +1,16 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Hints for the exercise, from least to most specific.
+
+pub const HINTS: &[&str] = &[
+    "A taproot sighash commits to all the outputs being spent, not just the one this input \
+     spends. Wrap `prevouts` in `Prevouts::All` and look at \
+     `SighashCache::taproot_key_spend_signature_hash`.",
+    "Use `TapSighashType::Default`. The output key is the internal key tweaked with the (empty) \
+     script tree, get the tweaked key pair with `keypair.tap_tweak(secp, None)` and sign the \
+     `Message` with `secp.sign_schnorr(&msg, &tweaked.to_inner())`.",
+    "With `TapSighashType::Default` the witness is a single element, the 64 byte signature with \
+     no sighash type byte. Build a `bitcoin::taproot::Signature { sig, hash_ty }`, push \
+     `signature.to_vec()` onto `sighasher.witness_mut(input_index)`, and get the signed \
+     transaction back with `sighasher.into_transaction()`.",
+];
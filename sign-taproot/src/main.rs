@@ -2,22 +2,29 @@
 
 //! Demonstrate creating a transaction that spends to and from p2tr outputs.
 
-use std::str::FromStr;
-
-use bitcoin::hashes::Hash;
-use bitcoin::key::{KeyPair, TapTweak, TweakedKeyPair, UntweakedPublicKey};
+use bitcoin::consensus::encode::serialize_hex;
 use bitcoin::locktime::absolute;
-use bitcoin::secp256k1::{rand, Message, Secp256k1, SecretKey, Signing, Verification};
-use bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
-use bitcoin::{
-    Address, Network, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness,
-};
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::{ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness};
+use workshop_common::taproot::{dummy_unspent_transaction_output, receivers_address, senders_keys};
+use workshop_common::{CHANGE_AMOUNT, SPEND_AMOUNT};
+
+// The exercise is `sign` in `src/exercise.rs`, building with `--features solution` swaps in the
+// reference solution from `src/solution.rs`.
+#[cfg(not(feature = "solution"))]
+mod exercise;
+#[cfg(feature = "solution")]
+#[path = "solution.rs"]
+mod exercise;
 
-const DUMMY_UTXO_AMOUNT: u64 = 20_000_000;
-const SPEND_AMOUNT: u64 = 5_000_000;
-const CHANGE_AMOUNT: u64 = 14_999_000; // 1000 sat fee.
+use exercise::sign;
+
+mod hints;
 
 fn main() {
+    // Stuck? Run `cargo run -- hint 1`.
+    workshop_common::hint_command(hints::HINTS);
+
     // We need a signing secp256k1 context, if you have not seen this before just pass it in when
     // needed and otherwise ignore it.
     let secp = Secp256k1::new();
@@ -34,65 +41,39 @@ fn main() {
     let address = receivers_address();
 
     // The input for the transaction we are constructing.
-    let input = todo!();
+    let input = TxIn {
+        previous_output: dummy_out_point, // The dummy output we are spending.
+        script_sig: ScriptBuf::default(), // For a p2tr script_sig is empty.
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        witness: Witness::default(), // Filled in after signing.
+    };
 
     // The spend output is locked to a key controlled by the receiver.
-    let spend = todo!();
+    let spend = TxOut {
+        value: SPEND_AMOUNT,
+        script_pubkey: address.script_pubkey(),
+    };
 
     // The change output is locked to a key controlled by us.
-    let change = todo!();
+    let change = TxOut {
+        value: CHANGE_AMOUNT,
+        script_pubkey: ScriptBuf::new_v1_p2tr(&secp, internal_key, None), // Change comes back to us.
+    };
 
     // The transaction we want to sign and broadcast.
-    let mut unsigned_tx = todo!();
-
-    //
-    // TODO: Sign the unsigned transaction.
-    //
-}
-
-/// An example of keys controlled by the transaction sender.
-///
-/// In a real application these would be actual secrets.
-fn senders_keys<C: Signing>(secp: &Secp256k1<C>) -> KeyPair {
-    let sk = SecretKey::new(&mut rand::thread_rng());
-    KeyPair::from_secret_key(secp, &sk)
-}
-
-/// A dummy address for the receiver.
-///
-/// We lock the spend output to the key associated with this address.
-///
-/// (FWIW this is an arbitrary mainnet address from block 805222.)
-fn receivers_address() -> Address {
-    Address::from_str("bc1p0dq0tzg2r780hldthn5mrznmpxsxc0jux5f20fwj0z3wqxxk6fpqm7q0va")
-        .expect("a valid address")
-        .require_network(Network::Bitcoin)
-        .expect("valid address for mainnet")
-}
-
-/// Creates a p2wpkh output locked to the key associated with `wpkh`.
-///
-/// An utxo is described by the `OutPoint` (txid and index within the transaction that it was
-/// created). Using the out point one can get the transaction by `txid` and using the `vout` get the
-/// transaction value and script pubkey (`TxOut`) of the utxo.
-///
-/// This output is locked to keys that we control, in a real application this would be a valid
-/// output taken from a transaction that appears in the chain.
-fn dummy_unspent_transaction_output<C: Verification>(
-    secp: &Secp256k1<C>,
-    internal_key: UntweakedPublicKey,
-) -> (OutPoint, TxOut) {
-    let script_pubkey = ScriptBuf::new_v1_p2tr(secp, internal_key, None);
-
-    let out_point = OutPoint {
-        txid: Txid::all_zeros(), // Obviously invalid.
-        vout: 0,
+    let unsigned_tx = Transaction {
+        version: 2,                          // Post BIP-68.
+        lock_time: absolute::LockTime::ZERO, // Ignore the locktime.
+        input: vec![input],                  // Input goes into index 0.
+        output: vec![spend, change],         // Outputs, order does not matter.
     };
+    let input_index = 0;
 
-    let utxo = TxOut {
-        value: DUMMY_UTXO_AMOUNT,
-        script_pubkey,
-    };
+    // Sign the input, this is the exercise.
+    let prevouts = [dummy_utxo];
+    let tx = sign(&secp, &keypair, unsigned_tx, input_index, &prevouts);
 
-    (out_point, utxo)
+    // BOOM! Transaction signed and ready to broadcast.
+    workshop_common::print_prevouts(&prevouts);
+    println!("{}", serialize_hex(&tx));
 }
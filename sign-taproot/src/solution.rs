@@ -0,0 +1,53 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The reference solution, built with `--features solution`.
+
+use bitcoin::hashes::Hash;
+use bitcoin::key::{KeyPair, TapTweak, TweakedKeyPair};
+use bitcoin::secp256k1::{Message, Secp256k1, Signing, Verification};
+use bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
+use bitcoin::{Transaction, TxOut};
+
+/// Signs input `input_index` of `unsigned_tx` with a key path spend, `prevouts` are the outputs
+/// spent by all the inputs.
+pub fn sign<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    keypair: &KeyPair,
+    unsigned_tx: Transaction,
+    input_index: usize,
+    prevouts: &[TxOut],
+) -> Transaction {
+    // Get the sighash to sign.
+    let sighash_type = TapSighashType::Default;
+    let prevouts = Prevouts::All(prevouts);
+
+    let mut sighasher = SighashCache::new(unsigned_tx);
+    let sighash = sighasher
+        .taproot_key_spend_signature_hash(input_index, &prevouts, sighash_type)
+        .expect("failed to construct sighash");
+
+    // Sign the sighash using the secp256k1 library (exported by rust-bitcoin). A key path spend
+    // signs with the tweaked key, the output commits to the internal key tweaked with the (empty)
+    // script tree.
+    let tweaked: TweakedKeyPair = keypair.tap_tweak(secp, None);
+    let msg = Message::from_slice(sighash.as_byte_array()).expect("32 byte sighash");
+    let sig = secp.sign_schnorr(&msg, &tweaked.to_inner());
+
+    // The signature must verify against the output key i.e., the x-only key in the script pubkey.
+    let (output_key, _parity) = tweaked.to_inner().x_only_public_key();
+    secp.verify_schnorr(&sig, &msg, &output_key)
+        .expect("signature verifies against the tweaked output key");
+
+    // Update the witness stack. With the default sighash type the witness is just the signature.
+    let signature = bitcoin::taproot::Signature {
+        sig,
+        hash_ty: sighash_type,
+    };
+    sighasher
+        .witness_mut(input_index)
+        .expect("getting mutable witness reference should work")
+        .push(signature.to_vec());
+
+    // Get the signed transaction.
+    sighasher.into_transaction()
+}
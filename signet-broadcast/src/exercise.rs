@@ -0,0 +1,20 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The exercise, replace the `todo!()` with your solution.
+
+use bitcoin::secp256k1::{Secp256k1, SecretKey, Signing, Verification};
+use bitcoin::{Transaction, TxOut};
+
+/// Signs the only input of `unsigned_tx`, it spends `utxo` which is locked to `sk`.
+///
+/// This is a p2wpkh spend, the same as `sign-segwit-v0`. Remember these coins are real, a wrong
+/// signature is rejected by the network.
+#[allow(unused_variables)]
+pub fn sign<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    sk: &SecretKey,
+    unsigned_tx: Transaction,
+    utxo: &TxOut,
+) -> Transaction {
+    todo!("sign the input and fill in its witness")
+}
@@ -0,0 +1,13 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Hints for the exercise, from least to most specific.
+
+pub const HINTS: &[&str] = &[
+    "This is a p2wpkh spend, the same as `sign-segwit-v0`. The script code comes from \
+     `utxo.script_pubkey.p2wpkh_script_code()`.",
+    "Get the sighash with `segwit_signature_hash(0, &script_code, utxo.value, \
+     EcdsaSighashType::All)` and sign it with `secp.sign_ecdsa(&msg, sk)`.",
+    "The witness is [sig, pubkey], the DER signature with the sighash type byte appended then \
+     `sk.public_key(secp).serialize()`. Push both onto `sighasher.witness_mut(0)` and return \
+     `sighasher.into_transaction()`.",
+];
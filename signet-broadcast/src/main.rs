@@ -0,0 +1,201 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Spend real coins on signet and broadcast the transaction.
+//!
+//! The other exercises stop at "ready to broadcast", this one closes the loop. Signet coins are
+//! worthless but the network is real: if the transaction is wrong the network says so.
+//!
+//! 1. `cargo run -- address` creates a key (saved to `signet-key.wif`) and prints its address.
+//! 2. Get some coins sent to that address, from a faucet (e.g. https://signetfaucet.com) or from
+//!    whoever runs the workshop. Note the txid and output index (vout) of the coins.
+//! 3. `cargo run -- send <txid>:<vout> <amount in sats> [<address>]` spends the coins, less a fee,
+//!    to `<address>` (default: back to ourselves) and broadcasts the transaction.
+//!
+//! The transaction is broadcast through a signet bitcoind if `SIGNET_RPC_URL` is set, with
+//! `SIGNET_RPC_COOKIE` (path to the cookie file) or `SIGNET_RPC_USER` and `SIGNET_RPC_PASS` for
+//! authentication. Otherwise the transaction is printed for you to paste into a block explorer's
+//! broadcast page.
+
+use std::path::PathBuf;
+use std::process;
+use std::str::FromStr;
+
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::locktime::absolute;
+use bitcoin::secp256k1::{Secp256k1, SecretKey, Signing, Verification};
+use bitcoin::{
+    Address, Network, OutPoint, PrivateKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness,
+};
+use bitcoincore_rpc::{Auth, Client, RpcApi};
+use workshop_common::FEE;
+
+// The exercise is `sign` in `src/exercise.rs`, building with `--features solution` swaps in the
+// reference solution from `src/solution.rs`.
+#[cfg(not(feature = "solution"))]
+mod exercise;
+#[cfg(feature = "solution")]
+#[path = "solution.rs"]
+mod exercise;
+
+use exercise::sign;
+
+mod hints;
+
+/// The file our secret key is kept in, in the directory `cargo run` is run from.
+const KEY_FILE: &str = "signet-key.wif";
+
+fn main() {
+    // Stuck? Run `cargo run -- hint 1`.
+    workshop_common::hint_command(hints::HINTS);
+
+    // We need a signing secp256k1 context, if you have not seen this before just pass it in when
+    // needed and otherwise ignore it.
+    let secp = Secp256k1::new();
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    match args.as_slice() {
+        ["address"] => {
+            let sk = load_or_create_key();
+            println!("Send signet coins to: {}", address(&secp, &sk));
+        }
+        ["send", out_point, amount] => send(&secp, out_point, amount, None),
+        ["send", out_point, amount, to] => send(&secp, out_point, amount, Some(to)),
+        _ => usage(),
+    }
+}
+
+/// Spends `out_point`, an output of `amount` sats locked to our key, to `to`.
+fn send<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    out_point: &str,
+    amount: &str,
+    to: Option<&str>,
+) {
+    let sk = load_or_create_key();
+    let our_address = address(secp, &sk);
+
+    // The coins we are spending. Unlike the other exercises this is a real output, if the out point
+    // or the amount are wrong the network will reject the transaction.
+    let out_point = OutPoint::from_str(out_point).unwrap_or_else(|_| usage());
+    let amount = amount.parse::<u64>().unwrap_or_else(|_| usage());
+    let utxo = TxOut {
+        value: amount,
+        script_pubkey: our_address.script_pubkey(),
+    };
+
+    // Where the coins go, a signet address.
+    let to = match to {
+        Some(to) => Address::from_str(to)
+            .expect("a valid address")
+            .require_network(Network::Signet)
+            .expect("valid address for signet"),
+        None => our_address,
+    };
+
+    // The input for the transaction we are constructing.
+    let input = TxIn {
+        previous_output: out_point,       // The output we are spending.
+        script_sig: ScriptBuf::default(), // For a p2wpkh script_sig is empty.
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        witness: Witness::default(), // Filled in after signing.
+    };
+
+    // Everything less the fee goes to `to`, there is no change. The fee is the same flat fee the
+    // other exercises pay, plenty for a one input one output transaction on signet.
+    let spend = TxOut {
+        value: amount
+            .checked_sub(FEE)
+            .expect("amount is more than the fee"),
+        script_pubkey: to.script_pubkey(),
+    };
+
+    // The transaction we want to sign and broadcast.
+    let unsigned_tx = Transaction {
+        version: 2,                          // Post BIP-68.
+        lock_time: absolute::LockTime::ZERO, // Ignore the locktime.
+        input: vec![input],                  // Input goes into index 0.
+        output: vec![spend],                 // A single output.
+    };
+
+    // Sign it, just like `sign-segwit-v0`, this is the exercise.
+    let tx = sign(secp, &sk, unsigned_tx, &utxo);
+
+    // BOOM! Transaction signed, this time we really broadcast it.
+    broadcast(&tx, &utxo);
+}
+
+/// Broadcasts `tx`, spending `utxo`, through the configured signet node, or prints it if there is
+/// none.
+fn broadcast(tx: &Transaction, utxo: &TxOut) {
+    let url = match std::env::var("SIGNET_RPC_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            // Printed like the other exercises.
+            workshop_common::print_prevouts(std::slice::from_ref(utxo));
+            println!("{}", serialize_hex(tx));
+            println!();
+            println!("SIGNET_RPC_URL is not set, paste the transaction above into a signet block");
+            println!("explorer's broadcast page, e.g. https://mempool.space/signet/tx/push");
+            return;
+        }
+    };
+
+    let auth = match (
+        std::env::var_os("SIGNET_RPC_COOKIE"),
+        std::env::var("SIGNET_RPC_USER"),
+        std::env::var("SIGNET_RPC_PASS"),
+    ) {
+        (Some(cookie), _, _) => Auth::CookieFile(PathBuf::from(cookie)),
+        (None, Ok(user), Ok(pass)) => Auth::UserPass(user, pass),
+        _ => Auth::None,
+    };
+    let client = Client::new(&url, auth).expect("failed to create RPC client");
+
+    // The node checks the transaction the same way every other node on the network will.
+    match client.send_raw_transaction(tx) {
+        Ok(txid) => println!(
+            "Broadcast {}, see https://mempool.space/signet/tx/{}",
+            txid, txid
+        ),
+        Err(e) => {
+            eprintln!("Failed to broadcast the transaction: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Returns the p2wpkh signet address for `sk`.
+fn address<C: Signing>(secp: &Secp256k1<C>, sk: &SecretKey) -> Address {
+    let pk = bitcoin::PublicKey::new(sk.public_key(secp));
+    Address::p2wpkh(&pk, Network::Signet).expect("key is compressed")
+}
+
+/// Returns the secret key in `KEY_FILE`, creating it first if needed.
+///
+/// Unlike the other exercises we have to keep the key, the coins are locked to it. A new key comes
+/// from [`workshop_common::secret_key`] so `WORKSHOP_SECRET_KEY` can choose it.
+fn load_or_create_key() -> SecretKey {
+    match std::fs::read_to_string(KEY_FILE) {
+        Ok(wif) => {
+            // Signet keys use the testnet WIF encoding.
+            let key = PrivateKey::from_wif(wif.trim()).expect("valid WIF in key file");
+            assert_eq!(key.network, Network::Testnet, "key file is not for signet");
+            key.inner
+        }
+        Err(_) => {
+            let sk = workshop_common::secret_key(0);
+            let wif = PrivateKey::new(sk, Network::Signet).to_wif();
+            std::fs::write(KEY_FILE, wif).expect("failed to write key file");
+            eprintln!("Created a new key in {}", KEY_FILE);
+            sk
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("Usage:");
+    eprintln!("  cargo run -- address");
+    eprintln!("  cargo run -- send <txid>:<vout> <amount in sats> [<address>]");
+    process::exit(1)
+}
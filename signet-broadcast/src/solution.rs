@@ -0,0 +1,45 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The reference solution, built with `--features solution`.
+
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::{Message, Secp256k1, SecretKey, Signing, Verification};
+use bitcoin::sighash::{EcdsaSighashType, SighashCache};
+use bitcoin::{Transaction, TxOut};
+
+/// Signs the only input of `unsigned_tx`, it spends `utxo` which is locked to `sk`.
+pub fn sign<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    sk: &SecretKey,
+    unsigned_tx: Transaction,
+    utxo: &TxOut,
+) -> Transaction {
+    let input_index = 0;
+    let script_code = utxo
+        .script_pubkey
+        .p2wpkh_script_code()
+        .expect("valid p2wpkh script pubkey");
+
+    // Get the sighash to sign. Segwit v0 sighashes commit to the value of the output being spent.
+    let sighash_type = EcdsaSighashType::All;
+    let mut sighasher = SighashCache::new(unsigned_tx);
+    let sighash = sighasher
+        .segwit_signature_hash(input_index, &script_code, utxo.value, sighash_type)
+        .expect("failed to create sighash");
+
+    // Sign the sighash using the secp256k1 library (exported by rust-bitcoin).
+    let msg = Message::from_slice(sighash.as_byte_array()).expect("32 byte sighash");
+    let sig = secp.sign_ecdsa(&msg, sk);
+
+    // Update the witness stack, it is [sig, pubkey].
+    let pk = sk.public_key(secp);
+    let mut sig_with_flag = sig.serialize_der().to_vec();
+    sig_with_flag.push(sighash_type.to_u32() as u8);
+    let witness = sighasher
+        .witness_mut(input_index)
+        .expect("getting mutable witness reference should work");
+    witness.push(sig_with_flag);
+    witness.push(pk.serialize());
+
+    sighasher.into_transaction()
+}
@@ -0,0 +1,23 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The exercise, replace the `todo!()` with your solution.
+
+use bitcoin::secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey, Verification, XOnlyPublicKey};
+use bitcoin::OutPoint;
+
+/// Returns the output keys in `output_keys` that belong to the receiver with `scan_sk` and
+/// `spend_pk`, along with the tweak to add to the spend secret key to spend each one.
+///
+/// Do the sender's ECDH from the other side, `input_hash * b_scan * A` where `A` is the sum of
+/// the input public keys, then check output keys `k = 0, 1, ...` until one is missing.
+#[allow(unused_variables)]
+pub fn scan<C: Verification>(
+    secp: &Secp256k1<C>,
+    scan_sk: &SecretKey,
+    spend_pk: &PublicKey,
+    input_public_keys: &[PublicKey],
+    outpoints: &[OutPoint],
+    output_keys: &[XOnlyPublicKey],
+) -> Vec<(XOnlyPublicKey, Scalar)> {
+    todo!("find the outputs that pay the receiver")
+}
@@ -0,0 +1,14 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Hints for the exercise, from least to most specific.
+
+pub const HINTS: &[&str] = &[
+    "The receiver finds the sender's shared secret from the other side of the ECDH. The sender \
+     used `a * B_scan`, the receiver uses `b_scan * A` where `A` is the sum of the input public \
+     keys.",
+    "Sum `input_public_keys` with `PublicKey::combine_keys` and get `input_hash(outpoints, &a)`. \
+     Multiply `scan_sk` by the input hash with `mul_tweak`, then multiply `A` by the result to \
+     get the shared secret.",
+    "For `k = 0, 1, ...` call `output_key(secp, &shared_secret, spend_pk, k)`. Keep the key and \
+     its tweak while the key is in `output_keys`, and stop at the first one that is not.",
+];
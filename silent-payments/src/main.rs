@@ -0,0 +1,454 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Demonstrate deriving and detecting a silent payment output (BIP-352).
+//!
+//! The receiver publishes a single static address made up of two public keys, a scan key and a
+//! spend key. The sender combines the secret keys of the inputs they are spending with the scan key
+//! (ECDH) to derive a fresh taproot output key, so no two payments share an output and nothing on
+//! chain links them to the address. The receiver does the same ECDH with the input public keys and
+//! their scan secret to find payments to them.
+//!
+//! This leaves out labels, the rules for which input types are eligible, and the `sp1...` address
+//! encoding. rust-bitcoin does not implement silent payments so the protocol is written out here
+//! with the point operations from the secp256k1 library.
+
+use bitcoin::consensus::encode::{serialize, serialize_hex};
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::key::{KeyPair, TapTweak, TweakedPublicKey};
+use bitcoin::locktime::absolute;
+use bitcoin::secp256k1::{
+    rand, Parity, PublicKey, Scalar, Secp256k1, SecretKey, Signing, Verification, XOnlyPublicKey,
+};
+use bitcoin::{OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness};
+use workshop_common::{DUMMY_UTXO_AMOUNT, FEE, SPEND_AMOUNT};
+
+// The exercise is `scan` in `src/exercise.rs`, building with `--features solution` swaps in the
+// reference solution from `src/solution.rs`.
+#[cfg(not(feature = "solution"))]
+mod exercise;
+#[cfg(feature = "solution")]
+#[path = "solution.rs"]
+mod exercise;
+
+use exercise::scan;
+
+mod hints;
+
+/// The sender spends two dummy utxos.
+const CHANGE_AMOUNT: u64 = 2 * DUMMY_UTXO_AMOUNT - SPEND_AMOUNT - FEE;
+
+fn main() {
+    // Stuck? Run `cargo run -- hint 1`.
+    workshop_common::hint_command(hints::HINTS);
+
+    // We need a signing secp256k1 context, if you have not seen this before just pass it in when
+    // needed and otherwise ignore it.
+    let secp = Secp256k1::new();
+
+    // The receiver publishes their silent payment address once, it is made up of two public keys.
+    // The scan secret key can be kept online to find payments, the spend secret key can not be
+    // derived from it and can be kept offline.
+    let (scan_sk, scan_pk) = generate_keys(&secp);
+    let (spend_sk, spend_pk) = generate_keys(&secp);
+    let address = SilentPaymentAddress {
+        scan: scan_pk,
+        spend: spend_pk,
+    };
+
+    // The sender has two coins, one p2wpkh and one key path p2tr.
+    // In a real application these would come from the chain.
+    let segwit_sk = workshop_common::secret_key(0);
+    let segwit_pk = segwit_sk.public_key(&secp);
+    let (segwit_out_point, _) = workshop_common::dummy_utxo(0, p2wpkh_script_pubkey(&segwit_pk));
+    let taproot_keypair =
+        KeyPair::from_secret_key(&secp, &workshop_common::secret_key(1)).tap_tweak(&secp, None);
+    let (taproot_output_key, _parity) = taproot_keypair.to_inner().x_only_public_key();
+    let (taproot_out_point, _) = workshop_common::dummy_utxo(
+        1,
+        ScriptBuf::new_v1_p2tr_tweaked(TweakedPublicKey::dangerous_assume_tweaked(
+            taproot_output_key,
+        )),
+    );
+
+    // The sender derives the receiver's output key from the secret keys of the inputs being spent.
+    // For a p2tr input that is the secret key for the output key i.e., the tweaked key.
+    let inputs = [
+        (segwit_out_point, InputKey::Ecdsa(segwit_sk)),
+        (
+            taproot_out_point,
+            InputKey::Taproot(taproot_keypair.to_inner().secret_key()),
+        ),
+    ];
+    let output_key = sender_output_keys(&secp, &inputs, &address, 1)[0];
+
+    // The inputs for the transaction we are constructing.
+    let input = |previous_output| TxIn {
+        previous_output,
+        script_sig: ScriptBuf::default(), // For p2wpkh and p2tr script_sig is empty.
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        witness: Witness::default(), // Filled in after signing.
+    };
+
+    // The spend output is a plain p2tr output. The output key is used as is, unlike BIP-86 there is
+    // no taproot tweak.
+    let spend = TxOut {
+        value: SPEND_AMOUNT,
+        script_pubkey: ScriptBuf::new_v1_p2tr_tweaked(TweakedPublicKey::dangerous_assume_tweaked(
+            output_key,
+        )),
+    };
+
+    // The change output is locked to a key controlled by the sender.
+    let change = TxOut {
+        value: CHANGE_AMOUNT,
+        script_pubkey: p2wpkh_script_pubkey(&segwit_pk), // Change comes back to us.
+    };
+
+    // The transaction the sender signs and broadcasts, signing is as in `sign-segwit-v0` and
+    // `sign-taproot`.
+    let tx = Transaction {
+        version: 2,                          // Post BIP-68.
+        lock_time: absolute::LockTime::ZERO, // Ignore the locktime.
+        input: vec![input(segwit_out_point), input(taproot_out_point)],
+        output: vec![spend, change], // Outputs, order does not matter.
+    };
+    println!("Unsigned transaction: {}", serialize_hex(&tx));
+
+    // The receiver scans every transaction. From the chain they get the out points and public keys
+    // of the inputs (the p2wpkh key from the witness, the p2tr key from the output being spent)
+    // and the p2tr output keys.
+    let outpoints = tx
+        .input
+        .iter()
+        .map(|input| input.previous_output)
+        .collect::<Vec<_>>();
+    let input_public_keys = [
+        segwit_pk,
+        // An x-only key always has even y.
+        PublicKey::from_x_only_public_key(taproot_output_key, Parity::Even),
+    ];
+    let output_keys = tx
+        .output
+        .iter()
+        .filter(|output| output.script_pubkey.is_v1_p2tr())
+        .map(|output| {
+            XOnlyPublicKey::from_slice(&output.script_pubkey.as_bytes()[2..]).expect("valid key")
+        })
+        .collect::<Vec<_>>();
+
+    // Scan the transaction for outputs paying the receiver, this is the exercise.
+    let found = scan(
+        &secp,
+        &scan_sk,
+        &address.spend,
+        &input_public_keys,
+        &outpoints,
+        &output_keys,
+    );
+    assert_eq!(found.len(), 1);
+    let (found_key, tweak) = found[0];
+    assert_eq!(found_key, output_key);
+    println!("Found silent payment output key: {}", found_key);
+
+    // The receiver can spend the output with their spend secret key plus the tweak.
+    let output_sk = spend_sk.add_tweak(&tweak).expect("not zero");
+    let (derived_key, _parity) = output_sk.x_only_public_key(&secp);
+    assert_eq!(derived_key, output_key);
+}
+
+/// A silent payment address, encoded as an `sp1...` bech32m string it is what the receiver
+/// publishes.
+#[derive(Debug, Clone, Copy)]
+struct SilentPaymentAddress {
+    scan: PublicKey,
+    spend: PublicKey,
+}
+
+/// The secret key for one of the inputs a sender is spending.
+#[derive(Debug, Clone, Copy)]
+enum InputKey {
+    /// The key for a p2wpkh input, the public key appears in the witness.
+    Ecdsa(SecretKey),
+    /// The key for a key path p2tr input, the public key is the x-only output key.
+    Taproot(SecretKey),
+}
+
+impl InputKey {
+    /// Returns the secret key for the public key a receiver sees on chain.
+    fn secret_key<C: Signing>(&self, secp: &Secp256k1<C>) -> SecretKey {
+        match *self {
+            InputKey::Ecdsa(sk) => sk,
+            // The receiver only has the x-only key, which means the point with even y. If our key
+            // is the one with odd y use its negation.
+            InputKey::Taproot(sk) => match sk.x_only_public_key(secp).1 {
+                Parity::Even => sk,
+                Parity::Odd => sk.negate(),
+            },
+        }
+    }
+}
+
+/// Returns the first `count` output keys for `address` when spending `inputs`.
+///
+/// Paying the same receiver more than once in a transaction uses the next output key for each
+/// payment (`k = 0, 1, ...`).
+fn sender_output_keys<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    inputs: &[(OutPoint, InputKey)],
+    address: &SilentPaymentAddress,
+    count: u32,
+) -> Vec<XOnlyPublicKey> {
+    // Sum the input secret keys, `a = a_1 + a_2 + ...`.
+    let mut keys = inputs.iter().map(|(_, key)| key.secret_key(secp));
+    let first = keys.next().expect("at least one input");
+    let a = keys.fold(first, |acc, sk| {
+        acc.add_tweak(&Scalar::from(sk)).expect("not zero")
+    });
+
+    // The shared secret is `input_hash * a * B_scan`.
+    let outpoints = inputs
+        .iter()
+        .map(|(outpoint, _)| *outpoint)
+        .collect::<Vec<_>>();
+    let input_hash = input_hash(&outpoints, &a.public_key(secp));
+    let a = a.mul_tweak(&input_hash).expect("not zero");
+    let shared_secret = address
+        .scan
+        .mul_tweak(secp, &Scalar::from(a))
+        .expect("not infinity");
+
+    (0..count)
+        .map(|k| output_key(secp, &shared_secret, &address.spend, k).0)
+        .collect()
+}
+
+/// Returns `hash_BIP0352/Inputs(outpoint_L || A)` where `outpoint_L` is the smallest of the
+/// `outpoints` and `A` is the sum of the input public keys.
+///
+/// Committing to an out point makes the output key unique even if the same keys are spent again.
+fn input_hash(outpoints: &[OutPoint], a: &PublicKey) -> Scalar {
+    // Out points are compared as serialized i.e., txid then little endian vout.
+    let smallest = outpoints
+        .iter()
+        .map(serialize)
+        .min()
+        .expect("at least one input");
+    scalar(tagged_hash("BIP0352/Inputs", &[&smallest, &a.serialize()]))
+}
+
+/// Returns the `k`th output key `P_k = B_spend + t_k * G` and the tweak `t_k`.
+fn output_key<C: Verification>(
+    secp: &Secp256k1<C>,
+    shared_secret: &PublicKey,
+    spend_pk: &PublicKey,
+    k: u32,
+) -> (XOnlyPublicKey, Scalar) {
+    // `t_k = hash_BIP0352/SharedSecret(shared_secret || ser_32(k))`
+    let tweak = scalar(tagged_hash(
+        "BIP0352/SharedSecret",
+        &[&shared_secret.serialize(), &k.to_be_bytes()],
+    ));
+    let key = spend_pk.add_exp_tweak(secp, &tweak).expect("not infinity");
+    (key.x_only_public_key().0, tweak)
+}
+
+/// Interprets a hash as a scalar, the chance of it being out of range is negligible.
+fn scalar(hash: [u8; 32]) -> Scalar {
+    Scalar::from_be_bytes(hash).expect("hash is less than the curve order")
+}
+
+/// The BIP-340 tagged hash `sha256(sha256(tag) || sha256(tag) || data)`.
+fn tagged_hash(tag: &str, data: &[&[u8]]) -> [u8; 32] {
+    let tag = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    for data in data {
+        engine.input(data);
+    }
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+/// Generates a random key pair, for the receiver.
+///
+/// In a real application these would be actual secrets.
+fn generate_keys<C: Signing>(secp: &Secp256k1<C>) -> (SecretKey, PublicKey) {
+    let sk = SecretKey::new(&mut rand::thread_rng());
+    let pk = sk.public_key(secp);
+
+    (sk, pk)
+}
+
+/// Returns the p2wpkh script pubkey for `pk`.
+fn p2wpkh_script_pubkey(pk: &PublicKey) -> ScriptBuf {
+    let wpkh = bitcoin::PublicKey::new(*pk)
+        .wpubkey_hash()
+        .expect("key is compressed");
+    ScriptBuf::new_v0_p2wpkh(&wpkh)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::secp256k1::All;
+    use bitcoin::Txid;
+
+    use super::*;
+
+    /// Returns a p2wpkh input with a fresh key and its public key as seen on chain.
+    fn ecdsa_input(secp: &Secp256k1<All>, vout: u32) -> (OutPoint, InputKey, PublicKey) {
+        let (sk, pk) = generate_keys(secp);
+        let outpoint = OutPoint {
+            txid: Txid::all_zeros(),
+            vout,
+        };
+        (outpoint, InputKey::Ecdsa(sk), pk)
+    }
+
+    /// Returns a p2tr input whose output key has odd y if `odd` is true (and even y otherwise) and
+    /// its public key as seen on chain.
+    fn taproot_input(
+        secp: &Secp256k1<All>,
+        vout: u32,
+        odd: bool,
+    ) -> (OutPoint, InputKey, PublicKey) {
+        loop {
+            let (sk, _) = generate_keys(secp);
+            let (x_only, parity) = sk.x_only_public_key(secp);
+            if (parity == Parity::Odd) == odd {
+                let outpoint = OutPoint {
+                    txid: Txid::all_zeros(),
+                    vout,
+                };
+                let pk = PublicKey::from_x_only_public_key(x_only, Parity::Even);
+                return (outpoint, InputKey::Taproot(sk), pk);
+            }
+        }
+    }
+
+    /// Returns the scan and spend secret keys of a receiver and their silent payment address.
+    fn receiver(secp: &Secp256k1<All>) -> (SecretKey, SecretKey, SilentPaymentAddress) {
+        let (scan_sk, scan) = generate_keys(secp);
+        let (spend_sk, spend) = generate_keys(secp);
+        (scan_sk, spend_sk, SilentPaymentAddress { scan, spend })
+    }
+
+    /// Runs the sender and the receiver side for `inputs`, returns the output keys and what the
+    /// receiver found.
+    fn send_and_scan(
+        inputs: &[(OutPoint, InputKey, PublicKey)],
+        count: u32,
+    ) -> (
+        SecretKey,
+        Vec<XOnlyPublicKey>,
+        Vec<(XOnlyPublicKey, Scalar)>,
+    ) {
+        let secp = Secp256k1::new();
+        let (scan_sk, spend_sk, address) = receiver(&secp);
+
+        let sender_inputs = inputs.iter().map(|(o, k, _)| (*o, *k)).collect::<Vec<_>>();
+        let output_keys = sender_output_keys(&secp, &sender_inputs, &address, count);
+
+        let outpoints = inputs.iter().map(|(o, _, _)| *o).collect::<Vec<_>>();
+        let public_keys = inputs.iter().map(|(_, _, pk)| *pk).collect::<Vec<_>>();
+        let found = scan(
+            &secp,
+            &scan_sk,
+            &address.spend,
+            &public_keys,
+            &outpoints,
+            &output_keys,
+        );
+
+        (spend_sk, output_keys, found)
+    }
+
+    #[test]
+    fn receiver_finds_and_can_spend_output() {
+        let secp = Secp256k1::new();
+        let inputs = [ecdsa_input(&secp, 0), taproot_input(&secp, 1, false)];
+
+        let (spend_sk, output_keys, found) = send_and_scan(&inputs, 1);
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, output_keys[0]);
+        let output_sk = spend_sk.add_tweak(&found[0].1).unwrap();
+        assert_eq!(output_sk.x_only_public_key(&secp).0, output_keys[0]);
+    }
+
+    #[test]
+    fn taproot_input_with_odd_y_is_negated() {
+        let secp = Secp256k1::new();
+        let inputs = [taproot_input(&secp, 0, true), taproot_input(&secp, 1, true)];
+
+        let (_, output_keys, found) = send_and_scan(&inputs, 1);
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, output_keys[0]);
+    }
+
+    #[test]
+    fn receiver_finds_multiple_outputs() {
+        let secp = Secp256k1::new();
+        let inputs = [ecdsa_input(&secp, 0)];
+
+        let (_, output_keys, found) = send_and_scan(&inputs, 3);
+
+        assert_eq!(
+            found.iter().map(|(key, _)| *key).collect::<Vec<_>>(),
+            output_keys
+        );
+        assert_eq!(
+            output_keys
+                .iter()
+                .collect::<std::collections::BTreeSet<_>>()
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn other_receiver_finds_nothing() {
+        let secp = Secp256k1::new();
+        let (outpoint, key, pk) = ecdsa_input(&secp, 0);
+        let (_, _, address) = receiver(&secp);
+        let output_keys = sender_output_keys(&secp, &[(outpoint, key)], &address, 1);
+
+        let (other_scan_sk, _, other) = receiver(&secp);
+        let found = scan(
+            &secp,
+            &other_scan_sk,
+            &other.spend,
+            &[pk],
+            &[outpoint],
+            &output_keys,
+        );
+        assert!(found.is_empty());
+    }
+
+    #[test]
+    fn input_order_does_not_matter() {
+        let secp = Secp256k1::new();
+        let (_, _, address) = receiver(&secp);
+        let (o1, k1, _) = ecdsa_input(&secp, 0);
+        let (o2, k2, _) = taproot_input(&secp, 1, true);
+
+        let forwards = sender_output_keys(&secp, &[(o1, k1), (o2, k2)], &address, 1);
+        let backwards = sender_output_keys(&secp, &[(o2, k2), (o1, k1)], &address, 1);
+        assert_eq!(forwards, backwards);
+    }
+
+    #[test]
+    fn output_key_commits_to_outpoint() {
+        let secp = Secp256k1::new();
+        let (_, _, address) = receiver(&secp);
+        let (outpoint, key, _) = ecdsa_input(&secp, 0);
+        let other_outpoint = OutPoint {
+            vout: 1,
+            ..outpoint
+        };
+
+        let a = sender_output_keys(&secp, &[(outpoint, key)], &address, 1);
+        let b = sender_output_keys(&secp, &[(other_outpoint, key)], &address, 1);
+        assert_ne!(a, b);
+    }
+}
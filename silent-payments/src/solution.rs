@@ -0,0 +1,42 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The reference solution, built with `--features solution`.
+
+use bitcoin::secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey, Verification, XOnlyPublicKey};
+use bitcoin::OutPoint;
+
+use super::{input_hash, output_key};
+
+/// Returns the output keys in `output_keys` that belong to the receiver with `scan_sk` and
+/// `spend_pk`, along with the tweak to add to the spend secret key to spend each one.
+pub fn scan<C: Verification>(
+    secp: &Secp256k1<C>,
+    scan_sk: &SecretKey,
+    spend_pk: &PublicKey,
+    input_public_keys: &[PublicKey],
+    outpoints: &[OutPoint],
+    output_keys: &[XOnlyPublicKey],
+) -> Vec<(XOnlyPublicKey, Scalar)> {
+    // Sum the input public keys, `A = A_1 + A_2 + ...`.
+    let keys = input_public_keys.iter().collect::<Vec<_>>();
+    let a = PublicKey::combine_keys(&keys).expect("not infinity");
+
+    // The shared secret is `input_hash * b_scan * A`, the same point as the sender's because
+    // `b_scan * A = b_scan * a * G = a * B_scan`.
+    let input_hash = input_hash(outpoints, &a);
+    let b_scan = scan_sk.mul_tweak(&input_hash).expect("not zero");
+    let shared_secret = a
+        .mul_tweak(secp, &Scalar::from(b_scan))
+        .expect("not infinity");
+
+    // Keep going until an output key is not in the transaction.
+    let mut found = vec![];
+    for k in 0.. {
+        let (key, tweak) = output_key(secp, &shared_secret, spend_pk, k);
+        if !output_keys.contains(&key) {
+            break;
+        }
+        found.push((key, tweak));
+    }
+    found
+}
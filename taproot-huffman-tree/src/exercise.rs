@@ -0,0 +1,26 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The exercise, replace the `todo!()` with your solution.
+
+use bitcoin::key::KeyPair;
+use bitcoin::secp256k1::{Secp256k1, Signing, Verification};
+use bitcoin::sighash::{Prevouts, SighashCache};
+use bitcoin::taproot::TaprootSpendInfo;
+use bitcoin::{ScriptBuf, Transaction, TxOut};
+
+/// Signs input `input_index` by satisfying `leaf_script` with a signature from `keypair`.
+///
+/// The sighash commits to the leaf hash. The witness is the signature, then the script, then the
+/// control block, which `spend_info` builds for any leaf in the tree.
+#[allow(unused_variables)]
+pub fn sign_leaf<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    sighasher: &mut SighashCache<Transaction>,
+    input_index: usize,
+    prevouts: &Prevouts<TxOut>,
+    spend_info: &TaprootSpendInfo,
+    leaf_script: &ScriptBuf,
+    keypair: &KeyPair,
+) {
+    todo!("sign the input and fill in its witness")
+}
@@ -0,0 +1,14 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Hints for the exercise, from least to most specific.
+
+pub const HINTS: &[&str] = &[
+    "The sighash commits to the leaf being executed, see `TapLeafHash::from_script` with \
+     `LeafVersion::TapScript` and `SighashCache::taproot_script_spend_signature_hash`.",
+    "Sign with `keypair` as is, the key in the leaf script is not tweaked. \
+     `spend_info.control_block(&(leaf_script.clone(), LeafVersion::TapScript))` proves the leaf \
+     is in the tree, the deeper the leaf the longer the proof.",
+    "Push the signature (`taproot::Signature { sig, hash_ty }.to_vec()`), then \
+     `leaf_script.as_bytes()`, then `control_block.serialize()` onto \
+     `sighasher.witness_mut(input_index)`.",
+];
@@ -0,0 +1,185 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Demonstrate building a taproot script tree from leaf weights (a Huffman tree) and spending it
+//! via two different leaves.
+//!
+//! The more likely a leaf is to be used the closer to the root it should be, a leaf at depth `d`
+//! needs `d` hashes in its control block so likely spends are cheaper.
+
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::key::{KeyPair, UntweakedPublicKey, XOnlyPublicKey};
+use bitcoin::locktime::absolute;
+use bitcoin::opcodes::all::OP_CHECKSIG;
+use bitcoin::script::Builder;
+use bitcoin::secp256k1::{Secp256k1, Signing};
+use bitcoin::sighash::{Prevouts, SighashCache};
+use bitcoin::taproot::{LeafVersion, TaprootBuilder};
+use bitcoin::{ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness};
+use workshop_common::taproot::receivers_address;
+use workshop_common::{DUMMY_UTXO_AMOUNT, FEE, SPEND_AMOUNT};
+
+// The exercise is `sign_leaf` in `src/exercise.rs`, building with `--features solution` swaps in
+// the reference solution from `src/solution.rs`.
+#[cfg(not(feature = "solution"))]
+mod exercise;
+#[cfg(feature = "solution")]
+#[path = "solution.rs"]
+mod exercise;
+
+use exercise::sign_leaf;
+
+mod hints;
+
+/// We spend two dummy utxos.
+const CHANGE_AMOUNT: u64 = 2 * DUMMY_UTXO_AMOUNT - SPEND_AMOUNT - FEE;
+
+fn main() {
+    // Stuck? Run `cargo run -- hint 1`.
+    workshop_common::hint_command(hints::HINTS);
+
+    // We need a signing secp256k1 context, if you have not seen this before just pass it in when
+    // needed and otherwise ignore it.
+    let secp = Secp256k1::new();
+
+    // The internal key, here we only spend via the script path.
+    let internal_key = senders_internal_key(&secp);
+
+    // Four parties who can each spend the coins on their own, each with their own leaf script.
+    let alice = senders_leaf_keys(&secp, 1);
+    let bob = senders_leaf_keys(&secp, 2);
+    let carol = senders_leaf_keys(&secp, 3);
+    let dave = senders_leaf_keys(&secp, 4);
+    let alice_script = checksig_script(&alice.x_only_public_key().0);
+    let bob_script = checksig_script(&bob.x_only_public_key().0);
+    let carol_script = checksig_script(&carol.x_only_public_key().0);
+    let dave_script = checksig_script(&dave.x_only_public_key().0);
+
+    // Weight each leaf by how likely it is to be used, only the relative weights matter. Huffman
+    // coding pairs up the two lightest nodes until one is left: carol + dave (2), then that with
+    // bob (5), then that with alice (11).
+    let script_weights = [
+        (6, alice_script.clone()),
+        (3, bob_script.clone()),
+        (1, carol_script.clone()),
+        (1, dave_script.clone()),
+    ];
+    let spend_info = TaprootBuilder::with_huffman_tree(script_weights)
+        .expect("valid weights")
+        .finalize(&secp, internal_key)
+        .expect("the tree is complete");
+
+    // The likely leaf ends up near the root, the unlikely ones deep in the tree.
+    for (script, depth) in [
+        (&alice_script, 1),
+        (&bob_script, 2),
+        (&carol_script, 3),
+        (&dave_script, 3),
+    ] {
+        let control_block = spend_info
+            .control_block(&(script.clone(), LeafVersion::TapScript))
+            .expect("leaf is in the tree");
+        assert_eq!(control_block.merkle_branch.len(), depth);
+    }
+
+    // Get two unspent outputs that are locked to the script tree above.
+    // In a real application these would come from the chain.
+    let script_pubkey = ScriptBuf::new_v1_p2tr_tweaked(spend_info.output_key());
+    let (alice_out_point, alice_utxo) = workshop_common::dummy_utxo(0, script_pubkey.clone());
+    let (carol_out_point, carol_utxo) = workshop_common::dummy_utxo(1, script_pubkey);
+
+    // Get an address to send to.
+    let address = receivers_address();
+
+    // The inputs for the transaction we are constructing, Alice spends the one at index 0 and
+    // Carol the one at index 1.
+    let input = |previous_output| TxIn {
+        previous_output,                  // The dummy output we are spending.
+        script_sig: ScriptBuf::default(), // For a p2tr script_sig is empty.
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        witness: Witness::default(), // Filled in after signing.
+    };
+
+    // The spend output is locked to a key controlled by the receiver.
+    let spend = TxOut {
+        value: SPEND_AMOUNT,
+        script_pubkey: address.script_pubkey(),
+    };
+
+    // The change output goes back to the same script tree.
+    let change = TxOut {
+        value: CHANGE_AMOUNT,
+        script_pubkey: ScriptBuf::new_v1_p2tr_tweaked(spend_info.output_key()),
+    };
+
+    // The transaction we want to sign and broadcast.
+    let unsigned_tx = Transaction {
+        version: 2,                          // Post BIP-68.
+        lock_time: absolute::LockTime::ZERO, // Ignore the locktime.
+        input: vec![input(alice_out_point), input(carol_out_point)],
+        output: vec![spend, change], // Outputs, order does not matter.
+    };
+
+    // Taproot sighashes commit to all the outputs being spent.
+    let utxos = vec![alice_utxo, carol_utxo];
+    let prevouts = Prevouts::All(&utxos);
+
+    // Sign both inputs, each via its own leaf, this is the exercise.
+    let mut sighasher = SighashCache::new(unsigned_tx);
+    sign_leaf(
+        &secp,
+        &mut sighasher,
+        0,
+        &prevouts,
+        &spend_info,
+        &alice_script,
+        &alice,
+    );
+    sign_leaf(
+        &secp,
+        &mut sighasher,
+        1,
+        &prevouts,
+        &spend_info,
+        &carol_script,
+        &carol,
+    );
+
+    // Get the signed transaction.
+    let tx = sighasher.into_transaction();
+
+    // Carol's control block proves a deeper leaf so her witness is bigger, 32 bytes per level.
+    let control_block_len =
+        |input: usize| tx.input[input].witness.last().expect("control block").len();
+    assert_eq!(control_block_len(0), 33 + 32);
+    assert_eq!(control_block_len(1), 33 + 32 * 3);
+
+    // BOOM! Transaction signed and ready to broadcast.
+    workshop_common::print_prevouts(&utxos);
+    println!("{}", serialize_hex(&tx));
+}
+
+/// An example of the internal key of the sender's taproot output.
+///
+/// In a real application this would be derived from an actual secret, or be a provably
+/// unspendable key if the key path should be disabled.
+fn senders_internal_key<C: Signing>(secp: &Secp256k1<C>) -> UntweakedPublicKey {
+    let sk = workshop_common::secret_key(0);
+    let (internal_key, _parity) = KeyPair::from_secret_key(secp, &sk).x_only_public_key();
+    internal_key
+}
+
+/// An example of the key used in the sender's `n`th leaf script.
+///
+/// In a real application these would be actual secrets.
+fn senders_leaf_keys<C: Signing>(secp: &Secp256k1<C>, n: u32) -> KeyPair {
+    let sk = workshop_common::secret_key(n);
+    KeyPair::from_secret_key(secp, &sk)
+}
+
+/// Returns a tapscript that can be satisfied by a signature from `key`.
+fn checksig_script(key: &XOnlyPublicKey) -> ScriptBuf {
+    Builder::new()
+        .push_x_only_key(key)
+        .push_opcode(OP_CHECKSIG)
+        .into_script()
+}
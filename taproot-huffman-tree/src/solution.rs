@@ -0,0 +1,62 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The reference solution, built with `--features solution`.
+
+use bitcoin::hashes::Hash;
+use bitcoin::key::KeyPair;
+use bitcoin::secp256k1::{Message, Secp256k1, Signing, Verification};
+use bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
+use bitcoin::taproot::{self, LeafVersion, TapLeafHash, TaprootSpendInfo};
+use bitcoin::{ScriptBuf, Transaction, TxOut};
+
+/// Signs input `input_index` by satisfying `leaf_script` with a signature from `keypair`.
+pub fn sign_leaf<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    sighasher: &mut SighashCache<Transaction>,
+    input_index: usize,
+    prevouts: &Prevouts<TxOut>,
+    spend_info: &TaprootSpendInfo,
+    leaf_script: &ScriptBuf,
+    keypair: &KeyPair,
+) {
+    // Each leaf has a version, so that new script semantics can be soft forked in. Today there is
+    // only tapscript (0xc0), the leaf hash commits to the version and the script.
+    let leaf = (leaf_script.clone(), LeafVersion::TapScript);
+    let leaf_hash = TapLeafHash::from_script(leaf_script, LeafVersion::TapScript);
+
+    // Get the sighash to sign, it commits to the leaf being executed.
+    let sighash_type = TapSighashType::Default;
+    let sighash = sighasher
+        .taproot_script_spend_signature_hash(input_index, prevouts, leaf_hash, sighash_type)
+        .expect("failed to construct sighash");
+
+    let msg = Message::from_slice(sighash.as_byte_array()).expect("32 byte sighash");
+    let sig = secp.sign_schnorr(&msg, keypair);
+    secp.verify_schnorr(&sig, &msg, &keypair.x_only_public_key().0)
+        .expect("signature verifies against the leaf key");
+
+    // The control block holds the leaf version and the parity of the output key (packed into the
+    // first byte), the internal key, then the hashes of the other branches on the way to the root.
+    let control_block = spend_info
+        .control_block(&leaf)
+        .expect("leaf is in the tree");
+    assert_eq!(control_block.leaf_version, LeafVersion::TapScript);
+    assert_eq!(control_block.serialize()[0] & 0xfe, 0xc0);
+    assert!(control_block.verify_taproot_commitment(
+        secp,
+        spend_info.output_key().to_inner(),
+        leaf_script
+    ));
+
+    // Update the witness stack: the inputs to the script, then the script, then the control block.
+    let signature = taproot::Signature {
+        sig,
+        hash_ty: sighash_type,
+    };
+    let witness = sighasher
+        .witness_mut(input_index)
+        .expect("getting mutable witness reference should work");
+    witness.push(signature.to_vec());
+    witness.push(leaf_script.as_bytes());
+    witness.push(control_block.serialize());
+}
@@ -0,0 +1,20 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The exercise, replace the `todo!()` with your solution.
+
+use bitcoin::hashes::sha256d;
+use bitcoin::{Script, Transaction};
+
+/// Returns the BIP-143 SIGHASH_ALL sighash for input `input_index` of `tx`.
+///
+/// Lay out the preimage field by field as BIP-143 describes, the `write_*` helpers in `main.rs`
+/// serialize out points, outputs and scripts. The result is the double SHA256 of the preimage.
+#[allow(unused_variables)]
+pub fn bip143_sighash(
+    tx: &Transaction,
+    input_index: usize,
+    script_code: &Script,
+    value: u64,
+) -> sha256d::Hash {
+    todo!("compute the BIP-143 sighash")
+}
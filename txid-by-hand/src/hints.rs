@@ -0,0 +1,13 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Hints for the exercise, from least to most specific.
+
+pub const HINTS: &[&str] = &[
+    "The BIP-143 preimage is made of ten fields. Three of them are double SHA256 hashes, over the \
+     out points of all the inputs, their sequences, and all the outputs.",
+    "Build those three with the `write_out_point` and `write_tx_out` helpers (a sequence is 4 \
+     little endian bytes) and hash each with `sha256d::Hash::hash`.",
+    "The preimage is the version, hashPrevouts, hashSequence, this input's out point, the script \
+     code (see `write_script`), the value, this input's sequence, hashOutputs, the lock time, and \
+     the sighash type as 4 little endian bytes. Return `sha256d::Hash::hash(&preimage)`.",
+];
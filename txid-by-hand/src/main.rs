@@ -0,0 +1,214 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Serialize a transaction, compute its txid and wtxid, and compute a BIP-143 sighash, all by hand.
+//!
+//! Each value is cross-checked against rust-bitcoin, there is no magic in the library. Everything
+//! here is double SHA256 over bytes laid out as described in BIP-141 and BIP-143.
+
+use bitcoin::consensus::encode::{serialize, serialize_hex};
+use bitcoin::hashes::{sha256d, Hash};
+use bitcoin::locktime::absolute;
+use bitcoin::secp256k1::{Message, Secp256k1};
+use bitcoin::sighash::{EcdsaSighashType, SighashCache};
+use bitcoin::{OutPoint, Script, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness};
+use workshop_common::segwit_v0::{
+    dummy_unspent_transaction_output, receivers_address, senders_keys,
+};
+use workshop_common::{CHANGE_AMOUNT, SPEND_AMOUNT};
+
+// The exercise is `bip143_sighash` in `src/exercise.rs`, building with `--features solution` swaps
+// in the reference solution from `src/solution.rs`.
+#[cfg(not(feature = "solution"))]
+mod exercise;
+#[cfg(feature = "solution")]
+#[path = "solution.rs"]
+mod exercise;
+
+use exercise::bip143_sighash;
+
+mod hints;
+
+fn main() {
+    // Stuck? Run `cargo run -- hint 1`.
+    workshop_common::hint_command(hints::HINTS);
+
+    // We need a signing secp256k1 context, if you have not seen this before just pass it in when
+    // needed and otherwise ignore it.
+    let secp = Secp256k1::new();
+
+    // Get a secret key we control and the pubkeyhash of the associated pubkey.
+    // In a real application these would come from a stored secret.
+    let (sk, wpkh) = senders_keys(&secp);
+
+    // Get an address to send to.
+    let address = receivers_address();
+
+    // Get an unspent output that is locked to the key above that we control.
+    // In a real application these would come from the chain.
+    let (dummy_out_point, dummy_utxo) = dummy_unspent_transaction_output(&wpkh);
+
+    // The input for the transaction we are constructing.
+    let input = TxIn {
+        previous_output: dummy_out_point, // The dummy output we are spending.
+        script_sig: ScriptBuf::default(), // For a p2wpkh script_sig is empty.
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+        witness: Witness::default(), // Filled in after signing.
+    };
+
+    // The spend output is locked to a key controlled by the receiver.
+    let spend = TxOut {
+        value: SPEND_AMOUNT,
+        script_pubkey: address.script_pubkey(),
+    };
+
+    // The change output is locked to a key controlled by us.
+    let change = TxOut {
+        value: CHANGE_AMOUNT,
+        script_pubkey: ScriptBuf::new_v0_p2wpkh(&wpkh), // Change comes back to us.
+    };
+
+    // The transaction we want to sign and broadcast.
+    let mut tx = Transaction {
+        version: 2,                          // Post BIP-68.
+        lock_time: absolute::LockTime::ZERO, // Ignore the locktime.
+        input: vec![input],                  // Input goes into index 0.
+        output: vec![spend, change],         // Outputs, order does not matter.
+    };
+    let input_index = 0;
+
+    // The BIP-143 sighash, by hand (this is the exercise) and by rust-bitcoin.
+    let script_code = dummy_utxo
+        .script_pubkey
+        .p2wpkh_script_code()
+        .expect("valid p2wpkh script pubkey");
+    let sighash_type = EcdsaSighashType::All;
+    let sighash = bip143_sighash(&tx, input_index, &script_code, dummy_utxo.value);
+    let expected = SighashCache::new(&tx)
+        .segwit_signature_hash(input_index, &script_code, dummy_utxo.value, sighash_type)
+        .expect("failed to create sighash");
+    assert_eq!(sighash.to_byte_array(), expected.to_byte_array());
+
+    // Sign the sighash and update the witness, as in `sign-segwit-v0`.
+    let msg = Message::from_slice(sighash.as_byte_array()).expect("32 byte sighash");
+    let sig = secp.sign_ecdsa(&msg, &sk);
+    let mut sig_with_flag = sig.serialize_der().to_vec();
+    sig_with_flag.push(sighash_type.to_u32() as u8);
+    tx.input[input_index].witness.push(sig_with_flag);
+    tx.input[input_index]
+        .witness
+        .push(sk.public_key(&secp).serialize());
+
+    // The two serializations, by hand and by rust-bitcoin. A transaction with any witness data is
+    // always sent over the wire in the BIP-144 format.
+    let legacy = serialize_legacy(&tx);
+    let with_witness = serialize_with_witness(&tx);
+    assert_eq!(with_witness, serialize(&tx));
+    // Without any witness data rust-bitcoin uses the legacy format.
+    let mut stripped = tx.clone();
+    stripped.input[input_index].witness.clear();
+    assert_eq!(legacy, serialize(&stripped));
+
+    // The txid is the double SHA256 of the legacy serialization, it does not commit to the
+    // witness so signing does not change it.
+    let txid = sha256d::Hash::hash(&legacy);
+    assert_eq!(Txid::from_raw_hash(txid), tx.txid());
+
+    // The wtxid is the double SHA256 of the full serialization.
+    let wtxid = sha256d::Hash::hash(&with_witness);
+    assert_eq!(wtxid.to_byte_array(), tx.wtxid().to_byte_array());
+
+    // Hashes are shown in hex with their bytes reversed, a quirk inherited from the original
+    // client which treated them as little endian 256 bit numbers.
+    let mut reversed = txid.to_byte_array();
+    reversed.reverse();
+    assert_eq!(hex(&reversed), tx.txid().to_string());
+
+    println!("txid:  {}", tx.txid());
+    println!("wtxid: {}", tx.wtxid());
+
+    // BOOM! Transaction signed and ready to broadcast.
+    workshop_common::print_prevouts(&[dummy_utxo]);
+    println!("{}", serialize_hex(&tx));
+}
+
+/// Returns the legacy serialization of `tx`, the one without witness data.
+fn serialize_legacy(tx: &Transaction) -> Vec<u8> {
+    let mut buf = vec![];
+    buf.extend(tx.version.to_le_bytes());
+    write_inputs_and_outputs(&mut buf, tx);
+    buf.extend(tx.lock_time.to_consensus_u32().to_le_bytes());
+    buf
+}
+
+/// Returns the BIP-144 serialization of `tx`, the one with witness data.
+fn serialize_with_witness(tx: &Transaction) -> Vec<u8> {
+    let mut buf = vec![];
+    buf.extend(tx.version.to_le_bytes());
+    // The marker (a zero input count to old software) and the flag.
+    buf.extend([0x00, 0x01]);
+    write_inputs_and_outputs(&mut buf, tx);
+    // A witness for each input, each a list of byte vectors.
+    for input in &tx.input {
+        write_compact_size(&mut buf, input.witness.len() as u64);
+        for element in input.witness.iter() {
+            write_compact_size(&mut buf, element.len() as u64);
+            buf.extend(element);
+        }
+    }
+    buf.extend(tx.lock_time.to_consensus_u32().to_le_bytes());
+    buf
+}
+
+fn write_inputs_and_outputs(buf: &mut Vec<u8>, tx: &Transaction) {
+    write_compact_size(buf, tx.input.len() as u64);
+    for input in &tx.input {
+        write_out_point(buf, &input.previous_output);
+        write_script(buf, &input.script_sig);
+        buf.extend(input.sequence.0.to_le_bytes());
+    }
+    write_compact_size(buf, tx.output.len() as u64);
+    for output in &tx.output {
+        write_tx_out(buf, output);
+    }
+}
+
+/// An out point is the txid, in its internal byte order, then the little endian output index.
+fn write_out_point(buf: &mut Vec<u8>, out_point: &OutPoint) {
+    buf.extend(out_point.txid.to_byte_array());
+    buf.extend(out_point.vout.to_le_bytes());
+}
+
+fn write_tx_out(buf: &mut Vec<u8>, output: &TxOut) {
+    buf.extend(output.value.to_le_bytes());
+    write_script(buf, &output.script_pubkey);
+}
+
+/// A script is prefixed with its length.
+fn write_script(buf: &mut Vec<u8>, script: &Script) {
+    write_compact_size(buf, script.len() as u64);
+    buf.extend(script.as_bytes());
+}
+
+/// Writes `n` as a compact size (aka VarInt), one byte for small values, otherwise a marker byte
+/// then 2, 4 or 8 little endian bytes.
+fn write_compact_size(buf: &mut Vec<u8>, n: u64) {
+    match n {
+        0..=0xfc => buf.push(n as u8),
+        0xfd..=0xffff => {
+            buf.push(0xfd);
+            buf.extend((n as u16).to_le_bytes());
+        }
+        0x10000..=0xffff_ffff => {
+            buf.push(0xfe);
+            buf.extend((n as u32).to_le_bytes());
+        }
+        _ => {
+            buf.push(0xff);
+            buf.extend(n.to_le_bytes());
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
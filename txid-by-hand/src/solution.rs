@@ -0,0 +1,47 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The reference solution, built with `--features solution`.
+
+use bitcoin::hashes::{sha256d, Hash};
+use bitcoin::sighash::EcdsaSighashType;
+use bitcoin::{Script, Transaction};
+
+use super::{write_out_point, write_script, write_tx_out};
+
+/// Returns the BIP-143 SIGHASH_ALL sighash for input `input_index` of `tx`.
+pub fn bip143_sighash(
+    tx: &Transaction,
+    input_index: usize,
+    script_code: &Script,
+    value: u64,
+) -> sha256d::Hash {
+    let input = &tx.input[input_index];
+
+    // Hashes of all the out points, all the sequence numbers and all the outputs. Computing these
+    // once per transaction, not once per input, fixed the quadratic hashing of the legacy
+    // algorithm.
+    let mut prevouts = vec![];
+    let mut sequences = vec![];
+    for input in &tx.input {
+        write_out_point(&mut prevouts, &input.previous_output);
+        sequences.extend(input.sequence.0.to_le_bytes());
+    }
+    let mut outputs = vec![];
+    for output in &tx.output {
+        write_tx_out(&mut outputs, output);
+    }
+
+    let mut preimage = vec![];
+    preimage.extend(tx.version.to_le_bytes());
+    preimage.extend(sha256d::Hash::hash(&prevouts).to_byte_array());
+    preimage.extend(sha256d::Hash::hash(&sequences).to_byte_array());
+    write_out_point(&mut preimage, &input.previous_output);
+    write_script(&mut preimage, script_code);
+    preimage.extend(value.to_le_bytes()); // Committing to the value is new in segwit.
+    preimage.extend(input.sequence.0.to_le_bytes());
+    preimage.extend(sha256d::Hash::hash(&outputs).to_byte_array());
+    preimage.extend(tx.lock_time.to_consensus_u32().to_le_bytes());
+    preimage.extend(EcdsaSighashType::All.to_u32().to_le_bytes()); // Four bytes, not one.
+
+    sha256d::Hash::hash(&preimage)
+}
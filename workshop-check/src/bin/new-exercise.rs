@@ -0,0 +1,73 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Generate a new signing exercise crate from the templates in `templates/`.
+//!
+//! ```bash
+//! cargo run --bin new-exercise -- <name> <p2wpkh|p2tr>
+//! ```
+//!
+//! The new crate has a `todo!()` skeleton in `src/exercise.rs`, a reference solution built with
+//! `--features solution`, hints, and is picked up by `workshop-check` for grading. Fill in the
+//! TODOs and add it to the directory listing in the README.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+fn main() -> Result<()> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let (name, output) = match args.as_slice() {
+        [name, output] => (name.as_str(), output.as_str()),
+        _ => bail!("usage: new-exercise <name> <p2wpkh|p2tr>"),
+    };
+
+    let main = match output {
+        "p2wpkh" => include_str!("../../templates/main-p2wpkh.rs"),
+        "p2tr" => include_str!("../../templates/main-p2tr.rs"),
+        _ => bail!("unknown output type {}, expected p2wpkh or p2tr", output),
+    };
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    {
+        bail!("exercise names are lower case kebab case, e.g. sign-p2tr-with-anyprevout");
+    }
+
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("crate is in the repository")
+        .join(name);
+    if dir.exists() {
+        bail!("{} already exists", dir.display());
+    }
+
+    let files = [
+        ("Cargo.toml", include_str!("../../templates/Cargo.toml.in")),
+        ("src/main.rs", main),
+        (
+            "src/exercise.rs",
+            include_str!("../../templates/exercise.rs"),
+        ),
+        (
+            "src/solution.rs",
+            include_str!("../../templates/solution.rs"),
+        ),
+        ("src/hints.rs", include_str!("../../templates/hints.rs")),
+    ];
+    fs::create_dir_all(dir.join("src")).context("failed to create exercise directory")?;
+    for (path, template) in files {
+        let contents = template
+            .replace("{{name}}", name)
+            .replace("{{output}}", output);
+        fs::write(dir.join(path), contents).with_context(|| format!("failed to write {}", path))?;
+    }
+
+    println!("Created {}", dir.display());
+    println!(
+        "Fill in the TODOs and add `{}` to the directory listing in the README.",
+        name
+    );
+    Ok(())
+}
@@ -0,0 +1,419 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Grade the exercises against a throwaway regtest node.
+//!
+//! Each exercise is run twice with the same key (see `workshop-common`). The first run spends the
+//! dummy utxos, all zeros txids, and tells us (by printing its prevouts) which outputs it spends.
+//! We fund those outputs on regtest, mine past any lock times, and run the exercise again with the
+//! funded out points. An exercise passes if the node accepts the transaction from the second run,
+//! otherwise we report the node's rejection reason.
+//!
+//! Every exercise has a `[package.metadata.workshop-check]` table in its `Cargo.toml`, exercises
+//! that can not be graded this way set `skip` to the reason why and are reported as skipped.
+//!
+//! Needs a `bitcoind` binary, run it with:
+//!
+//! ```bash
+//! BITCOIND_EXE=$(which bitcoind) cargo run
+//! ```
+//!
+//! Pass exercise names to only grade those, e.g. `cargo run -- sign-taproot`, and `--solution` to
+//! grade the reference solutions of exercises that have a `todo!()` skeleton.
+
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context, Result};
+use bitcoin::consensus::encode::deserialize;
+use bitcoin::hashes::hex::FromHex;
+use bitcoin::hashes::Hash;
+use bitcoin::locktime::{absolute, relative};
+use bitcoin::secp256k1::{rand, SecretKey};
+use bitcoin::{Address, Amount, Network, OutPoint, Transaction, TxOut, Txid};
+use bitcoincore_rpc::{Auth, Client, RpcApi};
+use workshop_common::{OUT_POINT_ENV, SECRET_KEY_ENV};
+
+const RPC_USER: &str = "workshop";
+const RPC_PASSWORD: &str = "workshop";
+
+/// The most blocks we mine to get past an exercise's lock time.
+const MAX_LOCK_BLOCKS: u64 = 1_000;
+
+/// An exercise crate, as described by its `Cargo.toml`.
+struct Exercise {
+    name: String,
+    /// Why the exercise can not be graded, if it can't.
+    skip: Option<String>,
+    /// True if the crate has a `solution` feature, i.e. a `todo!()` skeleton by default.
+    has_solution: bool,
+}
+
+fn main() -> Result<()> {
+    let (flags, selected): (Vec<_>, Vec<_>) = std::env::args()
+        .skip(1)
+        .partition(|arg| arg.starts_with("--"));
+    let mut solution = false;
+    for flag in &flags {
+        match flag.as_str() {
+            "--solution" => solution = true,
+            _ => bail!("unknown option {}", flag),
+        }
+    }
+    let exercises = exercises()?;
+    for name in &selected {
+        if !exercises.iter().any(|exercise| exercise.name == *name) {
+            bail!("don't know how to grade {}", name);
+        }
+    }
+
+    let node = Bitcoind::start()?;
+
+    let mut failed = 0;
+    for exercise in &exercises {
+        if !selected.is_empty() && !selected.contains(&exercise.name) {
+            continue;
+        }
+        if let Some(reason) = &exercise.skip {
+            println!("{}: skipped: {}", exercise.name, reason);
+            continue;
+        }
+        match grade(&node, exercise, solution) {
+            Ok(()) => println!("{}: pass", exercise.name),
+            Err(e) => {
+                println!("{}: FAIL: {:#}", exercise.name, e);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Funds, runs and submits `exercise`, returns an error if the node rejects it.
+///
+/// Grades the reference solution if `solution` is true and the exercise has one.
+fn grade(node: &Bitcoind, exercise: &Exercise, solution: bool) -> Result<()> {
+    let sk = SecretKey::new(&mut rand::thread_rng());
+
+    // The first run spends the dummy utxos, the `n`th one has vout `n`.
+    let (prevouts, tx) = run_exercise(exercise, &sk, &[], solution)?;
+    if prevouts.len() != tx.input.len() {
+        bail!(
+            "exercise printed {} prevouts for {} inputs",
+            prevouts.len(),
+            tx.input.len()
+        );
+    }
+
+    // Fund the same outputs for real, keeping track of which dummy utxo each one replaces.
+    let funded = node.fund(&prevouts)?;
+    let mut out_points = vec![];
+    for (input, funded) in tx.input.iter().zip(funded) {
+        if input.previous_output.txid != Txid::all_zeros() {
+            bail!(
+                "input {} does not spend a dummy utxo",
+                input.previous_output
+            );
+        }
+        let n = input.previous_output.vout as usize;
+        if out_points.len() <= n {
+            // Dummy utxos the exercise makes but does not spend keep their dummy out point.
+            out_points.extend((out_points.len()..=n).map(|vout| OutPoint {
+                txid: Txid::all_zeros(),
+                vout: vout as u32,
+            }));
+        }
+        out_points[n] = funded;
+    }
+
+    let (_, tx) = run_exercise(exercise, &sk, &out_points, solution)?;
+    node.mine_past_lock_times(&tx)?;
+
+    let result = node
+        .client
+        .test_mempool_accept(&[&tx])
+        .context("testmempoolaccept failed")?
+        .pop()
+        .ok_or_else(|| anyhow!("testmempoolaccept returned no result"))?;
+    if !result.allowed {
+        bail!(
+            "rejected by node: {}",
+            result.reject_reason.unwrap_or_default()
+        );
+    }
+    node.client
+        .send_raw_transaction(&tx)
+        .context("sendrawtransaction failed")?;
+    Ok(())
+}
+
+/// Runs `exercise` with `sk` and `out_points` (its dummy utxos if empty), returns the prevouts it
+/// prints and the transaction it prints last.
+fn run_exercise(
+    exercise: &Exercise,
+    sk: &SecretKey,
+    out_points: &[OutPoint],
+    solution: bool,
+) -> Result<(Vec<TxOut>, Transaction)> {
+    let manifest = exercise_dir(&exercise.name).join("Cargo.toml");
+    let mut cargo = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_owned()));
+    cargo.args(["run", "-q", "--manifest-path"]).arg(&manifest);
+    if solution && exercise.has_solution {
+        cargo.args(["--features", "solution"]);
+    }
+    cargo.env(SECRET_KEY_ENV, sk.display_secret().to_string());
+    if out_points.is_empty() {
+        cargo.env_remove(OUT_POINT_ENV);
+    } else {
+        let out_points = out_points
+            .iter()
+            .map(OutPoint::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        cargo.env(OUT_POINT_ENV, out_points);
+    }
+    let output = cargo
+        .stderr(Stdio::inherit())
+        .output()
+        .with_context(|| format!("failed to run {}", manifest.display()))?;
+    if !output.status.success() {
+        bail!("exercise exited with {}", output.status);
+    }
+
+    let stdout = String::from_utf8(output.stdout).context("exercise output is not utf-8")?;
+    let prevouts = stdout
+        .lines()
+        .filter_map(workshop_common::parse_prevout)
+        .collect();
+    let hex = stdout
+        .lines()
+        .map(str::trim)
+        .rfind(|line| !line.is_empty())
+        .ok_or_else(|| anyhow!("exercise printed nothing"))?;
+    let bytes = Vec::<u8>::from_hex(hex).context("last line is not hex")?;
+    let tx = deserialize(&bytes).context("last line is not a transaction")?;
+    Ok((prevouts, tx))
+}
+
+/// Returns the exercises, those with a `[package.metadata.workshop-check]` table in their
+/// `Cargo.toml`.
+///
+/// An exercise that can not be graded sets `skip` in the table to the reason why.
+fn exercises() -> Result<Vec<Exercise>> {
+    let mut exercises = vec![];
+    for entry in std::fs::read_dir(repo_dir()).context("failed to read repository")? {
+        let dir = entry?.path();
+        let manifest = match std::fs::read_to_string(dir.join("Cargo.toml")) {
+            Ok(manifest) => manifest,
+            Err(_) => continue, // Not a crate.
+        };
+        let manifest = manifest
+            .parse::<toml::Value>()
+            .with_context(|| format!("invalid {}/Cargo.toml", dir.display()))?;
+        let check = match manifest
+            .get("package")
+            .and_then(|package| package.get("metadata"))
+            .and_then(|metadata| metadata.get("workshop-check"))
+        {
+            Some(check) => check,
+            None => continue, // Not an exercise.
+        };
+        let skip = match check.get("skip") {
+            Some(skip) => Some(
+                skip.as_str()
+                    .ok_or_else(|| {
+                        anyhow!("invalid {}/Cargo.toml: skip is not a string", dir.display())
+                    })?
+                    .to_owned(),
+            ),
+            None => None,
+        };
+        let has_solution = manifest
+            .get("features")
+            .and_then(|features| features.get("solution"))
+            .is_some();
+
+        let name = dir
+            .file_name()
+            .expect("directory has a name")
+            .to_string_lossy()
+            .into_owned();
+        exercises.push(Exercise {
+            name,
+            skip,
+            has_solution,
+        });
+    }
+    exercises.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(exercises)
+}
+
+/// The exercise crates live next to this one.
+fn exercise_dir(name: &str) -> PathBuf {
+    repo_dir().join(name)
+}
+
+fn repo_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("crate is in the repository")
+}
+
+/// A regtest bitcoind process that is killed, and its data directory removed, on drop.
+struct Bitcoind {
+    process: Child,
+    data_dir: PathBuf,
+    client: Client,
+}
+
+impl Bitcoind {
+    /// Starts bitcoind from `BITCOIND_EXE` and gives its wallet some mature coins.
+    fn start() -> Result<Bitcoind> {
+        let exe = std::env::var_os("BITCOIND_EXE")
+            .ok_or_else(|| anyhow!("set BITCOIND_EXE to the path of a bitcoind binary"))?;
+
+        let data_dir = std::env::temp_dir().join(format!("workshop-check-{}", std::process::id()));
+        std::fs::create_dir_all(&data_dir).context("failed to create data dir")?;
+        let rpc_port = free_port()?;
+
+        let process = Command::new(exe)
+            .arg("-regtest")
+            .arg(format!("-datadir={}", data_dir.display()))
+            .arg(format!("-rpcport={}", rpc_port))
+            .arg(format!("-port={}", free_port()?))
+            .arg(format!("-rpcuser={}", RPC_USER))
+            .arg(format!("-rpcpassword={}", RPC_PASSWORD))
+            .arg("-listen=0")
+            .arg("-fallbackfee=0.0001")
+            .stdout(Stdio::null())
+            .spawn()
+            .context("failed to start bitcoind")?;
+
+        let url = format!("http://127.0.0.1:{}", rpc_port);
+        let auth = Auth::UserPass(RPC_USER.to_owned(), RPC_PASSWORD.to_owned());
+        let client = Client::new(&url, auth).context("failed to create RPC client")?;
+        let node = Bitcoind {
+            process,
+            data_dir,
+            client,
+        };
+
+        // bitcoind takes a moment to start answering RPC calls.
+        let start = Instant::now();
+        while let Err(e) = node.client.get_blockchain_info() {
+            if start.elapsed() > Duration::from_secs(30) {
+                bail!("bitcoind did not start: {}", e);
+            }
+            std::thread::sleep(Duration::from_millis(200));
+        }
+
+        node.client
+            .create_wallet("workshop-check", None, None, None, None)
+            .context("failed to create wallet")?;
+        // Coinbase outputs can only be spent after 100 blocks.
+        node.mine(101)?;
+
+        Ok(node)
+    }
+
+    /// Pays each of `outputs`, confirms them, and returns the funded out points.
+    fn fund(&self, outputs: &[TxOut]) -> Result<Vec<OutPoint>> {
+        let mut out_points = vec![];
+        for output in outputs {
+            let address = Address::from_script(&output.script_pubkey, Network::Regtest)?;
+            let amount = Amount::from_sat(output.value);
+            let txid = self
+                .client
+                .send_to_address(&address, amount, None, None, None, None, None, None)
+                .context("failed to fund exercise output")?;
+
+            let tx = self
+                .client
+                .get_raw_transaction(&txid, None)
+                .with_context(|| format!("failed to get funding transaction {}", txid))?;
+            let vout = tx
+                .output
+                .iter()
+                .position(|funding| *funding == *output)
+                .with_context(|| {
+                    format!(
+                        "funding transaction {} does not pay {} to {}",
+                        txid, amount, address
+                    )
+                })?;
+            out_points.push(OutPoint::new(txid, vout as u32));
+        }
+        self.mine(1)?;
+
+        Ok(out_points)
+    }
+
+    /// Mines enough blocks for `tx`, spending outputs confirmed in the last block, to satisfy its
+    /// lock time and the relative lock times of its inputs.
+    fn mine_past_lock_times(&self, tx: &Transaction) -> Result<()> {
+        let mut blocks = 0;
+        // The outputs being spent have one confirmation already.
+        for input in &tx.input {
+            match input.sequence.to_relative_lock_time() {
+                Some(relative::LockTime::Blocks(height)) => {
+                    blocks = blocks.max(u64::from(height.value()).saturating_sub(1));
+                }
+                Some(relative::LockTime::Time(_)) => {
+                    bail!("relative lock times in seconds are not supported")
+                }
+                None => {}
+            }
+        }
+        // The next block can include a transaction with a lock time up to the current height.
+        if tx.is_lock_time_enabled() {
+            match tx.lock_time {
+                absolute::LockTime::Blocks(height) => {
+                    let tip = self.client.get_block_count()?;
+                    blocks = blocks.max(u64::from(height.to_consensus_u32()).saturating_sub(tip));
+                }
+                absolute::LockTime::Seconds(_) => {
+                    bail!("lock times in seconds are not supported")
+                }
+            }
+        }
+        if blocks > MAX_LOCK_BLOCKS {
+            bail!("lock time is {} blocks away, too far to mine", blocks);
+        }
+        self.mine(blocks)
+    }
+
+    /// Mines `blocks` blocks.
+    fn mine(&self, blocks: u64) -> Result<()> {
+        let miner = self
+            .client
+            .get_new_address(None, None)?
+            .require_network(Network::Regtest)?;
+        self.client.generate_to_address(blocks, &miner)?;
+        Ok(())
+    }
+}
+
+impl Drop for Bitcoind {
+    fn drop(&mut self) {
+        let _ = self.client.stop();
+        if self.process.try_wait().ok().flatten().is_none() {
+            std::thread::sleep(Duration::from_secs(1));
+            let _ = self.process.kill();
+        }
+        let _ = self.process.wait();
+        let _ = std::fs::remove_dir_all(&self.data_dir);
+    }
+}
+
+fn free_port() -> Result<u16> {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .context("failed to find a free port")?
+        .port();
+    Ok(port)
+}
@@ -0,0 +1,21 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The exercise, replace the `todo!()` with your solution.
+
+use bitcoin::secp256k1::{Secp256k1, SecretKey, Signing, Verification};
+use bitcoin::{Transaction, TxOut};
+
+/// Signs input `input_index` of `unsigned_tx` with `sk`, `prevouts` are the outputs spent by all
+/// the inputs.
+///
+/// TODO: Say what the student has to do.
+#[allow(unused_variables)]
+pub fn sign<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    sk: &SecretKey,
+    unsigned_tx: Transaction,
+    input_index: usize,
+    prevouts: &[TxOut],
+) -> Transaction {
+    todo!("sign the input and fill in its witness")
+}
@@ -0,0 +1,9 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Hints for the exercise, from least to most specific.
+
+pub const HINTS: &[&str] = &[
+    "TODO: A nudge in the right direction.",
+    "TODO: Which types and functions to use.",
+    "TODO: Close to the code itself.",
+];
@@ -0,0 +1,379 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Helpers shared by the exercise crates.
+//!
+//! Every exercise needs some keys, somewhere to send coins, and a coin to spend. None of that is
+//! what the exercise is teaching so it lives here instead of being copied into each `main.rs`.
+//!
+//! Keys are random and the dummy utxos have an all zeros txid unless `workshop-check` is grading
+//! the exercise, in which case it tells us (via the environment) which key to use and which outputs
+//! on its regtest node have been funded. `cargo run -- --deterministic <seed>` derives the key from
+//! the seed instead, so instructors can reproduce what attendees build. Exercises needing more than
+//! one key derive the others from that one, see [`secret_key`].
+//!
+//! Exercises with a `todo!()` skeleton also keep a few hints, see [`hint_command`].
+//!
+//! Before its transaction each exercise prints the outputs it spends, see [`print_prevouts`].
+
+use std::sync::OnceLock;
+use std::{env, process};
+
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::secp256k1::{rand, SecretKey};
+use bitcoin::{OutPoint, ScriptBuf, TxOut, Txid};
+
+/// The value of the dummy utxo each exercise spends.
+pub const DUMMY_UTXO_AMOUNT: u64 = 20_000_000;
+/// The value sent to the receiver.
+pub const SPEND_AMOUNT: u64 = 5_000_000;
+/// The fee paid by every exercise transaction.
+pub const FEE: u64 = 1_000;
+/// The value that comes back to us as change, when spending one dummy utxo.
+pub const CHANGE_AMOUNT: u64 = DUMMY_UTXO_AMOUNT - SPEND_AMOUNT - FEE;
+
+/// Environment variable `workshop-check` sets to the hex encoded secret key the exercise uses.
+pub const SECRET_KEY_ENV: &str = "WORKSHOP_SECRET_KEY";
+/// Environment variable `workshop-check` sets to the comma separated `txid:vout`s of the outputs it
+/// funded, one for each dummy utxo in the order the exercise creates them.
+pub const OUT_POINT_ENV: &str = "WORKSHOP_OUT_POINT";
+
+/// Returns the exercise's `n`th secret key, key 0 is the sender's.
+///
+/// Key 0 is random unless set by `workshop-check` or derived from the seed given with `cargo run --
+/// --deterministic <seed>`. The others (co-signers, other participants, leaf keys) are derived
+/// from key 0 so they are reproducible too.
+///
+/// With the same seed everybody builds the same transaction, byte for byte, as long as their
+/// signatures are deterministic too: ECDSA signatures are, Schnorr signatures made with
+/// `sign_schnorr` mix in random bytes (use `sign_schnorr_no_aux_rand` to compare).
+pub fn secret_key(n: u32) -> SecretKey {
+    static KEY: OnceLock<SecretKey> = OnceLock::new();
+    let sk = *KEY.get_or_init(first_secret_key);
+    if n == 0 {
+        return sk;
+    }
+    let mut engine = sha256::Hash::engine();
+    engine.input(format!("workshop/key/{}/", n).as_bytes());
+    engine.input(&sk.secret_bytes());
+    let hash = sha256::Hash::from_engine(engine);
+    SecretKey::from_slice(hash.as_byte_array()).expect("hash is a valid secret key")
+}
+
+/// Returns key 0, see [`secret_key`].
+fn first_secret_key() -> SecretKey {
+    if let Ok(hex) = env::var(SECRET_KEY_ENV) {
+        return hex.parse().expect("invalid secret key in environment");
+    }
+    match deterministic_seed() {
+        Some(seed) => {
+            let hash = sha256::Hash::hash(format!("workshop/key/{}", seed).as_bytes());
+            SecretKey::from_slice(hash.as_byte_array()).expect("hash is a valid secret key")
+        }
+        None => SecretKey::new(&mut rand::thread_rng()),
+    }
+}
+
+/// Returns the seed given with `--deterministic <seed>`, if any.
+fn deterministic_seed() -> Option<String> {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--deterministic" {
+            let seed = args.next();
+            if seed.is_none() {
+                eprintln!("usage: cargo run -- --deterministic <seed>");
+                process::exit(1);
+            }
+            return seed;
+        }
+    }
+    None
+}
+
+/// Returns the out point of the `n`th dummy utxo, an all zeros txid unless set by `workshop-check`.
+fn out_point(n: u32) -> OutPoint {
+    match env::var(OUT_POINT_ENV) {
+        Ok(s) => s
+            .split(',')
+            .nth(n as usize)
+            .expect("too few out points in environment")
+            .parse()
+            .expect("invalid out point in environment"),
+        Err(_) => OutPoint {
+            txid: Txid::all_zeros(), // Obviously invalid.
+            vout: n,
+        },
+    }
+}
+
+/// Creates the `n`th dummy utxo the exercise spends, worth [`DUMMY_UTXO_AMOUNT`] and locked by
+/// `script_pubkey`.
+///
+/// An utxo is described by the `OutPoint` (txid and index within the transaction that it was
+/// created). Using the out point one can get the transaction by `txid` and using the `vout` get
+/// the transaction value and script pubkey (`TxOut`) of the utxo.
+///
+/// In a real application this would be a valid output taken from a transaction that appears in
+/// the chain.
+pub fn dummy_utxo(n: u32, script_pubkey: ScriptBuf) -> (OutPoint, TxOut) {
+    let utxo = TxOut {
+        value: DUMMY_UTXO_AMOUNT,
+        script_pubkey,
+    };
+    (out_point(n), utxo)
+}
+
+/// Prints a hint and exits if the exercise was run as `cargo run -- hint <n>`, otherwise does
+/// nothing.
+///
+/// `hints` go from least to most specific, the first one is hint 1.
+pub fn hint_command(hints: &[&str]) {
+    let mut args = env::args().skip(1);
+    if args.next().as_deref() != Some("hint") {
+        return;
+    }
+
+    match args.next().and_then(|n| n.parse::<usize>().ok()) {
+        Some(n) if (1..=hints.len()).contains(&n) => {
+            println!("Hint {} of {}:\n\n{}", n, hints.len(), hints[n - 1]);
+            process::exit(0);
+        }
+        _ => {
+            eprintln!("usage: cargo run -- hint <1-{}>", hints.len());
+            process::exit(1);
+        }
+    }
+}
+
+/// Prints a `prevout <script pubkey hex> <amount in sats>` line for each of `prevouts`, the
+/// outputs spent by the transaction printed next, in input order.
+///
+/// Signatures commit to the amount spent (segwit) and to every output spent (taproot), so checking
+/// a transaction needs them, see `workshop-check`.
+pub fn print_prevouts(prevouts: &[TxOut]) {
+    for prevout in prevouts {
+        println!(
+            "prevout {} {}",
+            prevout.script_pubkey.to_hex_string(),
+            prevout.value
+        );
+    }
+}
+
+/// Parses a line printed by [`print_prevouts`], returns `None` if it is not one.
+pub fn parse_prevout(line: &str) -> Option<TxOut> {
+    let mut parts = line.split_whitespace();
+    if parts.next() != Some("prevout") {
+        return None;
+    }
+    let script_pubkey = ScriptBuf::from_hex(parts.next()?).ok()?;
+    let value = parts.next()?.parse().ok()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(TxOut {
+        value,
+        script_pubkey,
+    })
+}
+
+/// Helpers for exercises that spend a p2wpkh output.
+pub mod segwit_v0 {
+    use std::str::FromStr;
+
+    use bitcoin::secp256k1::{Secp256k1, SecretKey, Signing};
+    use bitcoin::{Address, Network, OutPoint, ScriptBuf, TxOut, WPubkeyHash};
+
+    use crate::{dummy_utxo, secret_key};
+
+    /// An example of keys controlled by the transaction sender.
+    ///
+    /// In a real application these would be actual secrets.
+    pub fn senders_keys<C: Signing>(secp: &Secp256k1<C>) -> (SecretKey, WPubkeyHash) {
+        let sk = secret_key(0);
+        let pk = bitcoin::PublicKey::new(sk.public_key(secp));
+        let wpkh = pk.wpubkey_hash().expect("key is compressed");
+
+        (sk, wpkh)
+    }
+
+    /// A dummy address for the receiver.
+    ///
+    /// We lock the spend output to the key associated with this address.
+    ///
+    /// (FWIW this is an arbitrary mainnet address.)
+    pub fn receivers_address() -> Address {
+        Address::from_str("bc1q7cyrfmck2ffu2ud3rn5l5a8yv6f0chkp0zpemf")
+            .expect("a valid address")
+            .require_network(Network::Bitcoin)
+            .expect("valid address for mainnet")
+    }
+
+    /// Creates a p2wpkh output locked to the key associated with `wpkh`.
+    ///
+    /// This output is locked to keys that we control, see [`dummy_utxo`].
+    pub fn dummy_unspent_transaction_output(wpkh: &WPubkeyHash) -> (OutPoint, TxOut) {
+        dummy_utxo(0, ScriptBuf::new_v0_p2wpkh(wpkh))
+    }
+}
+
+/// Helpers for exercises that spend a p2tr output.
+pub mod taproot {
+    use std::str::FromStr;
+
+    use bitcoin::key::{KeyPair, UntweakedPublicKey};
+    use bitcoin::secp256k1::{Secp256k1, Signing, Verification};
+    use bitcoin::{Address, Network, OutPoint, ScriptBuf, TxOut};
+
+    use crate::{dummy_utxo, secret_key};
+
+    /// An example of keys controlled by the transaction sender.
+    ///
+    /// In a real application these would be actual secrets.
+    pub fn senders_keys<C: Signing>(secp: &Secp256k1<C>) -> KeyPair {
+        let sk = secret_key(0);
+        KeyPair::from_secret_key(secp, &sk)
+    }
+
+    /// A dummy address for the receiver.
+    ///
+    /// We lock the spend output to the key associated with this address.
+    ///
+    /// (FWIW this is an arbitrary mainnet address from block 805222.)
+    pub fn receivers_address() -> Address {
+        Address::from_str("bc1p0dq0tzg2r780hldthn5mrznmpxsxc0jux5f20fwj0z3wqxxk6fpqm7q0va")
+            .expect("a valid address")
+            .require_network(Network::Bitcoin)
+            .expect("valid address for mainnet")
+    }
+
+    /// Creates a p2tr output locked to `internal_key`, with no script tree.
+    ///
+    /// This output is locked to keys that we control, see [`dummy_utxo`].
+    pub fn dummy_unspent_transaction_output<C: Verification>(
+        secp: &Secp256k1<C>,
+        internal_key: UntweakedPublicKey,
+    ) -> (OutPoint, TxOut) {
+        dummy_utxo(0, ScriptBuf::new_v1_p2tr(secp, internal_key, None))
+    }
+}
+
+/// A small interpreter for the witness scripts of the timelock exercises.
+///
+/// rust-bitcoin does not include a script interpreter. This one only knows the opcodes the
+/// timelock exercises use (pushes, `OP_CHECKLOCKTIMEVERIFY`, `OP_CHECKSEQUENCEVERIFY`, `OP_DROP`
+/// and `OP_CHECKSIG`) so they can show a lock time being enforced without a node.
+pub mod interpreter {
+    use bitcoin::hashes::Hash;
+    use bitcoin::opcodes::all::{OP_CHECKSIG, OP_CLTV, OP_CSV, OP_DROP};
+    use bitcoin::script::{self, Instruction};
+    use bitcoin::secp256k1::{Message, Secp256k1, Verification};
+    use bitcoin::sighash::SighashCache;
+    use bitcoin::{absolute, ecdsa, PublicKey, Script, Sequence, Transaction};
+
+    /// Why a script failed.
+    #[derive(Debug)]
+    pub enum ScriptError {
+        Invalid,
+        UnsupportedOpcode,
+        StackUnderflow,
+        NegativeLockTime,
+        UnsatisfiedLockTime,
+        EvalFalse,
+    }
+
+    /// Runs `witness_script` on `stack` for input `input_index` of `tx`, as a node would.
+    ///
+    /// `value` is the amount of the output being spent, p2wsh signatures commit to it.
+    pub fn execute<C: Verification>(
+        secp: &Secp256k1<C>,
+        witness_script: &Script,
+        mut stack: Vec<Vec<u8>>,
+        tx: &Transaction,
+        input_index: usize,
+        value: u64,
+    ) -> Result<(), ScriptError> {
+        for instruction in witness_script.instructions() {
+            match instruction.map_err(|_| ScriptError::Invalid)? {
+                Instruction::PushBytes(bytes) => stack.push(bytes.as_bytes().to_vec()),
+                Instruction::Op(OP_CLTV) => {
+                    let script_lock_time =
+                        absolute::LockTime::from_consensus(top_lock_time(&stack)?);
+
+                    // The transaction's lock time must be the same unit and at least as late.
+                    if !script_lock_time.is_implied_by(tx.lock_time) {
+                        return Err(ScriptError::UnsatisfiedLockTime);
+                    }
+                    // And the transaction's lock time must be enforced.
+                    if !tx.input[input_index].sequence.enables_absolute_lock_time() {
+                        return Err(ScriptError::UnsatisfiedLockTime);
+                    }
+                }
+                Instruction::Op(OP_CSV) => {
+                    // With the disable bit set the argument is not a lock time, the opcode does
+                    // nothing (left for future soft forks).
+                    let sequence = Sequence::from_consensus(top_lock_time(&stack)?);
+                    let script_lock_time = match sequence.to_relative_lock_time() {
+                        Some(lock_time) => lock_time,
+                        None => continue,
+                    };
+
+                    // Relative lock times only exist in version 2 transactions.
+                    if tx.version < 2 {
+                        return Err(ScriptError::UnsatisfiedLockTime);
+                    }
+                    // The input's relative lock time must be enabled, the same unit and at least as
+                    // long.
+                    match tx.input[input_index].sequence.to_relative_lock_time() {
+                        Some(lock_time) if script_lock_time.is_implied_by(lock_time) => {}
+                        _ => return Err(ScriptError::UnsatisfiedLockTime),
+                    }
+                }
+                Instruction::Op(OP_DROP) => {
+                    stack.pop().ok_or(ScriptError::StackUnderflow)?;
+                }
+                Instruction::Op(OP_CHECKSIG) => {
+                    let pk = stack.pop().ok_or(ScriptError::StackUnderflow)?;
+                    let sig = stack.pop().ok_or(ScriptError::StackUnderflow)?;
+                    let valid = check_sig(secp, &sig, &pk, witness_script, tx, input_index, value);
+                    stack.push(if valid { vec![1] } else { vec![] });
+                }
+                Instruction::Op(_) => return Err(ScriptError::UnsupportedOpcode),
+            }
+        }
+
+        // Segwit requires exactly one true element left on the stack.
+        match &stack[..] {
+            [top] if top.iter().any(|byte| *byte != 0) => Ok(()),
+            _ => Err(ScriptError::EvalFalse),
+        }
+    }
+
+    /// Returns the lock time argument of `OP_CHECKLOCKTIMEVERIFY` or `OP_CHECKSEQUENCEVERIFY`, the
+    /// top of `stack`, which the opcodes leave in place.
+    fn top_lock_time(stack: &[Vec<u8>]) -> Result<u32, ScriptError> {
+        let top = stack.last().ok_or(ScriptError::StackUnderflow)?;
+        let n = script::read_scriptint(top).map_err(|_| ScriptError::Invalid)?;
+        u32::try_from(n).map_err(|_| ScriptError::NegativeLockTime)
+    }
+
+    /// Returns true if `sig` is a valid signature for input `input_index` by `pk`.
+    fn check_sig<C: Verification>(
+        secp: &Secp256k1<C>,
+        sig: &[u8],
+        pk: &[u8],
+        witness_script: &Script,
+        tx: &Transaction,
+        input_index: usize,
+        value: u64,
+    ) -> bool {
+        let (sig, pk) = match (ecdsa::Signature::from_slice(sig), PublicKey::from_slice(pk)) {
+            (Ok(sig), Ok(pk)) => (sig, pk),
+            _ => return false,
+        };
+        let sighash = SighashCache::new(tx)
+            .segwit_signature_hash(input_index, witness_script, value, sig.hash_ty)
+            .expect("input exists");
+        let msg = Message::from_slice(sighash.as_byte_array()).expect("32 byte sighash");
+        secp.verify_ecdsa(&msg, &sig.sig, &pk.inner).is_ok()
+    }
+}